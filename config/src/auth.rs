@@ -0,0 +1,81 @@
+// Peer authorization:
+//     maps the public key a peer authenticated with to the replica it is
+//     allowed to act as, so the id a peer claims is never trusted on its own
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use types::Replica;
+
+#[derive(Debug, PartialEq)]
+pub enum AuthError {
+    // The key is not in the cluster's pk_map
+    UnknownKey,
+    // claimed, actual
+    IdMismatch(Replica, Replica),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            AuthError::UnknownKey => write!(f, "peer presented an unknown key"),
+            AuthError::IdMismatch(claimed, actual) => write!(
+                f,
+                "peer claimed to be {} but its key belongs to {}",
+                claimed, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PeerTable {
+    by_key: HashMap<Vec<u8>, Replica>,
+}
+
+impl PeerTable {
+    pub fn new(pk_map: &HashMap<Replica, Vec<u8>>) -> Self {
+        PeerTable {
+            by_key: pk_map.iter().map(|(id, pk)| (pk.clone(), *id)).collect(),
+        }
+    }
+
+    /// Returns the replica a connection authenticated with `key` speaks for.
+    /// If the peer also claimed an id, it has to match the key.
+    pub fn authorize(&self, key: &[u8], claimed: Option<Replica>) -> Result<Replica, AuthError> {
+        let actual = *self.by_key.get(key).ok_or(AuthError::UnknownKey)?;
+        match claimed {
+            Some(id) if id != actual => Err(AuthError::IdMismatch(id, actual)),
+            _ => Ok(actual),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> PeerTable {
+        let mut pk_map = HashMap::new();
+        for i in 0..4 as Replica {
+            pk_map.insert(i, vec![i as u8; 32]);
+        }
+        PeerTable::new(&pk_map)
+    }
+
+    #[test]
+    fn authorized_peer() {
+        assert_eq!(table().authorize(&[2; 32], Some(2)), Ok(2));
+        assert_eq!(table().authorize(&[2; 32], None), Ok(2));
+    }
+
+    #[test]
+    fn impersonation_is_rejected() {
+        assert_eq!(
+            table().authorize(&[3; 32], Some(2)),
+            Err(AuthError::IdMismatch(2, 3))
+        );
+        assert_eq!(table().authorize(&[9; 32], Some(2)), Err(AuthError::UnknownKey));
+    }
+}
@@ -38,15 +38,10 @@ impl Client {
         if 2 * self.num_faults >= self.num_nodes {
             return Err(ParseError::IncorrectFaults(self.num_faults, self.num_nodes));
         }
-        for repl in &self.net_map {
-            if !is_valid_replica(*repl.0, self.num_nodes) {
-                return Err(ParseError::InvalidMapEntry(*repl.0));
-            }
-        }
         match self.crypto_alg {
             Algorithm::ED25519 => {
                 for repl in &self.server_pk {
-                    if !is_valid_replica(*repl.0, self.num_nodes) {
+                    if !is_valid_replica(*repl.0, &self.net_map) {
                         return Err(ParseError::InvalidMapEntry(*repl.0));
                     }
                     if repl.1.len() != crypto::ED25519_PK_SIZE {
@@ -56,7 +51,7 @@ impl Client {
             }
             Algorithm::SECP256K1 => {
                 for repl in &self.server_pk {
-                    if !is_valid_replica(*repl.0, self.num_nodes) {
+                    if !is_valid_replica(*repl.0, &self.net_map) {
                         return Err(ParseError::InvalidMapEntry(*repl.0));
                     }
                     if repl.1.len() != crypto::SECP256K1_PK_SIZE {
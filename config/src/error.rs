@@ -12,6 +12,25 @@ pub enum ParseError {
     InvalidSkSize(usize),
     // feature name that is not implemented
     Unimplemented(&'static str),
+    // reason the phase timing is invalid
+    InvalidTiming(&'static str),
+    // channel size
+    InvalidChannelSize(usize),
+    // two replicas with the same address
+    DuplicateAddress(u16, u16),
+    // two replicas with the same public key
+    DuplicatePk(u16, u16),
+    // n, below MIN_NODES without solo mode
+    TooFewNodes(usize),
+    // n, f of a solo config
+    InvalidSolo(usize, usize),
+    // replica with a missing or zero weight, more than half the total weight,
+    // or a weight but no such replica
+    InvalidWeight(u16),
+    // sum of the weights, above MAX_TOTAL_WEIGHT
+    TotalWeightTooLarge(u64),
+    // reason the socket options are invalid
+    InvalidSocket(&'static str),
 }
 
 impl std::fmt::Display for ParseError {
@@ -29,6 +48,33 @@ impl std::fmt::Display for ParseError {
                 write!(f, "{} feature is not yet implemented", feature)
             }
             ParseError::InvalidSkSize(s) => write!(f, "invalid secret key size ({})", s),
+            ParseError::InvalidTiming(reason) => write!(f, "invalid phase timing: {}", reason),
+            ParseError::InvalidChannelSize(s) => write!(f, "invalid channel size ({})", s),
+            ParseError::DuplicateAddress(a, b) => {
+                write!(f, "replicas {} and {} have the same address", a, b)
+            }
+            ParseError::DuplicatePk(a, b) => {
+                write!(f, "replicas {} and {} have the same public key", a, b)
+            }
+            ParseError::TooFewNodes(n) => write!(
+                f,
+                "{} replicas cannot tolerate a fault; use at least {} or solo mode",
+                n,
+                crate::MIN_NODES
+            ),
+            ParseError::InvalidSolo(n, fault) => write!(
+                f,
+                "solo mode needs n = 1 and f = 0, got n = {} and f = {}",
+                n, fault
+            ),
+            ParseError::InvalidWeight(r) => write!(f, "invalid weight for replica {}", r),
+            ParseError::TotalWeightTooLarge(total) => write!(
+                f,
+                "the weights add up to {}, more than {}",
+                total,
+                crate::MAX_TOTAL_WEIGHT
+            ),
+            ParseError::InvalidSocket(reason) => write!(f, "invalid socket options: {}", reason),
         }
     }
 }
@@ -42,6 +88,15 @@ impl std::error::Error for ParseError {
             ParseError::InvalidPkSize(_) => "invalid public key size",
             ParseError::InvalidSkSize(_) => "invalid secret key size",
             ParseError::Unimplemented(_) => "feature unimplemented",
+            ParseError::InvalidTiming(_) => "invalid phase timing",
+            ParseError::InvalidChannelSize(_) => "invalid channel size",
+            ParseError::DuplicateAddress(_, _) => "duplicate address",
+            ParseError::DuplicatePk(_, _) => "duplicate public key",
+            ParseError::TooFewNodes(_) => "too few nodes",
+            ParseError::InvalidSolo(_, _) => "invalid solo config",
+            ParseError::InvalidWeight(_) => "invalid weight",
+            ParseError::TotalWeightTooLarge(_) => "total weight too large",
+            ParseError::InvalidSocket(_) => "invalid socket options",
         }
     }
 
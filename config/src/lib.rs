@@ -15,6 +15,17 @@ pub use node::*;
 mod error;
 pub use error::*;
 
-fn is_valid_replica(r: types::Replica, n: usize) -> bool {
-    n > r as usize
+mod timing;
+pub use timing::*;
+
+mod socket;
+pub use socket::*;
+
+mod auth;
+pub use auth::*;
+
+// Replicas are whatever ids the net map lists, which need be neither dense
+// nor start at 0
+fn is_valid_replica<T>(r: types::Replica, net_map: &std::collections::HashMap<types::Replica, T>) -> bool {
+    net_map.contains_key(&r)
 }
@@ -7,16 +7,74 @@
 // Crypto Config:
 //     algorithm_type, pvt_key, map[id]public_key
 
-use super::{is_valid_replica, ParseError};
+use super::{is_valid_replica, ParseError, PeerTable, PhaseDurations, SocketOptions, Timing};
+use crypto::hash::HashAlgorithm;
 use crypto::Algorithm;
 use serde::{Deserialize, Serialize};
 use serde_json::from_reader;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
+use std::time::Duration;
 use toml::from_str;
 use types::Replica;
 
+/// Channel capacity when the config does not say
+pub const DEFAULT_CHANNEL_SIZE: usize = 100_000;
+/// Largest channel capacity a config may ask for
+pub const MAX_CHANNEL_SIZE: usize = 10_000_000;
+/// Largest encoded block body hashed on the reactor thread when the config
+/// does not say
+pub const DEFAULT_INLINE_HASH_LIMIT: usize = 64 * 1024;
+/// Worker threads of the client runtime when the config does not say
+pub const DEFAULT_CLIENT_THREADS: usize = 2;
+/// Milliseconds a client may take to accept a block when the config does
+/// not say
+pub const DEFAULT_CLIENT_WRITE_TIMEOUT: u64 = 5_000;
+/// Client connections served at once when the config does not say
+pub const DEFAULT_MAX_CLIENTS: usize = 1024;
+/// Beacons kept in memory when the config does not say
+pub const DEFAULT_BEACON_HISTORY: usize = 1024;
+/// Reconnects a kept message is written again on when the config does not
+/// say
+pub const DEFAULT_DEAD_LETTER_RETRIES: usize = 3;
+/// The smallest cluster that tolerates a fault. Anything below it only runs
+/// in solo mode.
+pub const MIN_NODES: usize = 3;
+/// Largest total of the replica weights. Every unit of weight is a slot in
+/// the leader schedule.
+pub const MAX_TOTAL_WEIGHT: u64 = 1 << 16;
+
+/// What a node does with a message that does not decode or does not verify
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum MessageHandling {
+    /// Panic, to catch bugs in test clusters
+    Strict,
+    /// Log it and carry on
+    Lenient,
+}
+
+impl Default for MessageHandling {
+    fn default() -> Self {
+        MessageHandling::Lenient
+    }
+}
+
+/// What a node does with a client connection once it serves `max_clients`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ClientOverflow {
+    /// Tell the new client the node is busy and close it
+    Reject,
+    /// Close the connection that has been idle longest to make room
+    EvictIdle,
+}
+
+impl Default for ClientOverflow {
+    fn default() -> Self {
+        ClientOverflow::Reject
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Node {
     // Node network config
@@ -30,9 +88,248 @@ pub struct Node {
     pub block_size: usize,
     pub client_port: u16,
     pub payload: usize,
+    #[serde(default)]
+    pub timing: Timing,
+    // keepalive and buffer sizes of the connections to the other replicas
+    #[serde(default)]
+    pub socket: SocketOptions,
+    // number of blocks on top of a committed block before it is final
+    #[serde(default)]
+    pub finality_depth: u64,
+    // hand our own shares to ourselves through the inbound path instead of
+    // adding them to the gatherers directly
+    #[serde(default)]
+    pub loopback: bool,
+    // number of epochs at startup left out of the latency summary
+    #[serde(default)]
+    pub warmup_epochs: u64,
+    // number of concurrent writes when sending blocks to clients, 0 for the
+    // default
+    #[serde(default)]
+    pub client_fanout: usize,
+    // milliseconds a client may take to accept a block before it is
+    // dropped, none for DEFAULT_CLIENT_WRITE_TIMEOUT, 0 to wait forever
+    #[serde(default)]
+    pub client_write_timeout: Option<u64>,
+    // client connections served at once, none for DEFAULT_MAX_CLIENTS, 0
+    // for no limit
+    #[serde(default)]
+    pub max_clients: Option<usize>,
+    // whether a client connecting beyond max_clients is turned away or
+    // takes the place of the one idle longest
+    #[serde(default)]
+    pub client_overflow: ClientOverflow,
+    // number of random peers a node hands deliver shards to, 0 to send every
+    // replica its shard
+    #[serde(default)]
+    pub gossip_fanout: usize,
+    // only the replicas the beacon needs broadcast reconstruction shares, the
+    // rest hold theirs back until someone asks
+    #[serde(default)]
+    pub lazy_reconstruct: bool,
+    // number of committed blocks kept in memory, 0 to keep all of them
+    #[serde(default)]
+    pub storage_capacity: usize,
+    // number of most recent committed blocks never evicted from storage
+    #[serde(default)]
+    pub storage_retain: u64,
+    // tell the other replicas which beacon we got at the end of every epoch
+    #[serde(default)]
+    pub epoch_done: bool,
+    // number of acks for its commitments a leader waits for before proposing,
+    // for at most the ack_wait window; 0 to not wait
+    #[serde(default)]
+    pub ack_threshold: usize,
+    // number of shares and commitments per message when the next leader
+    // hands them out, 0 to send them all in one message
+    #[serde(default)]
+    pub commit_chunk: usize,
+    // number of epochs a vote or deliver shard may wait to be sent before it
+    // is dropped as stale, 0 to send it however late
+    #[serde(default)]
+    pub send_ttl: u64,
+    // path of a Unix socket answering status queries, none to not open one
+    #[serde(default)]
+    pub admin_socket: Option<String>,
+    // bytes a node may send in an epoch before reconstruction shares wait
+    // behind everything else, 0 for no limit
+    #[serde(default)]
+    pub epoch_byte_cap: u64,
+    // number of other shard sets to try when the data rebuilt from all the
+    // gathered shards does not decode, 0 to give up right away
+    #[serde(default)]
+    pub reconstruct_retries: usize,
+    // capacity of the channel from the reactor to the network, none for
+    // DEFAULT_CHANNEL_SIZE
+    #[serde(default)]
+    pub consensus_channel: Option<usize>,
+    // whether a malformed or unverifiable message aborts the node or is
+    // skipped
+    #[serde(default)]
+    pub message_handling: MessageHandling,
+    // name of the chain the cluster runs; when set, every protocol message
+    // is tagged with it and the genesis block, and messages tagged for
+    // another chain or not at all are dropped. None to not tag messages
+    #[serde(default)]
+    pub chain_id: Option<String>,
+    // run a single replica that commits its own proposals without sharing
+    // anything, for local testing; needs num_nodes = 1 and num_faults = 0
+    #[serde(default)]
+    pub solo: bool,
+    // file the highest certificate and the block it certifies are saved to,
+    // so that a restarted node proposes on the right tip; none to not save it
+    #[serde(default)]
+    pub checkpoint: Option<String>,
+    // environment variable holding the passphrase the checkpoint is
+    // encrypted with; none to write it in the clear. Only the checkpoint is
+    // sealed, and it holds nothing secret: secret_key_bytes and
+    // threshold_secret stay in the clear in the config file
+    #[serde(default)]
+    pub checkpoint_passphrase_env: Option<String>,
+    // blocks whose body encodes to more bytes than this are hashed off the
+    // reactor thread, none for DEFAULT_INLINE_HASH_LIMIT
+    #[serde(default)]
+    pub inline_hash_limit: Option<usize>,
+    // ask the sender of a certificate for a block we have not committed,
+    // and adopt the certificate once the block arrives, instead of dropping
+    // the certificate
+    #[serde(default)]
+    pub cert_sync: bool,
+    // replica a new node asks for a snapshot of the cluster to start from,
+    // instead of replaying every epoch; none to start from genesis
+    #[serde(default)]
+    pub bootstrap_from: Option<Replica>,
+    // the leader votes for its own proposal as soon as it is hashed, so it
+    // needs one vote fewer from the others to certify it
+    #[serde(default)]
+    pub self_vote: bool,
+    // take the commitments the next leader deals for the beacon without
+    // checking its signature or our shares against them; only for
+    // benchmarking trusted clusters
+    #[serde(default)]
+    pub trust_commits: bool,
+    // check the shards of Deliver messages and the next leader's
+    // commitments on a thread of their own instead of the reactor
+    #[serde(default)]
+    pub offload_shares: bool,
+    // threads the signatures of incoming votes, proposals, certificates and
+    // reconfigurations are checked on before the reactor handles them, 0 to
+    // check them on the reactor
+    #[serde(default)]
+    pub verify_workers: usize,
+    // messages kept per peer that could not be delivered to it, to be sent
+    // again once it reconnects, 0 to only log and count them
+    #[serde(default)]
+    pub dead_letter_capacity: usize,
+    // times a kept message is written again on a reconnect before it is
+    // given up on, none for DEFAULT_DEAD_LETTER_RETRIES, 0 for no limit.
+    // Votes and deliver shards are also given up on once send_ttl epochs
+    // have passed
+    #[serde(default)]
+    pub dead_letter_retries: Option<usize>,
+    // blocks the leader proposes per epoch, each with up to block_size
+    // transactions, committed together; 0 or 1 for one block
+    #[serde(default)]
+    pub batch_size: usize,
+    // whether the blocks we propose have their bodies compressed when they
+    // are sent, sharded and saved
+    #[serde(default)]
+    pub compress_blocks: bool,
+    // build the next proposal while waiting out the epoch before our turn
+    // to lead, and propose it as it is if it still extends the tip we would
+    // propose on; false to only build it once the turn comes
+    #[serde(default)]
+    pub prefetch_proposal: bool,
+    // whether a certificate is one threshold signature that num_faults + 1
+    // votes combine into, rather than the votes themselves; every replica
+    // has to agree, and votes count alike whatever the weights
+    #[serde(default)]
+    pub threshold_certificates: bool,
+    // this replica's share of the threshold key, the key's public key and
+    // the public keys of every replica's share, as genconfig deals them
+    #[serde(default)]
+    pub threshold_secret: Vec<u8>,
+    #[serde(default)]
+    pub threshold_public: Vec<u8>,
+    #[serde(default)]
+    pub threshold_shares: HashMap<Replica, Vec<u8>>,
+    // milliseconds handling one message may take before further messages of
+    // its kind wait until nothing else is ready, 0 for no limit
+    #[serde(default)]
+    pub handler_budget_ms: u64,
+    // worker threads of the runtime client connections are served on, none
+    // for DEFAULT_CLIENT_THREADS, 0 to serve them on the consensus runtime
+    #[serde(default)]
+    pub client_threads: Option<usize>,
+    // serve no clients at all: the client port is not bound and no
+    // transactions are taken, for nodes that only produce beacons
+    #[serde(default)]
+    pub disable_clients: bool,
+    // client transactions the reactor has not taken yet, none for
+    // DEFAULT_CHANNEL_SIZE; clients are not read from while that many wait
+    #[serde(default)]
+    pub mempool_capacity: Option<usize>,
+    // file the transactions of every committed block are appended to, one
+    // line of JSON per block; none to not write them out
+    #[serde(default)]
+    pub commit_log: Option<String>,
+    // committed blocks that may wait for the commit sink, none for
+    // DEFAULT_CHANNEL_SIZE; blocks beyond that are dropped and counted
+    #[serde(default)]
+    pub commit_sink_capacity: Option<usize>,
+    // file every beacon is appended to, one line of JSON per epoch, so that
+    // past beacons can be looked up after they left memory or the node
+    // restarted; none to keep only those in memory
+    #[serde(default)]
+    pub beacon_log: Option<String>,
+    // beacons kept in memory, none for DEFAULT_BEACON_HISTORY; older ones
+    // are only in the beacon log
+    #[serde(default)]
+    pub beacon_history: Option<usize>,
+    // file every protocol message the node takes in is recorded to, with
+    // its origin and when it came, for replaying the run later; none to not
+    // record. The file is replaced when the node starts
+    #[serde(default)]
+    pub message_trace: Option<String>,
+    // voting weight of every replica, for staked deployments: leaders take
+    // turns in proportion to weight and a vote certificate needs more
+    // weight than the num_faults heaviest replicas hold together; empty for
+    // equal weights
+    #[serde(default)]
+    pub weights: HashMap<Replica, u64>,
+    // how many epochs the cluster may be ahead of us before we stop taking
+    // part and skip ahead to it, syncing our beacon shares from the dealers
+    // and the chain from a snapshot, 0 to never skip
+    #[serde(default)]
+    pub max_epoch_skew: u64,
+    // milliseconds a replica's clock may be off from ours, as estimated from
+    // the timestamps on the certificates it sends us, before we warn about
+    // it; 0 to never warn
+    #[serde(default)]
+    pub max_clock_drift_ms: u64,
+    // wait until num_nodes - num_faults replicas, counting us, are up before
+    // starting epoch 0, so that their first phase windows line up; without
+    // it every replica starts delta after it boots
+    #[serde(default)]
+    pub startup_barrier: bool,
+    // squarings of the delay function the beacon is put through at the end
+    // of every epoch, 0 to emit it undelayed. Expensive: evaluating it takes
+    // hundreds of times longer than checking it and blocks the node while it
+    // runs, so it has to fit well within an epoch
+    #[serde(default)]
+    pub vdf_iterations: u64,
+    // file, or named pipe, external values to mix into the beacon are read
+    // from, one `<epoch> <value in hex>` per line; none to mix nothing in
+    #[serde(default)]
+    pub entropy_source: Option<String>,
 
     // Crypto primitives
     pub crypto_alg: Algorithm,
+    // function blocks and messages are hashed with, the same on every
+    // replica. It is selected once for the whole process when the node
+    // starts
+    #[serde(default)]
+    pub hash_alg: HashAlgorithm,
     pub pk_map: HashMap<Replica, Vec<u8>>,
     pub secret_key_bytes: Vec<u8>,
 
@@ -42,6 +339,9 @@ pub struct Node {
 
     pub rand_beacon_parameter: Option<crypto::EVSSParams381>,
     pub rand_beacon_queue: HashMap<Replica, std::collections::VecDeque<crypto::EVSSShare381>>,
+    // The commitment behind each share in `rand_beacon_queue`, in the same
+    // order, which reconstruction shares are checked against
+    pub rand_beacon_commits: HashMap<Replica, std::collections::VecDeque<crypto::EVSSCommit381>>,
 
     pub rand_beacon_shares: Vec<(Vec<std::collections::VecDeque<crypto::EVSSShare381>>, Vec<crypto::EVSSCommit381>)>,
 }
@@ -54,18 +354,45 @@ impl Node {
                 self.net_map.len(),
             ));
         }
+        if self.solo {
+            if self.num_nodes != 1 || self.num_faults != 0 {
+                return Err(ParseError::InvalidSolo(self.num_nodes, self.num_faults));
+            }
+        } else if self.num_nodes < MIN_NODES {
+            return Err(ParseError::TooFewNodes(self.num_nodes));
+        }
         if 2 * self.num_faults >= self.num_nodes {
             return Err(ParseError::IncorrectFaults(self.num_faults, self.num_nodes));
         }
-        for repl in &self.net_map {
-            if !is_valid_replica(*repl.0, self.num_nodes) {
-                return Err(ParseError::InvalidMapEntry(*repl.0));
+        if !is_valid_replica(self.id, &self.net_map) {
+            return Err(ParseError::InvalidMapEntry(self.id));
+        }
+        if self.threshold_certificates {
+            if self.threshold_secret.len() != crypto::threshold::SECRET_SHARE_SIZE {
+                return Err(ParseError::InvalidSkSize(self.threshold_secret.len()));
+            }
+            if self.threshold_shares.len() != self.num_nodes {
+                return Err(ParseError::InvalidMapLen(
+                    self.num_nodes,
+                    self.threshold_shares.len(),
+                ));
+            }
+            if self.threshold_public.len() != crypto::threshold::PUBLIC_KEY_SIZE {
+                return Err(ParseError::InvalidPkSize(self.threshold_public.len()));
+            }
+            for (r, pk) in &self.threshold_shares {
+                if !is_valid_replica(*r, &self.net_map) {
+                    return Err(ParseError::InvalidMapEntry(*r));
+                }
+                if pk.len() != crypto::threshold::PUBLIC_KEY_SIZE {
+                    return Err(ParseError::InvalidPkSize(pk.len()));
+                }
             }
         }
         match self.crypto_alg {
             Algorithm::ED25519 => {
                 for repl in &self.pk_map {
-                    if !is_valid_replica(*repl.0, self.num_nodes) {
+                    if !is_valid_replica(*repl.0, &self.net_map) {
                         return Err(ParseError::InvalidMapEntry(*repl.0));
                     }
                     if repl.1.len() != crypto::ED25519_PK_SIZE {
@@ -78,7 +405,7 @@ impl Node {
             }
             Algorithm::SECP256K1 => {
                 for repl in &self.pk_map {
-                    if !is_valid_replica(*repl.0, self.num_nodes) {
+                    if !is_valid_replica(*repl.0, &self.net_map) {
                         return Err(ParseError::InvalidMapEntry(*repl.0));
                     }
                     if repl.1.len() != crypto::SECP256K1_PK_SIZE {
@@ -94,6 +421,47 @@ impl Node {
                 return Err(ParseError::Unimplemented("RSA"));
             }
         }
+        if let Some((a, b)) = first_duplicate(&self.net_map) {
+            return Err(ParseError::DuplicateAddress(a, b));
+        }
+        if let Some((a, b)) = first_duplicate(&self.pk_map) {
+            return Err(ParseError::DuplicatePk(a, b));
+        }
+        if !self.weights.is_empty() {
+            for r in self.net_map.keys() {
+                if self.weights.get(r).map_or(true, |w| *w == 0) {
+                    return Err(ParseError::InvalidWeight(*r));
+                }
+            }
+            for r in self.weights.keys() {
+                if !is_valid_replica(*r, &self.net_map) {
+                    return Err(ParseError::InvalidWeight(*r));
+                }
+            }
+            let total: u64 = self.weights.values().fold(0, |t, w| t.saturating_add(*w));
+            if total > MAX_TOTAL_WEIGHT {
+                return Err(ParseError::TotalWeightTooLarge(total));
+            }
+            // Nobody may lead two epochs in a row
+            for (r, w) in self.weights.iter() {
+                if 2 * w > total {
+                    return Err(ParseError::InvalidWeight(*r));
+                }
+            }
+        }
+        self.phase_durations().validate()?;
+        self.socket.validate()?;
+        for size in [
+            self.consensus_channel_size(),
+            self.mempool_capacity(),
+            self.commit_sink_capacity(),
+        ]
+        .iter()
+        {
+            if *size == 0 || *size > MAX_CHANNEL_SIZE {
+                return Err(ParseError::InvalidChannelSize(*size));
+            }
+        }
         Ok(())
     }
 
@@ -102,6 +470,7 @@ impl Node {
             block_size: 0,
             client_port: 0,
             crypto_alg: Algorithm::ED25519,
+            hash_alg: HashAlgorithm::SHA256,
             delta: 50,
             id: 0,
             net_map: HashMap::new(),
@@ -110,10 +479,68 @@ impl Node {
             pk_map: HashMap::new(),
             secret_key_bytes: Vec::new(),
             payload: 0,
+            timing: Timing::default(),
+            socket: SocketOptions::default(),
+            finality_depth: 0,
+            loopback: false,
+            warmup_epochs: 0,
+            client_fanout: 0,
+            client_write_timeout: None,
+            max_clients: None,
+            client_overflow: ClientOverflow::Reject,
+            gossip_fanout: 0,
+            lazy_reconstruct: false,
+            storage_capacity: 0,
+            storage_retain: 0,
+            epoch_done: false,
+            ack_threshold: 0,
+            commit_chunk: 0,
+            send_ttl: 0,
+            admin_socket: None,
+            epoch_byte_cap: 0,
+            reconstruct_retries: 0,
+            consensus_channel: None,
+            message_handling: MessageHandling::Lenient,
+            chain_id: None,
+            solo: false,
+            checkpoint: None,
+            checkpoint_passphrase_env: None,
+            inline_hash_limit: None,
+            cert_sync: false,
+            bootstrap_from: None,
+            self_vote: false,
+            trust_commits: false,
+            offload_shares: false,
+            verify_workers: 0,
+            dead_letter_capacity: 0,
+            dead_letter_retries: None,
+            batch_size: 0,
+            compress_blocks: false,
+            prefetch_proposal: false,
+            threshold_certificates: false,
+            threshold_secret: Vec::new(),
+            threshold_public: Vec::new(),
+            threshold_shares: HashMap::new(),
+            handler_budget_ms: 0,
+            client_threads: None,
+            disable_clients: false,
+            mempool_capacity: None,
+            commit_log: None,
+            commit_sink_capacity: None,
+            beacon_log: None,
+            beacon_history: None,
+            message_trace: None,
+            weights: HashMap::new(),
+            max_epoch_skew: 0,
+            max_clock_drift_ms: 0,
+            startup_barrier: false,
+            vdf_iterations: 0,
+            entropy_source: None,
             bi_pp_map: HashMap::new(),
             bi_p: None,
             rand_beacon_parameter: None,
             rand_beacon_queue: HashMap::new(),
+            rand_beacon_commits: HashMap::new(),
             rand_beacon_shares: Vec::new(),
         }
     }
@@ -177,9 +604,90 @@ impl Node {
             .clone()
     }
 
+    /// Returns the table used to authorize incoming peer connections
+    pub fn peer_table(&self) -> PeerTable {
+        PeerTable::new(&self.pk_map)
+    }
+
+    /// Capacity of the channel the reactor sends to the network through
+    pub fn consensus_channel_size(&self) -> usize {
+        self.consensus_channel.unwrap_or(DEFAULT_CHANNEL_SIZE)
+    }
+
+    /// Largest encoded block body the reactor hashes itself
+    pub fn inline_hash_limit(&self) -> usize {
+        self.inline_hash_limit.unwrap_or(DEFAULT_INLINE_HASH_LIMIT)
+    }
+
+    /// Worker threads of the client runtime, 0 for none of its own
+    pub fn client_threads(&self) -> usize {
+        self.client_threads.unwrap_or(DEFAULT_CLIENT_THREADS)
+    }
+
+    /// Client connections served at once, 0 for no limit
+    pub fn max_clients(&self) -> usize {
+        self.max_clients.unwrap_or(DEFAULT_MAX_CLIENTS)
+    }
+
+    /// How long a client may take to accept a block, if there is a limit
+    pub fn client_write_timeout(&self) -> Option<Duration> {
+        match self.client_write_timeout.unwrap_or(DEFAULT_CLIENT_WRITE_TIMEOUT) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    /// Client transactions that may wait for the reactor
+    pub fn mempool_capacity(&self) -> usize {
+        self.mempool_capacity.unwrap_or(DEFAULT_CHANNEL_SIZE)
+    }
+
+    /// Beacons kept in memory
+    pub fn beacon_history(&self) -> usize {
+        self.beacon_history.unwrap_or(DEFAULT_BEACON_HISTORY)
+    }
+
+    /// Reconnects a kept message is written again on, 0 for no limit
+    pub fn dead_letter_retries(&self) -> usize {
+        self.dead_letter_retries.unwrap_or(DEFAULT_DEAD_LETTER_RETRIES)
+    }
+
+    /// Committed blocks that may wait for the commit sink
+    pub fn commit_sink_capacity(&self) -> usize {
+        self.commit_sink_capacity.unwrap_or(DEFAULT_CHANNEL_SIZE)
+    }
+
+    /// How long handling one message may take, none for no limit
+    pub fn handler_budget(&self) -> Option<std::time::Duration> {
+        match self.handler_budget_ms {
+            0 => None,
+            ms => Some(std::time::Duration::from_millis(ms)),
+        }
+    }
+
+    /// Returns the phase windows, falling back to multiples of delta
+    pub fn phase_durations(&self) -> PhaseDurations {
+        self.timing.resolve(self.delta)
+    }
+
     /// Returns the address at which a server should listen to incoming client
     /// connections
     pub fn client_ip(&self) -> String {
         format!("0.0.0.0:{}", self.client_port)
     }
 }
+
+// Two replicas sharing an entry are most likely one config copied twice with
+// only the id changed. Reports the lowest such pair.
+fn first_duplicate<T: PartialEq>(map: &HashMap<Replica, T>) -> Option<(Replica, Replica)> {
+    let mut ids: Vec<_> = map.keys().cloned().collect();
+    ids.sort();
+    for (i, a) in ids.iter().enumerate() {
+        for b in &ids[i + 1..] {
+            if map[a] == map[b] {
+                return Some((*a, *b));
+            }
+        }
+    }
+    None
+}
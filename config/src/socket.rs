@@ -0,0 +1,90 @@
+// Socket options for the connections between replicas:
+//     a peer that goes away without closing its connection is only noticed
+//     once keepalive probes go unanswered, after about
+//     idle + interval * count seconds. Shorter settings notice dead peers
+//     sooner, but send more probes and may give up on a peer that is only
+//     slow or briefly unreachable.
+
+use super::ParseError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct SocketOptions {
+    /// Seconds a connection may be idle before the first probe; 0 turns
+    /// keepalive off
+    pub keepalive_idle: u64,
+    /// Seconds between unanswered probes
+    pub keepalive_interval: u64,
+    /// Unanswered probes after which the peer is taken to be dead
+    pub keepalive_count: u32,
+    /// Kernel send buffer size in bytes; the system default if unset
+    pub send_buffer: Option<usize>,
+    /// Kernel receive buffer size in bytes; the system default if unset
+    pub recv_buffer: Option<usize>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        SocketOptions {
+            keepalive_idle: 10,
+            keepalive_interval: 2,
+            keepalive_count: 3,
+            send_buffer: None,
+            recv_buffer: None,
+        }
+    }
+}
+
+impl SocketOptions {
+    /// Roughly how long a dead peer goes unnoticed, if keepalive is on
+    pub fn detection_time(&self) -> Option<u64> {
+        if self.keepalive_idle == 0 {
+            return None;
+        }
+        Some(self.keepalive_idle + self.keepalive_interval * self.keepalive_count as u64)
+    }
+
+    pub fn validate(&self) -> Result<(), ParseError> {
+        if self.keepalive_idle > 0 && (self.keepalive_interval == 0 || self.keepalive_count == 0) {
+            return Err(ParseError::InvalidSocket(
+                "keepalive needs a non-zero probe interval and count",
+            ));
+        }
+        if self.send_buffer == Some(0) || self.recv_buffer == Some(0) {
+            return Err(ParseError::InvalidSocket("socket buffers must be non-zero"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_notices_a_dead_peer_in_sixteen_seconds() {
+        let opts = SocketOptions::default();
+        assert!(opts.validate().is_ok());
+        assert_eq!(opts.detection_time(), Some(16));
+    }
+
+    #[test]
+    fn keepalive_off() {
+        let mut opts = SocketOptions::default();
+        opts.keepalive_idle = 0;
+        opts.keepalive_count = 0;
+        assert!(opts.validate().is_ok());
+        assert_eq!(opts.detection_time(), None);
+    }
+
+    #[test]
+    fn invalid_options() {
+        let mut opts = SocketOptions::default();
+        opts.keepalive_count = 0;
+        assert!(opts.validate().is_err());
+        let mut opts = SocketOptions::default();
+        opts.recv_buffer = Some(0);
+        assert!(opts.validate().is_err());
+    }
+}
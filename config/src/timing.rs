@@ -0,0 +1,135 @@
+// Phase timing config:
+//     every window defaults to a fixed multiple of delta, but can be
+//     overridden (in milliseconds) independently of the network delay
+
+use super::ParseError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Timing {
+    /// How long the leader waits for certificates before proposing (2Δ)
+    pub propose: Option<u64>,
+    /// Offset from the epoch start at which proposal shares are delivered (7Δ)
+    pub deliver_propose: Option<u64>,
+    /// Offset from the epoch start at which commit shares are delivered (8Δ)
+    pub deliver_commit: Option<u64>,
+    /// How long a replica waits after delivering commits before voting (Δ)
+    pub vote: Option<u64>,
+    /// How long a replica waits after a vote certificate before committing (2Δ)
+    pub commit: Option<u64>,
+    /// Total length of an epoch (11Δ)
+    pub epoch: Option<u64>,
+    /// How much longer a leader short of acks waits before proposing (2Δ)
+    #[serde(default)]
+    pub ack_wait: Option<u64>,
+    /// How long a phase may run past the end of its epoch before the
+    /// watchdog moves the node on to the next epoch (2Δ)
+    #[serde(default)]
+    pub stall_margin: Option<u64>,
+}
+
+/// The resolved phase durations in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhaseDurations {
+    pub propose: u64,
+    pub deliver_propose: u64,
+    pub deliver_commit: u64,
+    pub vote: u64,
+    pub commit: u64,
+    pub epoch: u64,
+    pub ack_wait: u64,
+    pub stall_margin: u64,
+}
+
+impl Timing {
+    pub fn resolve(&self, delta: u64) -> PhaseDurations {
+        PhaseDurations {
+            propose: self.propose.unwrap_or(delta * 2),
+            deliver_propose: self.deliver_propose.unwrap_or(delta * 7),
+            deliver_commit: self.deliver_commit.unwrap_or(delta * 8),
+            vote: self.vote.unwrap_or(delta),
+            commit: self.commit.unwrap_or(delta * 2),
+            epoch: self.epoch.unwrap_or(delta * 11),
+            ack_wait: self.ack_wait.unwrap_or(delta * 2),
+            stall_margin: self.stall_margin.unwrap_or(delta * 2),
+        }
+    }
+}
+
+impl PhaseDurations {
+    /// Checks that the windows still happen in the order the protocol expects
+    pub fn validate(&self) -> Result<(), ParseError> {
+        if self.propose == 0 || self.vote == 0 || self.commit == 0 || self.stall_margin == 0 {
+            return Err(ParseError::InvalidTiming("phase windows must be non-zero"));
+        }
+        if self.propose >= self.deliver_propose {
+            return Err(ParseError::InvalidTiming(
+                "the leader must propose before proposals are delivered",
+            ));
+        }
+        if self.propose + self.ack_wait >= self.deliver_propose {
+            return Err(ParseError::InvalidTiming(
+                "the leader must stop waiting for acks before proposals are delivered",
+            ));
+        }
+        if self.deliver_propose >= self.deliver_commit {
+            return Err(ParseError::InvalidTiming(
+                "proposals must be delivered before commits",
+            ));
+        }
+        if self.deliver_commit + self.vote + self.commit > self.epoch {
+            return Err(ParseError::InvalidTiming(
+                "deliver, vote and commit do not fit in the epoch",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_eleven_delta() {
+        let d = Timing::default().resolve(50);
+        assert_eq!(d.epoch, 550);
+        assert!(d.validate().is_ok());
+    }
+
+    #[test]
+    fn custom_durations() {
+        let t = Timing {
+            propose: Some(20),
+            deliver_propose: Some(300),
+            deliver_commit: Some(350),
+            vote: Some(50),
+            commit: Some(100),
+            epoch: Some(600),
+            ack_wait: Some(100),
+            stall_margin: Some(100),
+        };
+        let d = t.resolve(50);
+        assert!(d.validate().is_ok());
+        assert!(d.propose < d.deliver_propose);
+        assert!(d.deliver_propose < d.deliver_commit);
+        assert!(d.deliver_commit + d.vote + d.commit <= d.epoch);
+    }
+
+    #[test]
+    fn ack_wait_must_end_before_delivery() {
+        let mut t = Timing::default();
+        t.ack_wait = Some(300);
+        assert!(t.resolve(50).validate().is_err());
+    }
+
+    #[test]
+    fn out_of_order_durations() {
+        let mut t = Timing::default();
+        t.deliver_commit = Some(100);
+        assert!(t.resolve(50).validate().is_err());
+        let mut t = Timing::default();
+        t.epoch = Some(400);
+        assert!(t.resolve(50).validate().is_err());
+    }
+}
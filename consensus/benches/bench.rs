@@ -20,7 +20,7 @@ fn tree_get_dummy_acc<T: Serialize>(
     data: &T,
 ) -> (Vec<Vec<u8>>, DataWithAcc) {
     let shards = accumulator::to_shards(
-        &to_bytes(data),
+        &to_bytes(data).unwrap(),
         cx_num_nodes as usize,
         cx_num_faults as usize,
     );
@@ -75,6 +75,8 @@ fn generate_propose() -> HashMap<usize, Propose> {
         let content = Content {
             acks: certificate.votes.clone(),
             commits: vec![poly.get_commit(); *test],
+            reconfig: Vec::new(),
+            transactions: Vec::new(),
         };
         let mut block = Block::new();
         block.body.data = content;
@@ -83,6 +85,7 @@ fn generate_propose() -> HashMap<usize, Propose> {
             new_block: block,
             certificate: certificate,
             epoch: 0,
+            batch: Vec::new(),
         };
         propose_map.insert(*test, propose);
     }
@@ -143,7 +146,7 @@ pub fn bi_propose_to_shards(c: &mut Criterion) {
         group.throughput(Throughput::Bytes(*n as u64));
         group.bench_with_input(BenchmarkId::from_parameter(*n), n, |b, &n| {
             b.iter(|| {
-                let shards = accumulator::to_shards(&to_bytes(&data), n, (n - 1) / 2);
+                let shards = accumulator::to_shards(&to_bytes(&data).unwrap(), n, (n - 1) / 2);
                 let mut values = Vec::with_capacity(n as usize);
                 for shard in shards.iter() {
                     values.push(F381::from_be_bytes_mod_order(&hash::ser_and_hash(&shard)));
@@ -224,8 +227,87 @@ pub fn bi_shards_to_propose(c: &mut Criterion) {
     group.finish();
 }
 
+pub fn deliver_messages(c: &mut Criterion) {
+    const N: usize = 64;
+    let rng = &mut StdRng::seed_from_u64(SEED);
+    let params = EVSS381::setup((N - 1) / 2, rng).unwrap();
+    let poly = EVSS381::commit(&params, F381::rand(rng), rng).unwrap();
+    let mut block = Block::new();
+    block.body.data.commits = vec![poly.get_commit(); N];
+    let acc = tree_get_dummy_acc(N as u16, ((N - 1) / 2) as u16, &block);
+    let mut group = c.benchmark_group("deliver_messages");
+    BenchmarkGroup::sampling_mode(&mut group, criterion::SamplingMode::Flat);
+    group.bench_function("vec", |b| {
+        b.iter(|| {
+            let mut out = Vec::with_capacity(N);
+            for i in 0..N {
+                // What the deliver functions did with plain `Vec<u8>` shards
+                out.push((
+                    acc.0[i].clone(),
+                    i as Replica,
+                    accumulator::get_sign(&acc.1, i as Replica),
+                ));
+                out.push((acc.0[0].clone(), 0, accumulator::get_sign(&acc.1, 0)));
+            }
+            out
+        })
+    });
+    let shards: Vec<bytes::Bytes> = acc.0.iter().cloned().map(bytes::Bytes::from).collect();
+    group.bench_function("bytes", |b| {
+        b.iter(|| {
+            let mut out = Vec::with_capacity(N);
+            for i in 0..N {
+                out.push(types::ProtocolMsg::DeliverPropose(
+                    shards[i].clone(),
+                    i as Replica,
+                    accumulator::get_sign(&acc.1, i as Replica),
+                    0,
+                ));
+                out.push(types::ProtocolMsg::DeliverPropose(
+                    shards[0].clone(),
+                    0,
+                    accumulator::get_sign(&acc.1, 0),
+                    0,
+                ));
+            }
+            out
+        })
+    });
+    group.finish();
+}
+
+// Sharding a padded proposal, rebuilding it from the minimum number of
+// shards and decoding it again, without the rest of the epoch; see the epoch
+// bench for that.
+pub fn payload_shards(c: &mut Criterion) {
+    const N: usize = 16;
+    let propose_map = generate_propose();
+    let mut group = c.benchmark_group("payload_shards");
+    BenchmarkGroup::sampling_mode(&mut group, criterion::SamplingMode::Flat);
+    for payload in [0, 1 << 10, 1 << 14, 1 << 17, 1 << 20].iter() {
+        let mut propose = propose_map.get(&10).unwrap().clone();
+        propose.new_block.add_payload(*payload);
+        group.throughput(Throughput::Bytes(*payload as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(*payload),
+            &propose,
+            |b, propose| {
+                b.iter(|| {
+                    let shards = accumulator::to_shards(&to_bytes(propose).unwrap(), N, (N - 1) / 2);
+                    let mut received: Vec<_> = shards.into_iter().map(Some).collect();
+                    for i in 0..(N - 1) / 2 {
+                        received[i] = None;
+                    }
+                    Propose::from_bytes(&accumulator::from_shards(received, N, (N - 1) / 2))
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
 criterion_group!(
     name = benches;
     config = Criterion::default().sample_size(BENCH_COUNT);
-    targets = tree_propose_to_shards, tree_shards_to_propose, bi_propose_to_shards, bi_shards_to_propose);
+    targets = tree_propose_to_shards, tree_shards_to_propose, bi_propose_to_shards, bi_shards_to_propose, deliver_messages, payload_shards);
 criterion_main!(benches);
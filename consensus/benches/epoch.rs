@@ -0,0 +1,59 @@
+// Latency of one epoch of an in-memory cluster as proposals grow. Every
+// leader pads its block with `payload` bytes, so that sharding, delivering
+// and rebuilding the proposal move blocks of that size. Each sample spawns
+// a fresh cluster and times it from the start until every replica has
+// committed the block of epoch 1 and moved on to epoch 2. Needs the `bench`
+// feature, without which blocks are not padded:
+//
+//   cargo bench -p consensus --features bench --bench epoch
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion, Throughput};
+use std::time::{Duration, Instant};
+
+static PAYLOADS: [usize; 5] = [0, 1 << 10, 1 << 14, 1 << 17, 1 << 20];
+const BENCH_COUNT: usize = 10;
+
+/// Spawns a cluster for `configs` and waits out its first epoch
+async fn first_epoch(configs: Vec<config::Node>) -> Duration {
+    let started = Instant::now();
+    let cluster = common::spawn_cluster(configs);
+    let outcomes = common::first_epoch(&cluster).await;
+    let took = started.elapsed();
+    for (i, outcome) in outcomes.iter().enumerate() {
+        assert!(outcome.committed.is_some(), "replica {} committed nothing", i);
+    }
+    took
+}
+
+pub fn epoch_latency(c: &mut Criterion) {
+    let mut group = c.benchmark_group("epoch_latency");
+    BenchmarkGroup::sampling_mode(&mut group, criterion::SamplingMode::Flat);
+    for payload in PAYLOADS.iter() {
+        let mut configs = common::generate_configs(4, 1, 2);
+        for config in configs.iter_mut() {
+            config.payload = *payload;
+        }
+        group.throughput(Throughput::Bytes(*payload as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(*payload), &configs, |b, configs| {
+            b.iter_custom(|iters| {
+                let mut total = Duration::from_secs(0);
+                for _ in 0..iters {
+                    // A runtime of its own, so that nothing is left running
+                    // from the cluster before
+                    let rt = tokio::runtime::Runtime::new().unwrap();
+                    total += rt.block_on(first_epoch(configs.clone()));
+                }
+                total
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    name = benches;
+    config = Criterion::default().sample_size(BENCH_COUNT);
+    targets = epoch_latency);
+criterion_main!(benches);
@@ -0,0 +1,77 @@
+// Per-epoch cost of the reconstruction share queue on a replica that has
+// shares queued for epochs ahead of its own: collecting the current epoch and
+// dropping the ones behind it. `scan` is the queue as it was, a `VecDeque`
+// walked from the front; `btree` is the map keyed by epoch that `Context`
+// keeps now.
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion};
+use num_traits::Zero;
+use std::collections::{BTreeMap, VecDeque};
+use types::Height;
+
+static TEST_POINTS: [usize; 7] = [3, 10, 20, 30, 50, 75, 100];
+// How many epochs the rest of the network is ahead by
+const AHEAD: Height = 10;
+
+fn share() -> crypto::EVSSShare381 {
+    crypto::EVSSShare381 {
+        point: crypto::F381::zero(),
+        value: crypto::F381::zero(),
+        challenge: crypto::F381::zero(),
+        proof: crypto::EVSSProof381 {
+            w: crypto::EVSSG1Affine381::zero(),
+            random_v: None,
+        },
+    }
+}
+
+fn scan(queue: &mut VecDeque<(crypto::EVSSShare381, Height)>, epoch: Height) -> usize {
+    let mut vec = Vec::new();
+    while !queue.is_empty() && queue.front().unwrap().1 < epoch {
+        queue.pop_front();
+    }
+    while !queue.is_empty() && queue.front().unwrap().1 == epoch {
+        vec.push(queue.pop_front().unwrap().0);
+    }
+    vec.len()
+}
+
+fn btree(queue: &mut BTreeMap<Height, Vec<crypto::EVSSShare381>>, epoch: Height) -> usize {
+    let later = queue.split_off(&(epoch + 1));
+    let mut done = std::mem::replace(queue, later);
+    done.remove(&epoch).unwrap_or_default().len()
+}
+
+pub fn reconstruct_queue(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reconstruct_queue");
+    BenchmarkGroup::sampling_mode(&mut group, criterion::SamplingMode::Flat);
+    for n in TEST_POINTS.iter() {
+        let per_epoch = n - (n - 1) / 3;
+        // The replica catches up to the last epoch queued, dropping the rest
+        let mut deque = VecDeque::new();
+        let mut map = BTreeMap::new();
+        for e in 0..=AHEAD {
+            for _ in 0..per_epoch {
+                deque.push_back((share(), e));
+                map.entry(e).or_insert_with(Vec::new).push(share());
+            }
+        }
+        group.bench_with_input(BenchmarkId::new("scan", n), n, |b, _| {
+            b.iter_batched(
+                || deque.clone(),
+                |mut q| scan(&mut q, AHEAD),
+                criterion::BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("btree", n), n, |b, _| {
+            b.iter_batched(
+                || map.clone(),
+                |mut q| btree(&mut q, AHEAD),
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, reconstruct_queue);
+criterion_main!(benches);
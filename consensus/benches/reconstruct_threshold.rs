@@ -0,0 +1,78 @@
+// Reconstruction cost as a function of the cluster size and the number of
+// faults it tolerates. Every replica rebuilds the proposal and the
+// commitments from `num_nodes - num_faults` erasure shards each epoch, and
+// the beacon from `num_faults + 1` EVSS shares of a polynomial of degree
+// `num_faults`. The shards missing are the data shards, so that the
+// erasure path always has to decode. Two sweeps: the largest number of
+// faults for each size, and a fixed size with more and more faults.
+use bytes::Bytes;
+use consensus::bft::node::accumulator::{to_shards, ShareGatherer};
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion, Throughput};
+use crypto::hash::PROPOSE_DOMAIN;
+use crypto::rand::{rngs::StdRng, Rng, SeedableRng};
+use crypto::{UniformRand, EVSS381, F381};
+use types::Replica;
+
+const SEED: u64 = 42;
+static TEST_POINTS: [usize; 7] = [3, 10, 20, 30, 50, 75, 100];
+// The cluster size the fault sweep is run at
+const SWEEP_NODES: usize = 64;
+static SWEEP_FAULTS: [usize; 5] = [1, 5, 10, 15, 21];
+// Roughly a full block
+const PAYLOAD: usize = 256 * 1024;
+const BENCH_COUNT: usize = 10;
+
+// Both sweeps, as (num_nodes, num_faults)
+fn thresholds() -> Vec<(usize, usize)> {
+    let mut points: Vec<(usize, usize)> = TEST_POINTS.iter().map(|n| (*n, (*n - 1) / 3)).collect();
+    points.extend(SWEEP_FAULTS.iter().map(|f| (SWEEP_NODES, *f)));
+    points
+}
+
+fn id(num_nodes: usize, num_faults: usize) -> BenchmarkId {
+    BenchmarkId::from_parameter(format!("n{}-f{}", num_nodes, num_faults))
+}
+
+pub fn erasure_reconstruct(c: &mut Criterion) {
+    let rng = &mut StdRng::seed_from_u64(SEED);
+    let data: Vec<u8> = (0..PAYLOAD).map(|_| rng.gen()).collect();
+    let mut group = c.benchmark_group("erasure_reconstruct");
+    BenchmarkGroup::sampling_mode(&mut group, criterion::SamplingMode::Flat);
+    for (n, f) in thresholds() {
+        let shards = to_shards(&data, n, f);
+        let mut gatherer = ShareGatherer::new(n as Replica, (n - f) as Replica, PROPOSE_DOMAIN);
+        for (i, shard) in shards.into_iter().enumerate().skip(f) {
+            gatherer.shard[i] = Some(Bytes::from(shard));
+            gatherer.shard_num += 1;
+        }
+        group.throughput(Throughput::Bytes(PAYLOAD as u64));
+        group.bench_function(id(n, f), |b| {
+            b.iter(|| gatherer.reconstruct(n as Replica, f as Replica).unwrap());
+        });
+    }
+    group.finish();
+}
+
+pub fn evss_reconstruct(c: &mut Criterion) {
+    let rng = &mut StdRng::seed_from_u64(SEED);
+    let mut group = c.benchmark_group("evss_reconstruct");
+    BenchmarkGroup::sampling_mode(&mut group, criterion::SamplingMode::Flat);
+    for (n, f) in thresholds() {
+        let params = EVSS381::setup(f, rng).unwrap();
+        let poly = EVSS381::commit(&params, F381::rand(rng), rng).unwrap();
+        let shares: Vec<_> = (f..=2 * f)
+            .map(|i| EVSS381::get_share(F381::from((i + 1) as u16), &params, &poly, rng).unwrap())
+            .collect();
+        group.throughput(Throughput::Elements(shares.len() as u64));
+        group.bench_function(id(n, f), |b| {
+            b.iter(|| EVSS381::reconstruct(&shares));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    name = benches;
+    config = Criterion::default().sample_size(BENCH_COUNT);
+    targets = erasure_reconstruct, evss_reconstruct);
+criterion_main!(benches);
@@ -0,0 +1,99 @@
+// Cost of the signatures every epoch makes and checks: one signature or
+// verification per algorithm, and a certificate checked with
+// `Context::certifies` as the reactor does. Group names start with `sig_` so that
+// tools/bench-guard.sh can compare them against a saved baseline.
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use consensus::bft::node::context::Context;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion};
+use crypto::hash::{ser_and_hash, with_domain, VOTE_DOMAIN};
+use crypto::rand::{rngs::StdRng, SeedableRng};
+use crypto_lib::{ed25519, secp256k1, Keypair, PublicKey};
+use genconfig::{generate, Setup};
+use types::{Certificate, Vote};
+
+const SEED: u64 = 42;
+static TEST_POINTS: [usize; 7] = [3, 10, 20, 30, 50, 75, 100];
+const BENCH_COUNT: usize = 10;
+
+fn keypair(alg: &str) -> (Keypair, PublicKey) {
+    match alg {
+        "ed25519" => {
+            let kp = ed25519::Keypair::generate();
+            let pk = PublicKey::Ed25519(kp.public());
+            (Keypair::Ed25519(kp), pk)
+        }
+        "secp256k1" => {
+            let kp = secp256k1::Keypair::generate();
+            let pk = PublicKey::Secp256k1(kp.public());
+            (Keypair::Secp256k1(kp), pk)
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn vote_msg() -> Vec<u8> {
+    ser_and_hash(&"block").to_vec()
+}
+
+pub fn sig_sign(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sig_sign");
+    BenchmarkGroup::sampling_mode(&mut group, criterion::SamplingMode::Flat);
+    let msg = with_domain(VOTE_DOMAIN, &vote_msg());
+    for alg in ["ed25519", "secp256k1"].iter() {
+        let (sk, _) = keypair(alg);
+        group.bench_function(*alg, |b| b.iter(|| sk.sign(&msg).unwrap()));
+    }
+    group.finish();
+}
+
+pub fn sig_verify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sig_verify");
+    BenchmarkGroup::sampling_mode(&mut group, criterion::SamplingMode::Flat);
+    let msg = with_domain(VOTE_DOMAIN, &vote_msg());
+    for alg in ["ed25519", "secp256k1"].iter() {
+        let (sk, pk) = keypair(alg);
+        let auth = sk.sign(&msg).unwrap();
+        group.bench_function(*alg, |b| b.iter(|| assert!(pk.verify(&msg, &auth))));
+    }
+    group.finish();
+}
+
+// A certificate carries f + 1 votes, checked by a replica as it checks the
+// ones it receives
+pub fn sig_certificate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sig_certificate");
+    BenchmarkGroup::sampling_mode(&mut group, criterion::SamplingMode::Flat);
+    let hash = ser_and_hash(&"block");
+    for n in &TEST_POINTS {
+        let num_faults = (*n - 1) / 2;
+        let mut setup = Setup::localhost(*n, num_faults, 4000, 10000);
+        setup.epochs = 1;
+        let (configs, _) = generate(&setup, &mut StdRng::seed_from_u64(SEED));
+        let cxs: Vec<Context> = configs[..num_faults + 1]
+            .iter()
+            .map(|config| common::context_and_sent(config).0)
+            .collect();
+        let votes = cxs
+            .iter()
+            .map(|cx| Vote {
+                msg: hash.to_vec(),
+                origin: cx.myid,
+                auth: cx.vote_auth(&hash),
+            })
+            .collect();
+        let cert = Certificate { votes };
+        let checker = &cxs[0];
+        group.bench_with_input(BenchmarkId::from_parameter(n), &cert, |b, cert| {
+            b.iter(|| assert!(checker.certifies(cert, &hash)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    name = benches;
+    config = Criterion::default().sample_size(BENCH_COUNT);
+    targets = sig_sign, sig_verify, sig_certificate);
+criterion_main!(benches);
@@ -1,10 +1,14 @@
 use super::context::Context;
+use bytes::Bytes;
 use crypto::*;
 use crypto_lib::PublicKey;
 use reed_solomon_erasure::galois_8::ReedSolomon;
 use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Notify;
 use types::{DataWithAcc, Replica, SignedData};
-use util::io::to_bytes;
+use util::io::canonical_bytes;
 
 pub fn to_shards(data: &[u8], num_nodes: usize, num_faults: usize) -> Vec<Vec<u8>> {
     let num_data_shards = num_nodes - num_faults;
@@ -34,7 +38,8 @@ pub fn from_shards(mut data: Vec<Option<Vec<u8>>>, num_nodes: usize, num_faults:
     for shard in 0..num_data_shards {
         result.append(&mut data[shard].clone().unwrap());
     }
-    result.truncate(result.len() - *result.last().unwrap() as usize);
+    // A bad shard can leave any padding length here
+    result.truncate(result.len().saturating_sub(*result.last().unwrap() as usize));
     result
 }
 
@@ -53,6 +58,20 @@ mod tests {
         let reconstructed = super::from_shards(received, 4, 1);
         assert_eq!(array.to_vec(), reconstructed);
     }
+
+    #[test]
+    fn ack_is_not_a_vote() {
+        use crypto::hash::{with_domain, ACK_DOMAIN, VOTE_DOMAIN};
+        use crypto_lib::{ed25519, Keypair, PublicKey};
+        let kp = ed25519::Keypair::generate();
+        let pk = PublicKey::Ed25519(kp.public());
+        let sk = Keypair::Ed25519(kp);
+        let msg = crypto::hash::ser_and_hash(&"commit");
+        let ack = sk.sign(&with_domain(ACK_DOMAIN, &msg)).unwrap();
+        assert!(pk.verify(&with_domain(ACK_DOMAIN, &msg), &ack));
+        assert!(!pk.verify(&with_domain(VOTE_DOMAIN, &msg), &ack));
+        assert!(!pk.verify(&msg, &ack));
+    }
 }
 
 pub fn get_size(num_nodes: Replica) -> Replica {
@@ -63,15 +82,31 @@ pub fn get_size(num_nodes: Replica) -> Replica {
     n + 1
 }
 
-pub fn get_acc<T: Serialize>(cx: &Context, data: &T) -> (Vec<Vec<u8>>, DataWithAcc) {
-    let shards = to_shards(
-        &to_bytes(data),
-        cx.num_nodes as usize,
-        cx.num_faults as usize,
-    );
-    let size = get_size(cx.num_nodes) as usize;
+/// The shards `data` is split into, one per replica, or why it could not
+/// be serialized
+pub fn shards_of<T: Serialize>(
+    data: &T,
+    num_nodes: Replica,
+    num_faults: Replica,
+) -> bincode::Result<Vec<Vec<u8>>> {
+    Ok(to_shards(&canonical_bytes(data)?, num_nodes as usize, num_faults as usize))
+}
+
+pub fn get_acc<T: Serialize>(
+    cx: &Context,
+    data: &T,
+    domain: &[u8],
+) -> bincode::Result<(Vec<Vec<u8>>, DataWithAcc)> {
+    let shards = shards_of(data, cx.num_nodes, cx.num_faults)?;
+    let acc = acc_from_shards(cx, &shards, domain);
+    Ok((shards, acc))
+}
+
+// The accumulator tree over `shards`, one per replica, and its height
+fn acc_tree(num_nodes: Replica, shards: &[Vec<u8>]) -> (Vec<Vec<u8>>, usize) {
+    let size = get_size(num_nodes) as usize;
     let mut tree = vec![Vec::new(); (1 << size) + 1];
-    for i in 0..cx.num_nodes as usize {
+    for i in 0..num_nodes as usize {
         tree[1 << size - 1 | i] = hash::ser_and_hash(&shards[i]).to_vec();
     }
     for i in 0..(1 << size - 1) - 1 {
@@ -79,17 +114,38 @@ pub fn get_acc<T: Serialize>(cx: &Context, data: &T) -> (Vec<Vec<u8>>, DataWithA
         tree[index] =
             hash::ser_and_hash(&(tree[index << 1].clone(), tree[index << 1 | 1].clone())).to_vec();
     }
-    (
-        shards,
-        DataWithAcc {
-            sign: cx
-                .my_secret_key
-                .sign(&hash::ser_and_hash(&tree[1]))
-                .unwrap(),
-            tree: tree,
-            size: size as Replica,
-        },
-    )
+    (tree, size)
+}
+
+/// Signs an accumulator over `shards` as they are, one per replica
+pub fn acc_from_shards(cx: &Context, shards: &[Vec<u8>], domain: &[u8]) -> DataWithAcc {
+    let (tree, size) = acc_tree(cx.num_nodes, shards);
+    DataWithAcc {
+        sign: cx
+            .my_secret_key
+            .sign(&hash::with_domain(domain, &hash::ser_and_hash(&tree[1])))
+            .unwrap(),
+        tree: tree,
+        size: size as Replica,
+    }
+}
+
+/// Whether `acc` is the accumulator over `data` that `pk` signed
+pub fn check_acc<T: Serialize>(
+    num_nodes: Replica,
+    num_faults: Replica,
+    data: &T,
+    acc: &DataWithAcc,
+    pk: &PublicKey,
+    domain: &[u8],
+) -> bool {
+    let shards = match shards_of(data, num_nodes, num_faults) {
+        Ok(shards) => shards,
+        Err(_) => return false,
+    };
+    let (tree, _) = acc_tree(num_nodes, &shards);
+    acc.tree.get(1) == Some(&tree[1])
+        && pk.verify(&hash::with_domain(domain, &hash::ser_and_hash(&tree[1])), &acc.sign)
 }
 
 pub fn get_sign(acc: &DataWithAcc, n: Replica) -> SignedData {
@@ -107,33 +163,105 @@ pub fn get_sign(acc: &DataWithAcc, n: Replica) -> SignedData {
     }
 }
 
+/// Checks that `sh` is the shard `sign` vouches for, in an accumulator `pk`
+/// signed under `domain`. Needs no gatherer, so it can run anywhere.
+pub fn verify_share(domain: &[u8], sh: &Bytes, pk: &PublicKey, sign: &SignedData) -> Result<(), &'static str> {
+    // The hash should match with the sign.
+    if hash::ser_and_hash(sh).to_vec() != sign.start {
+        return Err("The hash of the shard does not match");
+    }
+    if !pk.verify(
+        &hash::with_domain(domain, &hash::ser_and_hash(&sign.chain.last().unwrap().1)),
+        &sign.sign,
+    ) {
+        return Err("The signature of the shard does not match");
+    }
+    // The share should match with the accumulator.
+    let mut change = sign.index;
+    change >>= 1;
+    for i in 0..sign.chain.len() - 1 {
+        let h = if change & 1 == 0 {
+            hash::ser_and_hash(&(sign.chain[i].1.clone(), sign.chain[i + 1].0.clone())).to_vec()
+        } else {
+            hash::ser_and_hash(&(sign.chain[i + 1].0.clone(), sign.chain[i].1.clone())).to_vec()
+        };
+        if h != sign.chain[i + 1].1 {
+            return Err("Accumulator value does not match");
+        }
+        change >>= 1;
+    }
+    Ok(())
+}
+
+/// Drops a share that did not check out
+pub fn reject_share(reason: &str) {
+    println!("[WARN] {}.", reason);
+    debug_assert!(false);
+}
+
+/// How far a gatherer is with the current round
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GathererStatus {
+    /// Shards added since the last `clear`
+    pub received: usize,
+    /// Shards needed to reconstruct
+    pub threshold: usize,
+    /// Where the shards came from, in order
+    pub contributors: Vec<Replica>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ShareGatherer {
     pub size: Replica,
+    pub domain: Vec<u8>,
     pub reference: Option<(Vec<u8>, Vec<u8>)>,
-    pub shard: Vec<Option<Vec<u8>>>,
+    pub shard: Vec<Option<Bytes>>,
+    /// The proof each shard came with, so that it can be passed on
+    pub signs: Vec<Option<SignedData>>,
     pub shard_num: Replica,
+    /// Number of shards needed to reconstruct
+    pub threshold: Replica,
+    // Notified once when `shard_num` reaches `threshold`
+    #[serde(skip)]
+    ready: Arc<Notify>,
 }
 
 impl ShareGatherer {
-    pub fn new(num_nodes: Replica) -> Self {
+    pub fn new(num_nodes: Replica, threshold: Replica, domain: &[u8]) -> Self {
         ShareGatherer {
             size: num_nodes,
+            domain: domain.to_vec(),
             reference: None,
             shard: vec![None; num_nodes as usize],
+            signs: vec![None; num_nodes as usize],
             shard_num: 0,
+            threshold,
+            ready: Arc::new(Notify::new()),
         }
     }
 
     pub fn clear(&mut self) {
         self.reference = None;
         self.shard = vec![None; self.size as usize];
+        self.signs = vec![None; self.size as usize];
         self.shard_num = 0;
+        // A notification for the old shards must not leak into the new round
+        self.ready = Arc::new(Notify::new());
+    }
+
+    /// Completes once enough shards to reconstruct have been added since the
+    /// last `clear`. The notification is given exactly once, so only one of
+    /// the futures returned by this completes for each round. The future
+    /// does not borrow the gatherer and can be raced against anything that
+    /// changes it.
+    pub fn ready(&self) -> impl Future<Output = ()> + 'static {
+        let ready = Arc::clone(&self.ready);
+        async move { ready.notified().await }
     }
 
     pub fn add_share(
         &mut self,
-        sh: Vec<u8>,
+        sh: Bytes,
         n: Replica,
         _pp: &EVSSPublicParams381,
         pk: &PublicKey,
@@ -142,18 +270,17 @@ impl ShareGatherer {
         if self.shard[n as usize].is_some() {
             return;
         }
-        // The hash should match with the sign.
-        if hash::ser_and_hash(&sh).to_vec() != sign.start {
-            println!("[WARN] The hash of the shard does not match.");
-            debug_assert!(false);
-            return;
+        match verify_share(&self.domain, &sh, pk, &sign) {
+            Ok(()) => self.insert_verified(sh, n, sign),
+            Err(e) => reject_share(e),
         }
-        if !pk.verify(
-            &hash::ser_and_hash(&sign.chain.last().unwrap().1),
-            &sign.sign,
-        ) {
-            println!("[WARN] The signature of the shard does not match.");
-            debug_assert!(false);
+    }
+
+    /// Adds a share `verify_share` accepted. Only the first share at each
+    /// index counts, and a share signed differently from the first one is
+    /// equivocation.
+    pub fn insert_verified(&mut self, sh: Bytes, n: Replica, sign: SignedData) {
+        if self.shard[n as usize].is_some() {
             return;
         }
         if self.reference.is_none() {
@@ -163,30 +290,40 @@ impl ShareGatherer {
             ));
         } else {
             if self.reference.as_ref().unwrap().1 != sign.sign {
-                println!("[WARN] Equivocation detected.");
-                debug_assert!(false);
                 // TODO: Broadcast the blame.
+                reject_share("Equivocation detected");
                 return;
             }
         }
-        // The share should match with the accumulator.
-        let mut change = sign.index;
-        change >>= 1;
-        for i in 0..sign.chain.len() - 1 {
-            let h = if change & 1 == 0 {
-                hash::ser_and_hash(&(sign.chain[i].1.clone(), sign.chain[i + 1].0.clone())).to_vec()
-            } else {
-                hash::ser_and_hash(&(sign.chain[i + 1].0.clone(), sign.chain[i].1.clone())).to_vec()
-            };
-            if h != sign.chain[i + 1].1 {
-                println!("[WARN] Accumulator value does not match.");
-                debug_assert!(false);
-                return;
-            }
-            change >>= 1;
-        }
         self.shard[n as usize] = Some(sh);
+        self.signs[n as usize] = Some(sign);
         self.shard_num += 1;
+        if self.shard_num == self.threshold {
+            self.ready.notify_one();
+        }
+    }
+
+    /// Shard `n` with its proof, if we have it
+    pub fn held(&self, n: Replica) -> Option<(Bytes, SignedData)> {
+        let sh = self.shard.get(n as usize)?.clone()?;
+        let sign = self.signs.get(n as usize)?.clone()?;
+        Some((sh, sign))
+    }
+
+    /// The shards added so far. Contributors are shard indices, which
+    /// `Context::shard_owner` maps to replicas.
+    pub fn progress(&self) -> GathererStatus {
+        GathererStatus {
+            received: self.shard_num as usize,
+            threshold: self.threshold as usize,
+            contributors: self
+                .shard
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| s.is_some())
+                .map(|(n, _)| n as Replica)
+                .collect(),
+        }
     }
 
     pub fn reconstruct(&mut self, num_nodes: Replica, num_faults: Replica) -> Option<Vec<u8>> {
@@ -194,9 +331,60 @@ impl ShareGatherer {
             return None;
         }
         Some(from_shards(
-            self.shard.clone(),
+            self.shard
+                .iter()
+                .map(|s| s.as_ref().map(|b| b.to_vec()))
+                .collect(),
             num_nodes as usize,
             num_faults as usize,
         ))
     }
+
+    /// Like `reconstruct`, but only returns what `check` accepts. If the
+    /// data from all the shards is rejected, it is rebuilt from other sets
+    /// of `num_nodes - num_faults` shards, up to `retries` of them, so a
+    /// shard that verified but does not fit the others can be left out.
+    pub fn reconstruct_checked<T>(
+        &mut self,
+        num_nodes: Replica,
+        num_faults: Replica,
+        retries: usize,
+        check: impl Fn(&[u8]) -> Option<T>,
+    ) -> Option<T> {
+        let bytes = self.reconstruct(num_nodes, num_faults)?;
+        if let Some(x) = check(&bytes) {
+            return Some(x);
+        }
+        let held: Vec<usize> = (0..self.shard.len()).filter(|i| self.shard[*i].is_some()).collect();
+        let k = (num_nodes - num_faults) as usize;
+        // Indices into `held` of the shards used, in lexicographic order
+        let mut pick: Vec<usize> = (0..k).collect();
+        for attempt in 0..retries {
+            let mut subset = vec![None; self.shard.len()];
+            for p in pick.iter() {
+                subset[held[*p]] = self.shard[held[*p]].as_ref().map(|b| b.to_vec());
+            }
+            let bytes = from_shards(subset, num_nodes as usize, num_faults as usize);
+            if let Some(x) = check(&bytes) {
+                log::warn!(target:"consensus", "Reconstructed from shards {:?} after {} retries", pick.iter().map(|p| held[*p]).collect::<Vec<_>>(), attempt + 1);
+                return Some(x);
+            }
+            // The next set, if there is one
+            let mut i = k;
+            loop {
+                if i == 0 {
+                    return None;
+                }
+                i -= 1;
+                if pick[i] < held.len() - k + i {
+                    break;
+                }
+            }
+            pick[i] += 1;
+            for j in i + 1..k {
+                pick[j] = pick[j - 1] + 1;
+            }
+        }
+        None
+    }
 }
@@ -0,0 +1,198 @@
+// A read-only Unix socket for looking at a live node. Every line sent to it
+// is one command, answered with one line of JSON:
+//
+//   status   everything below, plus the gatherers, the outbound backlog and
+//            the messages held back for being slow to handle
+//   epoch    the epoch, phase and leader
+//   leader   the leaders of this and the next few epochs
+//   peers    the last epoch each replica was heard from
+//   mempool  client transactions and reconfigurations not committed yet
+//   gatherers  the shards each gatherer has of this epoch's messages, by
+//            the replicas they came from and those still missing
+//   beacon <epoch>   the beacon of a past epoch, null if there is none
+//   beacons <from> <to>  the beacons of the epochs from one to the other,
+//            both included
+
+use super::context::Context;
+use super::offload::Gatherer;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+use types::Height;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Query {
+    Status,
+    Epoch,
+    Leader,
+    Peers,
+    Mempool,
+    Gatherers,
+    Beacon(Height),
+    Beacons(Height, Height),
+}
+
+impl Query {
+    pub fn parse(line: &str) -> Option<Query> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let epoch = |i: usize| words.get(i).and_then(|w| w.parse::<Height>().ok());
+        match words.as_slice() {
+            ["status"] => Some(Query::Status),
+            ["epoch"] => Some(Query::Epoch),
+            ["leader"] => Some(Query::Leader),
+            ["peers"] => Some(Query::Peers),
+            ["mempool"] => Some(Query::Mempool),
+            ["gatherers"] => Some(Query::Gatherers),
+            ["beacon", _] => Some(Query::Beacon(epoch(1)?)),
+            ["beacons", _, _] => Some(Query::Beacons(epoch(1)?, epoch(2)?)),
+            _ => None,
+        }
+    }
+}
+
+/// A query on its way to the reactor, which owns the context
+pub struct AdminRequest {
+    pub query: Query,
+    pub reply: oneshot::Sender<String>,
+}
+
+/// Answers `query` from the state the reactor is in
+pub fn answer(cx: &Context, phase: &str, query: Query) -> String {
+    let epoch = json!({
+        "epoch": cx.epoch,
+        "phase": phase,
+        "leader": cx.last_leader,
+    });
+    let value = match query {
+        Query::Epoch => epoch,
+        Query::Leader => json!({
+            "epoch": cx.epoch,
+            "leader": cx.current_leader(),
+            "next_leader": cx.next_leader(),
+            "schedule": (cx.epoch..cx.epoch + cx.num_nodes as Height)
+                .map(|e| json!({ "epoch": e, "leader": cx.leader_for_epoch(e) }))
+                .collect::<Vec<_>>(),
+        }),
+        Query::Peers => peers(cx),
+        Query::Mempool => mempool(cx),
+        Query::Gatherers => json!({
+            "epoch": cx.epoch,
+            "propose": gatherer(cx, Gatherer::Propose),
+            "vote_cert": gatherer(cx, Gatherer::VoteCert),
+            "commit": gatherer(cx, Gatherer::Commit),
+        }),
+        Query::Beacon(e) => json!(cx.beacons.get_beacon(e)),
+        Query::Beacons(from, to) => json!(cx.beacons.range(from..=to)),
+        Query::Status => json!({
+            "id": cx.myid,
+            "epoch": epoch,
+            "height": cx.height,
+            "finalized_height": cx.finalized_height,
+            "peers": peers(cx),
+            "mempool": mempool(cx),
+            "gatherers": {
+                "threshold": cx.propose_gatherer.threshold,
+                "propose": cx.propose_gatherer.shard_num,
+                "vote_cert": cx.vote_cert_gatherer.shard_num,
+                "commit": cx.commit_gatherer.shard_num,
+            },
+            "backlog": cx.backlog.len(),
+            "deferred": cx.handler_budget.deferred(),
+            "catching_up_to": cx.quarantine,
+            "dropped_sends": cx.dropped_sends,
+            "invalid_signatures": cx.invalid_signatures.load(Ordering::Relaxed),
+        }),
+    };
+    value.to_string()
+}
+
+fn peers(cx: &Context) -> Value {
+    let peers: Vec<Value> = cx
+        .active_replicas
+        .iter()
+        .cloned()
+        .filter(|i| *i != cx.myid)
+        .map(|i| json!({ "id": i, "last_heard": cx.last_heard.get(&i) }))
+        .collect();
+    Value::Array(peers)
+}
+
+fn gatherer(cx: &Context, kind: Gatherer) -> Value {
+    let status = cx.gatherer_progress(kind);
+    let missing: Vec<_> = cx
+        .active_replicas
+        .iter()
+        .filter(|r| !status.contributors.contains(r))
+        .collect();
+    json!({
+        "received": status.received,
+        "threshold": status.threshold,
+        "contributors": status.contributors,
+        "missing": missing,
+    })
+}
+
+fn mempool(cx: &Context) -> Value {
+    json!({
+        "transactions_received": cx.transactions_received,
+        "pending_transactions": cx.storage.pending_tx.len(),
+        "pending_reconfig": cx.pending_reconfig.len(),
+        "sink_overflows": cx.commit_sink.overflows(),
+    })
+}
+
+/// Listens on `path`, handing every command to the reactor through
+/// `requests`. Connections are closed once the reactor is gone. A socket
+/// file left behind by an earlier run is replaced.
+pub async fn serve(path: PathBuf, requests: Sender<AdminRequest>) {
+    let _ = std::fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(l) => l,
+        Err(e) => {
+            log::error!(target:"consensus", "Failed to open the admin socket at {}: {}", path.display(), e);
+            return;
+        }
+    };
+    loop {
+        let conn = match listener.accept().await {
+            Ok((conn, _)) => conn,
+            Err(e) => {
+                log::warn!(target:"consensus", "Failed to accept an admin connection: {}", e);
+                continue;
+            }
+        };
+        let requests = requests.clone();
+        tokio::spawn(async move {
+            if let Err(e) = session(conn, requests).await {
+                log::debug!(target:"consensus", "Admin connection closed: {}", e);
+            }
+        });
+    }
+}
+
+async fn session(conn: UnixStream, requests: Sender<AdminRequest>) -> std::io::Result<()> {
+    let (read, mut write) = conn.into_split();
+    let mut lines = BufReader::new(read).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match Query::parse(&line) {
+            None => json!({ "error": format!("unknown command {:?}", line.trim()) }).to_string(),
+            Some(query) => {
+                let (reply, answer) = oneshot::channel();
+                if requests.send(AdminRequest { query, reply }).await.is_err() {
+                    return Ok(());
+                }
+                match answer.await {
+                    Ok(a) => a,
+                    Err(_) => return Ok(()),
+                }
+            }
+        };
+        write.write_all(response.as_bytes()).await?;
+        write.write_all(b"\n").await?;
+    }
+    Ok(())
+}
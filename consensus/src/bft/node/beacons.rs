@@ -0,0 +1,145 @@
+// The history of the beacon, so that applications can look up the
+// randomness of past epochs, for instance to replay a lottery, without
+// running consensus again. The newest beacons are kept in memory. With a
+// file, every beacon is also appended to it as a line of JSON and the older
+// ones are read back from there. An epoch whose beacon could not be rebuilt
+// has none.
+
+use config::Node;
+use crypto::hash::Hash;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::ops::{Bound, RangeBounds};
+use std::path::{Path, PathBuf};
+use types::Height;
+
+/// The beacon of an epoch
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BeaconOutput {
+    pub epoch: Height,
+    pub value: Hash,
+}
+
+pub struct BeaconLog {
+    recent: BTreeMap<Height, Hash>,
+    // Beacons kept in memory
+    depth: usize,
+    file: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl BeaconLog {
+    /// A log that keeps the newest `depth` beacons and forgets older ones
+    pub fn in_memory(depth: usize) -> Self {
+        BeaconLog {
+            recent: BTreeMap::new(),
+            depth,
+            file: None,
+        }
+    }
+
+    /// A log that appends to the file at `path`, creating it if needed, and
+    /// keeps the newest `depth` beacons in memory. The beacons already in
+    /// the file stay part of the history.
+    pub fn open(path: &Path, depth: usize) -> io::Result<Self> {
+        let mut log = BeaconLog::in_memory(depth);
+        if path.exists() {
+            log.recent = read_file(path)?;
+            log.prune();
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        log.file = Some((path.to_path_buf(), BufWriter::new(file)));
+        Ok(log)
+    }
+
+    /// The log the config asks for. A file that cannot be opened is fatal,
+    /// since the operator is counting on the history.
+    pub fn from_config(config: &Node) -> Self {
+        match config.beacon_log.as_ref() {
+            Some(path) => match BeaconLog::open(Path::new(path), config.beacon_history()) {
+                Ok(log) => log,
+                Err(e) => panic!("Failed to open the beacon log at {}: {}", path, e),
+            },
+            None => BeaconLog::in_memory(config.beacon_history()),
+        }
+    }
+
+    /// Records the beacon of `epoch`. It is in the file once this returns.
+    pub fn append(&mut self, epoch: Height, value: Hash) -> io::Result<()> {
+        self.recent.insert(epoch, value);
+        self.prune();
+        if let Some((_, file)) = self.file.as_mut() {
+            serde_json::to_writer(&mut *file, &BeaconOutput { epoch, value })?;
+            file.write_all(b"\n")?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    fn prune(&mut self) {
+        while self.recent.len() > self.depth {
+            let oldest = *self.recent.keys().next().unwrap();
+            self.recent.remove(&oldest);
+        }
+    }
+
+    /// The beacon of `epoch`, if it was produced and is still in the history
+    pub fn get_beacon(&self, epoch: Height) -> Option<BeaconOutput> {
+        match self.recent.get(&epoch) {
+            Some(value) => Some(BeaconOutput { epoch, value: *value }),
+            None => self.range(epoch..=epoch).pop(),
+        }
+    }
+
+    /// The beacons of `epochs` in the history, oldest first
+    pub fn range(&self, epochs: impl RangeBounds<Height>) -> Vec<BeaconOutput> {
+        let in_memory = match (epochs.start_bound(), self.recent.keys().next()) {
+            (_, None) => false,
+            (Bound::Included(start), Some(oldest)) => start >= oldest,
+            (Bound::Excluded(start), Some(oldest)) => start + 1 >= *oldest,
+            (Bound::Unbounded, Some(_)) => false,
+        };
+        let from_file;
+        let beacons = match &self.file {
+            Some((path, _)) if !in_memory => match read_file(path) {
+                Ok(all) => {
+                    from_file = all;
+                    &from_file
+                }
+                Err(e) => {
+                    log::error!(target:"consensus", "Cannot read the beacon log at {}: {}", path.display(), e);
+                    &self.recent
+                }
+            },
+            _ => &self.recent,
+        };
+        beacons
+            .range(epochs)
+            .map(|(epoch, value)| BeaconOutput { epoch: *epoch, value: *value })
+            .collect()
+    }
+
+    /// Number of beacons in memory
+    pub fn len(&self) -> usize {
+        self.recent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recent.is_empty()
+    }
+}
+
+/// The beacons in the file at `path`. A line cut short, as by a crash while
+/// it was written, ends the log; a later line for an epoch replaces an
+/// earlier one.
+fn read_file(path: &Path) -> io::Result<BTreeMap<Height, Hash>> {
+    let mut beacons = BTreeMap::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        match serde_json::from_str::<BeaconOutput>(&line?) {
+            Ok(beacon) => beacons.insert(beacon.epoch, beacon.value),
+            Err(_) => break,
+        };
+    }
+    Ok(beacons)
+}
@@ -0,0 +1,80 @@
+// A soft time budget for handling one message. Handlers run to completion
+// on the reactor, so a message that takes too long cannot be interrupted;
+// instead, further messages of its kind wait until nothing else is queued,
+// so that they no longer hold up everything else.
+
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+use types::{Height, ProtocolMsg, Replica};
+
+/// Messages that may wait behind all others at once; beyond this they are
+/// handled right away again
+pub const MAX_DEFERRED: usize = 10_000;
+
+pub struct HandlerBudget {
+    limit: Option<Duration>,
+    /// Kinds of message whose last handling went over the budget
+    slow: HashSet<&'static str>,
+    deferred: VecDeque<(Replica, ProtocolMsg)>,
+}
+
+impl HandlerBudget {
+    /// `None` handles everything as it comes
+    pub fn new(limit: Option<Duration>) -> Self {
+        HandlerBudget {
+            limit,
+            slow: HashSet::new(),
+            deferred: VecDeque::new(),
+        }
+    }
+
+    /// Hands `msg` back to be handled now, or keeps it for later if its
+    /// kind is slow
+    pub fn admit(&mut self, origin: Replica, msg: ProtocolMsg) -> Option<(Replica, ProtocolMsg)> {
+        if !self.slow.contains(msg.to_string()) || self.deferred.len() >= MAX_DEFERRED {
+            return Some((origin, msg));
+        }
+        self.deferred.push_back((origin, msg));
+        None
+    }
+
+    /// Notes that handling a message of `kind` from `origin` took `took`.
+    /// Going over the budget defers the kind, coming in under it again
+    /// stops deferring it.
+    pub fn record(&mut self, kind: &'static str, origin: Replica, took: Duration) {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => return,
+        };
+        if took > limit {
+            if self.slow.insert(kind) {
+                println!(
+                    "[WARN] Handling {} from {} took {} ms; deferring them behind other messages.",
+                    kind,
+                    origin,
+                    took.as_millis()
+                );
+            }
+        } else {
+            self.slow.remove(kind);
+        }
+    }
+
+    pub fn has_deferred(&self) -> bool {
+        !self.deferred.is_empty()
+    }
+
+    pub fn deferred(&self) -> usize {
+        self.deferred.len()
+    }
+
+    /// The oldest deferred message that is not for an epoch before `epoch`
+    pub fn next_deferred(&mut self, epoch: Height) -> Option<(Replica, ProtocolMsg)> {
+        while let Some((origin, msg)) = self.deferred.pop_front() {
+            if msg.epoch() >= epoch {
+                return Some((origin, msg));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,59 @@
+// The tip a node proposes on, saved so that it survives a restart. Without
+// it a restarted leader would propose on top of genesis.
+
+use super::sealing::{self, Sealer};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use types::{Block, Certificate};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The highest certificate we know of
+    pub cert: Certificate,
+    /// The block `cert` certifies
+    pub block: Block,
+}
+
+/// Writes `checkpoint` to `path`, replacing the previous one only once the
+/// new one is completely written. With a `sealer` it is written encrypted.
+pub fn save(path: &Path, checkpoint: &Checkpoint, sealer: Option<&mut Sealer>) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut bytes = bincode::serialize(checkpoint)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    if let Some(sealer) = sealer {
+        bytes = sealer.seal(&bytes);
+    }
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// The checkpoint at `path`, if there is one and it decodes. An encrypted
+/// one is only read with a `sealer` for the passphrase it was written with.
+pub fn load(path: &Path, sealer: Option<&mut Sealer>) -> Option<Checkpoint> {
+    let mut bytes = std::fs::read(path).ok()?;
+    match (sealing::is_sealed(&bytes), sealer) {
+        (true, Some(sealer)) => match sealer.open(&bytes) {
+            Ok(plain) => bytes = plain,
+            Err(e) => {
+                log::warn!(target:"consensus", "Ignoring the checkpoint at {}: {}", path.display(), e);
+                return None;
+            }
+        },
+        (true, None) => {
+            log::warn!(target:"consensus", "Ignoring the checkpoint at {}: it is encrypted and no passphrase is configured", path.display());
+            return None;
+        }
+        // Written before encryption was turned on; the next save seals it
+        (false, Some(_)) => {
+            log::warn!(target:"consensus", "The checkpoint at {} is not encrypted", path.display());
+        }
+        (false, None) => (),
+    }
+    match bincode::deserialize(&bytes) {
+        Ok(checkpoint) => Some(checkpoint),
+        Err(e) => {
+            log::warn!(target:"consensus", "Ignoring the unreadable checkpoint at {}: {}", path.display(), e);
+            None
+        }
+    }
+}
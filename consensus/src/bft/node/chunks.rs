@@ -0,0 +1,110 @@
+// Sending the next leader's commitments and shares in pieces, for clusters
+// where one Commit message would be too large to frame or to hold at once
+
+use std::collections::{BTreeMap, VecDeque};
+use types::{DataWithAcc, Height, ProtocolMsg};
+
+type Shares = VecDeque<crypto::EVSSShare381>;
+type Commits = Vec<crypto::EVSSCommit381>;
+
+/// Splits what would be one `Commit` message into `CommitChunk`s of at most
+/// `chunk` shares and commitments each. The accumulator signature goes with
+/// the first chunk only.
+pub fn split_commit(
+    shares: &Shares,
+    commits: &Commits,
+    sign: &DataWithAcc,
+    epoch: Height,
+    chunk: usize,
+) -> Vec<ProtocolMsg> {
+    let chunk = chunk.max(1);
+    let total = ((commits.len() + chunk - 1) / chunk).max(1) as u16;
+    (0..total)
+        .map(|index| {
+            let start = index as usize * chunk;
+            let end = (start + chunk).min(commits.len());
+            ProtocolMsg::CommitChunk(
+                epoch,
+                index,
+                total,
+                shares.range(start..end.min(shares.len())).cloned().collect(),
+                commits[start..end].to_vec(),
+                if index == 0 { Some(sign.clone()) } else { None },
+            )
+        })
+        .collect()
+}
+
+/// Collects the chunks of one epoch until the whole `Commit` is there
+pub struct CommitAssembler {
+    // Number of shares and commitments a complete `Commit` has
+    expected: usize,
+    epoch: Height,
+    total: u16,
+    chunks: BTreeMap<u16, (Shares, Commits)>,
+    sign: Option<DataWithAcc>,
+}
+
+impl CommitAssembler {
+    pub fn new(expected: usize) -> Self {
+        CommitAssembler {
+            expected,
+            epoch: 0,
+            total: 0,
+            chunks: BTreeMap::new(),
+            sign: None,
+        }
+    }
+
+    /// Adds a chunk. Chunks from an older epoch than the one being assembled
+    /// are dropped, a newer one starts over. Returns the reassembled shares,
+    /// commitments and signature once every chunk is in and they add up to
+    /// the expected number of each.
+    pub fn add(
+        &mut self,
+        epoch: Height,
+        index: u16,
+        total: u16,
+        shares: Shares,
+        commits: Commits,
+        sign: Option<DataWithAcc>,
+    ) -> Option<(Shares, Commits, DataWithAcc)> {
+        if epoch < self.epoch {
+            return None;
+        }
+        if epoch > self.epoch || self.chunks.is_empty() && self.sign.is_none() {
+            self.epoch = epoch;
+            self.total = total;
+            self.chunks.clear();
+            self.sign = None;
+        }
+        if total != self.total || index >= total || self.chunks.contains_key(&index) {
+            log::warn!(target:"consensus", "Dropping commit chunk {}/{} for epoch {}", index, total, epoch);
+            return None;
+        }
+        if shares.len() != commits.len() {
+            log::warn!(target:"consensus", "Commit chunk {} has {} shares for {} commitments", index, shares.len(), commits.len());
+            return None;
+        }
+        if let Some(sign) = sign {
+            self.sign = Some(sign);
+        }
+        self.chunks.insert(index, (shares, commits));
+        if self.chunks.len() < self.total as usize || self.sign.is_none() {
+            return None;
+        }
+        let expected = self.expected;
+        let mut all_shares = VecDeque::with_capacity(expected);
+        let mut all_commits = Vec::with_capacity(expected);
+        for (_, (mut s, mut c)) in std::mem::take(&mut self.chunks) {
+            all_shares.append(&mut s);
+            all_commits.append(&mut c);
+        }
+        let sign = self.sign.take().unwrap();
+        if all_commits.len() != expected {
+            log::warn!(target:"consensus", "Reassembled {} commitments, expected {}", all_commits.len(), expected);
+            return None;
+        }
+        Some((all_shares, all_commits, sign))
+    }
+}
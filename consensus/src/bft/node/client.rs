@@ -0,0 +1,189 @@
+// Serving clients on a runtime of its own. Client connections then compete
+// for threads with each other rather than with the reactor and the peer
+// network, so a flood of client traffic cannot delay a phase. Transactions
+// reach the reactor over a channel either way.
+//
+// Every committed block goes to every client. The blocks are written by a
+// fixed pool of `client_fanout` workers rather than a task per client per
+// block, so a burst of commits with many clients connected does not spawn a
+// storm of tasks. A client that does not take a block within
+// `client_write_timeout` is dropped, and holds up no worker after that.
+//
+// At most `max_clients` connections are served at once. What happens to one
+// more is up to `client_overflow`: it is told the node is busy and closed,
+// or it takes the slot of the connection idle longest, which is closed.
+
+use super::intake::TxSender;
+use bytes::{Bytes, BytesMut};
+use config::Node;
+use futures::StreamExt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::{Builder, Handle, Runtime};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio_util::codec::{Encoder, FramedRead};
+use types::{Block, ClientMsg};
+use util::admission::{Admission, Admit, Ticket};
+use util::codec::{tx, EnCodec};
+use util::fanout::Fanout;
+
+/// Where client connections are served
+pub enum ClientRuntime {
+    /// On the consensus runtime
+    Shared(Handle),
+    /// On a runtime of their own
+    Dedicated(Runtime),
+}
+
+impl ClientRuntime {
+    /// A runtime with `threads` workers, or `consensus` itself for 0
+    pub fn new(threads: usize, consensus: Handle) -> std::io::Result<Self> {
+        if threads == 0 {
+            return Ok(ClientRuntime::Shared(consensus));
+        }
+        let rt = Builder::new_multi_thread()
+            .worker_threads(threads)
+            .thread_name("client")
+            .enable_all()
+            .build()?;
+        Ok(ClientRuntime::Dedicated(rt))
+    }
+
+    pub fn handle(&self) -> &Handle {
+        match self {
+            ClientRuntime::Shared(h) => h,
+            ClientRuntime::Dedicated(rt) => rt.handle(),
+        }
+    }
+
+    pub fn is_dedicated(&self) -> bool {
+        matches!(self, ClientRuntime::Dedicated(_))
+    }
+
+    /// Runs `setup` to completion on this runtime. Tasks it spawns, such as
+    /// the client listener, stay there.
+    pub fn block_on<F: Future>(&self, setup: F) -> F::Output {
+        self.handle().block_on(setup)
+    }
+}
+
+/// Serves clients on `listener`: what they send goes to `txs`, and the
+/// blocks sent on the returned channel go to all of them. Spawns on the
+/// runtime it is called on.
+pub async fn start(config: &Node, listener: TcpListener, txs: TxSender) -> UnboundedSender<Arc<Block>> {
+    let (send, blocks) = unbounded_channel();
+    let fanout = Fanout::new(config.client_fanout, config.client_write_timeout());
+    tokio::spawn(serve(listener, Admission::from_config(config), txs, blocks, fanout));
+    send
+}
+
+async fn serve(
+    listener: TcpListener,
+    admission: Admission,
+    txs: TxSender,
+    mut blocks: UnboundedReceiver<Arc<Block>>,
+    mut fanout: Fanout<ClientWriter>,
+) {
+    let mut codec = EnCodec::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, addr)) => match admission.admit() {
+                    Admit::Accepted(ticket) => {
+                        log::debug!(target:"net", "Serving the client at {}", addr);
+                        let ticket = Arc::new(ticket);
+                        let (read, write) = stream.into_split();
+                        fanout.add_client(ClientWriter {
+                            write,
+                            ticket: Arc::downgrade(&ticket),
+                        });
+                        tokio::spawn(take_transactions(read, ticket, txs.clone()));
+                    }
+                    Admit::Busy => {
+                        log::info!(target:"net", "Turning away the client at {}: serving as many as we take", addr);
+                        tokio::spawn(turn_away(stream));
+                    }
+                },
+                Err(e) => log::warn!(target:"net", "Failed to accept a client: {}", e),
+            },
+            block = blocks.recv() => match block {
+                Some(block) => {
+                    let mut buf = BytesMut::new();
+                    match codec.encode(block, &mut buf) {
+                        Ok(()) => fanout.send(Bytes::from(buf)),
+                        Err(e) => log::error!(target:"net", "Not sending a block to the clients: {}", e),
+                    }
+                }
+                None => return,
+            },
+        }
+    }
+}
+
+/// Hands what a client sends to the reactor until it goes away or loses its
+/// slot. The slot is given back once this returns.
+async fn take_transactions(read: OwnedReadHalf, ticket: Arc<Ticket>, txs: TxSender) {
+    let mut framed = FramedRead::new(read, tx::Codec::new());
+    let evicted = ticket.evicted();
+    tokio::pin!(evicted);
+    loop {
+        tokio::select! {
+            _ = &mut evicted => {
+                log::info!(target:"net", "Closing a client to make room for another");
+                return;
+            }
+            tx = framed.next() => match tx {
+                Some(Ok(tx)) => {
+                    ticket.touch();
+                    if txs.send(tx).await.is_err() {
+                        return;
+                    }
+                }
+                Some(Err(e)) => {
+                    log::warn!(target:"net", "Failed to read from a client: {}", e);
+                    return;
+                }
+                None => return,
+            },
+        }
+    }
+}
+
+/// Tells a client over the limit that the node is busy, and closes it
+async fn turn_away(mut stream: TcpStream) {
+    let busy = bincode::serialize(&ClientMsg::Busy).expect("failed to encode a client message");
+    if let Err(e) = stream.write_all(&busy).await {
+        log::debug!(target:"net", "Failed to tell a client the node is busy: {}", e);
+    }
+}
+
+/// The write half of a client connection, which fails once the client is
+/// gone or its slot was taken, so that the fan-out drops it
+struct ClientWriter {
+    write: OwnedWriteHalf,
+    ticket: Weak<Ticket>,
+}
+
+impl AsyncWrite for ClientWriter {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let me = self.get_mut();
+        if me.ticket.upgrade().map_or(true, |ticket| ticket.is_evicted()) {
+            return Poll::Ready(Err(io::Error::new(io::ErrorKind::ConnectionAborted, "the client was closed")));
+        }
+        Pin::new(&mut me.write).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().write).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().write).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,144 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use tokio::time::Instant;
+
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Where the reactor gets the current time and its phase deadlines from
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+    fn sleep_until(&self, deadline: Instant) -> Sleep;
+}
+
+/// Milliseconds since the Unix epoch by the system clock, which unlike
+/// `Clock` is comparable across machines
+pub fn wall_clock_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// The tokio clock
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Sleep {
+        Box::pin(tokio::time::sleep_until(deadline))
+    }
+}
+
+struct MockState {
+    now: Instant,
+    wakers: Vec<Waker>,
+}
+
+/// A clock that only moves when told to, for tests
+#[derive(Clone)]
+pub struct MockClock {
+    state: Arc<Mutex<MockState>>,
+    start: Instant,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        let start = Instant::now();
+        MockClock {
+            state: Arc::new(Mutex::new(MockState {
+                now: start,
+                wakers: Vec::new(),
+            })),
+            start,
+        }
+    }
+
+    /// Moves time forward by `by` and wakes every sleeper that is now due.
+    pub fn advance(&self, by: Duration) {
+        let wakers = {
+            let mut state = self.state.lock().unwrap();
+            state.now += by;
+            std::mem::take(&mut state.wakers)
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// How far the clock has been advanced in total
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().now - self.start
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct MockSleep {
+    state: Arc<Mutex<MockState>>,
+    deadline: Instant,
+}
+
+impl Future for MockSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.now >= self.deadline {
+            Poll::Ready(())
+        } else {
+            state.wakers.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.state.lock().unwrap().now
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Sleep {
+        Box::pin(MockSleep {
+            state: Arc::clone(&self.state),
+            deadline,
+        })
+    }
+}
+
+/// The deadline of the current phase
+pub struct Timer {
+    clock: Arc<dyn Clock>,
+    sleep: Sleep,
+}
+
+impl Timer {
+    pub fn new(clock: Arc<dyn Clock>, deadline: Instant) -> Self {
+        let sleep = clock.sleep_until(deadline);
+        Timer { clock, sleep }
+    }
+
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    pub fn reset(&mut self, deadline: Instant) {
+        self.sleep = self.clock.sleep_until(deadline);
+    }
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.sleep.as_mut().poll(cx)
+    }
+}
@@ -1,17 +1,38 @@
-use super::accumulator::ShareGatherer;
+use super::accumulator::{check_acc, get_acc, GathererStatus, ShareGatherer};
+use super::beacons::BeaconLog;
+use super::budget::HandlerBudget;
+use super::checkpoint::{self, Checkpoint};
+use super::chunks::CommitAssembler;
+use super::entropy;
+use super::events::{self, EpochSummary, EventSender, NodeEvent};
+use super::metrics::EpochMetrics;
+use super::offload::{Check, Gatherer};
+use super::prefetch::{PrefetchKey, Prefetched};
+use super::sealing::Sealer;
+use super::sink::SinkFeed;
+use super::verifier::Signature;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::convert::TryInto;
 
 // use crossfire::mpsc::{SharedSenderFRecvB, TxFuture};
+use crypto::rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use crypto::threshold;
+use crypto::CanonicalSerialize;
 use crypto_lib::{ed25519, secp256k1, Keypair, PublicKey};
-use tokio::sync::mpsc::UnboundedSender;
+use num_traits::Zero;
+use tokio::sync::mpsc::{error::TrySendError, Sender, UnboundedSender};
 // use crate::Sender;
-use config::Node;
+use config::{MessageHandling, Node, ParseError, MIN_NODES};
+use crypto::hash::{empty_hash, ser_and_hash, with_domain, Hash, ACK_DOMAIN, CERT_DOMAIN, HASH_SIZE, COMMIT_DOMAIN, PROPOSE_DOMAIN, RECONFIG_DOMAIN, SNAPSHOT_DOMAIN, VOTE_DOMAIN};
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use types::{
-    Block, Certificate, Height, Propose, ProtocolMsg, Replica, DataWithAcc, Storage, Vote,
-    GENESIS_BLOCK,
+    Block, BlockBody, BlockBuilder, Certificate, Content, Destination, Height, Propose, ProtocolMsg, Reconfig, Replica,
+    DataWithAcc, Storage, Snapshot, Transaction, Vote, commit_from_bytes, genesis_block,
 };
+use util::io::{canonical_bytes, encoded_len, sorted};
 
 // type Sender<T> = TxFuture<T, SharedFutureBoth>;
 
@@ -21,25 +42,72 @@ pub struct Context {
     pub myid: Replica,
     pub pub_key_map: HashMap<Replica, PublicKey>,
     pub my_secret_key: Keypair,
-    pub net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
-    pub cli_send: UnboundedSender<Block>,
+    /// Set when certificates are threshold signatures
+    pub threshold: Option<ThresholdKeys>,
+    pub crypto_alg: crypto::Algorithm,
+    pub net_send: Sender<(Destination, Arc<ProtocolMsg>)>,
+    /// Where committed blocks go out to the clients
+    pub cli_send: UnboundedSender<Arc<Block>>,
     pub is_client_apollo_enabled: bool,
 
     pub storage: Storage,
     pub height: Height,
     pub epoch: Height,
     pub last_leader: Replica,
+    /// Replicas that take turns being leader, sorted by id
+    pub active_replicas: Vec<Replica>,
+    /// Voting weight of each replica; empty when they all weigh the same
+    pub weights: HashMap<Replica, u64>,
+    /// With weights, the leader of epoch `e` is `leader_schedule[e % len]`
+    pub leader_schedule: Vec<Replica>,
+    /// The last block we committed with a vote certificate for it
     pub last_seen_block: Arc<Block>,
+    /// The vote certificate for `last_seen_block`, which we hand the next
+    /// leader to build on
+    pub last_seen_cert: Certificate,
     pub last_committed_block_ht: Height,
+    pub finalized_height: Height,
+    pub finality_depth: Height,
     pub payload: usize,
 
     pub highest_cert: Certificate,
     pub highest_height: Height,
+    /// Where the tip is saved to survive a restart
+    pub checkpoint: Option<PathBuf>,
+    /// Height of the block in the saved checkpoint
+    pub checkpoint_height: Height,
+    /// Encrypts the checkpoint, if a passphrase is configured
+    pub sealer: Option<Sealer>,
+    /// Whether certificates for blocks we have not committed are synced
+    /// instead of dropped
+    pub cert_sync: bool,
+    /// The replica we wait for a snapshot from, until one is installed
+    pub bootstrap: Option<Replica>,
+    /// The (encoded) public key of every replica, as handed out in
+    /// snapshots
+    pub member_keys: HashMap<Replica, Vec<u8>>,
+    /// Whether the leader votes for its own proposal
+    pub self_vote: bool,
+    /// Whether the next leader's commitments are taken unchecked
+    pub trust_commits: bool,
+    /// Whether shards and commitments are checked off the reactor
+    pub offload_shares: bool,
+    /// Checks for the share checker, handed over after each message
+    pub pending_checks: VecDeque<Check>,
+    /// Certificates waiting for the block we asked for, by block hash, with
+    /// the epoch we asked in
+    pub pending_certs: HashMap<Hash, (Certificate, Height)>,
+    /// Holds back messages of kinds that took too long to handle
+    pub handler_budget: HandlerBudget,
 
     pub received_propose: Option<Propose>,
     pub received_propose_sign: Option<DataWithAcc>,
 
-    pub received_vote: Vec<Vote>,
+    /// Votes for our proposals by epoch, in the order they arrived. Only
+    /// the current epoch and the next are kept.
+    pub received_vote: BTreeMap<Height, Vec<Vote>>,
+    /// The last epoch we formed a vote certificate for
+    pub vote_certified: Option<Height>,
     pub received_ack: Vec<Vote>,
 
     pub received_certificate: Option<Certificate>,
@@ -54,72 +122,411 @@ pub struct Context {
     pub propose_share_sent: bool,
     pub vote_cert_share_sent: bool,
     pub commit_share_sent: bool,
+    /// Whether we acknowledged the next leader's commitments this epoch
+    pub ack_sent: bool,
 
     pub propose_gatherer: ShareGatherer,
     pub vote_cert_gatherer: ShareGatherer,
     pub commit_gatherer: ShareGatherer,
     
     pub rand_beacon_parameter: crypto::EVSSParams381,
-    pub rand_beacon_queue: HashMap<Replica, std::collections::VecDeque<crypto::EVSSShare381>>,
+    /// Our shares of each dealer's beacons still to be used, one per epoch
+    pub rand_beacon_queue: HashMap<Replica, VecDeque<Dealt>>,
+    /// Which of our own deals each of the last epochs used, if any, so that
+    /// a replica that skipped ahead can be told where our queue stood
+    pub used_deals: VecDeque<(Height, Option<(Height, usize)>)>,
+    /// Dealers we asked where their queue stands after skipping ahead, and
+    /// have not heard back from
+    pub deal_sync: HashSet<Replica>,
+    /// What the reconstruction shares of an epoch are checked against: the
+    /// commitment of each dealer's share, in dealer order
+    pub beacon_commits: BTreeMap<Height, Vec<crypto::EVSSCommit381>>,
 
-    pub reconstruct_queue: std::collections::VecDeque<(crypto::EVSSShare381, Height)>,
+    /// Reconstruction shares by the epoch they are for
+    pub reconstruct_queue: std::collections::BTreeMap<Height, Vec<crypto::EVSSShare381>>,
+    /// Replicas whose reconstruction share we queued, by epoch
+    pub reconstruct_senders: HashSet<(Replica, Height)>,
 
     pub shards: Vec<std::collections::VecDeque<crypto::EVSSShare381>>,
     pub commits: Vec<crypto::EVSSCommit381>,
 
     pub rand_beacon_shares: Vec<(Vec<std::collections::VecDeque<crypto::EVSSShare381>>, Vec<crypto::EVSSCommit381>)>,
+
+    /// Number of outbound messages that could not be handed to the network
+    pub send_failures: u64,
+    /// Set once the network channel is closed; the reactor exits cleanly
+    pub shutdown: bool,
+    /// Messages waiting for room in `net_send`, at most `MAX_BACKLOG` long
+    pub backlog: VecDeque<(Destination, Arc<ProtocolMsg>)>,
+    /// Number of outbound messages dropped because the network was too slow
+    pub dropped_sends: u64,
+    /// Epochs a phase message may wait in the backlog, 0 for no limit
+    pub send_ttl: Height,
+    /// Messages dropped from the backlog for being stale, by kind
+    pub dropped_stale: HashMap<&'static str, u64>,
+    /// Encoded bytes handed to the network this epoch, once per recipient
+    pub bytes_sent: u64,
+    /// Bytes per epoch after which reconstruction shares are deprioritized,
+    /// 0 for no limit
+    pub epoch_byte_cap: u64,
+    /// Messages that only go out once the backlog is empty
+    pub low_priority: VecDeque<(Destination, Arc<ProtocolMsg>)>,
+    /// Other shard sets tried when a reconstructed proposal does not decode
+    pub reconstruct_retries: usize,
+    /// Whether a malformed message panics or is skipped
+    pub message_handling: MessageHandling,
+    /// How far ahead the cluster may get before we skip ahead, 0 for no limit
+    pub max_epoch_skew: Height,
+    /// The highest epoch each replica sent us a message for
+    pub seen_epochs: HashMap<Replica, Height>,
+    /// Milliseconds a replica's clock may be off from ours before we warn,
+    /// 0 to never warn
+    pub max_clock_drift_ms: u64,
+    /// How far ahead of ours each replica's clock is, in milliseconds
+    pub clock_drift: HashMap<Replica, i64>,
+    /// Replicas we warned about whose clocks are still off
+    pub drifting: HashSet<Replica>,
+    /// The epoch we are skipping ahead to. Until then we take no part.
+    pub quarantine: Option<Height>,
+    /// Messages for epochs we have not reached yet, keyed by epoch
+    pub future_msgs: BTreeMap<Height, Vec<(Replica, ProtocolMsg)>>,
+    /// Externally sourced values to mix into the beacon, keyed by epoch.
+    /// Ours are only proposed; the value mixed in is the one committed.
+    pub external_entropy: BTreeMap<Height, Hash>,
+    /// The external values committed blocks carry, keyed by the epoch whose
+    /// beacon they are mixed into
+    pub agreed_entropy: BTreeMap<Height, Hash>,
+    /// Steps of the delay function the beacon goes through, 0 for none
+    pub vdf_iterations: u64,
+    /// Whether our own shares go through `self_inbox` like everyone else's
+    pub loopback: bool,
+    /// Messages from ourselves, handled like network messages
+    pub self_inbox: VecDeque<ProtocolMsg>,
+    /// How many peers get deliver shards from us, 0 for all of them
+    pub gossip_fanout: usize,
+    /// Shards relayed this epoch, by message kind and shard index
+    pub gossip_seen: HashSet<(&'static str, Replica)>,
+    /// Whether reconstruction shares are only broadcast by the replicas the
+    /// beacon needs
+    pub lazy_reconstruct: bool,
+    /// A single replica that commits its own proposals without sharing
+    pub solo: bool,
+    /// Our reconstruction share for an epoch, kept until someone asks for it
+    pub withheld_share: Option<(Vec<crypto::EVSSShare381>, Height)>,
+
+    /// Signed reconfiguration requests not yet committed in a block
+    pub pending_reconfig: Vec<(Reconfig, Vec<u8>)>,
+    /// Finalized reconfigurations, applied at the next epoch boundary
+    pub scheduled_reconfig: Vec<Reconfig>,
+    /// Keys rotated out, with the last epoch in which they are still accepted
+    pub retired_keys: HashMap<Replica, (PublicKey, Height)>,
+    /// Our key once our own rotation takes effect
+    pub next_secret_key: Option<Keypair>,
+    /// Signatures the verifier pool already checked, as (origin, signed
+    /// bytes, signature), with the epoch of the message they came in
+    pub verified_signatures: HashMap<(Replica, Vec<u8>, Vec<u8>), Height>,
+    /// Messages the verifier pool dropped for a bad signature
+    pub invalid_signatures: Arc<AtomicU64>,
+
+    pub metrics: EpochMetrics,
+
+    /// Acks a leader waits for before proposing
+    pub ack_threshold: usize,
+    /// Set while we are the leader and hold the proposal back for acks
+    pub awaiting_acks: bool,
+
+    /// Shares and commitments per `CommitChunk`, 0 to send one `Commit`
+    pub commit_chunk: usize,
+    /// The chunks of the next leader's commitments received so far
+    pub commit_assembler: CommitAssembler,
+
+    /// The last epoch we took a proposal for
+    pub proposal_epoch: Option<Height>,
+
+    /// Whether we broadcast our beacon at the end of every epoch
+    pub epoch_done: bool,
+    /// Beacons reported for recent epochs, ours included, by replica
+    pub epoch_reports: BTreeMap<Height, HashMap<Replica, Hash>>,
+
+    /// The epoch each replica was last heard from in
+    pub last_heard: HashMap<Replica, Height>,
+    /// Number of transactions clients sent us
+    pub transactions_received: u64,
+    /// Client transactions a proposal of ours takes at most
+    pub block_size: usize,
+    /// Blocks in each of our proposals, at least 1
+    pub batch_size: usize,
+    /// Whether the blocks we propose are encoded with compressed bodies
+    pub compress_blocks: bool,
+    /// Whether we build our proposal while waiting for our turn to lead
+    pub prefetch_proposal: bool,
+    /// The proposal built ahead of our next epoch as leader
+    pub prefetched: Option<Prefetched>,
+    /// Client transactions kept for our proposals before clients are no
+    /// longer read from
+    pub mempool_capacity: usize,
+    /// Where the transactions of committed blocks are streamed to
+    pub commit_sink: SinkFeed,
+    /// The highest block handed to the commit sink
+    pub sunk_height: Height,
+    /// The beacons of past epochs
+    pub beacons: BeaconLog,
+    /// What we know so far about how the current epoch goes
+    pub epoch_summary: EpochSummary,
+
+    pub events: EventSender,
 }
 
 const EXTRA_SPACE: usize = 100;
+pub const MAX_BACKLOG: usize = 10_000;
+/// How many epochs ahead of us a message may be and still be held
+pub const MAX_EPOCHS_AHEAD: Height = 2;
+/// How many past epochs we can tell a replica that skipped ahead where our
+/// deals stood in
+pub const DEAL_HISTORY: usize = 4;
+/// How many epochs a rotated out key keeps verifying signatures
+pub const KEY_GRACE_EPOCHS: Height = 2;
+/// How many past epochs of completion reports are kept
+pub const EPOCH_REPORT_WINDOW: Height = 16;
+/// How many certificates may wait for their block at once
+pub const MAX_PENDING_CERTS: usize = 64;
+/// How many epochs a certificate waits for its block before it is dropped
+pub const PENDING_CERT_EPOCHS: Height = 2;
+/// The beacon of an epoch that could not be rebuilt
+pub const NO_BEACON: Hash = [0; HASH_SIZE];
+
+/// Decodes a secret key as found in the config, returning the keypair and
+/// its encoded public key.
+pub(crate) fn decode_keypair(alg: &crypto::Algorithm, secret_key_bytes: &[u8]) -> (Keypair, Vec<u8>) {
+    match alg {
+        crypto::Algorithm::ED25519 => {
+            let mut sk_copy = secret_key_bytes.to_vec();
+            let kp = ed25519::Keypair::decode(&mut sk_copy)
+                .expect("Failed to decode the secret key from the config");
+            let pk = kp.public().encode().to_vec();
+            (Keypair::Ed25519(kp), pk)
+        }
+        crypto::Algorithm::SECP256K1 => {
+            let sk_copy = secret_key_bytes.to_vec();
+            let sk = secp256k1::SecretKey::from_bytes(sk_copy)
+                .expect("Failed to decode the secret key from the config");
+            let kp = secp256k1::Keypair::from(sk);
+            let pk = kp.public().encode().to_vec();
+            (Keypair::Secp256k1(kp), pk)
+        }
+        _ => panic!("Unimplemented algorithm"),
+    }
+}
+
+/// The origin of the one vote of an aggregated certificate, whose signature
+/// is the threshold signature of a quorum
+pub const AGGREGATE_ORIGIN: Replica = Replica::MAX;
+
+/// Whether `cert` is the threshold signature of a quorum rather than their
+/// votes
+pub fn is_aggregate(cert: &Certificate) -> bool {
+    match cert.votes.as_slice() {
+        [vote] => vote.origin == AGGREGATE_ORIGIN,
+        _ => false,
+    }
+}
+
+/// Our share of the threshold key, and what votes and aggregated
+/// certificates are checked against
+pub struct ThresholdKeys {
+    pub secret: threshold::SecretShare,
+    pub public: threshold::PublicKey,
+    pub shares: HashMap<Replica, threshold::PublicKey>,
+}
+
+impl ThresholdKeys {
+    /// The keys of `config`, if it asks for threshold certificates
+    pub fn from_config(config: &Node) -> Option<Self> {
+        if !config.threshold_certificates {
+            return None;
+        }
+        let shares = config
+            .threshold_shares
+            .iter()
+            .map(|(r, pk)| {
+                let pk = threshold::PublicKey::from_bytes(pk).expect("Failed to decode a threshold key share from the config");
+                (*r, pk)
+            })
+            .collect();
+        Some(ThresholdKeys {
+            secret: threshold::SecretShare::from_bytes(&config.threshold_secret)
+                .expect("Failed to decode the threshold secret from the config"),
+            public: threshold::PublicKey::from_bytes(&config.threshold_public)
+                .expect("Failed to decode the threshold key from the config"),
+            shares,
+        })
+    }
+}
+
+/// The hash of the block `cert` votes for; the genesis block for an empty one
+pub fn certified_hash(cert: &Certificate) -> Option<Hash> {
+    match cert.votes.first() {
+        None => Some(empty_hash()),
+        Some(vote) => vote.msg.clone().try_into().ok(),
+    }
+}
+
+/// Checks that `pk` signed the commitments `c` and that each of the shares
+/// `sh` opens its commitment. This is the expensive part of checking a
+/// commit and needs nothing but its arguments.
+pub fn verify_commit(
+    num_nodes: Replica,
+    num_faults: Replica,
+    pk: &PublicKey,
+    pp: &crypto::EVSSPublicParams381,
+    sh: &VecDeque<crypto::EVSSShare381>,
+    c: &[crypto::EVSSCommit381],
+    z: &DataWithAcc,
+) -> Result<(), &'static str> {
+    if !check_acc(num_nodes, num_faults, &c, z, pk, COMMIT_DOMAIN) {
+        return Err("The signature of the commit does not match");
+    }
+    let rng = &mut StdRng::from_entropy();
+    for i in 0..num_nodes as usize {
+        if !crypto::EVSS381::check(pp, &c[i], &sh[i], rng).unwrap_or(false) {
+            return Err("Received a share that does not match its commitment");
+        }
+    }
+    Ok(())
+}
+
+/// One of our shares of a dealer's beacons and the commitment it opens
+#[derive(Clone)]
+pub struct Dealt {
+    pub share: crypto::EVSSShare381,
+    pub commit: crypto::EVSSCommit381,
+    /// The epoch it was dealt in, zero for those we started with, and its
+    /// place in that deal
+    pub deal: (Height, usize),
+}
+
+/// Our shares of every dealer's beacons in `config`, all dealt before the
+/// first epoch
+fn initial_deals(config: &Node) -> HashMap<Replica, VecDeque<Dealt>> {
+    config
+        .rand_beacon_queue
+        .iter()
+        .map(|(dealer, shares)| {
+            let commits = config.rand_beacon_commits.get(dealer).cloned().unwrap_or_default();
+            let queue = shares
+                .iter()
+                .cloned()
+                .zip(commits)
+                .enumerate()
+                .map(|(i, (share, commit))| Dealt { share, commit, deal: (0, i) })
+                .collect();
+            (*dealer, queue)
+        })
+        .collect()
+}
+
+/// A replica's share of the beacon: the sum of its shares from every dealer
+pub fn sum_shares(parts: &[crypto::EVSSShare381]) -> crypto::EVSSShare381 {
+    let mut sum = crypto::EVSSShare381 {
+        point: crypto::F381::zero(),
+        value: crypto::F381::zero(),
+        challenge: crypto::F381::zero(),
+        proof: crypto::EVSSProof381 {
+            w: crypto::EVSSG1Affine381::zero(),
+            random_v: None,
+        }
+    };
+    for u in parts.iter() {
+        sum.point += u.point;
+        sum.value += u.value;
+        sum.challenge += u.challenge;
+        sum.proof.w += &u.proof.w;
+    }
+    sum
+}
+
+pub(crate) fn decode_public_key(alg: &crypto::Algorithm, pk_data: &[u8]) -> Option<PublicKey> {
+    match alg {
+        crypto::Algorithm::ED25519 => ed25519::PublicKey::decode(pk_data)
+            .ok()
+            .map(PublicKey::Ed25519),
+        crypto::Algorithm::SECP256K1 => secp256k1::PublicKey::decode(pk_data)
+            .ok()
+            .map(PublicKey::Secp256k1),
+        _ => None,
+    }
+}
 
 impl Context {
+    /// The state of the replica `config` is for. Fails for clusters too
+    /// small to tolerate a fault, unless the replica runs solo.
     pub fn new(
         config: &Node,
-        net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
-        cli_send: UnboundedSender<Block>,
-    ) -> Self {
-        let genesis_block = Arc::new(GENESIS_BLOCK);
+        net_send: Sender<(Destination, Arc<ProtocolMsg>)>,
+        cli_send: UnboundedSender<Arc<Block>>,
+    ) -> Result<Self, ParseError> {
+        // The thresholds below make no sense for such clusters
+        if !config.solo && config.num_nodes < MIN_NODES {
+            return Err(ParseError::TooFewNodes(config.num_nodes));
+        }
+        let genesis_block = Arc::new(genesis_block());
+        let threshold = (config.num_nodes - config.num_faults) as u16;
         let mut c = Context {
             num_nodes: config.num_nodes as u16,
             num_faults: config.num_faults as u16,
             myid: config.id,
-            my_secret_key: match config.crypto_alg {
-                crypto::Algorithm::ED25519 => {
-                    let mut sk_copy = config.secret_key_bytes.clone();
-                    let kp = ed25519::Keypair::decode(&mut sk_copy)
-                        .expect("Failed to decode the secret key from the config");
-                    Keypair::Ed25519(kp)
-                }
-                crypto::Algorithm::SECP256K1 => {
-                    let sk_copy = config.secret_key_bytes.clone();
-                    let sk = secp256k1::SecretKey::from_bytes(sk_copy)
-                        .expect("Failed to decode the secret key from the config");
-                    let kp = secp256k1::Keypair::from(sk);
-                    Keypair::Secp256k1(kp)
-                }
-                _ => panic!("Unimplemented algorithm"),
-            },
+            my_secret_key: decode_keypair(&config.crypto_alg, &config.secret_key_bytes).0,
+            threshold: ThresholdKeys::from_config(config),
+            crypto_alg: config.crypto_alg.clone(),
             pub_key_map: HashMap::with_capacity(config.num_nodes),
             net_send: net_send,
             cli_send: cli_send,
-            storage: Storage::new(EXTRA_SPACE * config.block_size),
+            // Finalization looks blocks up by height, so those stay around
+            storage: Storage::bounded(
+                EXTRA_SPACE * config.block_size,
+                config.storage_capacity,
+                config.storage_retain.max(config.finality_depth + 1),
+            ),
             /// The height and next leader are both 1 because the genesis block
             /// is of height 0 and its author is replica 0
             height: 0,
             epoch: 0,
             last_leader: 0,
+            active_replicas: Vec::new(),
+            weights: config.weights.clone(),
+            leader_schedule: Vec::new(),
             last_seen_block: Arc::clone(&genesis_block),
+            last_seen_cert: Certificate::empty_cert(),
             last_committed_block_ht: 0,
+            finalized_height: 0,
+            finality_depth: config.finality_depth,
             is_client_apollo_enabled: false,
             payload: config.payload * config.block_size,
 
             highest_cert: Certificate::empty_cert(),
             highest_height: 0,
+            checkpoint: config.checkpoint.as_ref().map(PathBuf::from),
+            checkpoint_height: 0,
+            sealer: config.checkpoint_passphrase_env.as_ref().map(|var| {
+                let passphrase = std::env::var(var)
+                    .unwrap_or_else(|_| panic!("The checkpoint passphrase variable {} is not set", var));
+                Sealer::new(passphrase.as_bytes())
+            }),
+            cert_sync: config.cert_sync,
+            bootstrap: config.bootstrap_from,
+            member_keys: config.pk_map.clone(),
+            self_vote: config.self_vote,
+            trust_commits: config.trust_commits,
+            offload_shares: config.offload_shares,
+            pending_checks: VecDeque::new(),
+            pending_certs: HashMap::new(),
+            handler_budget: HandlerBudget::new(config.handler_budget()),
 
             received_propose: None,
             received_propose_sign: None,
 
-            received_vote: Vec::new(),
+            received_vote: BTreeMap::new(),
+            vote_certified: None,
             received_ack: Vec::new(),
 
             received_certificate: None,
@@ -134,51 +541,1758 @@ impl Context {
             propose_share_sent: false,
             vote_cert_share_sent: false,
             commit_share_sent: false,
+            ack_sent: false,
 
-            propose_gatherer: ShareGatherer::new(config.num_nodes as u16),
-            vote_cert_gatherer: ShareGatherer::new(config.num_nodes as u16),
-            commit_gatherer: ShareGatherer::new(config.num_nodes as u16),
+            propose_gatherer: ShareGatherer::new(config.num_nodes as u16, threshold, PROPOSE_DOMAIN),
+            vote_cert_gatherer: ShareGatherer::new(config.num_nodes as u16, threshold, CERT_DOMAIN),
+            commit_gatherer: ShareGatherer::new(config.num_nodes as u16, threshold, COMMIT_DOMAIN),
 
             rand_beacon_parameter: config.rand_beacon_parameter.clone().unwrap(),
-            rand_beacon_queue: config.rand_beacon_queue.clone(),
+            rand_beacon_queue: initial_deals(config),
+            used_deals: VecDeque::new(),
+            deal_sync: HashSet::new(),
+            beacon_commits: BTreeMap::new(),
 
-            reconstruct_queue: std::collections::VecDeque::with_capacity(config.num_nodes * 2),
+            reconstruct_queue: std::collections::BTreeMap::new(),
+            reconstruct_senders: HashSet::new(),
 
             shards: vec![std::collections::VecDeque::with_capacity(config.num_nodes); config.num_nodes],
             commits: Vec::with_capacity(config.num_nodes),
 
             rand_beacon_shares: config.rand_beacon_shares.clone(),
+
+            send_failures: 0,
+            shutdown: false,
+            backlog: VecDeque::new(),
+            dropped_sends: 0,
+            send_ttl: config.send_ttl,
+            dropped_stale: HashMap::new(),
+            bytes_sent: 0,
+            epoch_byte_cap: config.epoch_byte_cap,
+            low_priority: VecDeque::new(),
+            reconstruct_retries: config.reconstruct_retries,
+            message_handling: config.message_handling,
+            max_epoch_skew: config.max_epoch_skew,
+            seen_epochs: HashMap::new(),
+            max_clock_drift_ms: config.max_clock_drift_ms,
+            clock_drift: HashMap::new(),
+            drifting: HashSet::new(),
+            quarantine: None,
+            future_msgs: BTreeMap::new(),
+            external_entropy: BTreeMap::new(),
+            agreed_entropy: BTreeMap::new(),
+            vdf_iterations: config.vdf_iterations,
+            loopback: config.loopback,
+            self_inbox: VecDeque::new(),
+            gossip_fanout: config.gossip_fanout,
+            gossip_seen: HashSet::new(),
+            lazy_reconstruct: config.lazy_reconstruct,
+            solo: config.solo,
+            withheld_share: None,
+
+            pending_reconfig: Vec::new(),
+            scheduled_reconfig: Vec::new(),
+            retired_keys: HashMap::new(),
+            next_secret_key: None,
+            verified_signatures: HashMap::new(),
+            invalid_signatures: Arc::new(AtomicU64::new(0)),
+
+            metrics: EpochMetrics::new(config.warmup_epochs),
+
+            ack_threshold: config.ack_threshold,
+            awaiting_acks: false,
+
+            commit_chunk: config.commit_chunk,
+            commit_assembler: CommitAssembler::new(config.num_nodes),
+
+            proposal_epoch: None,
+
+            epoch_done: config.epoch_done,
+            epoch_reports: BTreeMap::new(),
+
+            last_heard: HashMap::with_capacity(config.num_nodes),
+            transactions_received: 0,
+            block_size: config.block_size,
+            batch_size: config.batch_size.max(1),
+            compress_blocks: config.compress_blocks,
+            prefetch_proposal: config.prefetch_proposal,
+            prefetched: None,
+            mempool_capacity: config.mempool_capacity(),
+            commit_sink: SinkFeed::none(),
+            sunk_height: 0,
+            beacons: BeaconLog::from_config(config),
+            epoch_summary: EpochSummary::default(),
+
+            events: events::event_channel(),
+        };
+        c.storage.insert_committed(Arc::clone(&genesis_block));
+        for (id, pk_data) in &config.pk_map {
+            let pk = decode_public_key(&config.crypto_alg, pk_data)
+                .expect("Failed to decode the public key from the config");
+            c.pub_key_map.insert(*id, pk);
+        }
+        c.set_active_replicas(config.pk_map.keys().cloned().collect());
+        if let Some(first) = c.leader_schedule.first().or(c.active_replicas.first()) {
+            c.last_leader = *first;
+        }
+        c.restore_checkpoint();
+        Ok(c)
+    }
+
+    /// Sends `msg` to all the other replicas.
+    pub fn broadcast(&mut self, msg: ProtocolMsg) {
+        self.send(Destination::All, msg);
+    }
+
+    /// Sends `msg` to replica `to`.
+    pub fn send_to(&mut self, to: Replica, msg: ProtocolMsg) {
+        self.send(Destination::One(to), msg);
+    }
+
+    /// Sends `msg` to `to`. If the network task has gone away the failure is
+    /// logged and counted, and the context is marked for shutdown instead of
+    /// panicking. If the network is not keeping up the message waits in the
+    /// backlog, which the reactor drains as room frees up.
+    ///
+    /// Once the epoch's byte cap is used up, reconstruction shares are put
+    /// behind everything else instead.
+    pub fn send(&mut self, to: Destination, msg: ProtocolMsg) {
+        let msg = Arc::new(msg);
+        if self.over_byte_cap() {
+            if let ProtocolMsg::Reconstruct(_, _) = msg.as_ref() {
+                self.low_priority.push_back((to, msg));
+                return;
+            }
+        }
+        if !self.backlog.is_empty() {
+            // Do not overtake what is already waiting
+            self.enqueue(to, msg);
+            return;
+        }
+        let size = self.frame_bytes(&to, &msg);
+        match self.net_send.try_send((to, msg)) {
+            Ok(()) => self.bytes_sent += size,
+            Err(TrySendError::Full((to, msg))) => self.enqueue(to, msg),
+            Err(TrySendError::Closed((to, msg))) => {
+                self.send_failures += 1;
+                log::error!(target:"consensus", "Failed to send {} to {}: network channel closed", msg.to_string(), to);
+                self.shutdown = true;
+            }
+        }
+    }
+
+    /// The peers we hand their deliver shards to. Without gossip that is
+    /// every other replica; with gossip it is `gossip_fanout` random ones,
+    /// relying on the other holders to reach the rest.
+    pub fn shard_recipients(&self) -> Vec<Replica> {
+        let peers: Vec<Replica> = self
+            .active_replicas
+            .iter()
+            .cloned()
+            .filter(|i| *i != self.myid)
+            .collect();
+        if self.gossip_fanout == 0 || self.gossip_fanout >= peers.len() {
+            return peers;
+        }
+        let rng = &mut StdRng::from_entropy();
+        peers
+            .choose_multiple(rng, self.gossip_fanout)
+            .cloned()
+            .collect()
+    }
+
+    /// The shard of an erasure coded message that belongs to `id`: its
+    /// position among the active replicas, so the layout does not depend on
+    /// ids being dense or starting at 0. `None` if `id` is not active.
+    pub fn shard_index(&self, id: Replica) -> Option<Replica> {
+        self.active_replicas
+            .binary_search(&id)
+            .ok()
+            .map(|i| i as Replica)
+    }
+
+    /// The replica shard `index` belongs to
+    pub fn shard_owner(&self, index: Replica) -> Option<Replica> {
+        self.active_replicas.get(index as usize).cloned()
+    }
+
+    /// Pushes a deliver shard that reached us second hand on to a few random
+    /// peers, once per shard and epoch. Owners broadcast their own shard, so
+    /// copies straight from the owner, and our own shard, are not relayed.
+    pub fn relay_shard(&mut self, origin: Replica, msg: &ProtocolMsg) {
+        if self.gossip_fanout == 0 {
+            return;
+        }
+        let n = match msg {
+            ProtocolMsg::DeliverPropose(_, n, _, _)
+            | ProtocolMsg::DeliverVoteCert(_, n, _, _)
+            | ProtocolMsg::DeliverCommit(_, n, _, _) => *n,
+            _ => return,
+        };
+        if self.shard_owner(n) == Some(origin) || self.shard_index(self.myid) == Some(n) {
+            return;
+        }
+        if self.gossip_seen.insert((msg.to_string(), n)) {
+            let to = self.shard_recipients();
+            self.send(Destination::Subset(to), msg.clone());
+        }
+    }
+
+    /// Decides whether to look at a proposal `origin` sent us. Only the
+    /// leader of the current epoch may propose, for that epoch and as the
+    /// author of the block, and only its first proposal that checks out is
+    /// taken.
+    pub fn admit_propose(&mut self, origin: Replica, p: &Propose) -> Result<(), &'static str> {
+        if p.epoch != self.epoch {
+            return Err("the proposal is not for the current epoch");
+        }
+        if origin != self.last_leader || p.new_block.header.author != self.last_leader {
+            return Err("the proposal is not from the leader of the epoch");
+        }
+        if self.proposal_epoch == Some(self.epoch) {
+            return Err("the leader already proposed in this epoch");
+        }
+        if p.batch.len() >= self.batch_size {
+            return Err("the proposal has more blocks than a batch may");
+        }
+        if p.batch.iter().any(|b| b.header.author != self.last_leader) {
+            return Err("a block of the batch is not by the leader");
+        }
+        Ok(())
+    }
+
+    /// Checks what an admitted proposal carries: the certificate for its
+    /// block, the signatures on its reconfigurations and the acks of the
+    /// commitments. Once one checks out no other proposal is admitted in
+    /// the epoch, so one that does not cannot keep out the leader's real
+    /// one. `p.new_block` has to be hashed.
+    pub fn verify_propose(&mut self, p: &Propose) -> Result<(), &'static str> {
+        let hash = p.new_block.hash.to_vec();
+        for cert in p.certificate.votes.iter() {
+            if cert.msg != hash {
+                return Err("The hash of the certification does not match block");
+            }
+            if !self.verify_vote(cert) {
+                return Err("The auth of the certification does not match block");
+            }
+        }
+        for (change, auth) in p.new_block.body.data.reconfig.iter() {
+            if !self.check_reconfig(change, auth) {
+                return Err("The reconfiguration in the block is not signed by its replica");
+            }
+        }
+        let commit_hash = ser_and_hash(&p.new_block.body.data.commits);
+        for cert in p.new_block.body.data.acks.iter() {
+            if cert.msg != commit_hash {
+                return Err("The hash of the certification does not match commit");
+            }
+            if !self.verify(cert.origin, &with_domain(ACK_DOMAIN, &cert.msg), &cert.auth) {
+                return Err("The auth of the certification does not match commit");
+            }
+        }
+        self.proposal_epoch = Some(self.epoch);
+        Ok(())
+    }
+
+    /// Records that `origin` finished epoch `e` with `beacon`. A beacon that
+    /// differs from one already reported for the epoch means the replicas
+    /// no longer agree, which is logged and published as an event. A zero
+    /// beacon only means the replica could not rebuild it, so it is not
+    /// compared. Returns whether the report disagreed.
+    pub fn record_epoch_done(&mut self, origin: Replica, e: Height, beacon: Hash) -> bool {
+        if e + EPOCH_REPORT_WINDOW < self.epoch {
+            return false;
+        }
+        let reports = self.epoch_reports.entry(e).or_insert_with(HashMap::new);
+        if reports.contains_key(&origin) {
+            return false;
+        }
+        let disagrees = beacon != NO_BEACON
+            && reports.values().any(|b| *b != NO_BEACON && *b != beacon);
+        reports.insert(origin, beacon);
+        if disagrees {
+            log::error!(target:"consensus", "Replica {} finished epoch {} with beacon {:x?}, which disagrees with {:x?}", origin, e, beacon, reports);
+            self.emit(NodeEvent::BeaconDisagreement(e, origin, beacon));
+        }
+        let oldest = self.epoch.saturating_sub(EPOCH_REPORT_WINDOW);
+        self.epoch_reports = self.epoch_reports.split_off(&oldest);
+        disagrees
+    }
+
+    /// The replicas that reported finishing epoch `e`
+    pub fn completed_by(&self, e: Height) -> Vec<Replica> {
+        let mut ids: Vec<Replica> = self
+            .epoch_reports
+            .get(&e)
+            .map(|r| r.keys().cloned().collect())
+            .unwrap_or_default();
+        ids.sort();
+        ids
+    }
+
+    /// Whether we broadcast our reconstruction share for the current epoch
+    /// right away. With lazy reconstruction that is `num_nodes - num_faults`
+    /// replicas, rotating every epoch, which together with everyone's own
+    /// share is just enough for the beacon.
+    pub fn reconstructs_eagerly(&self) -> bool {
+        if !self.lazy_reconstruct {
+            return true;
+        }
+        let me = match self.shard_index(self.myid) {
+            Some(me) => me,
+            None => return false,
+        };
+        let offset = (self.epoch % self.num_nodes as Height) as Replica;
+        let rank = (me + self.num_nodes - offset) % self.num_nodes;
+        rank < self.num_nodes - self.num_faults
+    }
+
+    /// How much `r`'s vote counts
+    pub fn weight_of(&self, r: Replica) -> u64 {
+        if self.weights.is_empty() {
+            1
+        } else {
+            self.weights.get(&r).cloned().unwrap_or(0)
+        }
+    }
+
+    /// The most weight the faulty replicas may hold: `num_faults`, or what
+    /// the `num_faults` heaviest active replicas hold together, as any of
+    /// them may be the faulty ones
+    pub fn fault_weight(&self) -> u64 {
+        if self.weights.is_empty() {
+            return self.num_faults as u64;
+        }
+        let mut weights: Vec<u64> = self.active_replicas.iter().map(|r| self.weight_of(*r)).collect();
+        weights.sort_unstable_by(|a, b| b.cmp(a));
+        weights.iter().take(self.num_faults as usize).sum()
+    }
+
+    /// Whether votes from the distinct replicas `voters` are enough for a
+    /// vote certificate, i.e. outweigh the faulty replicas
+    pub fn is_vote_quorum<I: IntoIterator<Item = Replica>>(&self, voters: I) -> bool {
+        voters.into_iter().map(|r| self.weight_of(r)).sum::<u64>() > self.fault_weight()
+    }
+
+    /// Counts `origin`'s vote for our proposal in epoch `e`. Only the first
+    /// valid vote from each replica counts, and once the counted votes for
+    /// one block are a quorum the certificate is formed from them and
+    /// returned; votes after that, or for an earlier epoch, are ignored.
+    /// Votes for the next epoch are kept until it starts, and the first vote
+    /// in that epoch forms its certificate if they are a quorum already.
+    pub fn accept_vote(&mut self, origin: Replica, vote: Vote, e: Height) -> Option<Certificate> {
+        if e < self.epoch || e > self.epoch + 1 || self.vote_certified == Some(e) {
+            return None;
+        }
+        // Votes that came before the epoch may already be a quorum
+        if self.quorum_votes(self.votes_for(e)).is_none() {
+            if vote.origin != origin {
+                self.malformed(origin, "The vote is not from its sender");
+                return None;
+            }
+            if self.votes_for(e).iter().any(|v| v.origin == origin) {
+                log::warn!(target:"consensus", "Ignoring another vote from {} for epoch {}", origin, e);
+                return None;
+            }
+            if !self.verify_vote(&vote) {
+                self.malformed(origin, "Cannot verify the vote");
+                return None;
+            }
+            self.received_vote.entry(e).or_default().push(vote);
+        }
+        if e != self.epoch {
+            return None;
+        }
+        let votes = self.quorum_votes(self.votes_for(e))?;
+        self.vote_certified = Some(e);
+        Some(self.certificate_of(votes))
+    }
+
+    /// Our signature on a vote for `hash`: our share of the threshold
+    /// signature with threshold certificates, else our own
+    pub fn vote_auth(&self, hash: &Hash) -> Vec<u8> {
+        let msg = with_domain(VOTE_DOMAIN, hash);
+        match self.threshold.as_ref() {
+            Some(keys) => keys.secret.sign(&msg),
+            None => self.my_secret_key.sign(&msg).unwrap(),
+        }
+    }
+
+    /// Checks the signature of `vote`: that of an aggregated certificate
+    /// against the threshold key, a share against the key of its replica's
+    /// share, and any other against its replica's key
+    pub fn verify_vote(&self, vote: &Vote) -> bool {
+        let msg = with_domain(VOTE_DOMAIN, &vote.msg);
+        match self.threshold.as_ref() {
+            Some(keys) if vote.origin == AGGREGATE_ORIGIN => keys.public.verify(&msg, &vote.auth),
+            Some(keys) => keys
+                .shares
+                .get(&vote.origin)
+                .map_or(false, |pk| pk.verify(&msg, &vote.auth)),
+            None => self.verify(vote.origin, &msg, &vote.auth),
+        }
+    }
+
+    /// The certificate of `votes`, a quorum for one block. With threshold
+    /// certificates it is the signature `num_faults + 1` of them combine
+    /// into, else the votes themselves.
+    pub fn certificate_of(&self, votes: Vec<Vote>) -> Certificate {
+        if self.threshold.is_none() || votes.is_empty() {
+            return Certificate { votes };
+        }
+        let shares: Vec<(Replica, &[u8])> = votes
+            .iter()
+            .take(self.num_faults as usize + 1)
+            .map(|v| (v.origin, v.auth.as_slice()))
+            .collect();
+        match threshold::combine(&shares) {
+            Some(auth) => Certificate {
+                votes: vec![Vote {
+                    msg: votes[0].msg.clone(),
+                    origin: AGGREGATE_ORIGIN,
+                    auth,
+                }],
+            },
+            None => {
+                log::error!(target:"consensus", "Cannot combine the votes into a threshold signature; certifying with the votes");
+                Certificate { votes }
+            }
+        }
+    }
+
+    /// Whether `cert` is a valid certificate for `hash`: a valid aggregated
+    /// signature, or a quorum of valid votes
+    pub fn certifies(&self, cert: &Certificate, hash: &Hash) -> bool {
+        let hash = hash.to_vec();
+        if is_aggregate(cert) {
+            return cert.votes[0].msg == hash && self.verify_vote(&cert.votes[0]);
+        }
+        let voters: HashSet<Replica> = cert
+            .votes
+            .iter()
+            .filter(|v| v.msg == hash && self.verify_vote(v))
+            .map(|v| v.origin)
+            .collect();
+        self.is_vote_quorum(voters)
+    }
+
+    /// The votes we got for our proposal in epoch `e`
+    pub fn votes_for(&self, e: Height) -> &[Vote] {
+        self.received_vote.get(&e).map_or(&[], |v| v.as_slice())
+    }
+
+    /// The votes among `votes` for the first block that has a quorum of
+    /// them, in the order they arrived
+    pub fn quorum_votes(&self, votes: &[Vote]) -> Option<Vec<Vote>> {
+        votes.iter().find_map(|first| {
+            let same: Vec<Vote> = votes.iter().filter(|v| v.msg == first.msg).cloned().collect();
+            if self.is_vote_quorum(same.iter().map(|v| v.origin)) {
+                Some(same)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Acknowledges the commitments of the next leader once they can be
+    /// reconstructed from the shards we gathered. At most one ack goes out
+    /// per epoch, however often the shards reach the threshold.
+    pub fn ack_commit(&mut self) {
+        if self.ack_sent {
+            return;
+        }
+        let bytes = match self.commit_gatherer.reconstruct(self.num_nodes, self.num_faults) {
+            Some(bytes) => bytes,
+            None => return,
+        };
+        let hash = ser_and_hash(&commit_from_bytes(&bytes));
+        let vote = Vote {
+            msg: hash.to_vec(),
+            origin: self.myid,
+            auth: self.my_secret_key.sign(&with_domain(ACK_DOMAIN, &hash)).unwrap(),
+        };
+        self.ack_sent = true;
+        if self.myid != self.next_leader() {
+            self.send_to(self.next_leader(), ProtocolMsg::Ack(vote, self.epoch));
+        }
+    }
+
+    /// Asks for the shards of the `kind` gatherer we lack, if there are too
+    /// few to reconstruct from. Each request goes to the shard's owner,
+    /// which broadcast it, and to `num_faults` replicas whose shards we do
+    /// have, so that some replica we know to be up is asked. Returns the
+    /// number of shards asked for.
+    pub fn pull_missing_shares(&mut self, kind: Gatherer) -> usize {
+        let gatherer = match kind {
+            Gatherer::Propose => &self.propose_gatherer,
+            Gatherer::VoteCert => &self.vote_cert_gatherer,
+            Gatherer::Commit => &self.commit_gatherer,
+        };
+        if gatherer.shard_num >= gatherer.threshold {
+            return 0;
+        }
+        let (held, missing): (Vec<Replica>, Vec<Replica>) = (0..self.active_replicas.len() as Replica)
+            .partition(|n| gatherer.shard.get(*n as usize).map_or(false, |s| s.is_some()));
+        let helpers: Vec<Replica> = held
+            .iter()
+            .filter_map(|n| self.shard_owner(*n))
+            .filter(|r| *r != self.myid)
+            .take(self.num_faults as usize)
+            .collect();
+        log::info!(target:"consensus", "Asking for {} missing {:?} shards of epoch {}", missing.len(), kind, self.epoch);
+        for n in missing.iter() {
+            let mut to = helpers.clone();
+            match self.shard_owner(*n) {
+                Some(owner) if owner != self.myid && !to.contains(&owner) => to.push(owner),
+                _ => (),
+            }
+            if !to.is_empty() {
+                self.send(Destination::Subset(to), ProtocolMsg::RequestShare(self.epoch, kind, *n));
+            }
+        }
+        missing.len()
+    }
+
+    /// Answers `origin`'s request for shard `n` of the `kind` gatherer of
+    /// epoch `e` with the Deliver* message it came in, if we have it
+    pub fn answer_share_request(&mut self, origin: Replica, e: Height, kind: Gatherer, n: Replica) {
+        if e != self.epoch {
+            return;
+        }
+        let (sh, sign) = match self.gatherer(kind).held(n) {
+            Some(held) => held,
+            None => return,
+        };
+        let msg = match kind {
+            Gatherer::Propose => ProtocolMsg::DeliverPropose(sh, n, sign, e),
+            Gatherer::VoteCert => ProtocolMsg::DeliverVoteCert(sh, n, sign, e),
+            Gatherer::Commit => ProtocolMsg::DeliverCommit(sh, n, sign, e),
+        };
+        self.send_to(origin, msg);
+    }
+
+    /// How far the `kind` gatherer is, with its contributors as replicas
+    pub fn gatherer_progress(&self, kind: Gatherer) -> GathererStatus {
+        let gatherer = match kind {
+            Gatherer::Propose => &self.propose_gatherer,
+            Gatherer::VoteCert => &self.vote_cert_gatherer,
+            Gatherer::Commit => &self.commit_gatherer,
+        };
+        let mut status = gatherer.progress();
+        status.contributors = status
+            .contributors
+            .iter()
+            .filter_map(|n| self.shard_owner(*n))
+            .collect();
+        status
+    }
+
+    pub fn gatherer(&mut self, kind: Gatherer) -> &mut ShareGatherer {
+        match kind {
+            Gatherer::Propose => &mut self.propose_gatherer,
+            Gatherer::VoteCert => &mut self.vote_cert_gatherer,
+            Gatherer::Commit => &mut self.commit_gatherer,
+        }
+    }
+
+    /// Checks the commitments `origin` dealt us for the beacon before our
+    /// shares of them are queued: they must come from the next leader and
+    /// carry its signature, and every share must open its commitment.
+    pub fn check_commit(
+        &self,
+        origin: Replica,
+        sh: &VecDeque<crypto::EVSSShare381>,
+        c: &[crypto::EVSSCommit381],
+        z: &DataWithAcc,
+    ) -> Result<(), &'static str> {
+        match self.commit_checks(origin, sh, c)? {
+            Some((pk, pp)) => verify_commit(self.num_nodes, self.num_faults, &pk, &pp, sh, c, z),
+            None => Ok(()),
+        }
+    }
+
+    /// The checks of `check_commit` that need our state. Returns the key
+    /// and parameters for the rest, which `verify_commit` does, or `None`
+    /// if the commitments are trusted.
+    pub fn commit_checks(
+        &self,
+        origin: Replica,
+        sh: &VecDeque<crypto::EVSSShare381>,
+        c: &[crypto::EVSSCommit381],
+    ) -> Result<Option<(PublicKey, crypto::EVSSPublicParams381)>, &'static str> {
+        if sh.len() < self.num_nodes as usize || c.len() < self.num_nodes as usize {
+            return Err("Received an incomplete commit");
+        }
+        if self.trust_commits {
+            return Ok(None);
+        }
+        let leader = self.next_leader();
+        if origin != leader {
+            return Err("Received a commit from a replica that is not the next leader");
+        }
+        let pk = self.pub_key_map.get(&leader).ok_or("Received a commit from an unknown replica")?;
+        Ok(Some((pk.clone(), self.rand_beacon_parameter.get_public_params())))
+    }
+
+    /// Number of reconstruction shares we hold for epoch `e`
+    pub fn reconstruct_shares(&self, e: Height) -> usize {
+        self.reconstruct_queue.get(&e).map_or(0, |v| v.len())
+    }
+
+    /// Queues `origin`'s reconstruction share for epoch `e`. Only the first
+    /// share from each replica counts, and it is only queued if each of its
+    /// parts opens its dealer's commitment, so a share that would not
+    /// rebuild the beacon never takes one of the slots. That goes for our
+    /// own share as well, which only fails if our queue is out of step.
+    /// A share at a point we already have is rejected, and once there are
+    /// enough shares to rebuild the beacon the rest are ignored, so a flood
+    /// of shares cannot grow the reconstruction. Returns whether the share
+    /// was queued.
+    pub fn accept_reconstruct(&mut self, origin: Replica, parts: Vec<crypto::EVSSShare381>, e: Height) -> bool {
+        if let Some(last) = self.reconstruct_queue.keys().next_back() {
+            if e < *last {
+                return false;
+            }
+        }
+        if self.reconstruct_shares(e) >= (self.num_nodes - self.num_faults) as usize {
+            return false;
+        }
+        if !self.reconstruct_senders.insert((origin, e)) {
+            log::warn!(target:"consensus", "Ignoring another reconstruction share from {} for epoch {}", origin, e);
+            return false;
+        }
+        // A share that fails is not checked again: the sender had its one
+        // try for the epoch
+        if let Err(reason) = self.check_reconstruct(origin, &parts, e) {
+            log::warn!(target:"consensus", "Ignoring a reconstruction share from {} for epoch {}: {}", origin, e, reason);
+            return false;
+        }
+        let sh = sum_shares(&parts);
+        if self.reconstruct_queue.get(&e).map_or(false, |v| v.iter().any(|s| s.point == sh.point)) {
+            log::warn!(target:"consensus", "Ignoring a reconstruction share from {} at a point we already have", origin);
+            return false;
+        }
+        self.reconstruct_queue.entry(e).or_insert_with(Vec::new).push(sh);
+        true
+    }
+
+    /// Checks that `parts` are `origin`'s shares of the beacon of epoch `e`:
+    /// one for each dealer, at `origin`'s point and opening the commitment
+    /// we drew for that dealer in the epoch
+    pub fn check_reconstruct(
+        &self,
+        origin: Replica,
+        parts: &[crypto::EVSSShare381],
+        e: Height,
+    ) -> Result<(), &'static str> {
+        let commits = self
+            .beacon_commits
+            .get(&e)
+            .ok_or("No commitments to check the reconstruction share against")?;
+        if parts.len() != commits.len() {
+            return Err("The reconstruction share does not have one part per dealer");
+        }
+        let index = self.shard_index(origin).ok_or("The sender is not an active replica")?;
+        let point = crypto::F381::from(index + 1);
+        let pp = self.rand_beacon_parameter.get_public_params();
+        let rng = &mut StdRng::from_entropy();
+        for (sh, c) in parts.iter().zip(commits.iter()) {
+            if sh.point != point {
+                return Err("The reconstruction share is not at the sender's point");
+            }
+            if !crypto::EVSS381::check(&pp, c, sh, rng).unwrap_or(false) {
+                return Err("The reconstruction share does not match its commitment");
+            }
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the beacon for the current epoch from the queued shares, or
+    /// returns zeros if there are not enough of them. Every queued share
+    /// opened its commitments, so any `num_faults + 1` of them rebuild the
+    /// dealt value and the ones that did not were never queued. Shares for
+    /// this and earlier epochs are used up.
+    pub fn take_beacon(&mut self) -> Hash {
+        let epoch = self.epoch;
+        // Everything from the next epoch on stays queued, the rest goes
+        let later = self.reconstruct_queue.split_off(&(epoch + 1));
+        let mut done = std::mem::replace(&mut self.reconstruct_queue, later);
+        let vec = done.remove(&epoch).unwrap_or_default();
+        self.reconstruct_senders.retain(|(_, h)| *h > epoch);
+        self.beacon_commits = self.beacon_commits.split_off(&(epoch + 1));
+        let mut hash = NO_BEACON;
+        if vec.len() >= (self.num_nodes - self.num_faults) as usize {
+            let secret = crypto::EVSS381::reconstruct(&vec[..=self.num_faults as usize]);
+            let mut buf = Vec::new();
+            secret.serialize(&mut buf).unwrap();
+            hash = crypto::hash::ser_and_hash(&buf);
+        }
+        hash
+    }
+
+    /// Keeps the external value for epoch `e` until the block that carries
+    /// it is proposed. Values too late to be carried, or too far ahead, are
+    /// dropped.
+    pub fn add_entropy(&mut self, e: Height, value: Hash) {
+        if e < self.epoch + entropy::ENTROPY_LEAD || e > self.epoch + entropy::MAX_ENTROPY_AHEAD {
+            log::warn!(target:"consensus", "Dropping external entropy for epoch {} while in epoch {}", e, self.epoch);
+            return;
+        }
+        self.external_entropy.insert(e, value);
+    }
+
+    /// Takes the external value `block`, committed in epoch `e`, carries as
+    /// the one to mix into the beacon of `e + ENTROPY_LEAD`
+    pub fn agree_entropy(&mut self, e: Height, block: &Block) {
+        let value = match entropy::carried(block) {
+            Some(value) => value,
+            None => return,
+        };
+        let target = e + entropy::ENTROPY_LEAD;
+        match self.external_entropy.get(&target) {
+            Some(ours) if *ours != value => {
+                log::warn!(target:"consensus", "The block committed in epoch {} carries another external value for epoch {} than ours; using it", e, target)
+            }
+            _ => (),
+        }
+        self.agreed_entropy.insert(target, value);
+    }
+
+    /// Mixes the external value committed for the current epoch, if there
+    /// is one, into `beacon`. Values for this and earlier epochs are used
+    /// up.
+    pub fn mix_entropy(&mut self, beacon: Hash) -> Hash {
+        self.external_entropy = self.external_entropy.split_off(&(self.epoch + 1));
+        let later = self.agreed_entropy.split_off(&(self.epoch + 1));
+        let agreed = std::mem::replace(&mut self.agreed_entropy, later);
+        match agreed.get(&self.epoch) {
+            Some(value) if beacon != NO_BEACON => entropy::mix(&beacon, value),
+            _ => beacon,
+        }
+    }
+
+    /// Our external value to carry in the block proposed in epoch `e`
+    fn entropy_to_carry(&self, e: Height) -> Option<&Hash> {
+        self.external_entropy.get(&(e + entropy::ENTROPY_LEAD))
+    }
+
+    /// Puts `beacon` through the delay function, if one is configured. A
+    /// beacon that could not be rebuilt is left as it is.
+    pub fn delay_beacon(&mut self, beacon: Hash) -> Hash {
+        if self.vdf_iterations == 0 || beacon == NO_BEACON {
+            return beacon;
+        }
+        let (delayed, proof) = entropy::delay(&beacon, self.vdf_iterations);
+        self.emit(NodeEvent::BeaconDelayed(self.epoch, beacon, proof));
+        delayed
+    }
+
+    /// Queues our shares `sh` of the beacons `dealer` dealt in epoch `e`,
+    /// with the commitments `c` they open, to be used up one per epoch
+    pub fn queue_dealt(&mut self, dealer: Replica, e: Height, sh: VecDeque<crypto::EVSSShare381>, c: &[crypto::EVSSCommit381]) {
+        let queue = self.rand_beacon_queue.entry(dealer).or_insert_with(VecDeque::new);
+        if queue.iter().any(|d| d.deal.0 == e) {
+            // Already resent when we caught up
+            return;
+        }
+        let dealt = sh.into_iter().zip(c.iter().cloned()).enumerate();
+        queue.extend(dealt.map(|(i, (share, commit))| Dealt { share, commit, deal: (e, i) }));
+    }
+
+    /// Our reconstruction share for the next beacon: the next share from
+    /// every dealer's queue. The commitments behind them are what everyone
+    /// else's shares for the epoch are checked against.
+    pub fn reconstruct_share(&mut self) -> Vec<crypto::EVSSShare381> {
+        let mut parts = Vec::with_capacity(self.num_nodes as usize);
+        let mut commits = Vec::with_capacity(self.num_nodes as usize);
+        for i in self.active_replicas.clone() {
+            let dealt = self.rand_beacon_queue.get_mut(&i).and_then(|queue| queue.pop_front());
+            if i == self.myid {
+                self.used_deals.push_back((self.epoch, dealt.as_ref().map(|d| d.deal)));
+                if self.used_deals.len() > DEAL_HISTORY {
+                    self.used_deals.pop_front();
+                }
+            }
+            if let Some(dealt) = dealt {
+                parts.push(dealt.share);
+                commits.push(dealt.commit);
+            }
+        }
+        self.beacon_commits.insert(self.epoch, commits);
+        parts
+    }
+
+    fn enqueue(&mut self, to: Destination, msg: Arc<ProtocolMsg>) {
+        // Certificates and reconstruction shares are best effort, so they
+        // are the first to go when the network is stalled.
+        match msg.as_ref() {
+            ProtocolMsg::Certificate(_, _, _) | ProtocolMsg::Reconstruct(_, _) => {
+                self.dropped_sends += 1;
+                return;
+            }
+            _ => (),
+        }
+        if self.backlog.len() >= MAX_BACKLOG {
+            let (old_to, old) = self.backlog.pop_front().unwrap();
+            log::warn!(target:"consensus", "Outbound backlog full; dropping {} to {}", old.to_string(), old_to);
+            self.dropped_sends += 1;
+        }
+        self.backlog.push_back((to, msg));
+    }
+
+    /// Whether `msg` is no use to anyone any more. Votes, vote certificates
+    /// and deliver shards only matter in the phase they were made for, so
+    /// they expire `send_ttl` epochs after the epoch they carry.
+    pub fn is_stale(&self, msg: &ProtocolMsg) -> bool {
+        if self.send_ttl == 0 {
+            return false;
+        }
+        msg.is_phase_bound() && msg.epoch() + self.send_ttl <= self.epoch
+    }
+
+    /// Takes the next message to write from the backlog, dropping the stale
+    /// ones in front of it so fresh traffic goes out first. Low priority
+    /// messages come after the backlog. The message is counted as sent.
+    pub fn next_outbound(&mut self) -> Option<(Destination, Arc<ProtocolMsg>)> {
+        let mut next = None;
+        while let Some((to, msg)) = self.backlog.pop_front() {
+            if !self.is_stale(&msg) {
+                next = Some((to, msg));
+                break;
+            }
+            log::debug!(target:"consensus", "Dropping stale {} for epoch {} to {}", msg.to_string(), msg.epoch(), to);
+            *self.dropped_stale.entry(msg.to_string()).or_insert(0) += 1;
+        }
+        let (to, msg) = next.or_else(|| self.low_priority.pop_front())?;
+        self.bytes_sent += self.frame_bytes(&to, &msg);
+        Some((to, msg))
+    }
+
+    /// Whether anything waits for room in `net_send`
+    pub fn has_outbound(&self) -> bool {
+        !self.backlog.is_empty() || !self.low_priority.is_empty()
+    }
+
+    /// Whether this epoch's traffic has used up the byte cap
+    pub fn over_byte_cap(&self) -> bool {
+        self.epoch_byte_cap > 0 && self.bytes_sent >= self.epoch_byte_cap
+    }
+
+    /// The bytes `msg` takes on the wire, summed over its recipients
+    fn frame_bytes(&self, to: &Destination, msg: &ProtocolMsg) -> u64 {
+        let recipients = to.peers(self.myid, &self.active_replicas).len();
+        (encoded_len(msg) * recipients) as u64
+    }
+
+    /// Returns `msg` if it can be processed in the current epoch. Messages
+    /// from the next few epochs are held until `take_ready` releases them,
+    /// anything further ahead is dropped.
+    pub fn hold_if_early(&mut self, origin: Replica, msg: ProtocolMsg) -> Option<ProtocolMsg> {
+        let e = msg.epoch();
+        if e <= self.epoch {
+            return Some(msg);
+        }
+        if e > self.epoch + MAX_EPOCHS_AHEAD {
+            log::warn!(target:"consensus", "Dropping {} from {} for epoch {} while in epoch {}", msg.to_string(), origin, e, self.epoch);
+            return None;
+        }
+        self.future_msgs
+            .entry(e)
+            .or_insert_with(Vec::new)
+            .push((origin, msg));
+        None
+    }
+
+    /// The epoch at least one correct replica has reached, going by the
+    /// messages we got: the `num_faults + 1`-th highest epoch any replica
+    /// sent us, so that faulty replicas cannot make it up
+    pub fn cluster_epoch(&self) -> Height {
+        let mut seen: Vec<Height> = self.seen_epochs.values().cloned().collect();
+        seen.sort_unstable_by(|a, b| b.cmp(a));
+        seen.get(self.num_faults as usize).cloned().unwrap_or(0)
+    }
+
+    /// Updates the estimate of how far `origin`'s clock is off from ours
+    /// with a message it sent at `sent_ms` by its clock that arrived at
+    /// `now_ms` by ours. The estimate is smoothed over messages and includes
+    /// the time in transit. Warns once when it goes past
+    /// `max_clock_drift_ms`, and again only after it came back within it;
+    /// returns whether it just did.
+    pub fn observe_clock(&mut self, origin: Replica, sent_ms: u64, now_ms: u64) -> bool {
+        let sample = sent_ms as i64 - now_ms as i64;
+        let drift = self.clock_drift.entry(origin).or_insert(sample);
+        *drift += (sample - *drift) / 4;
+        let drift = *drift;
+        if self.max_clock_drift_ms == 0 || drift.abs() as u64 <= self.max_clock_drift_ms {
+            self.drifting.remove(&origin);
+            return false;
+        }
+        if !self.drifting.insert(origin) {
+            return false;
+        }
+        log::warn!(target:"consensus", "The clock of replica {} is {} ms off from ours; phases may not line up", origin, drift);
+        self.emit(NodeEvent::ClockDrift(origin, drift));
+        true
+    }
+
+    /// Notes the epoch of a message from `origin`. If that puts the cluster
+    /// more than `max_epoch_skew` epochs ahead of us, we go into quarantine
+    /// until we have skipped ahead to it; returns whether we just did.
+    pub fn observe_epoch(&mut self, origin: Replica, e: Height) -> bool {
+        let seen = self.seen_epochs.entry(origin).or_insert(0);
+        *seen = (*seen).max(e);
+        if self.max_epoch_skew == 0 || self.quarantine.is_some() {
+            return false;
+        }
+        let cluster = self.cluster_epoch();
+        if cluster <= self.epoch + self.max_epoch_skew {
+            return false;
+        }
+        println!("[WARN] The cluster is at epoch {} while we are at {}; catching up.", cluster, self.epoch);
+        self.quarantine = Some(cluster);
+        self.emit(NodeEvent::Quarantined(self.epoch, cluster));
+        true
+    }
+
+    /// Skips from quarantine to the epoch we were catching up to, or
+    /// wherever the cluster got to since, dropping whatever we had of the
+    /// epochs in between. The shares of the skipped epochs are used up, and
+    /// since we missed whatever was dealt meanwhile, every dealer is asked
+    /// where its queue stands. The chain is synced from a snapshot of a
+    /// replica that is at that epoch. Returns the epoch.
+    pub fn fast_forward(&mut self) -> Option<Height> {
+        let target = self.quarantine.take()?.max(self.cluster_epoch());
+        if let Some(leader) = self.leader_for_epoch(target) {
+            self.last_leader = leader;
+        }
+        let skipped = target.saturating_sub(self.epoch) as usize;
+        for queue in self.rand_beacon_queue.values_mut() {
+            queue.drain(..skipped.min(queue.len()));
+        }
+        self.epoch = target;
+        self.propose_gatherer.clear();
+        self.vote_cert_gatherer.clear();
+        self.commit_gatherer.clear();
+        self.gossip_seen.clear();
+        self.received_vote = self.received_vote.split_off(&target);
+        self.received_ack.clear();
+        self.received_propose = None;
+        self.received_propose_sign = None;
+        self.received_certificate = None;
+        self.received_certificate_sign = None;
+        self.received_commit = None;
+        self.received_commit_sign = None;
+        self.propose_share_sent = false;
+        self.vote_cert_share_sent = false;
+        self.commit_share_sent = false;
+        self.ack_sent = false;
+        self.withheld_share = None;
+        self.future_msgs = self.future_msgs.split_off(&target);
+        self.reconstruct_queue = self.reconstruct_queue.split_off(&target);
+        self.reconstruct_senders.retain(|(_, h)| *h >= target);
+        self.beacon_commits = self.beacon_commits.split_off(&target);
+        self.verified_signatures.retain(|_, e| *e >= target);
+        self.epoch_summary = EpochSummary::default();
+        let myid = self.myid;
+        self.deal_sync = self.active_replicas.iter().cloned().filter(|r| *r != myid).collect();
+        self.broadcast(ProtocolMsg::DealRequest(target));
+        if self.bootstrap.is_none() {
+            // Asked for at the start of the next epoch, once the block of
+            // this one is committed
+            self.bootstrap = self
+                .seen_epochs
+                .iter()
+                .filter(|(r, e)| **r != myid && **e >= target)
+                .map(|(r, _)| *r)
+                .min();
+        }
+        self.emit(NodeEvent::CaughtUp(target));
+        Some(target)
+    }
+
+    /// Answers `origin`, which skipped ahead to epoch `e`, with what our
+    /// queue of deals held in that epoch, or in ours if we are not there
+    /// yet, and its shares of them. Nothing is sent if that is further back
+    /// than the last `DEAL_HISTORY` epochs we kept track of.
+    pub fn answer_deal_request(&mut self, origin: Replica, e: Height) {
+        let since = e.min(self.epoch);
+        let used: Vec<Option<(Height, usize)>> = self
+            .used_deals
+            .iter()
+            .filter(|(u, _)| *u > since)
+            .map(|(_, deal)| *deal)
+            .collect();
+        if used.len() as Height != self.epoch - since {
+            log::info!(target:"consensus", "Cannot tell {} what our deals were in epoch {}; that is too far back", origin, e);
+            return;
+        }
+        let queue = match self.rand_beacon_queue.get(&self.myid) {
+            Some(queue) => queue,
+            None => return,
+        };
+        let index = match self.shard_index(origin) {
+            Some(index) => index as usize,
+            None => return,
+        };
+        let held: Vec<(Height, usize)> = used.into_iter().flatten().chain(queue.iter().map(|d| d.deal)).collect();
+        let mut deals: Vec<types::Deal> = Vec::new();
+        for (dealt_in, _) in held.iter() {
+            if *dealt_in == 0 || deals.iter().any(|(d, _, _, _)| d == dealt_in) {
+                continue;
+            }
+            let (shares, commits) = match self.rand_beacon_shares.get(*dealt_in as usize % 100) {
+                Some(deal) => deal,
+                None => return,
+            };
+            let sign = match get_acc(self, commits, COMMIT_DOMAIN) {
+                Ok((_, sign)) => sign,
+                Err(err) => {
+                    log::error!(target:"consensus", "Cannot resend the deal of epoch {}: {}", dealt_in, err);
+                    return;
+                }
+            };
+            deals.push((*dealt_in, shares[index].clone(), commits.clone(), sign));
+        }
+        self.send_to(origin, ProtocolMsg::Deals(since, held, deals));
+    }
+
+    /// Takes `origin`'s answer to the `DealRequest` we sent when we skipped
+    /// ahead: what its queue of deals held in epoch `e`. Once the deals
+    /// check out, our queue for `origin` is rebuilt from them, less the
+    /// shares used up since `e`.
+    pub fn accept_deals(&mut self, origin: Replica, e: Height, held: Vec<(Height, usize)>, deals: Vec<types::Deal>) {
+        if !self.deal_sync.contains(&origin) {
+            log::warn!(target:"consensus", "Ignoring deals from {} we did not ask for", origin);
+            return;
+        }
+        let pk = match self.pub_key_map.get(&origin) {
+            Some(pk) => pk.clone(),
+            None => return,
         };
-        c.storage
-            .committed_blocks_by_hash
-            .insert(GENESIS_BLOCK.hash, Arc::clone(&genesis_block));
-        c.storage
-            .committed_blocks_by_ht
-            .insert(0, Arc::clone(&genesis_block));
-        for (id, mut pk_data) in &config.pk_map {
-            let pk = match config.crypto_alg {
-                crypto::Algorithm::ED25519 => {
-                    let kp = ed25519::PublicKey::decode(&mut pk_data)
-                        .expect("Failed to decode the secret key from the config");
-                    PublicKey::Ed25519(kp)
+        let pp = self.rand_beacon_parameter.get_public_params();
+        for (_, sh, c, z) in deals.iter() {
+            if sh.len() < self.num_nodes as usize || c.len() < self.num_nodes as usize {
+                return self.malformed(origin, "Received an incomplete deal");
+            }
+            if let Err(err) = verify_commit(self.num_nodes, self.num_faults, &pk, &pp, sh, c, z) {
+                return self.malformed(origin, err);
+            }
+        }
+        let ours = self.rand_beacon_queue.get(&origin).cloned().unwrap_or_default();
+        let mut queue = VecDeque::with_capacity(held.len());
+        for deal in held {
+            let dealt = if deal.0 == 0 {
+                // Only we hold our shares of these
+                match ours.iter().find(|d| d.deal == deal) {
+                    Some(dealt) => dealt.clone(),
+                    None => {
+                        log::warn!(target:"consensus", "The deals of {} start further back than ours; keeping ours", origin);
+                        return;
+                    }
                 }
-                crypto::Algorithm::SECP256K1 => {
-                    let sk = secp256k1::PublicKey::decode(&pk_data)
-                        .expect("Failed to decode the secret key from the config");
-                    PublicKey::Secp256k1(sk)
+            } else {
+                let dealt = deals.iter().find(|(d, _, _, _)| *d == deal.0).and_then(|(_, sh, c, _)| {
+                    Some(Dealt { share: sh.get(deal.1)?.clone(), commit: c.get(deal.1)?.clone(), deal })
+                });
+                match dealt {
+                    Some(dealt) => dealt,
+                    None => return self.malformed(origin, "The deals do not hold a share they list"),
                 }
-                _ => panic!("Unimplemented algorithm"),
             };
-            c.pub_key_map.insert(*id, pk);
+            queue.push_back(dealt);
+        }
+        let used = self.epoch.saturating_sub(e) as usize;
+        queue.drain(..used.min(queue.len()));
+        self.rand_beacon_queue.insert(origin, queue);
+        self.deal_sync.remove(&origin);
+    }
+
+    /// Removes and returns the held messages that belong to an epoch we
+    /// have now reached, in the order they arrived.
+    pub fn take_ready(&mut self) -> Vec<(Replica, ProtocolMsg)> {
+        let later = self.future_msgs.split_off(&(self.epoch + 1));
+        let ready = std::mem::replace(&mut self.future_msgs, later);
+        ready.into_iter().flat_map(|(_, msgs)| msgs).collect()
+    }
+
+    /// Adopts `cert` as the highest certificate if the block it certifies is
+    /// known and higher than the current one. Between two blocks of the same
+    /// height the one with the smaller hash wins, so that every replica picks
+    /// the same parent whatever order the certificates arrive in.
+    pub fn update_highest_cert(&mut self, cert: Certificate) {
+        let hash = match certified_hash(&cert) {
+            Some(hash) => hash,
+            None => {
+                println!("[WARN] The certificate does not certify a block hash.");
+                return;
+            }
+        };
+        let height = match self.storage.committed_blocks_by_hash.get(&hash) {
+            Some(block) => block.header.height,
+            None => return,
+        };
+        let current = certified_hash(&self.highest_cert).unwrap_or_else(empty_hash);
+        if height > self.highest_height || (height == self.highest_height && hash < current) {
+            self.highest_cert = cert;
+            self.highest_height = height;
+            let block = Arc::clone(&self.storage.committed_blocks_by_hash[&hash]);
+            let cert = self.highest_cert.clone();
+            self.save_checkpoint(&cert, &block);
+        }
+    }
+
+    /// Adopts `cert`, received from `origin`, like `update_highest_cert`.
+    /// With `cert_sync` on, a certificate for a block we have not committed
+    /// is kept and the block asked from `origin`, if it is a quorum of valid
+    /// votes. It waits `PENDING_CERT_EPOCHS` epochs for the block at most.
+    pub fn accept_certificate(&mut self, origin: Replica, cert: Certificate) {
+        let hash = match certified_hash(&cert) {
+            Some(hash) if self.cert_sync => hash,
+            _ => return self.update_highest_cert(cert),
+        };
+        if self.storage.committed_blocks_by_hash.contains_key(&hash) {
+            return self.update_highest_cert(cert);
+        }
+        if self.pending_certs.contains_key(&hash) {
+            return;
+        }
+        if !self.certifies(&cert, &hash) {
+            log::warn!(target:"consensus", "Dropping a certificate from {} for an unknown block: it is not a quorum of valid votes", origin);
+            return;
+        }
+        let epoch = self.epoch;
+        self.pending_certs
+            .retain(|_, (_, asked)| *asked + PENDING_CERT_EPOCHS >= epoch);
+        if self.pending_certs.len() >= MAX_PENDING_CERTS {
+            log::warn!(target:"consensus", "Dropping a certificate from {} for an unknown block; too many are waiting", origin);
+            return;
+        }
+        self.pending_certs.insert(hash, (cert, epoch));
+        self.send_to(origin, ProtocolMsg::BlockRequest(hash, epoch));
+    }
+
+    /// Answers `origin`'s request for the committed block `hash`
+    pub fn answer_block_request(&mut self, origin: Replica, hash: Hash, e: Height) {
+        let block = match self.storage.committed_blocks_by_hash.get(&hash) {
+            Some(block) => Block::clone(block),
+            None => return,
+        };
+        self.send_to(origin, ProtocolMsg::BlockResponse(block, e));
+    }
+
+    /// Takes a block asked for by `accept_certificate`. It is committed and
+    /// its certificate adopted if the certificate holds a quorum of valid
+    /// votes for it.
+    pub fn accept_synced_block(&mut self, origin: Replica, mut block: Block) {
+        // The hash is whatever the sender says until we compute it
+        block.update_hash();
+        let cert = match self.pending_certs.remove(&block.hash) {
+            Some((cert, _)) => cert,
+            None => {
+                log::warn!(target:"consensus", "Ignoring a block from {} we did not ask for", origin);
+                return;
+            }
+        };
+        if !self.certifies(&cert, &block.hash) {
+            log::warn!(target:"consensus", "The certificate for the block from {} is not a quorum of valid votes", origin);
+            return;
         }
-        c
+        self.commit_block(Arc::new(block));
+        self.update_highest_cert(cert);
     }
 
+    /// Our state for a node bootstrapping from us; none while nothing
+    /// beyond genesis is certified
+    pub fn snapshot(&self) -> Option<Snapshot> {
+        if self.highest_cert.votes.is_empty() {
+            return None;
+        }
+        let hash = certified_hash(&self.highest_cert)?;
+        let block = self.storage.committed_blocks_by_hash.get(&hash)?;
+        let members = sorted(&self.member_keys);
+        let beacons = self
+            .epoch_reports
+            .iter()
+            .filter_map(|(e, reports)| reports.get(&self.myid).map(|b| (*e, *b)))
+            .collect();
+        Some(Snapshot {
+            epoch: self.epoch,
+            block: Block::clone(block),
+            cert: self.highest_cert.clone(),
+            finalized_height: self.finalized_height,
+            members,
+            beacons,
+        })
+    }
+
+    /// Answers `origin`'s request for a snapshot, signed so that it can
+    /// tell the snapshot came from us
+    pub fn answer_snapshot_request(&mut self, origin: Replica, e: Height) {
+        let snapshot = match self.snapshot() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+        let auth = match canonical_bytes(&snapshot) {
+            Ok(bytes) => self.my_secret_key.sign(&with_domain(SNAPSHOT_DOMAIN, &bytes)).unwrap(),
+            Err(e) => {
+                log::error!(target:"consensus", "Cannot serialize a snapshot: {}", e);
+                return;
+            }
+        };
+        self.send_to(origin, ProtocolMsg::SnapshotResponse(snapshot, auth, e));
+    }
+
+    /// Checks a snapshot from `origin`: that the replica we bootstrap from
+    /// signed it, and that its certificate certifies its block. The votes
+    /// are checked against the keys we hold, never against the members the
+    /// snapshot names, since those are whatever the sender says they are.
+    pub fn check_snapshot(&self, origin: Replica, snapshot: &Snapshot, auth: &[u8]) -> Result<(), &'static str> {
+        if self.bootstrap != Some(origin) {
+            return Err("The snapshot is not from the replica we bootstrap from");
+        }
+        let bytes = canonical_bytes(snapshot).map_err(|_| "The snapshot does not serialize")?;
+        if !self.verify(origin, &with_domain(SNAPSHOT_DOMAIN, &bytes), auth) {
+            return Err("Cannot verify the signature on the snapshot");
+        }
+        let mut block = snapshot.block.clone();
+        block.update_hash();
+        if certified_hash(&snapshot.cert) != Some(block.hash) {
+            return Err("The certificate is not for the block of the snapshot");
+        }
+        if !self.certifies(&snapshot.cert, &block.hash) {
+            return Err("The certificate of the snapshot does not verify against our keys");
+        }
+        Ok(())
+    }
+
+    /// Starts over from `snapshot` if it checks out and is ahead of us: its
+    /// block becomes our committed tip and we skip to its epoch. Later
+    /// blocks are synced as their certificates come in. The members and
+    /// keys stay as they are; they only change through reconfigurations
+    /// in certified blocks. Returns whether it was installed.
+    pub fn install_snapshot(&mut self, origin: Replica, snapshot: Snapshot, auth: &[u8]) -> bool {
+        if let Err(e) = self.check_snapshot(origin, &snapshot, auth) {
+            self.malformed(origin, e);
+            return false;
+        }
+        let mut block = snapshot.block;
+        block.update_hash();
+        let height = block.header.height;
+        self.bootstrap = None;
+        if height <= self.highest_height {
+            log::info!(target:"consensus", "Ignoring a snapshot at height {}; we are at {}", height, self.highest_height);
+            return false;
+        }
+        if snapshot.members != sorted(&self.member_keys) {
+            log::warn!(target:"consensus", "The snapshot from {} names other members than we know of; keeping ours", origin);
+        }
+        let block = Arc::new(block);
+        self.storage.insert_committed(Arc::clone(&block));
+        self.highest_cert = snapshot.cert.clone();
+        self.highest_height = height;
+        self.last_seen_block = block;
+        self.last_seen_cert = snapshot.cert;
+        // Nothing below the tip is ours to commit or stream
+        self.last_committed_block_ht = height;
+        self.sunk_height = height;
+        self.finalized_height = snapshot.finalized_height;
+        for (e, beacon) in snapshot.beacons {
+            if e + EPOCH_REPORT_WINDOW >= snapshot.epoch {
+                self.epoch_reports.entry(e).or_default().insert(origin, beacon);
+            }
+        }
+        if snapshot.epoch > self.epoch {
+            self.quarantine = Some(snapshot.epoch);
+        }
+        log::info!(target:"consensus", "Bootstrapped from {} at epoch {}, height {}", origin, snapshot.epoch, height);
+        self.emit(NodeEvent::Bootstrapped(snapshot.epoch, height));
+        true
+    }
+
+    /// Records `block`, committed with `cert`, as the block we hand the next
+    /// leader to build on
+    pub fn set_last_seen(&mut self, block: Arc<Block>, cert: Certificate) {
+        self.save_checkpoint(&cert, &block);
+        self.last_seen_block = block;
+        self.last_seen_cert = cert;
+    }
+
+    /// The block our next proposal extends and the height it goes at
+    pub fn proposal_parent(&self) -> (Hash, Height) {
+        let prev = certified_hash(&self.highest_cert).unwrap_or_else(empty_hash);
+        (prev, self.highest_height + 1)
+    }
+
+    // Saves `block` and `cert` if they are higher than what was saved before
+    fn save_checkpoint(&mut self, cert: &Certificate, block: &Block) {
+        let path = match self.checkpoint.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+        if block.header.height <= self.checkpoint_height {
+            return;
+        }
+        let checkpoint = Checkpoint {
+            cert: cert.clone(),
+            block: block.clone(),
+        };
+        match checkpoint::save(path, &checkpoint, self.sealer.as_mut()) {
+            Ok(()) => self.checkpoint_height = block.header.height,
+            Err(e) => log::warn!(target:"consensus", "Failed to save a checkpoint to {}: {}", path.display(), e),
+        }
+    }
+
+    /// Picks up the tip saved before a restart. The block is taken as
+    /// committed and both proposes and hands on its certificate.
+    pub fn restore_checkpoint(&mut self) {
+        let sealer = self.sealer.as_mut();
+        let checkpoint = match self.checkpoint.as_ref().and_then(|p| checkpoint::load(p, sealer)) {
+            Some(checkpoint) => checkpoint,
+            None => return,
+        };
+        let block = Arc::new(checkpoint.block);
+        let height = block.header.height;
+        self.storage.insert_committed(Arc::clone(&block));
+        self.highest_cert = checkpoint.cert.clone();
+        self.highest_height = height;
+        self.last_seen_block = block;
+        self.last_seen_cert = checkpoint.cert;
+        // Whatever is below the tip was dealt with before the restart
+        self.last_committed_block_ht = height;
+        self.finalized_height = height.saturating_sub(self.finality_depth);
+        self.checkpoint_height = height;
+        log::info!(target:"consensus", "Restored the tip at height {} from the checkpoint", height);
+    }
+
+    /// Stores a committed block and updates the committed tip.
+    pub fn commit_block(&mut self, block: Arc<Block>) {
+        self.storage.insert_committed(Arc::clone(&block));
+        self.emit(NodeEvent::BlockCommitted(block.header.height, block.hash));
+        let included = &block.body.data.reconfig;
+        self.pending_reconfig
+            .retain(|(c, _)| !included.iter().any(|(d, _)| d == c));
+        for tx in block.body.data.transactions.iter() {
+            self.storage.pending_tx.remove(&ser_and_hash(tx));
+        }
+        // A height committed again, or below one already streamed, was
+        // streamed before
+        if block.header.height > self.sunk_height {
+            self.sunk_height = block.header.height;
+            self.commit_sink
+                .push(block.header.height, block.body.data.transactions.clone());
+            // Nobody listens with clients disabled
+            let _ = self.cli_send.send(Arc::clone(&block));
+        }
+        self.mark_committed(block.header.height);
+    }
+
+    /// Commits the blocks of a batch, oldest first, and then `head`, which
+    /// is returned
+    pub fn commit_batch(&mut self, batch: Vec<Block>, head: Block) -> Arc<Block> {
+        for block in batch {
+            self.commit_block(Arc::new(block));
+        }
+        let head = Arc::new(head);
+        self.commit_block(Arc::clone(&head));
+        head
+    }
+
+    /// Keeps a client transaction for our next proposals, once
+    pub fn queue_transaction(&mut self, tx: Transaction) {
+        self.transactions_received += 1;
+        self.storage.pending_tx.insert(ser_and_hash(&tx), tx);
+    }
+
+    /// Whether there is room for more client transactions
+    pub fn mempool_has_room(&self) -> bool {
+        self.storage.pending_tx.len() < self.mempool_capacity
+    }
+
+    /// The transactions our next proposal carries: the oldest pending ones,
+    /// up to `block_size`. They stay pending until a block with them is
+    /// committed.
+    pub fn proposal_transactions(&self) -> Vec<Transaction> {
+        self.storage
+            .pending_tx
+            .values()
+            .take(self.block_size)
+            .cloned()
+            .collect()
+    }
+
+    /// The transactions of the blocks of our next proposal: `batch_size`
+    /// lists of the oldest pending ones, up to `block_size` each, some
+    /// possibly empty
+    pub fn batch_transactions(&self) -> Vec<Vec<Transaction>> {
+        let mut pending = self.storage.pending_tx.values();
+        (0..self.batch_size)
+            .map(|_| pending.by_ref().take(self.block_size).cloned().collect())
+            .collect()
+    }
+
+    /// The blocks of our next proposal, extending `proposal_parent()`: all
+    /// but the last of the batch, built, and the last, still to be built and
+    /// hashed
+    pub fn assemble_proposal(&self, e: Height) -> (Vec<Block>, BlockBuilder) {
+        let (mut prev, mut height) = self.proposal_parent();
+        // All but the last block of a batch only carry transactions; the
+        // last carries the rest
+        let mut transactions = self.batch_transactions();
+        let last = transactions.pop().unwrap_or_default();
+        let mut batch = Vec::with_capacity(transactions.len());
+        for transactions in transactions {
+            let block = BlockBuilder::new()
+                .prev(prev)
+                .author(self.myid)
+                .height(height)
+                .body(BlockBody { data: Content { transactions, ..Content::new() } })
+                .compressed(self.compress_blocks)
+                .build()
+                .expect("every part of a batch block is set");
+            prev = block.hash;
+            height += 1;
+            batch.push(block);
+        }
+        let content = Content {
+            commits: self.commits.clone(),
+            acks: self.received_ack.clone(),
+            reconfig: self.pending_reconfig.clone(),
+            transactions: last,
+        };
+        let builder = BlockBuilder::new()
+            .prev(prev)
+            .author(self.myid)
+            .height(height)
+            .body(BlockBody { data: content })
+            .compressed(self.compress_blocks)
+            .extra(entropy::carry(self.entropy_to_carry(e)));
+        // Padding so that benchmarks move realistically sized blocks
+        #[cfg(feature = "bench")]
+        let builder = builder.payload(self.payload);
+        (batch, builder)
+    }
+
+    /// What a proposal assembled now for epoch `e` is built from
+    pub fn prefetch_key(&self, e: Height) -> PrefetchKey {
+        PrefetchKey {
+            epoch: e,
+            parent: self.proposal_parent(),
+            seen: certified_hash(&self.last_seen_cert),
+            acks: self.received_ack.len(),
+            reconfig: self.pending_reconfig.len(),
+            entropy: self.entropy_to_carry(e).cloned(),
+            transactions: self
+                .storage
+                .pending_tx
+                .keys()
+                .take(self.batch_size * self.block_size)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Whether to build the proposal for the next epoch now: we lead it, and
+    /// have not built one yet on the block we last saw committed
+    pub fn prefetch_due(&self) -> bool {
+        if !self.prefetch_proposal || self.solo || self.quarantine.is_some() || self.next_leader() != self.myid {
+            return false;
+        }
+        match self.prefetched.as_ref() {
+            Some(p) => p.key.epoch != self.epoch + 1 || p.key.seen != certified_hash(&self.last_seen_cert),
+            None => true,
+        }
+    }
+
+    /// The proposal built ahead of this epoch, if it can go out as it is
+    pub fn take_prefetched(&mut self) -> Option<(Vec<Block>, Block)> {
+        let p = self.prefetched.take()?;
+        if p.key.epoch != self.epoch {
+            return None;
+        }
+        let now = PrefetchKey {
+            epoch: self.epoch,
+            parent: self.proposal_parent(),
+            seen: certified_hash(&self.last_seen_cert),
+            acks: self.received_ack.len(),
+            reconfig: self.pending_reconfig.len(),
+            entropy: self.entropy_to_carry(self.epoch).cloned(),
+            // Only the ones it carries are looked up
+            transactions: Vec::new(),
+        };
+        let pending = &self.storage.pending_tx;
+        let used = p.key.still_holds(&now, self.ack_threshold, |h| pending.contains_key(h));
+        self.emit(NodeEvent::ProposalPrefetched(self.epoch, used));
+        if !used {
+            log::debug!(target:"consensus", "Not proposing the block prefetched for epoch {}: its inputs changed", self.epoch);
+            return None;
+        }
+        Some((p.batch, p.block))
+    }
+
+    /// Records that a block at `height` was committed and finalizes every
+    /// height that is now `finality_depth` blocks below the tip.
+    pub fn mark_committed(&mut self, height: Height) {
+        if height <= self.last_committed_block_ht {
+            return;
+        }
+        self.last_committed_block_ht = height;
+        while self.finalized_height + self.finality_depth < self.last_committed_block_ht {
+            self.finalized_height += 1;
+            if let Some(block) = self.storage.committed_blocks_by_ht.get(&self.finalized_height) {
+                self.scheduled_reconfig
+                    .extend(block.body.data.reconfig.iter().map(|(c, _)| c.clone()));
+            }
+            self.emit(NodeEvent::Finalized(self.finalized_height));
+        }
+    }
+
+    /// Checks `auth` on `msg` against the key of `origin`. A key that
+    /// `origin` rotated away from is still accepted for `KEY_GRACE_EPOCHS`
+    /// epochs so that votes and certificates in flight stay valid.
+    pub fn verify(&self, origin: Replica, msg: &[u8], auth: &[u8]) -> bool {
+        if !self.verified_signatures.is_empty()
+            && self
+                .verified_signatures
+                .contains_key(&(origin, msg.to_vec(), auth.to_vec()))
+        {
+            return true;
+        }
+        if let Some(pk) = self.pub_key_map.get(&origin) {
+            if pk.verify(msg, auth) {
+                return true;
+            }
+        }
+        match self.retired_keys.get(&origin) {
+            Some((pk, until)) => self.epoch <= *until && pk.verify(msg, auth),
+            None => false,
+        }
+    }
+
+    /// Reports a message from `origin` that does not decode or verify. In
+    /// strict mode this panics; otherwise the caller skips the message.
+    pub fn malformed(&self, origin: Replica, reason: &str) {
+        match self.message_handling {
+            MessageHandling::Strict => panic!("Malformed message from {}: {}", origin, reason),
+            MessageHandling::Lenient => println!("[WARN] {}.", reason),
+        }
+    }
+
+    /// Asks the other replicas to switch our key to the one encoded in
+    /// `secret_key_bytes`. We keep signing with the current key until the
+    /// block carrying the request is finalized.
+    pub fn rotate_key(&mut self, secret_key_bytes: &[u8]) {
+        let (kp, pk) = decode_keypair(&self.crypto_alg, secret_key_bytes);
+        let change = Reconfig::RotateKey(self.myid, pk);
+        let bytes = match canonical_bytes(&change) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                log::error!(target:"consensus", "Not rotating our key: cannot serialize the request: {}", e);
+                return;
+            }
+        };
+        let auth = self
+            .my_secret_key
+            .sign(&with_domain(RECONFIG_DOMAIN, &bytes))
+            .unwrap();
+        self.next_secret_key = Some(kp);
+        self.pending_reconfig.push((change.clone(), auth.clone()));
+        self.broadcast(ProtocolMsg::Reconfig(change, auth, self.epoch));
+    }
+
+    /// The keys a signature by `origin` is accepted under now
+    pub fn keys_of(&self, origin: Replica) -> Vec<PublicKey> {
+        let mut keys: Vec<PublicKey> = self.pub_key_map.get(&origin).cloned().into_iter().collect();
+        if let Some((pk, until)) = self.retired_keys.get(&origin) {
+            if self.epoch <= *until {
+                keys.push(pk.clone());
+            }
+        }
+        keys
+    }
+
+    /// The signatures the handler of `msg` checks, for the verifier pool to
+    /// check instead
+    pub fn signatures_of(&self, msg: &ProtocolMsg) -> Vec<Signature> {
+        let signature = |origin: Replica, domain: &[u8], msg: &[u8], auth: &[u8]| Signature {
+            origin,
+            keys: self.keys_of(origin),
+            msg: with_domain(domain, msg),
+            auth: auth.to_vec(),
+        };
+        // Threshold vote signatures are not under replica keys; the
+        // handlers check them
+        let vote = |v: &Vote| match self.threshold {
+            Some(_) => None,
+            None => Some(signature(v.origin, VOTE_DOMAIN, &v.msg, &v.auth)),
+        };
+        let reconfig = |change: &Reconfig, auth: &[u8]| match (change, canonical_bytes(change)) {
+            (Reconfig::RotateKey(r, _), Ok(bytes)) => Some(signature(*r, RECONFIG_DOMAIN, &bytes, auth)),
+            // Left for the handler to reject
+            (_, Err(_)) => None,
+        };
+        match msg {
+            ProtocolMsg::Vote(v, _) => vote(v).into_iter().collect(),
+            ProtocolMsg::Certificate(p, _, _) => p.votes.iter().filter_map(vote).collect(),
+            ProtocolMsg::Propose(p, _) => {
+                let data = &p.new_block.body.data;
+                p.certificate
+                    .votes
+                    .iter()
+                    .filter_map(vote)
+                    .chain(data.acks.iter().map(|v| signature(v.origin, ACK_DOMAIN, &v.msg, &v.auth)))
+                    .chain(data.reconfig.iter().filter_map(|(change, auth)| reconfig(change, auth)))
+                    .collect()
+            }
+            ProtocolMsg::Reconfig(change, auth, _) => reconfig(change, auth).into_iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Remembers `signatures`, which came in a message for epoch `e`, as
+    /// verified
+    pub fn remember_verified(&mut self, e: Height, signatures: Vec<Signature>) {
+        for sig in signatures {
+            self.verified_signatures.insert((sig.origin, sig.msg, sig.auth), e);
+        }
+    }
+
+    /// Checks that `change` is signed by the replica it affects.
+    pub fn check_reconfig(&self, change: &Reconfig, auth: &[u8]) -> bool {
+        let msg = match canonical_bytes(change) {
+            Ok(bytes) => with_domain(RECONFIG_DOMAIN, &bytes),
+            Err(_) => return false,
+        };
+        match change {
+            Reconfig::RotateKey(r, _) => self.verify(*r, &msg, auth),
+        }
+    }
+
+    /// Remembers a reconfiguration request so a leader can include it.
+    pub fn queue_reconfig(&mut self, change: Reconfig, auth: Vec<u8>) {
+        if !self.check_reconfig(&change, &auth) {
+            println!("[WARN] Cannot verify the reconfiguration request.");
+            return;
+        }
+        if self.pending_reconfig.iter().any(|(c, _)| *c == change) {
+            return;
+        }
+        self.pending_reconfig.push((change, auth));
+    }
+
+    /// Applies the finalized reconfigurations. Every replica calls this at
+    /// the same epoch boundary.
+    pub fn apply_reconfig(&mut self) {
+        for change in std::mem::take(&mut self.scheduled_reconfig) {
+            match change {
+                Reconfig::RotateKey(r, pk_data) => {
+                    let pk = match decode_public_key(&self.crypto_alg, &pk_data) {
+                        Some(pk) => pk,
+                        None => {
+                            println!("[WARN] Cannot decode the rotated key of {}.", r);
+                            continue;
+                        }
+                    };
+                    self.member_keys.insert(r, pk_data);
+                    if let Some(old) = self.pub_key_map.insert(r, pk) {
+                        self.retired_keys
+                            .insert(r, (old, self.epoch + KEY_GRACE_EPOCHS));
+                    }
+                    if r == self.myid {
+                        if let Some(kp) = self.next_secret_key.take() {
+                            self.my_secret_key = kp;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Publishes `ev` to the current subscribers, if any.
+    pub fn emit(&self, ev: NodeEvent) {
+        let _ = self.events.send(ev);
+    }
+
+    pub fn subscribe(&self) -> tokio_stream::wrappers::BroadcastStream<NodeEvent> {
+        events::subscribe(&self.events)
+    }
+
+    /// The replica that leads the current epoch
+    pub fn current_leader(&self) -> Replica {
+        self.last_leader
+    }
+
+    /// The replica that leads the epoch after this one
     pub fn next_leader(&self) -> Replica {
-        self.next_of(self.last_leader)
+        self.leader_after(self.last_leader, self.epoch + 1)
+    }
+
+    /// The leader of epoch `e`, when `prev` led the epoch before. Without
+    /// weights this is the next active replica after `prev`.
+    pub fn leader_after(&self, prev: Replica, e: Height) -> Replica {
+        match self.leader_schedule.len() {
+            0 => self.next_of(prev),
+            n => self.leader_schedule[(e % n as Height) as usize],
+        }
     }
 
+    /// The replica that leads epoch `e` if the active replicas stay as they
+    /// are, following the same rotation the reactor does at every epoch
+    /// boundary. `None` for epochs that are already over.
+    pub fn leader_for_epoch(&self, e: Height) -> Option<Replica> {
+        if e < self.epoch {
+            return None;
+        }
+        if !self.leader_schedule.is_empty() {
+            return Some(self.leader_after(self.last_leader, e));
+        }
+        let turns = match self.active_replicas.len() {
+            0 => 0,
+            n => (e - self.epoch) % n as Height,
+        };
+        let mut leader = self.last_leader;
+        for _ in 0..turns {
+            leader = self.next_of(leader);
+        }
+        Some(leader)
+    }
+
+    /// Returns the active replica that leads after `prev`. `prev` does not
+    /// have to be active itself, e.g. when it was just removed.
     pub fn next_of(&self, prev: Replica) -> Replica {
-        (prev + 1) % self.num_nodes
+        self.active_replicas
+            .iter()
+            .find(|r| **r > prev)
+            .or(self.active_replicas.first())
+            .cloned()
+            .unwrap_or(prev)
+    }
+
+    /// Replaces the set of replicas that take turns being leader.
+    pub fn set_active_replicas(&mut self, mut ids: Vec<Replica>) {
+        ids.sort();
+        ids.dedup();
+        self.active_replicas = ids;
+        if !self.weights.is_empty() {
+            self.leader_schedule = self.weighted_schedule();
+        }
+    }
+
+    // Every active replica gets as many slots as its weight. The heaviest
+    // replicas take every other slot, the rest fill the gaps, so as long as
+    // nobody holds more than half the weight nobody leads twice in a row,
+    // which dealing the commitments for the next epoch relies on.
+    fn weighted_schedule(&self) -> Vec<Replica> {
+        let mut ids = self.active_replicas.clone();
+        ids.sort_by_key(|r| std::cmp::Reverse(self.weight_of(*r)));
+        let total: u64 = ids.iter().map(|r| self.weight_of(*r)).sum();
+        let slots = (0..total).step_by(2).chain((1..total).step_by(2));
+        let turns = ids
+            .iter()
+            .flat_map(|r| std::iter::repeat(*r).take(self.weight_of(*r) as usize));
+        let mut schedule = vec![0; total as usize];
+        for (slot, r) in slots.zip(turns) {
+            schedule[slot as usize] = r;
+        }
+        schedule
     }
 }
@@ -0,0 +1,237 @@
+// Messages that could not be handed to a peer. Writing to a peer whose
+// connection is gone fails; rather than panic or lose the message without a
+// trace, the outbox logs and counts it, and keeps the most recent ones per
+// peer so that they go out once the peer is connected again. The buffer is
+// bounded: a peer that stays away long enough only gets the last
+// `capacity` messages meant for it, the protocol having moved on from the
+// older ones anyway.
+//
+// Redelivery is bounded too. A kept message goes out again on at most
+// `retries` reconnects, so a peer whose connections keep dropping does not
+// have the same messages written to it forever. Votes and deliver shards
+// are only kept while their phase may still be running: once the outbox has
+// written messages `ttl` epochs past theirs, they are given up on rather
+// than handed to a peer that can only throw them away.
+
+use config::Node;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::{Receiver, Sender, UnboundedReceiver};
+use types::{Destination, Height, ProtocolMsg, Replica};
+
+/// The queue of the connection to one peer, as the network hands it out
+pub type Link = Sender<Arc<ProtocolMsg>>;
+
+struct Letter {
+    msg: Arc<ProtocolMsg>,
+    // Reconnects it was written again on
+    attempts: usize,
+}
+
+pub struct DeadLetters {
+    /// Messages kept per peer, 0 to keep none
+    capacity: usize,
+    /// Reconnects a message is written again on, 0 for no limit
+    retries: usize,
+    /// Epochs a vote or deliver shard is kept for, 0 to keep it however late
+    ttl: Height,
+    /// The newest epoch written to anyone
+    epoch: Height,
+    letters: HashMap<Replica, VecDeque<Letter>>,
+    /// Messages that could not be delivered, whether kept or not
+    undeliverable: Arc<AtomicU64>,
+    /// Kept messages pushed out by newer ones
+    discarded: u64,
+    /// Kept messages given up on, as out of retries or past their phase
+    expired: u64,
+}
+
+impl DeadLetters {
+    pub fn new(capacity: usize, undeliverable: Arc<AtomicU64>) -> Self {
+        DeadLetters {
+            capacity,
+            retries: 0,
+            ttl: 0,
+            epoch: 0,
+            letters: HashMap::new(),
+            undeliverable,
+            discarded: 0,
+            expired: 0,
+        }
+    }
+
+    /// Keeping as many messages per peer as the config says, for as long as
+    /// it says
+    pub fn from_config(config: &Node, undeliverable: Arc<AtomicU64>) -> Self {
+        Self::new(config.dead_letter_capacity, undeliverable).retrying(config.dead_letter_retries(), config.send_ttl)
+    }
+
+    /// Writing a kept message again on at most `retries` reconnects, and
+    /// giving up on votes and deliver shards `ttl` epochs after theirs; 0
+    /// for no limit
+    pub fn retrying(mut self, retries: usize, ttl: Height) -> Self {
+        self.retries = retries;
+        self.ttl = ttl;
+        self
+    }
+
+    /// Notes that messages of epoch `e` are being written
+    pub fn observe(&mut self, e: Height) {
+        self.epoch = self.epoch.max(e);
+    }
+
+    /// Takes `msg`, which could not be delivered to `to`
+    pub fn bury(&mut self, to: Replica, msg: Arc<ProtocolMsg>) {
+        self.keep(to, Letter { msg, attempts: 0 });
+    }
+
+    fn keep(&mut self, to: Replica, letter: Letter) {
+        self.undeliverable.fetch_add(1, Ordering::Relaxed);
+        log::warn!(target:"net", "Could not deliver {} to {}: not connected", letter.msg.to_string(), to);
+        if self.capacity == 0 {
+            return;
+        }
+        let letters = self.letters.entry(to).or_default();
+        if letters.len() >= self.capacity {
+            letters.pop_front();
+            self.discarded += 1;
+        }
+        letters.push_back(letter);
+    }
+
+    // Whether the phase `msg` was made for is over
+    fn is_expired(&self, msg: &ProtocolMsg) -> bool {
+        self.ttl > 0 && msg.is_phase_bound() && msg.epoch() + self.ttl <= self.epoch
+    }
+
+    // The letters for `to` still worth writing, oldest first, which are no
+    // longer kept
+    fn take(&mut self, to: Replica) -> Vec<Letter> {
+        let letters = self.letters.remove(&to).unwrap_or_default();
+        let mut live = Vec::with_capacity(letters.len());
+        for letter in letters {
+            if self.is_expired(&letter.msg) || (self.retries > 0 && letter.attempts >= self.retries) {
+                log::debug!(target:"net", "Giving up on {} to {}", letter.msg.to_string(), to);
+                self.expired += 1;
+            } else {
+                live.push(letter);
+            }
+        }
+        live
+    }
+
+    /// The messages kept for `to` still worth writing, oldest first, which
+    /// are no longer kept
+    pub fn exhume(&mut self, to: Replica) -> VecDeque<Arc<ProtocolMsg>> {
+        self.take(to).into_iter().map(|letter| letter.msg).collect()
+    }
+
+    /// Messages kept for `to`
+    pub fn kept(&self, to: Replica) -> usize {
+        self.letters.get(&to).map_or(0, VecDeque::len)
+    }
+
+    /// Messages that could not be delivered so far
+    pub fn undeliverable(&self) -> u64 {
+        self.undeliverable.load(Ordering::Relaxed)
+    }
+
+    /// Kept messages pushed out by newer ones so far
+    pub fn discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    /// Kept messages given up on so far
+    pub fn expired(&self) -> u64 {
+        self.expired
+    }
+}
+
+/// Hands the reactor's messages to the links of the peers they are for,
+/// waiting while a link is full. Whatever cannot be written goes to the dead
+/// letters, and comes out again when the peer reconnects.
+pub struct Outbox {
+    myid: Replica,
+    replicas: Vec<Replica>,
+    links: HashMap<Replica, Link>,
+    pub dead: DeadLetters,
+}
+
+impl Outbox {
+    pub fn new(myid: Replica, replicas: Vec<Replica>, links: HashMap<Replica, Link>, dead: DeadLetters) -> Self {
+        Outbox {
+            myid,
+            replicas,
+            links,
+            dead,
+        }
+    }
+
+    pub async fn send(&mut self, to: &Destination, msg: Arc<ProtocolMsg>) {
+        self.dead.observe(msg.epoch());
+        for id in to.peers(self.myid, &self.replicas) {
+            if let Err(msg) = self.write(id, Arc::clone(&msg)).await {
+                self.dead.bury(id, msg);
+            }
+        }
+    }
+
+    async fn write(&mut self, id: Replica, msg: Arc<ProtocolMsg>) -> Result<(), Arc<ProtocolMsg>> {
+        let msg = match self.links.get(&id) {
+            Some(link) => match link.send(msg).await {
+                Ok(()) => return Ok(()),
+                Err(e) => e.0,
+            },
+            None => msg,
+        };
+        // Gone for good until it reconnects
+        self.links.remove(&id);
+        Err(msg)
+    }
+
+    /// Takes the new connection of `id`, and writes to it what `id` missed
+    /// before anything new. Should the connection drop again meanwhile,
+    /// what was not written is kept for the next one.
+    pub async fn reconnect(&mut self, id: Replica, link: Link) {
+        let missed = self.dead.take(id);
+        if !missed.is_empty() {
+            log::info!(target:"net", "Redelivering {} messages to {}", missed.len(), id);
+        }
+        self.links.insert(id, link);
+        for mut letter in missed {
+            if !self.links.contains_key(&id) {
+                self.dead.keep(id, letter);
+                continue;
+            }
+            letter.attempts += 1;
+            if let Err(msg) = self.write(id, letter.msg).await {
+                self.dead.keep(
+                    id,
+                    Letter {
+                        msg,
+                        attempts: letter.attempts,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Writes out what the reactor sends until it stops, taking new
+    /// connections from `reconnects` as they come
+    pub async fn run(
+        mut self,
+        mut recv: Receiver<(Destination, Arc<ProtocolMsg>)>,
+        mut reconnects: UnboundedReceiver<(Replica, Link)>,
+    ) {
+        loop {
+            tokio::select! {
+                sent = recv.recv() => match sent {
+                    Some((to, msg)) => self.send(&to, msg).await,
+                    None => return,
+                },
+                Some((id, link)) = reconnects.recv() => self.reconnect(id, link).await,
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+// Mixing randomness from outside the protocol, e.g. a drand round or the
+// output of a VDF, into the beacon.
+//
+// The external value is committed through the protocol before any share of
+// the beacon it is mixed into goes out. The value for epoch e is carried in
+// the header of the block proposed in epoch e - ENTROPY_LEAD, which is voted
+// on and committed like any other. At the end of epoch e every replica mixes
+// in the value the block committed in that earlier epoch carries, whatever
+// its own source said. Replicas that committed the same block thus mix in
+// the same value.
+//
+// Trust assumptions: the protocol agrees on the value but does not check
+// it. A faulty leader can carry a value of its choosing, or none, but it
+// commits to it before any share of the beacon it goes into is released.
+// As long as that beacon cannot be predicted before its shares go out, the
+// leader cannot try values against it and keep the one it likes, and the
+// mix is as unpredictable as the beacon. Replicas whose own
+// source disagrees log a warning but go with the committed value; a source
+// whose values can be checked by anyone, such as drand, makes a leader that
+// carries another one easy to spot. Values for epoch e have to reach the
+// leader of epoch e - ENTROPY_LEAD before it proposes, and values for the
+// first ENTROPY_LEAD epochs cannot be carried at all. A replica that did not
+// commit the carrying block produces the unmixed beacon and so disagrees
+// with the others; `epoch_done` reports make this visible. A beacon that
+// could not be reconstructed stays all zeroes, so failures are not hidden.
+//
+// A node reads its values from the file `entropy_source` names, one per
+// line as `<epoch> <value in hex>`. A named pipe lets another process feed
+// them as they come.
+//
+// With `vdf_iterations` set, the beacon, mixed or not, is then put through
+// a verifiable delay function and its output is the beacon everyone uses.
+// Whoever contributes last can no longer try out their options, since the
+// output of each takes longer to learn than the epoch lasts. The delay is
+// spent by every replica at the end of every epoch, on the reactor thread.
+
+use crypto::hash::{ser_and_hash, Hash, HASH_SIZE};
+use crypto::vdf;
+use std::path::PathBuf;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use types::{Block, Height};
+
+/// Values for epochs further ahead than this are dropped, so a runaway
+/// source cannot grow the queue
+pub const MAX_ENTROPY_AHEAD: Height = 64;
+/// How many epochs before the beacon it is mixed into a value is committed
+pub const ENTROPY_LEAD: Height = 2;
+
+/// (epoch, external value)
+pub type EntropySender = UnboundedSender<(Height, Hash)>;
+pub type EntropyReceiver = UnboundedReceiver<(Height, Hash)>;
+
+/// A reactor given the receiving half of a channel whose sender was
+/// dropped mixes nothing in.
+pub fn entropy_channel() -> (EntropySender, EntropyReceiver) {
+    unbounded_channel()
+}
+
+/// Reads `<epoch> <value in hex>` lines from `path` into `values` until the
+/// file ends or the reactor is gone. Lines that do not parse are skipped.
+pub async fn read_from(path: PathBuf, values: EntropySender) {
+    let file = match File::open(&path).await {
+        Ok(f) => f,
+        Err(e) => {
+            log::error!(target:"consensus", "Failed to open the entropy source {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let mut lines = BufReader::new(file).lines();
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => match parse_line(&line) {
+                Some(value) => {
+                    if values.send(value).is_err() {
+                        return;
+                    }
+                }
+                None => log::warn!(target:"consensus", "Skipping {:?} from the entropy source", line),
+            },
+            Ok(None) => return,
+            Err(e) => {
+                log::error!(target:"consensus", "Failed to read the entropy source {}: {}", path.display(), e);
+                return;
+            }
+        }
+    }
+}
+
+/// The epoch and value on a `<epoch> <value in hex>` line
+pub fn parse_line(line: &str) -> Option<(Height, Hash)> {
+    let mut words = line.split_whitespace();
+    let epoch = words.next()?.parse().ok()?;
+    let hex = words.next()?;
+    if words.next().is_some() || hex.len() != 2 * HASH_SIZE || !hex.is_ascii() {
+        return None;
+    }
+    let mut value = [0; HASH_SIZE];
+    for (i, byte) in value.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some((epoch, value))
+}
+
+/// What a proposed block's header carries for the external `value`
+pub fn carry(value: Option<&Hash>) -> Vec<u8> {
+    value.map_or_else(Vec::new, |value| value.to_vec())
+}
+
+/// The external value `block` carries, if it carries one
+pub fn carried(block: &Block) -> Option<Hash> {
+    let mut value = [0; HASH_SIZE];
+    if block.header.extra.len() != value.len() {
+        return None;
+    }
+    value.copy_from_slice(&block.header.extra);
+    Some(value)
+}
+
+/// The beacon of an epoch with `external` mixed in
+pub fn mix(beacon: &Hash, external: &Hash) -> Hash {
+    ser_and_hash(&(beacon, external))
+}
+
+/// `beacon` put through the delay function for `iterations` steps, with the
+/// proof that it was
+pub fn delay(beacon: &Hash, iterations: u64) -> (Hash, vdf::Proof) {
+    let proof = vdf::eval(beacon, iterations);
+    (proof.value(), proof)
+}
+
+/// Whether `delayed` is what `beacon` turns into through the delay function
+/// for `iterations` steps, as `proof` claims
+pub fn verify_beacon(beacon: &Hash, delayed: &Hash, iterations: u64, proof: &vdf::Proof) -> bool {
+    proof.iterations == iterations && proof.value() == *delayed && vdf::verify(beacon, proof)
+}
@@ -0,0 +1,86 @@
+use crypto::hash::Hash;
+use crypto::vdf;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use types::{Certificate, Height, Replica, Transaction};
+
+/// Number of events buffered per subscriber before it starts lagging
+pub const EVENT_CHANNEL_SIZE: usize = 1024;
+
+/// Everything observable about a running node, in the order the reactor
+/// produced it
+#[derive(Debug, Clone)]
+pub enum NodeEvent {
+    TransactionReceived(Transaction),
+    /// (epoch, block hash)
+    BlockProposed(Height, Hash),
+    /// (epoch, votes) The node holds a vote certificate for the proposal
+    VoteCertified(Height, Certificate),
+    /// (height, block hash)
+    BlockCommitted(Height, Hash),
+    /// A committed block is now `finality_depth` blocks deep
+    Finalized(Height),
+    /// (epoch, beacon value)
+    BeaconProduced(Height, Hash),
+    /// (epoch, beacon before the delay, proof) The beacon was put through the
+    /// delay function; BeaconProduced follows with the delayed value
+    BeaconDelayed(Height, Hash, vdf::Proof),
+    /// (epoch, new leader)
+    LeaderChanged(Height, Replica),
+    /// The node moved to a new epoch
+    ViewChange(Height),
+    /// Warm-up is over; epochs from this one on count towards the metrics
+    WarmupEnded(Height),
+    /// (epoch, replica, beacon) The replica reported a beacon that differs
+    /// from one already seen for the epoch
+    BeaconDisagreement(Height, Replica, Hash),
+    /// (our epoch, the cluster's epoch) The cluster is too far ahead; the
+    /// node stopped taking part until it has caught up
+    Quarantined(Height, Height),
+    /// (replica, milliseconds its clock is ahead of ours, negative if
+    /// behind) The estimate went past `max_clock_drift_ms`
+    ClockDrift(Replica, i64),
+    /// The node skipped ahead to this epoch and takes part again from the
+    /// next one
+    CaughtUp(Height),
+    /// (epoch, height) The node installed a snapshot of the cluster taken in
+    /// the epoch, with its tip at the height
+    Bootstrapped(Height, Height),
+    /// (epoch, phase) The phase was still running well past the end of the
+    /// epoch, so the watchdog moved the node on to the next one
+    PhaseStalled(Height, &'static str),
+    /// (epoch, whether it was proposed) We built our proposal for the epoch
+    /// ahead of it; it is only proposed if what it was built from held
+    ProposalPrefetched(Height, bool),
+    /// How the epoch that just ended went, as this node saw it
+    EpochSummary(EpochSummary),
+}
+
+/// One record per epoch, put together at its end from what the node already
+/// tracks
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EpochSummary {
+    pub epoch: Height,
+    pub leader: Replica,
+    /// The block committed in the epoch, if any
+    pub block_hash: Option<Hash>,
+    /// From the start of the epoch to the commit
+    pub commit_latency_ms: Option<u64>,
+    /// Votes in the certificate for the epoch's proposal; 0 without one
+    pub votes_collected: usize,
+    /// Replicas whose shares the beacon was rebuilt from
+    pub reconstruct_participants: usize,
+    pub beacon_produced: bool,
+}
+
+pub type EventSender = broadcast::Sender<NodeEvent>;
+
+pub fn event_channel() -> EventSender {
+    broadcast::channel(EVENT_CHANNEL_SIZE).0
+}
+
+/// Subscribes to the events published after this call; earlier events are
+/// not replayed.
+pub fn subscribe(events: &EventSender) -> BroadcastStream<NodeEvent> {
+    BroadcastStream::new(events.subscribe())
+}
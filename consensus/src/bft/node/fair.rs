@@ -0,0 +1,97 @@
+// Taking in the messages of every peer in turn. All peers' messages come in
+// on one channel, so a peer that sends a lot, whether chatty or malicious,
+// would have the reactor busy with its messages while everyone else's wait
+// behind them. Instead, whatever is waiting on the channel is sorted into a
+// queue per origin, and the reactor takes one message from each origin in
+// turn: a flooding peer only ever delays the others by one message each.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use tokio::sync::mpsc::UnboundedReceiver;
+use types::{ProtocolMsg, Replica};
+
+/// Messages one origin may have waiting; beyond this its new messages are
+/// dropped
+pub const MAX_QUEUED_PER_ORIGIN: usize = 10_000;
+
+/// Messages moved from the channel to the queues at a time, so that a peer
+/// sending faster than we sort cannot keep us sorting
+const DRAIN_BATCH: usize = 1024;
+
+#[derive(Default)]
+pub struct FairQueue {
+    queues: HashMap<Replica, VecDeque<ProtocolMsg>>,
+    /// Origins with messages waiting, in the order they get their next turn
+    turns: VecDeque<Replica>,
+    /// Origins whose messages are being dropped, so that it is only
+    /// reported once per flood
+    overflowing: HashSet<Replica>,
+    len: usize,
+    dropped: u64,
+}
+
+impl FairQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, origin: Replica, msg: ProtocolMsg) {
+        let queue = self.queues.entry(origin).or_default();
+        if queue.len() >= MAX_QUEUED_PER_ORIGIN {
+            if self.overflowing.insert(origin) {
+                log::warn!(target:"consensus", "Replica {} has {} messages waiting; dropping its new ones", origin, queue.len());
+            }
+            self.dropped += 1;
+            return;
+        }
+        if queue.is_empty() {
+            self.turns.push_back(origin);
+        }
+        queue.push_back(msg);
+        self.len += 1;
+    }
+
+    /// The next message of the origin whose turn it is
+    pub fn pop(&mut self) -> Option<(Replica, ProtocolMsg)> {
+        let origin = self.turns.pop_front()?;
+        let queue = self.queues.get_mut(&origin)?;
+        let msg = queue.pop_front()?;
+        if queue.is_empty() {
+            self.overflowing.remove(&origin);
+        } else {
+            self.turns.push_back(origin);
+        }
+        self.len -= 1;
+        Some((origin, msg))
+    }
+
+    /// Sorts what is waiting on `recv` into the queues and takes the next
+    /// message in turn, waiting for one if there is none. `None` once the
+    /// channel is closed and every queue is empty.
+    pub async fn next(&mut self, recv: &mut UnboundedReceiver<(Replica, ProtocolMsg)>) -> Option<(Replica, ProtocolMsg)> {
+        if self.len == 0 {
+            let (origin, msg) = recv.recv().await?;
+            self.push(origin, msg);
+        }
+        for _ in 0..DRAIN_BATCH {
+            match recv.try_recv() {
+                Ok((origin, msg)) => self.push(origin, msg),
+                Err(_) => break,
+            }
+        }
+        self.pop()
+    }
+
+    /// Messages waiting
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Messages dropped because their origin had too many waiting
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
@@ -0,0 +1,80 @@
+// Hashing large blocks off the reactor thread. `Block::update_hash` encodes
+// the whole block, which for a large body would hold up every message
+// waiting behind it.
+
+use std::collections::VecDeque;
+use tokio::task::JoinHandle;
+use types::{Block, BlockBuildError, BlockBuilder};
+use util::io::encoded_len;
+
+/// Blocks being hashed, each with what to do with it once it is done
+pub struct HashQueue<T> {
+    limit: usize,
+    pending: VecDeque<(T, JoinHandle<Block>)>,
+}
+
+impl<T> HashQueue<T> {
+    /// Blocks whose body encodes to more than `limit` bytes are hashed on a
+    /// blocking thread
+    pub fn new(limit: usize) -> Self {
+        HashQueue {
+            limit,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Hashes a small `block` right away and hands it back with `then`. A
+    /// large one is hashed on a blocking thread and comes out of `next`.
+    pub fn hash(&mut self, mut block: Block, then: T) -> Option<(Block, T)> {
+        if encoded_len(&block.body) <= self.limit {
+            block.update_hash();
+            return Some((block, then));
+        }
+        let handle = tokio::task::spawn_blocking(move || {
+            block.update_hash();
+            block
+        });
+        self.pending.push_back((then, handle));
+        None
+    }
+
+    /// Like `hash`, for a block still to be built. Fails right away if the
+    /// builder is missing a part.
+    pub fn build(&mut self, builder: BlockBuilder, then: T) -> Result<Option<(Block, T)>, BlockBuildError> {
+        builder.check()?;
+        if builder.get_body().map_or(0, |b| encoded_len(b)) <= self.limit {
+            return builder.build().map(|block| Some((block, then)));
+        }
+        let handle = tokio::task::spawn_blocking(move || {
+            builder.build().expect("the builder was checked")
+        });
+        self.pending.push_back((then, handle));
+        Ok(None)
+    }
+
+    /// Number of blocks still being hashed
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// The blocks handed off, in the order they were, as they are hashed.
+    /// Never returns while there are none.
+    pub async fn next(&mut self) -> (Block, T) {
+        loop {
+            let handle = match self.pending.front_mut() {
+                Some((_, handle)) => handle,
+                None => return std::future::pending().await,
+            };
+            let res = handle.await;
+            let (then, _) = self.pending.pop_front().unwrap();
+            match res {
+                Ok(block) => return (block, then),
+                Err(e) => log::error!(target:"consensus", "Hashing a block failed: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+// The channel client transactions reach the reactor through. It is bounded,
+// so when the reactor falls behind, whoever feeds it waits instead of
+// buffering without limit, and the number of transactions waiting is
+// published for the client side to watch.
+
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{self, error::SendError, error::TrySendError};
+use tokio::sync::watch;
+use types::Transaction;
+
+// Counts and publishes under one lock, so that the watch never ends up
+// showing an older count than the latest
+struct Depth {
+    waiting: Mutex<usize>,
+    watch: watch::Sender<usize>,
+}
+
+impl Depth {
+    fn add(&self) {
+        let mut waiting = self.waiting.lock().unwrap();
+        *waiting += 1;
+        let _ = self.watch.send(*waiting);
+    }
+
+    fn sub(&self) {
+        let mut waiting = self.waiting.lock().unwrap();
+        *waiting -= 1;
+        let _ = self.watch.send(*waiting);
+    }
+}
+
+#[derive(Clone)]
+pub struct TxSender {
+    send: mpsc::Sender<Transaction>,
+    depth: Arc<Depth>,
+    // Keeps the watch open, so that the depth is published even while
+    // nobody else watches it
+    watch: watch::Receiver<usize>,
+    capacity: usize,
+}
+
+pub struct TxReceiver {
+    recv: mpsc::Receiver<Transaction>,
+    depth: Arc<Depth>,
+}
+
+/// A channel holding at most `capacity` transactions the reactor has not
+/// taken yet
+pub fn tx_channel(capacity: usize) -> (TxSender, TxReceiver) {
+    let (send, recv) = mpsc::channel(capacity);
+    let (watch_send, watch) = watch::channel(0);
+    let depth = Arc::new(Depth {
+        waiting: Mutex::new(0),
+        watch: watch_send,
+    });
+    let sender = TxSender {
+        send,
+        depth: Arc::clone(&depth),
+        watch,
+        capacity,
+    };
+    (sender, TxReceiver { recv, depth })
+}
+
+impl TxSender {
+    /// Hands `tx` to the reactor, waiting while the mempool is full
+    pub async fn send(&self, tx: Transaction) -> Result<(), SendError<Transaction>> {
+        // Counted before it is sent, so that it is never taken uncounted
+        self.depth.add();
+        let sent = self.send.send(tx).await;
+        if sent.is_err() {
+            self.depth.sub();
+        }
+        sent
+    }
+
+    /// Hands `tx` to the reactor unless the mempool is full, so that the
+    /// client can be told to back off instead
+    pub fn try_send(&self, tx: Transaction) -> Result<(), TrySendError<Transaction>> {
+        self.depth.add();
+        let sent = self.send.try_send(tx);
+        if sent.is_err() {
+            self.depth.sub();
+        }
+        sent
+    }
+
+    /// Transactions waiting for the reactor, including those of senders
+    /// waiting for room
+    pub fn depth(&self) -> usize {
+        *self.watch.borrow()
+    }
+
+    /// Follows `depth` as it changes
+    pub fn watch(&self) -> watch::Receiver<usize> {
+        self.watch.clone()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl TxReceiver {
+    pub async fn recv(&mut self) -> Option<Transaction> {
+        let tx = self.recv.recv().await?;
+        self.depth.sub();
+        Some(tx)
+    }
+}
@@ -0,0 +1,87 @@
+// Checks on the reactor's state at the points where it is easy to get wrong.
+// They are debug assertions: a violation panics in debug and test builds,
+// and release builds compile them away.
+
+use super::accumulator::ShareGatherer;
+use super::context::Context;
+use types::Replica;
+
+fn check_gatherer(name: &str, g: &ShareGatherer, num_nodes: u16) {
+    debug_assert!(
+        g.shard_num <= num_nodes && g.shard_num as usize <= g.shard.len(),
+        "the {} gatherer counts {} shards with {} replicas",
+        name,
+        g.shard_num,
+        num_nodes
+    );
+    debug_assert!(
+        g.shard_num as usize == g.shard.iter().filter(|s| s.is_some()).count(),
+        "the {} gatherer counts {} shards but holds {}",
+        name,
+        g.shard_num,
+        g.shard.iter().filter(|s| s.is_some()).count()
+    );
+}
+
+/// What has to hold after every phase transition
+pub fn check(cx: &Context) {
+    check_gatherer("propose", &cx.propose_gatherer, cx.num_nodes);
+    check_gatherer("vote certificate", &cx.vote_cert_gatherer, cx.num_nodes);
+    check_gatherer("commit", &cx.commit_gatherer, cx.num_nodes);
+    // Votes stop being counted at the first quorum
+    let votes = cx.votes_for(cx.epoch);
+    let counted = votes.len().saturating_sub(1);
+    debug_assert!(
+        cx.quorum_votes(&votes[..counted]).is_none(),
+        "{} votes counted past the quorum",
+        votes.len()
+    );
+    debug_assert!(
+        cx.active_replicas.is_empty() || cx.active_replicas.contains(&cx.last_leader),
+        "leader {} is not an active replica",
+        cx.last_leader
+    );
+}
+
+/// What has to hold once a new epoch has started, given the leader of the
+/// previous one
+pub fn check_epoch_start(cx: &Context, prev_leader: Replica) {
+    check(cx);
+    for (name, g) in [
+        ("propose", &cx.propose_gatherer),
+        ("vote certificate", &cx.vote_cert_gatherer),
+        ("commit", &cx.commit_gatherer),
+    ]
+    .iter()
+    {
+        debug_assert!(
+            g.shard_num == 0 && g.reference.is_none(),
+            "the {} gatherer was not cleared for epoch {}",
+            name,
+            cx.epoch
+        );
+    }
+    debug_assert!(
+        cx.received_vote.keys().all(|e| *e >= cx.epoch),
+        "votes carried into epoch {}",
+        cx.epoch
+    );
+    debug_assert!(
+        cx.last_leader == cx.leader_after(prev_leader, cx.epoch),
+        "leader went from {} to {} instead of {}",
+        prev_leader,
+        cx.last_leader,
+        cx.leader_after(prev_leader, cx.epoch)
+    );
+}
+
+/// What has to hold once the block of the epoch is committed
+pub fn check_committed(cx: &Context) {
+    check(cx);
+    debug_assert!(cx.received_propose.is_none(), "the proposal outlived its commit in epoch {}", cx.epoch);
+    debug_assert!(
+        cx.received_certificate.is_none(),
+        "the vote certificate outlived its commit in epoch {}",
+        cx.epoch
+    );
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+use types::Height;
+
+/// How long one epoch took, and how much it sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochSample {
+    pub epoch: Height,
+    pub duration: Duration,
+    /// Encoded bytes handed to the network, counted once per recipient
+    pub bytes_sent: u64,
+    /// Recorded during warm-up and left out of the summary
+    pub warmup: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+/// Epoch durations of a running node. The first `warmup_epochs` epochs pay
+/// for connection setup and cold caches, so they are kept but not
+/// summarized.
+#[derive(Debug, Clone)]
+pub struct EpochMetrics {
+    pub warmup_epochs: Height,
+    pub samples: Vec<EpochSample>,
+}
+
+impl EpochMetrics {
+    pub fn new(warmup_epochs: Height) -> Self {
+        EpochMetrics {
+            warmup_epochs,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Whether `epoch` is still part of the warm-up. Epoch 0 is the boot
+    /// delay and never measured.
+    pub fn is_warmup(&self, epoch: Height) -> bool {
+        epoch <= self.warmup_epochs
+    }
+
+    pub fn record(&mut self, epoch: Height, duration: Duration, bytes_sent: u64) -> &EpochSample {
+        self.samples.push(EpochSample {
+            epoch,
+            duration,
+            bytes_sent,
+            warmup: self.is_warmup(epoch),
+        });
+        self.samples.last().unwrap()
+    }
+
+    /// Statistics over the epochs after warm-up, if there are any yet.
+    pub fn summary(&self) -> Option<Summary> {
+        let measured: Vec<Duration> = self
+            .samples
+            .iter()
+            .filter(|s| !s.warmup)
+            .map(|s| s.duration)
+            .collect();
+        if measured.is_empty() {
+            return None;
+        }
+        let total: Duration = measured.iter().sum();
+        Some(Summary {
+            count: measured.len(),
+            mean: total / measured.len() as u32,
+            min: *measured.iter().min().unwrap(),
+            max: *measured.iter().max().unwrap(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warmup_is_tagged_and_excluded() {
+        let mut metrics = EpochMetrics::new(2);
+        assert!(metrics.record(1, Duration::from_millis(900), 0).warmup);
+        assert!(metrics.record(2, Duration::from_millis(700), 0).warmup);
+        assert_eq!(metrics.summary(), None);
+        assert!(!metrics.record(3, Duration::from_millis(100), 0).warmup);
+        assert!(!metrics.record(4, Duration::from_millis(300), 0).warmup);
+        assert_eq!(metrics.samples.len(), 4);
+        assert_eq!(
+            metrics.summary(),
+            Some(Summary {
+                count: 2,
+                mean: Duration::from_millis(200),
+                min: Duration::from_millis(100),
+                max: Duration::from_millis(300),
+            })
+        );
+    }
+}
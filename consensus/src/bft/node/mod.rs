@@ -1,6 +1,29 @@
 pub mod accumulator;
+pub mod admin;
+pub mod beacons;
+pub mod budget;
+pub mod checkpoint;
+pub mod client;
+pub mod chunks;
+pub mod clock;
 pub mod context;
+pub mod deadletter;
+pub mod entropy;
+pub mod events;
+pub mod fair;
+pub mod hashing;
+pub mod intake;
+pub mod invariants;
+pub mod metrics;
+pub mod network;
+pub mod offload;
+pub mod prefetch;
 pub mod reactor;
+pub mod sealing;
+pub mod sink;
+pub mod trace;
+pub mod validate;
+pub mod verifier;
 
 pub use reactor::*;
 // pub use proposal::*;
@@ -0,0 +1,485 @@
+// The connections between replicas. Every replica dials each of the others
+// and takes a connection from each in turn, so two replicas talk over two
+// connections: the one we dialed carries what we send, the one the peer
+// dialed what it sends us.
+//
+// Nothing on the way out buffers without bound. What the reactor sends goes
+// through a bounded channel to the outbox, which hands every message to a
+// bounded queue per peer and waits while that queue is full. A writer per
+// peer drains its queue into the socket. A peer that stops reading fills the
+// kernel's buffers, then its queue, then the reactor's channel, and the
+// reactor keeps what it cannot send in its own capped backlog.
+//
+// A peer whose connection fails is dialed again. Until it is back, what is
+// sent to it goes to the outbox's dead letters, as many as
+// `dead_letter_capacity` per peer, and is written first once it is.
+//
+// The frames to each peer are numbered, and the peer keeps saying on the
+// same connection which one it expects next. What it had not acknowledged
+// when a connection failed, and what was still queued for it, is written
+// again on the next one, from where the peer says to resume. The peer
+// delivers every frame once and in order, whichever connection it came on.
+//
+// Both ends of a connection prove who they are before anything else goes
+// over it. The end that accepts sends a fresh nonce, and the end that dialed
+// answers with the id it claims, its public key and its signature over the
+// nonce. The key has to be the one the config gives that id. The dialer then
+// checks the acceptor the same way with a nonce of its own. A peer can thus
+// neither speak for another replica nor be dialed in its place.
+
+use super::context::{decode_keypair, decode_public_key};
+use super::deadletter::{DeadLetters, Link, Outbox};
+use config::{Node, PeerTable, SocketOptions};
+use crypto::hash::{with_domain, PEER_DOMAIN};
+use crypto::rand::{rngs::StdRng, RngCore, SeedableRng};
+use crypto_lib::{Keypair, PublicKey};
+use futures::{SinkExt, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{channel, unbounded_channel, Receiver, Sender, UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
+use tokio_util::codec::{FramedRead, FramedWrite};
+use types::{Destination, ProtocolMsg, Replica};
+use util::codec::{proto, EnCodec};
+use util::peers::{accept_peers_with, route_peers, ConnEvent, ConnId, Stage};
+use util::sequence::{Delivery, InSeq, OutSeq, Seq, Sequenced};
+use util::socket;
+
+/// How long to wait before dialing a peer again
+pub const DIAL_RETRY: Duration = Duration::from_millis(100);
+
+/// Messages waiting for a peer's socket before sending to it waits
+pub const PEER_QUEUE: usize = 64;
+
+/// How long either end of a new connection has to prove who it is
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bytes of the nonce a replica is asked to sign
+pub const NONCE_SIZE: usize = 32;
+
+// Longest handshake message taken, well above any key and signature
+const MAX_HANDSHAKE: u32 = 4096;
+
+/// What the reactor sends
+pub type Outbound = (Destination, Arc<ProtocolMsg>);
+
+/// Connects to every other replica in the config, and takes their
+/// connections on `listener`. Returns once every peer is connected both
+/// ways, with the channel the reactor sends through, bounded by
+/// `consensus_channel_size`, and the messages of every peer.
+pub async fn start(config: &Node, listener: TcpListener) -> (Sender<Outbound>, UnboundedReceiver<(Replica, ProtocolMsg)>) {
+    let (send, recv) = channel(config.consensus_channel_size());
+    let (inbound, net_recv) = unbounded_channel();
+    let (reconnect, reconnects) = unbounded_channel();
+    let mut peers: Vec<Replica> = config.net_map.keys().cloned().filter(|id| *id != config.id).collect();
+    peers.sort();
+
+    let creds = Arc::new(Credentials::from_config(config));
+    // Peers dial us while we dial them, so both go on at once
+    let dial = async {
+        let mut links = HashMap::with_capacity(peers.len());
+        for id in peers.iter().cloned() {
+            let peer = Peer {
+                id,
+                addr: config.net_map[&id].clone(),
+                creds: Arc::clone(&creds),
+                opts: config.socket.clone(),
+                codec: EnCodec::from_config(config),
+            };
+            let conn = peer.connect().await;
+            let (link, queue) = channel(PEER_QUEUE);
+            links.insert(id, link);
+            tokio::spawn(peer.run(conn, queue, reconnect.clone()));
+        }
+        links
+    };
+    let admit = {
+        let creds = Arc::clone(&creds);
+        move |stream| identify(Arc::clone(&creds), stream)
+    };
+    let mut seqs = HashMap::with_capacity(peers.len());
+    let mut acks = HashMap::with_capacity(peers.len());
+    for id in peers.iter().cloned() {
+        let (acked, ack) = watch::channel(0);
+        seqs.insert(id, (InSeq::new(), acked));
+        acks.insert(id, ack);
+    }
+    let (rejoin, rejoined) = unbounded_channel();
+    let (routed, frames) = unbounded_channel();
+    tokio::spawn(route_peers(HashMap::new(), rejoined, routed, |id| {
+        log::info!(target:"net", "peer={} is not heard from until it dials us again", id)
+    }));
+    tokio::spawn(deliver(frames, seqs, inbound));
+    // Every connection is served as soon as the peer is identified, so that
+    // a peer that dialed us early hears where to resume without waiting for
+    // the others. Whoever connects again later takes the place of its lost
+    // connection.
+    let (heard, mut heard_from) = watch::channel(0);
+    let codec = proto::Codec::from_config(config);
+    let opts = config.socket.clone();
+    tokio::spawn(async move {
+        let mut connected = HashSet::new();
+        loop {
+            let mut conns = HashMap::new();
+            let joined = accept_peers_with(&listener, 1, &opts, admit.clone(), |ev| established(&mut conns, ev)).await;
+            for (id, stream) in joined {
+                if let Some(frames) = serve(conns[&id], id, stream, &codec, &acks) {
+                    if rejoin.send((id, frames)).is_err() {
+                        return;
+                    }
+                    connected.insert(id);
+                    let _ = heard.send(connected.len());
+                }
+            }
+        }
+    });
+    let accept = async {
+        while *heard_from.borrow() < peers.len() {
+            if heard_from.changed().await.is_err() {
+                return;
+            }
+        }
+    };
+    let (links, ()) = tokio::join!(dial, accept);
+    log::info!(target:"net", "Connected to {} peers", peers.len());
+    let dead = DeadLetters::from_config(config, Arc::new(AtomicU64::new(0)));
+    let outbox = Outbox::new(config.id, config.net_map.keys().cloned().collect(), links, dead);
+    tokio::spawn(outbox.run(recv, reconnects));
+    (send, net_recv)
+}
+
+/// What a replica proves who it is with, and checks the others against
+pub struct Credentials {
+    myid: Replica,
+    keypair: Keypair,
+    public: Vec<u8>,
+    table: PeerTable,
+    keys: HashMap<Replica, PublicKey>,
+}
+
+// What the end that dialed says about itself
+#[derive(Serialize, Deserialize)]
+struct Hello {
+    id: Replica,
+    key: Vec<u8>,
+    sign: Vec<u8>,
+    nonce: [u8; NONCE_SIZE],
+}
+
+impl Credentials {
+    pub fn from_config(config: &Node) -> Self {
+        let (keypair, public) = decode_keypair(&config.crypto_alg, &config.secret_key_bytes);
+        let keys = config
+            .pk_map
+            .iter()
+            .map(|(id, pk)| {
+                let pk = decode_public_key(&config.crypto_alg, pk).expect("Failed to decode the public key from the config");
+                (*id, pk)
+            })
+            .collect();
+        Credentials {
+            myid: config.id,
+            keypair,
+            public,
+            table: config.peer_table(),
+            keys,
+        }
+    }
+
+    // What `from` signs to show `to` it holds its key
+    fn proof(to: Replica, nonce: &[u8; NONCE_SIZE]) -> Vec<u8> {
+        with_domain(PEER_DOMAIN, &[&to.to_be_bytes()[..], &nonce[..]].concat())
+    }
+
+    fn sign(&self, to: Replica, nonce: &[u8; NONCE_SIZE]) -> Vec<u8> {
+        self.keypair
+            .sign(&Self::proof(to, nonce))
+            .expect("Failed to sign the handshake")
+    }
+
+    fn verify(&self, from: Replica, nonce: &[u8; NONCE_SIZE], sign: &[u8]) -> bool {
+        self.keys
+            .get(&from)
+            .map_or(false, |pk| pk.verify(&Self::proof(self.myid, nonce), sign))
+    }
+
+    /// The handshake of the end that dialed `to`. Fails unless `to` proves
+    /// it is who we meant to dial.
+    pub async fn hello(&self, to: Replica, stream: &mut TcpStream) -> io::Result<()> {
+        let mut theirs = [0; NONCE_SIZE];
+        stream.read_exact(&mut theirs).await?;
+        let hello = Hello {
+            id: self.myid,
+            key: self.public.clone(),
+            sign: self.sign(to, &theirs),
+            nonce: nonce(),
+        };
+        write_handshake(stream, &bincode::serialize(&hello).map_err(invalid)?).await?;
+        let sign = read_handshake(stream).await?;
+        if !self.verify(to, &hello.nonce, &sign) {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{} did not prove its key", to)));
+        }
+        Ok(())
+    }
+
+    /// The handshake of the end that accepted a connection. Returns the
+    /// replica the other end proved it is.
+    pub async fn admit(&self, stream: &mut TcpStream) -> io::Result<Replica> {
+        let ours = nonce();
+        stream.write_all(&ours).await?;
+        let hello: Hello = bincode::deserialize(&read_handshake(stream).await?).map_err(invalid)?;
+        let id = self
+            .table
+            .authorize(&hello.key, Some(hello.id))
+            .map_err(|e| io::Error::new(io::ErrorKind::PermissionDenied, e))?;
+        if !self.verify(id, &ours, &hello.sign) {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{} did not prove its key", id)));
+        }
+        write_handshake(stream, &self.sign(id, &hello.nonce)).await?;
+        Ok(id)
+    }
+}
+
+fn nonce() -> [u8; NONCE_SIZE] {
+    let mut nonce = [0; NONCE_SIZE];
+    StdRng::from_entropy().fill_bytes(&mut nonce);
+    nonce
+}
+
+fn invalid(e: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+async fn write_handshake(stream: &mut TcpStream, data: &[u8]) -> io::Result<()> {
+    stream.write_u32(data.len() as u32).await?;
+    stream.write_all(data).await
+}
+
+async fn read_handshake(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let len = stream.read_u32().await?;
+    if len > MAX_HANDSHAKE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("handshake of {} bytes", len)));
+    }
+    let mut data = vec![0; len as usize];
+    stream.read_exact(&mut data).await?;
+    Ok(data)
+}
+
+/// The connection we dial to one peer
+struct Peer {
+    id: Replica,
+    addr: String,
+    creds: Arc<Credentials>,
+    opts: SocketOptions,
+    codec: EnCodec,
+}
+
+impl Peer {
+    /// Dials until the peer answers
+    async fn connect(&self) -> (ConnId, TcpStream) {
+        loop {
+            match self.dial().await {
+                Ok(stream) => {
+                    let conn = ConnId::next();
+                    log::debug!(target:"net", "conn={} peer={} addr={} dialed", conn, self.id, self.addr);
+                    return (conn, stream);
+                }
+                Err(e) => {
+                    log::debug!(target:"net", "peer={} addr={} failed to connect: {}", self.id, self.addr, e);
+                    tokio::time::sleep(DIAL_RETRY).await;
+                }
+            }
+        }
+    }
+
+    async fn dial(&self) -> io::Result<TcpStream> {
+        let mut stream = socket::connect(&self.addr, &self.opts).await?;
+        match tokio::time::timeout(HANDSHAKE_TIMEOUT, self.creds.hello(self.id, &mut stream)).await {
+            Ok(done) => done?,
+            Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "the handshake timed out")),
+        }
+        Ok(stream)
+    }
+
+    /// Writes what is queued for the peer, and dials it again whenever the
+    /// connection fails. The queue of a failed connection is closed, so
+    /// that what is sent to the peer goes to the dead letters until a new
+    /// one is handed out on `reconnect`.
+    async fn run(
+        self,
+        (mut conn, mut stream): (ConnId, TcpStream),
+        mut queue: Receiver<Arc<ProtocolMsg>>,
+        reconnect: UnboundedSender<(Replica, Link)>,
+    ) {
+        let mut out = OutSeq::new();
+        loop {
+            match self.write(stream, &mut queue, &mut out).await {
+                Ok(()) => return,
+                Err(e) => log::warn!(target:"net", "conn={} peer={} failed to send: {}; reconnecting", conn, self.id, e),
+            }
+            // What was queued goes out on the next connection, after what
+            // may not have made it on this one
+            queue.close();
+            while let Some(msg) = queue.recv().await {
+                out.push(msg);
+            }
+            let (next, fresh_stream) = self.connect().await;
+            conn = next;
+            stream = fresh_stream;
+            let (link, fresh) = channel(PEER_QUEUE);
+            queue = fresh;
+            if reconnect.send((self.id, link)).is_err() {
+                return;
+            }
+        }
+    }
+
+    // Numbers what is queued with `out` and writes it, after what the peer
+    // missed of the frames written before. The peer first says which frame
+    // it expects, then acknowledges the frames it delivered. Returns once
+    // nothing more can be queued, or the connection fails.
+    async fn write(
+        &self,
+        stream: TcpStream,
+        queue: &mut Receiver<Arc<ProtocolMsg>>,
+        out: &mut OutSeq<Arc<ProtocolMsg>>,
+    ) -> io::Result<()> {
+        let (mut read, write) = stream.into_split();
+        let next = match tokio::time::timeout(HANDSHAKE_TIMEOUT, read.read_u64()).await {
+            Ok(next) => next?,
+            Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "the peer did not say where to resume")),
+        };
+        let (ack, mut acked) = watch::channel(next);
+        tokio::spawn(async move {
+            while let Ok(next) = read.read_u64().await {
+                if ack.send(next).is_err() {
+                    return;
+                }
+            }
+        });
+        let mut framed = FramedWrite::new(write, self.codec.clone());
+        for frame in out.resend(next) {
+            framed.send(frame).await?;
+        }
+        loop {
+            tokio::select! {
+                msg = queue.recv() => match msg {
+                    Some(msg) => framed.send(out.push(msg)).await?,
+                    None => return Ok(()),
+                },
+                changed = acked.changed() => match changed {
+                    Ok(()) => {
+                        let next = *acked.borrow();
+                        out.ack(next);
+                    }
+                    Err(_) => return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "the peer stopped acknowledging")),
+                },
+            }
+        }
+    }
+}
+
+/// The peer a fresh connection proves it is
+async fn identify(creds: Arc<Credentials>, mut stream: TcpStream) -> Option<(Replica, TcpStream)> {
+    match tokio::time::timeout(HANDSHAKE_TIMEOUT, creds.admit(&mut stream)).await {
+        Ok(Ok(id)) => Some((id, stream)),
+        Ok(Err(e)) => {
+            log::warn!(target:"net", "Refusing a connection: {}", e);
+            None
+        }
+        Err(_) => {
+            log::warn!(target:"net", "Refusing a connection: no handshake within {:?}", HANDSHAKE_TIMEOUT);
+            None
+        }
+    }
+}
+
+// Notes which connection a peer was given, to tag what is logged about it
+fn established(conns: &mut HashMap<Replica, ConnId>, ev: &ConnEvent) {
+    if let (Stage::Established, Some(peer)) = (ev.stage, ev.peer) {
+        conns.insert(peer, ev.conn);
+    }
+}
+
+/// What a peer sends, read off one of its connections
+type Frames = Pin<Box<dyn Stream<Item = Result<Sequenced<Option<ProtocolMsg>>, ReadError>> + Send>>;
+
+/// A read that failed, along with the connection it failed on
+struct ReadError(ConnId, io::Error);
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "conn={} {}", self.0, self.1)
+    }
+}
+
+/// Starts acknowledging what `id` sends on connection `conn`, and returns
+/// what it sends. `None` for a replica we do not take frames from.
+fn serve(
+    conn: ConnId,
+    id: Replica,
+    stream: TcpStream,
+    codec: &proto::Codec,
+    acks: &HashMap<Replica, watch::Receiver<Seq>>,
+) -> Option<Frames> {
+    let acked = match acks.get(&id) {
+        Some(acked) => acked.clone(),
+        None => {
+            log::warn!(target:"net", "conn={} peer={} is not in the config; dropping it", conn, id);
+            return None;
+        }
+    };
+    let (read, write) = stream.into_split();
+    tokio::spawn(acknowledge(conn, id, write, acked));
+    let frames = FramedRead::new(read, proto::SeqCodec(codec.clone()));
+    Some(Box::pin(frames.map(move |frame| frame.map_err(|e| ReadError(conn, e)))))
+}
+
+/// Tells `id` on connection `conn` which of its frames we expect next: first
+/// where to resume, then again after every delivery
+async fn acknowledge(conn: ConnId, id: Replica, mut write: OwnedWriteHalf, mut acked: watch::Receiver<Seq>) {
+    loop {
+        let next = *acked.borrow();
+        if let Err(e) = write.write_u64(next).await {
+            log::debug!(target:"net", "conn={} peer={} failed to acknowledge: {}", conn, id, e);
+            return;
+        }
+        if acked.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Hands on the frames of every peer in order and once each, whichever of
+/// its connections they came on
+async fn deliver(
+    mut frames: UnboundedReceiver<(Replica, Sequenced<Option<ProtocolMsg>>)>,
+    mut seqs: HashMap<Replica, (InSeq, watch::Sender<Seq>)>,
+    inbound: UnboundedSender<(Replica, ProtocolMsg)>,
+) {
+    while let Some((id, frame)) = frames.recv().await {
+        let (seq, acked) = match seqs.get_mut(&id) {
+            Some(x) => x,
+            None => continue,
+        };
+        match seq.accept(frame) {
+            Delivery::Deliver(Some(msg)) => {
+                if inbound.send((id, msg)).is_err() {
+                    return;
+                }
+            }
+            Delivery::Deliver(None) | Delivery::Gap { .. } => (),
+            Delivery::Duplicate(n) => log::debug!(target:"net", "peer={} sent frame {} again", id, n),
+        }
+        let _ = acked.send(seq.next_expected());
+    }
+}
@@ -0,0 +1,123 @@
+// Checking shards and beacon commitments on a thread of their own. The
+// signature and accumulator checks of the Deliver* shards and the EVSS
+// checks of a Commit are the bulk of the crypto a replica does, and done on
+// the reactor they hold up the proposals and votes queued behind them. The
+// checker only checks; whatever it is given comes back with the outcome and
+// the reactor applies it to its state, so the gatherers and the beacon
+// queues are only ever touched by the reactor.
+
+use super::accumulator::verify_share;
+use super::context::verify_commit;
+use bytes::Bytes;
+use crypto_lib::PublicKey;
+use std::collections::VecDeque;
+use tokio::sync::mpsc::{self, error::TrySendError, UnboundedReceiver};
+use types::{DataWithAcc, Height, Replica, SignedData};
+
+pub use types::Gatherer;
+
+/// Checks that may wait for the checker at once; beyond this they are
+/// dropped
+pub const MAX_PENDING_CHECKS: usize = 10_000;
+
+pub enum Check {
+    Shard {
+        kind: Gatherer,
+        /// The epoch the shard was received in
+        epoch: Height,
+        sh: Bytes,
+        n: Replica,
+        sign: SignedData,
+        pk: PublicKey,
+        domain: Vec<u8>,
+    },
+    /// The next leader's commitments and our shares of them
+    Commit {
+        origin: Replica,
+        epoch: Height,
+        sh: VecDeque<crypto::EVSSShare381>,
+        c: Vec<crypto::EVSSCommit381>,
+        z: DataWithAcc,
+        pk: PublicKey,
+        pp: crypto::EVSSPublicParams381,
+        num_nodes: Replica,
+        num_faults: Replica,
+    },
+}
+
+impl Check {
+    fn run(&self) -> Result<(), &'static str> {
+        match self {
+            Check::Shard {
+                sh, sign, pk, domain, ..
+            } => verify_share(domain, sh, pk, sign),
+            Check::Commit {
+                sh,
+                c,
+                z,
+                pk,
+                pp,
+                num_nodes,
+                num_faults,
+                ..
+            } => verify_commit(*num_nodes, *num_faults, pk, pp, sh, c, z),
+        }
+    }
+}
+
+pub struct ShareChecker {
+    jobs: Option<mpsc::Sender<Check>>,
+    done: UnboundedReceiver<(Check, Result<(), &'static str>)>,
+}
+
+impl ShareChecker {
+    /// A checker with a thread of its own, or none that checks nothing if
+    /// not `enabled`
+    pub fn new(enabled: bool) -> Self {
+        let (done_send, done) = mpsc::unbounded_channel();
+        if !enabled {
+            return ShareChecker { jobs: None, done };
+        }
+        let (jobs, mut jobs_recv) = mpsc::channel::<Check>(MAX_PENDING_CHECKS);
+        std::thread::Builder::new()
+            .name("share-check".into())
+            .spawn(move || {
+                // Ends once the reactor drops the checker
+                while let Some(check) = jobs_recv.blocking_recv() {
+                    let res = check.run();
+                    if done_send.send((check, res)).is_err() {
+                        return;
+                    }
+                }
+            })
+            .expect("failed to start the share checker");
+        ShareChecker {
+            jobs: Some(jobs),
+            done,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.jobs.is_some()
+    }
+
+    /// Hands `check` to the checker thread. Dropped with a warning if the
+    /// checker is too far behind.
+    pub fn submit(&self, check: Check) {
+        let jobs = self.jobs.as_ref().expect("the share checker is not enabled");
+        match jobs.try_send(check) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => println!("[WARN] The share checker is behind; dropping a check."),
+            Err(TrySendError::Closed(_)) => log::error!(target:"consensus", "The share checker is gone"),
+        }
+    }
+
+    /// The checks handed off, with their outcome, as they are done. Never
+    /// returns while there are none.
+    pub async fn next(&mut self) -> (Check, Result<(), &'static str>) {
+        match self.done.recv().await {
+            Some(done) => done,
+            None => std::future::pending().await,
+        }
+    }
+}
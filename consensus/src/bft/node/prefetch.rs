@@ -0,0 +1,52 @@
+// The next proposal, built ahead of its epoch. A leader would otherwise only
+// start assembling and hashing its blocks once its turn comes, with all of
+// it on the critical path of the epoch. With prefetching on, the next leader
+// builds them while it waits out the epoch before, and proposes them as they
+// are if they still extend the tip it would propose on. Otherwise they are
+// thrown away and the proposal is built as usual.
+
+use crypto::hash::Hash;
+use types::{Block, Height};
+
+/// What a prefetched proposal was built from
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefetchKey {
+    /// The epoch it is for
+    pub epoch: Height,
+    /// The block it extends and the height it goes at
+    pub parent: (Hash, Height),
+    /// The block certified by our last seen certificate when it was built
+    pub seen: Option<Hash>,
+    /// Acks it carries
+    pub acks: usize,
+    /// Reconfiguration requests it carries
+    pub reconfig: usize,
+    /// The external value it carries for the beacon
+    pub entropy: Option<Hash>,
+    /// The pending transactions it carries, in order
+    pub transactions: Vec<Hash>,
+}
+
+pub struct Prefetched {
+    pub key: PrefetchKey,
+    /// All but the last block of the batch
+    pub batch: Vec<Block>,
+    /// The last block, hashed
+    pub block: Block,
+}
+
+impl PrefetchKey {
+    /// Whether a proposal built from `self` can go out in place of one built
+    /// from `now`. It has to extend the same block with the same
+    /// reconfiguration requests and external value, and carry transactions
+    /// that are all still pending. Acks that came in since are only waited for if it carries
+    /// fewer than `ack_threshold`.
+    pub fn still_holds(&self, now: &PrefetchKey, ack_threshold: usize, pending: impl Fn(&Hash) -> bool) -> bool {
+        self.epoch == now.epoch
+            && self.parent == now.parent
+            && self.reconfig == now.reconfig
+            && self.entropy == now.entropy
+            && (self.acks >= now.acks || self.acks >= ack_threshold)
+            && self.transactions.iter().all(pending)
+    }
+}
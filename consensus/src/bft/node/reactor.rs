@@ -1,19 +1,32 @@
-use super::accumulator::{get_acc, get_sign, to_shards};
-use super::context::Context;
-use config::Node;
-use crypto::hash::EMPTY_HASH;
-use crypto::rand::{SeedableRng};
-use crypto::{CanonicalSerialize};
-use num_traits::Zero;
+use super::accumulator::{get_acc, get_sign, reject_share, shards_of};
+use super::admin::{self, AdminRequest};
+use super::chunks::split_commit;
+use super::clock::{wall_clock_ms, Clock, Timer};
+use super::context::{verify_commit, Context, NO_BEACON};
+use super::entropy::EntropyReceiver;
+use super::events::{EpochSummary, EventSender, NodeEvent};
+use super::fair::FairQueue;
+use super::hashing::HashQueue;
+use super::intake::TxReceiver;
+use super::invariants;
+use super::offload::{Check, Gatherer, ShareChecker};
+use super::prefetch::{PrefetchKey, Prefetched};
+use super::sink::{CommitSink, SinkFeed};
+use super::trace::{self, FileRecorder};
+use super::validate::check_batch;
+use super::verifier::SignatureVerifier;
+use config::{Node, PhaseDurations};
+use crypto::hash::{Hash, CERT_DOMAIN, COMMIT_DOMAIN, PROPOSE_DOMAIN};
 use std::time::Duration;
-use std::{convert::TryInto, sync::Arc};
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
-use tokio::time;
+use bytes::Bytes;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use tokio::sync::mpsc::{channel, Sender, UnboundedReceiver, UnboundedSender};
+use tokio::time::Instant;
 use types::{
-    commit_from_bytes, Block, Certificate, Content, Propose, ProtocolMsg, Replica,
-    Transaction, Vote,
+    Block, Certificate, DataWithAcc, Destination, Height, Propose,
+    ProtocolMsg, Replica, SignedData, Vote,
 };
-use util::io::to_bytes;
 
 #[derive(PartialEq, Debug)]
 enum Phase {
@@ -38,480 +51,1115 @@ impl Phase {
     }
 }
 
+/// A deliver action whose data cannot be serialized is dropped; the epoch
+/// goes on without our shards, as if we had not received the data.
+fn skip_deliver(cx: &Context, what: &str, e: bincode::Error) {
+    log::error!(target:"consensus", "Not delivering the {} of epoch {}: cannot serialize it: {}", what, cx.epoch, e);
+}
+
 fn deliver_propose(cx: &mut Context, myid: Replica) {
-    let shards = to_shards(
-        &to_bytes(&cx.received_propose.as_ref().unwrap())[..],
-        cx.num_nodes as usize,
-        cx.num_faults as usize,
-    );
-    cx.propose_gatherer.add_share(
-        shards[myid as usize].clone(),
-        myid,
-        cx.accumulator_pub_params_map.get(&cx.last_leader).unwrap(),
-        cx.pub_key_map.get(&cx.last_leader).unwrap(),
-        get_sign(cx.received_propose_sign.as_ref().unwrap(), myid),
-    );
-    for i in 0..cx.num_nodes {
-        if i != myid {
-            cx.net_send
-                .send((
-                    i,
-                    Arc::new(ProtocolMsg::DeliverPropose(
-                        shards[i as usize].clone(),
-                        i,
-                        get_sign(cx.received_propose_sign.as_ref().unwrap(), i),
-                    )),
-                ))
-                .unwrap();
-        }
+    // Every recipient shares the same buffers instead of a deep copy
+    let shards: Vec<Bytes> = match shards_of(cx.received_propose.as_ref().unwrap(), cx.num_nodes, cx.num_faults) {
+        Ok(shards) => shards.into_iter().map(Bytes::from).collect(),
+        Err(e) => return skip_deliver(cx, "proposal", e),
+    };
+    let me = match cx.shard_index(myid) {
+        Some(me) => me,
+        None => return,
+    };
+    if cx.loopback {
+        cx.self_inbox.push_back(ProtocolMsg::DeliverPropose(
+            shards[me as usize].clone(),
+            me,
+            get_sign(cx.received_propose_sign.as_ref().unwrap(), me),
+            cx.epoch,
+        ));
+    } else {
+        cx.propose_gatherer.add_share(
+            shards[me as usize].clone(),
+            me,
+            cx.accumulator_pub_params_map.get(&cx.last_leader).unwrap(),
+            cx.pub_key_map.get(&cx.last_leader).unwrap(),
+            get_sign(cx.received_propose_sign.as_ref().unwrap(), me),
+        );
+    }
+    for i in cx.shard_recipients() {
+        let n = match cx.shard_index(i) {
+            Some(n) => n,
+            None => continue,
+        };
+        cx.send_to(
+            i,
+            ProtocolMsg::DeliverPropose(
+                shards[n as usize].clone(),
+                n,
+                get_sign(cx.received_propose_sign.as_ref().unwrap(), n),
+                cx.epoch,
+            ),
+        );
     }
     if !cx.propose_share_sent {
-        cx.net_send
-            .send((
-                cx.num_nodes,
-                Arc::new(ProtocolMsg::DeliverPropose(
-                    shards[myid as usize].clone(),
-                    myid,
-                    get_sign(cx.received_propose_sign.as_ref().unwrap(), myid),
-                )),
-            ))
-            .unwrap();
+        cx.broadcast(ProtocolMsg::DeliverPropose(
+            shards[me as usize].clone(),
+            me,
+            get_sign(cx.received_propose_sign.as_ref().unwrap(), me),
+            cx.epoch,
+        ));
         cx.propose_share_sent = true;
     }
 }
 
 fn deliver_vote_cert(cx: &mut Context, myid: Replica) {
-    let shards = to_shards(
-        &to_bytes(&cx.received_certificate.as_ref().unwrap())[..],
-        cx.num_nodes as usize,
-        cx.num_faults as usize,
-    );
-    cx.vote_cert_gatherer.add_share(
-        shards[myid as usize].clone(),
-        myid,
-        cx.accumulator_pub_params_map.get(&cx.last_leader).unwrap(),
-        cx.pub_key_map.get(&cx.last_leader).unwrap(),
-        get_sign(cx.received_certificate_sign.as_ref().unwrap(), myid),
-    );
-    for i in 0..cx.num_nodes {
-        if i != myid {
-            cx.net_send
-                .send((
-                    i,
-                    Arc::new(ProtocolMsg::DeliverVoteCert(
-                        shards[i as usize].clone(),
-                        i,
-                        get_sign(cx.received_certificate_sign.as_ref().unwrap(), i),
-                    )),
-                ))
-                .unwrap();
-        }
+    let shards: Vec<Bytes> = match shards_of(cx.received_certificate.as_ref().unwrap(), cx.num_nodes, cx.num_faults) {
+        Ok(shards) => shards.into_iter().map(Bytes::from).collect(),
+        Err(e) => return skip_deliver(cx, "vote certificate", e),
+    };
+    let me = match cx.shard_index(myid) {
+        Some(me) => me,
+        None => return,
+    };
+    if cx.loopback {
+        cx.self_inbox.push_back(ProtocolMsg::DeliverVoteCert(
+            shards[me as usize].clone(),
+            me,
+            get_sign(cx.received_certificate_sign.as_ref().unwrap(), me),
+            cx.epoch,
+        ));
+    } else {
+        cx.vote_cert_gatherer.add_share(
+            shards[me as usize].clone(),
+            me,
+            cx.accumulator_pub_params_map.get(&cx.last_leader).unwrap(),
+            cx.pub_key_map.get(&cx.last_leader).unwrap(),
+            get_sign(cx.received_certificate_sign.as_ref().unwrap(), me),
+        );
+    }
+    for i in cx.shard_recipients() {
+        let n = match cx.shard_index(i) {
+            Some(n) => n,
+            None => continue,
+        };
+        cx.send_to(
+            i,
+            ProtocolMsg::DeliverVoteCert(
+                shards[n as usize].clone(),
+                n,
+                get_sign(cx.received_certificate_sign.as_ref().unwrap(), n),
+                cx.epoch,
+            ),
+        );
     }
     if !cx.vote_cert_share_sent {
-        cx.net_send
-            .send((
-                cx.num_nodes,
-                Arc::new(ProtocolMsg::DeliverVoteCert(
-                    shards[myid as usize].clone(),
-                    myid,
-                    get_sign(cx.received_certificate_sign.as_ref().unwrap(), myid),
-                )),
-            ))
-            .unwrap();
+        cx.broadcast(ProtocolMsg::DeliverVoteCert(
+            shards[me as usize].clone(),
+            me,
+            get_sign(cx.received_certificate_sign.as_ref().unwrap(), me),
+            cx.epoch,
+        ));
         cx.vote_cert_share_sent = true;
     }
 }
 
 fn deliver_commit(cx: &mut Context, myid: Replica) {
-    let shards = to_shards(
-        &to_bytes(&cx.received_commit.as_ref().unwrap())[..],
-        cx.num_nodes as usize,
-        cx.num_faults as usize,
-    );
-    cx.commit_gatherer.add_share(
-        shards[myid as usize].clone(),
-        myid,
-        cx.accumulator_pub_params_map
-            .get(&cx.next_leader())
-            .unwrap(),
-        cx.pub_key_map.get(&cx.next_leader()).unwrap(),
-        get_sign(cx.received_commit_sign.as_ref().unwrap(), myid),
-    );
-    for i in 0..cx.num_nodes {
-        if i != myid {
-            cx.net_send
-                .send((
-                    i,
-                    Arc::new(ProtocolMsg::DeliverCommit(
-                        shards[i as usize].clone(),
-                        i,
-                        get_sign(cx.received_commit_sign.as_ref().unwrap(), i),
-                    )),
-                ))
-                .unwrap();
-        }
+    let shards: Vec<Bytes> = match shards_of(cx.received_commit.as_ref().unwrap(), cx.num_nodes, cx.num_faults) {
+        Ok(shards) => shards.into_iter().map(Bytes::from).collect(),
+        Err(e) => return skip_deliver(cx, "commitments", e),
+    };
+    let me = match cx.shard_index(myid) {
+        Some(me) => me,
+        None => return,
+    };
+    if cx.loopback {
+        cx.self_inbox.push_back(ProtocolMsg::DeliverCommit(
+            shards[me as usize].clone(),
+            me,
+            get_sign(cx.received_commit_sign.as_ref().unwrap(), me),
+            cx.epoch,
+        ));
+    } else {
+        cx.commit_gatherer.add_share(
+            shards[me as usize].clone(),
+            me,
+            cx.accumulator_pub_params_map
+                .get(&cx.next_leader())
+                .unwrap(),
+            cx.pub_key_map.get(&cx.next_leader()).unwrap(),
+            get_sign(cx.received_commit_sign.as_ref().unwrap(), me),
+        );
+    }
+    for i in cx.shard_recipients() {
+        let n = match cx.shard_index(i) {
+            Some(n) => n,
+            None => continue,
+        };
+        cx.send_to(
+            i,
+            ProtocolMsg::DeliverCommit(
+                shards[n as usize].clone(),
+                n,
+                get_sign(cx.received_commit_sign.as_ref().unwrap(), n),
+                cx.epoch,
+            ),
+        );
     }
     if !cx.commit_share_sent {
-        cx.net_send
-            .send((
-                cx.num_nodes,
-                Arc::new(ProtocolMsg::DeliverCommit(
-                    shards[myid as usize].clone(),
-                    myid,
-                    get_sign(cx.received_commit_sign.as_ref().unwrap(), myid),
-                )),
-            ))
-            .unwrap();
+        cx.broadcast(ProtocolMsg::DeliverCommit(
+            shards[me as usize].clone(),
+            me,
+            get_sign(cx.received_commit_sign.as_ref().unwrap(), me),
+            cx.epoch,
+        ));
         cx.commit_share_sent = true;
     }
 }
 
+/// The proposal for this epoch from the shards we gathered. A proposal that
+/// does not decode, or is for another epoch, is rebuilt from other shard
+/// sets as configured.
+fn rebuild_propose(cx: &mut Context) -> Option<Propose> {
+    let epoch = cx.epoch;
+    cx.propose_gatherer.reconstruct_checked(
+        cx.num_nodes,
+        cx.num_faults,
+        cx.reconstruct_retries,
+        |bytes| Propose::try_from_bytes(bytes).filter(|p| p.epoch == epoch),
+    )
+}
+
+/// The blocks of `propose` as they are committed: the rest of the batch,
+/// hashed, and the new block, still to be hashed. The certificate goes on
+/// the first of them, the one whose parent it certifies. The padding is not
+/// part of the blocks as the application sees them.
+fn into_batch(propose: Propose) -> (Vec<Block>, Block) {
+    let Propose {
+        new_block: mut head,
+        certificate,
+        mut batch,
+        ..
+    } = propose;
+    match batch.first_mut() {
+        Some(first) => first.certificate = certificate,
+        None => head.certificate = certificate,
+    }
+    for block in batch.iter_mut() {
+        block.payload = Vec::new();
+        block.update_hash();
+    }
+    head.payload = Vec::new();
+    (batch, head)
+}
+
+/// Solo mode: with nobody to share with, our proposal is voted for and
+/// committed on the spot.
+fn commit_solo(cx: &mut Context, myid: Replica, propose: Propose) {
+    let (batch, mut block) = into_batch(propose);
+    block.update_hash();
+    let vote = sign_vote(cx, myid, &block.hash);
+    let certificate = cx.certificate_of(vec![vote]);
+    cx.emit(NodeEvent::VoteCertified(cx.epoch, certificate.clone()));
+    cx.epoch_summary.votes_collected = certificate.votes.len();
+    match check_batch(&batch, &block, cx, cx.received_certificate.as_ref()) {
+        Ok(()) => {
+            cx.epoch_summary.block_hash = Some(block.hash);
+            cx.agree_entropy(cx.epoch, &block);
+            let block = cx.commit_batch(batch, block);
+            cx.set_last_seen(block, certificate);
+        }
+        Err(e) => println!("[WARN] Refusing to commit block {:x?}: {}.", block.hash, e),
+    }
+}
+
+/// Whether `msg` is still handled in quarantine, because it only moves
+/// committed blocks around
+fn serves_sync(msg: &ProtocolMsg) -> bool {
+    match msg {
+        ProtocolMsg::BlockRequest(_, _)
+        | ProtocolMsg::BlockResponse(_, _)
+        | ProtocolMsg::SnapshotRequest(_)
+        | ProtocolMsg::SnapshotResponse(_, _, _) => true,
+        _ => false,
+    }
+}
+
+/// What a block hashed by the `HashQueue` is for
+enum Hashed {
+    /// (epoch, highest certificate, rest of the batch) Our proposal, still
+    /// to be sent
+    Propose(Height, Certificate, Vec<Block>),
+    /// (epoch) The proposal we vote for
+    Vote(Height),
+    /// (epoch, vote certificate, rest of the batch) The block we commit
+    Commit(Height, Option<Certificate>, Vec<Block>),
+    /// (what it was built from, rest of the batch) Our proposal for the next
+    /// epoch, built ahead of it
+    Prefetch(PrefetchKey, Vec<Block>),
+}
+
+fn sign_vote(cx: &Context, myid: Replica, hash: &Hash) -> Vote {
+    Vote {
+        msg: hash.to_vec(),
+        origin: myid,
+        auth: cx.vote_auth(hash),
+    }
+}
+
+/// Carries on with `block` now that it is hashed. Blocks hashed too late for
+/// the epoch they were for are dropped.
+fn on_hashed(cx: &mut Context, myid: Replica, block: Block, then: Hashed) {
+    match then {
+        Hashed::Propose(e, certificate, batch) if e == cx.epoch => {
+            let hash = block.hash;
+            cx.emit(NodeEvent::BlockProposed(e, hash));
+            let propose = Propose {
+                new_block: block,
+                certificate,
+                epoch: e,
+                batch,
+            };
+            let sign = match get_acc(cx, &propose, PROPOSE_DOMAIN) {
+                Ok((_, sign)) => sign,
+                Err(err) => {
+                    log::error!(target:"consensus", "Not proposing in epoch {}: cannot serialize the proposal: {}", e, err);
+                    return;
+                }
+            };
+            cx.broadcast(ProtocolMsg::Propose(propose.clone(), sign.clone()));
+            cx.received_propose = Some(propose);
+            cx.received_propose_sign = Some(sign);
+            if cx.self_vote {
+                // Counted like the votes of the others once the inbox is
+                // drained
+                let vote = sign_vote(cx, myid, &hash);
+                cx.self_inbox.push_back(ProtocolMsg::Vote(vote, e));
+            }
+        }
+        Hashed::Vote(e) if e == cx.epoch => {
+            let vote = sign_vote(cx, myid, &block.hash);
+            cx.send_to(cx.last_leader, ProtocolMsg::Vote(vote, e));
+        }
+        Hashed::Commit(e, voted, batch) if e == cx.epoch => match check_batch(&batch, &block, cx, voted.as_ref()) {
+            Ok(()) => {
+                cx.epoch_summary.block_hash = Some(block.hash);
+                cx.agree_entropy(e, &block);
+                let block = cx.commit_batch(batch, block);
+                // Without a vote certificate we cannot show the next leader
+                // the block is certified
+                if let Some(cert) = voted {
+                    cx.set_last_seen(block, cert);
+                }
+            }
+            Err(e) => println!("[WARN] Refusing to commit block {:x?}: {}.", block.hash, e),
+        },
+        Hashed::Prefetch(key, batch) if key.epoch == cx.epoch + 1 => {
+            cx.prefetched = Some(Prefetched { key, batch, block });
+        }
+        _ => println!("[WARN] Block {:x?} was hashed too late for its epoch.", block.hash),
+    }
+}
+
+/// Takes the next leader's commitments and our shares of them, whether they
+/// came in one message or were reassembled from chunks.
+fn on_commit(
+    cx: &mut Context,
+    origin: Replica,
+    sh: VecDeque<crypto::EVSSShare381>,
+    c: Vec<crypto::EVSSCommit381>,
+    z: DataWithAcc,
+) {
+    let (pk, pp) = match cx.commit_checks(origin, &sh, &c) {
+        Ok(Some(checks)) => checks,
+        Ok(None) => return accept_commit(cx, sh, c, z),
+        Err(e) => return cx.malformed(origin, e),
+    };
+    if cx.offload_shares {
+        let check = Check::Commit {
+            origin,
+            epoch: cx.epoch,
+            sh,
+            c,
+            z,
+            pk,
+            pp,
+            num_nodes: cx.num_nodes,
+            num_faults: cx.num_faults,
+        };
+        cx.pending_checks.push_back(check);
+        return;
+    }
+    match verify_commit(cx.num_nodes, cx.num_faults, &pk, &pp, &sh, &c, &z) {
+        Ok(()) => accept_commit(cx, sh, c, z),
+        Err(e) => cx.malformed(origin, e),
+    }
+}
+
+fn accept_commit(
+    cx: &mut Context,
+    sh: VecDeque<crypto::EVSSShare381>,
+    c: Vec<crypto::EVSSCommit381>,
+    z: DataWithAcc,
+) {
+    cx.queue_dealt(cx.next_leader(), cx.epoch, sh, &c);
+    cx.received_commit = Some(c);
+    cx.received_commit_sign = Some(z);
+}
+
+/// Adds a shard `leader` sent to the gatherer of `kind`, or hands it to the
+/// share checker first if checks are offloaded.
+fn gather(cx: &mut Context, kind: Gatherer, leader: Replica, sh: Bytes, n: Replica, sign: SignedData) {
+    let pp = cx.accumulator_pub_params_map.get(&leader).unwrap();
+    let pk = cx.pub_key_map.get(&leader).unwrap();
+    let gatherer = match kind {
+        Gatherer::Propose => &mut cx.propose_gatherer,
+        Gatherer::VoteCert => &mut cx.vote_cert_gatherer,
+        Gatherer::Commit => &mut cx.commit_gatherer,
+    };
+    if !cx.offload_shares {
+        gatherer.add_share(sh, n, pp, pk, sign);
+        return;
+    }
+    if gatherer.shard[n as usize].is_some() {
+        return;
+    }
+    let check = Check::Shard {
+        kind,
+        epoch: cx.epoch,
+        sh,
+        n,
+        sign,
+        pk: pk.clone(),
+        domain: gatherer.domain.clone(),
+    };
+    cx.pending_checks.push_back(check);
+}
+
+/// Applies a check the share checker is done with. Checks from an earlier
+/// epoch are dropped, as their gatherers were cleared.
+fn on_checked(cx: &mut Context, check: Check, res: Result<(), &'static str>) {
+    match check {
+        Check::Shard { kind, epoch, sh, n, sign, .. } if epoch == cx.epoch => match res {
+            Ok(()) => cx.gatherer(kind).insert_verified(sh, n, sign),
+            Err(e) => reject_share(e),
+        },
+        Check::Commit { origin, epoch, sh, c, z, .. } if epoch == cx.epoch => match res {
+            Ok(()) => accept_commit(cx, sh, c, z),
+            Err(e) => cx.malformed(origin, e),
+        },
+        _ => log::debug!(target:"consensus", "Dropping a share check from an earlier epoch"),
+    }
+}
+
+fn on_message(
+    cx: &mut Context,
+    myid: Replica,
+    timing: &PhaseDurations,
+    phase: &mut Phase,
+    phase_end: &mut Timer,
+    origin: Replica,
+    pmsg: ProtocolMsg,
+) {
+    let s = pmsg.to_string();
+    println!("{}: Received {:?}.", myid, s);
+    let time_before = phase_end.now();
+    // The budget is on how long the reactor is busy, whatever the clock
+    let started = std::time::Instant::now();
+    handle_message(cx, myid, timing, phase, phase_end, origin, pmsg);
+    cx.handler_budget.record(s, origin, started.elapsed());
+    let time_after = phase_end.now();
+    println!("{}: Message {:?} took {} ms.", myid, s, (time_after - time_before).as_millis());
+}
+
+/// Takes in `pmsg` from `origin`, whose signatures, if it was given to the
+/// verifier pool, checked out. It is handled now unless it is for a later
+/// epoch or of a kind that is slow to handle.
+fn take_in(
+    cx: &mut Context,
+    myid: Replica,
+    timing: &PhaseDurations,
+    phase: &mut Phase,
+    phase_end: &mut Timer,
+    origin: Replica,
+    pmsg: ProtocolMsg,
+) {
+    cx.last_heard.insert(origin, cx.epoch);
+    cx.observe_epoch(origin, pmsg.epoch());
+    if cx.quarantine.is_some() && !serves_sync(&pmsg) {
+        // Whatever it is, it is against state we are about to drop
+    } else if let Some(pmsg) = cx.hold_if_early(origin, pmsg) {
+        cx.relay_shard(origin, &pmsg);
+        if let Some((origin, pmsg)) = cx.handler_budget.admit(origin, pmsg) {
+            on_message(cx, myid, timing, phase, phase_end, origin, pmsg);
+        }
+    }
+}
+
+fn handle_message(
+    cx: &mut Context,
+    myid: Replica,
+    timing: &PhaseDurations,
+    phase: &mut Phase,
+    phase_end: &mut Timer,
+    origin: Replica,
+    pmsg: ProtocolMsg,
+) {
+    match pmsg {
+        ProtocolMsg::Certificate(p, _, sent) => {
+            cx.observe_clock(origin, sent, wall_clock_ms());
+            if myid == cx.last_leader && *phase == Phase::Propose {
+                // Check that the certificate is valid.
+                if p.votes.iter().any(|vote| !cx.verify_vote(vote)) {
+                    return cx.malformed(origin, "Cannot verify the certificate");
+                }
+                cx.accept_certificate(origin, p);
+            }
+        },
+        ProtocolMsg::Propose(mut p, z) => {
+            if let Err(e) = cx.admit_propose(origin, &p) {
+                println!("[WARN] Dropping a proposal from {}: {}.", origin, e);
+                return;
+            }
+            p.new_block.update_hash();
+            match cx.verify_propose(&p) {
+                Ok(()) => {
+                    cx.emit(NodeEvent::BlockProposed(p.epoch, p.new_block.hash));
+                    cx.received_propose = Some(p);
+                    cx.received_propose_sign = Some(z);
+                }
+                Err(e) => cx.malformed(origin, e),
+            }
+        },
+        ProtocolMsg::Vote(p, e) => {
+            if let Some(certificate) = cx.accept_vote(origin, p, e) {
+                match get_acc(cx, &certificate, CERT_DOMAIN) {
+                    Ok((_, sign)) => {
+                        cx.broadcast(ProtocolMsg::VoteCert(certificate.clone(), sign.clone(), cx.epoch));
+                        cx.emit(NodeEvent::VoteCertified(cx.epoch, certificate.clone()));
+                        cx.epoch_summary.votes_collected = certificate.votes.len();
+                        cx.received_certificate = Some(certificate);
+                        cx.received_certificate_sign = Some(sign);
+                        deliver_vote_cert(cx, myid);
+                        *phase = Phase::Commit;
+                        phase_end.reset(phase_end.now() + Duration::from_millis(timing.commit));
+                    }
+                    Err(err) => log::error!(target:"consensus", "Not sending the vote certificate of epoch {}: cannot serialize it: {}", cx.epoch, err),
+                }
+            }
+        },
+        ProtocolMsg::VoteCert(c, z, _) => {
+            cx.emit(NodeEvent::VoteCertified(cx.epoch, c.clone()));
+            cx.epoch_summary.votes_collected = c.votes.len();
+            cx.received_certificate = Some(c);
+            cx.received_certificate_sign = Some(z);
+            deliver_vote_cert(cx, myid);
+            *phase = Phase::Commit;
+            phase_end.reset(phase_end.now() + Duration::from_millis(timing.commit));
+        },
+        ProtocolMsg::DeliverPropose(sh, n, z, e) => {
+            if !cx.propose_share_sent && cx.shard_index(myid) == Some(n) {
+                cx.broadcast(ProtocolMsg::DeliverPropose(
+                    sh.clone(),
+                    n,
+                    z.clone(),
+                    e,
+                ));
+                cx.propose_share_sent = true;
+            }
+            gather(cx, Gatherer::Propose, cx.last_leader, sh, n, z);
+        }
+        ProtocolMsg::DeliverVoteCert(sh, n, z, e) => {
+            if !cx.vote_cert_share_sent && cx.shard_index(myid) == Some(n) {
+                cx.broadcast(ProtocolMsg::DeliverVoteCert(
+                    sh.clone(),
+                    n,
+                    z.clone(),
+                    e,
+                ));
+                cx.vote_cert_share_sent = true;
+            }
+            gather(cx, Gatherer::VoteCert, cx.last_leader, sh, n, z);
+        }
+        ProtocolMsg::Reconstruct(sh, e) => {
+            cx.accept_reconstruct(origin, sh, e);
+        }
+        ProtocolMsg::ReconstructRequest(from, e) => {
+            let held = match cx.withheld_share.as_ref() {
+                Some((share, h)) if *h == e => Some(share.clone()),
+                _ => None,
+            };
+            if let Some(share) = held {
+                cx.send_to(from, ProtocolMsg::Reconstruct(share, e));
+            }
+        }
+        ProtocolMsg::Commit(sh, c, z, _) => {
+            on_commit(cx, origin, sh, c, z);
+        }
+        ProtocolMsg::CommitChunk(e, index, total, sh, c, z) => {
+            if let Some((sh, c, z)) = cx.commit_assembler.add(e, index, total, sh, c, z) {
+                on_commit(cx, origin, sh, c, z);
+            }
+        }
+        ProtocolMsg::DeliverCommit(sh, n, z, e) => {
+            if !cx.commit_share_sent && cx.shard_index(myid) == Some(n) {
+                cx.broadcast(ProtocolMsg::DeliverCommit(
+                    sh.clone(),
+                    n,
+                    z.clone(),
+                    e,
+                ));
+                cx.commit_share_sent = true;
+            }
+            let leader = cx.next_leader();
+            gather(cx, Gatherer::Commit, leader, sh, n, z);
+        }
+        ProtocolMsg::Ack(v, _) => {
+            if !cx.received_ack.iter().any(|a| a.origin == v.origin) {
+                cx.received_ack.push(v);
+            }
+            if cx.awaiting_acks && cx.received_ack.len() >= cx.ack_threshold {
+                // No need to wait out the rest of the window
+                phase_end.reset(phase_end.now());
+            }
+        }
+        ProtocolMsg::Reconfig(change, auth, _) => {
+            cx.queue_reconfig(change, auth);
+        }
+        ProtocolMsg::EpochDone(e, beacon) => {
+            cx.record_epoch_done(origin, e, beacon);
+        }
+        ProtocolMsg::BlockRequest(hash, e) => {
+            cx.answer_block_request(origin, hash, e);
+        }
+        ProtocolMsg::BlockResponse(block, _) => {
+            cx.accept_synced_block(origin, block);
+        }
+        ProtocolMsg::SnapshotRequest(e) => {
+            cx.answer_snapshot_request(origin, e);
+        }
+        ProtocolMsg::SnapshotResponse(snapshot, auth, _) => {
+            if cx.bootstrap.is_some() {
+                cx.install_snapshot(origin, snapshot, &auth);
+            }
+        }
+        ProtocolMsg::Ready(false) => {
+            // A replica still waiting at the startup barrier
+            cx.send_to(origin, ProtocolMsg::Ready(true));
+        }
+        ProtocolMsg::Ready(true) => {}
+        ProtocolMsg::RequestShare(e, kind, n) => {
+            cx.answer_share_request(origin, e, kind, n);
+        }
+        ProtocolMsg::DealRequest(e) => {
+            cx.answer_deal_request(origin, e);
+        }
+        ProtocolMsg::Deals(e, held, deals) => {
+            cx.accept_deals(origin, e, held, deals);
+        }
+    };
+}
+
+/// Waits until `num_nodes - num_faults` replicas, counting us, are up, and
+/// returns when that was, or `None` if the network is gone. Our `Ready` goes
+/// out again every `delta` until then, in case the first one went out before
+/// the others were listening. Anything else that comes in meanwhile is left
+/// in `inbound` for the reactor.
+async fn startup_barrier(
+    cx: &mut Context,
+    inbound: &mut FairQueue,
+    net_recv: &mut UnboundedReceiver<(Replica, ProtocolMsg)>,
+    clock: &Arc<dyn Clock>,
+    recorder: &mut Option<FileRecorder>,
+    delta: Duration,
+) -> Option<Instant> {
+    let needed = (cx.num_nodes - cx.num_faults) as usize;
+    let mut ready = HashSet::new();
+    ready.insert(cx.myid);
+    cx.broadcast(ProtocolMsg::Ready(false));
+    let mut resend = Timer::new(Arc::clone(clock), clock.now() + delta);
+    while ready.len() < needed {
+        tokio::select! {
+            pmsg_opt = net_recv.recv() => {
+                let (origin, pmsg) = pmsg_opt?;
+                match pmsg {
+                    ProtocolMsg::Ready(_) => {
+                        if let Some(Err(e)) = recorder.as_mut().map(|r| r.record(origin, &pmsg)) {
+                            log::error!(target:"consensus", "Stopped recording the message trace: {}", e);
+                            *recorder = None;
+                        }
+                        ready.insert(origin);
+                    }
+                    pmsg => inbound.push(origin, pmsg),
+                }
+            },
+            permit = cx.net_send.reserve(), if cx.has_outbound() => {
+                match permit {
+                    Ok(permit) => {
+                        if let Some(out) = cx.next_outbound() {
+                            permit.send(out);
+                        }
+                    }
+                    Err(_) => return None,
+                }
+            },
+            _ = &mut resend => {
+                cx.broadcast(ProtocolMsg::Ready(false));
+                resend.reset(clock.now() + delta);
+            },
+        }
+    }
+    log::info!(target:"consensus", "{} of {} replicas are up; starting epoch 0", ready.len(), cx.num_nodes);
+    Some(clock.now())
+}
+
 pub async fn reactor(
     config: &Node,
     is_client_apollo_enabled: bool,
-    net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
+    net_send: Sender<(Destination, Arc<ProtocolMsg>)>,
     mut net_recv: UnboundedReceiver<(Replica, ProtocolMsg)>,
-    _cli_send: UnboundedSender<Arc<Block>>,
-    mut cli_recv: UnboundedReceiver<Transaction>,
+    cli_send: UnboundedSender<Arc<Block>>,
+    mut cli_recv: TxReceiver,
+    events: EventSender,
+    clock: Arc<dyn Clock>,
+    mut entropy: EntropyReceiver,
+    sink: Option<Box<dyn CommitSink>>,
 ) {
-    // Optimization to improve latency when the payloads are high
-    let (send, mut _recv) = unbounded_channel();
-    let mut cx = Context::new(config, net_send, send);
+    let mut cx = match Context::new(config, net_send, cli_send) {
+        Ok(cx) => cx,
+        Err(e) => {
+            log::error!(target:"consensus", "Not starting the reactor: {}", e);
+            return;
+        }
+    };
     cx.is_client_apollo_enabled = is_client_apollo_enabled;
+    cx.events = events;
+    cx.commit_sink = SinkFeed::new(sink, config.commit_sink_capacity());
     let myid = config.id;
     let delta = config.delta;
+    let timing = config.phase_durations();
     // A little time to boot everything up
-    let begin = time::Instant::now() + Duration::from_millis(delta);
+    let mut begin = clock.now() + Duration::from_millis(delta);
     let mut phase = Phase::End;
-    let phase_end = time::sleep_until(begin);
-    tokio::pin!(phase_end);
+    let mut epoch_started = clock.now();
+    let mut phase_end = Timer::new(Arc::clone(&clock), begin);
+    // No phase of an epoch should outlast it. One that does is stuck on
+    // something that will not come, and we give up on the rest of the epoch.
+    let stall_margin = Duration::from_millis(timing.stall_margin);
+    let mut watchdog = Timer::new(Arc::clone(&clock), begin + stall_margin);
+    let (admin_send, mut admin_recv) = channel::<AdminRequest>(16);
+    let mut admin_open = config.admin_socket.is_some();
+    let mut entropy_open = true;
+    let mut cli_open = !config.disable_clients;
+    let mut hashes = HashQueue::new(config.inline_hash_limit());
+    let mut checker = ShareChecker::new(config.offload_shares);
+    let mut verifier = SignatureVerifier::new(config.verify_workers, Arc::clone(&cx.invalid_signatures));
+    let mut recorder = trace::from_config(config, Arc::clone(&clock));
+    // Every peer's messages are taken in turn
+    let mut inbound = FairQueue::new();
+    if config.startup_barrier {
+        let delta = Duration::from_millis(delta);
+        match startup_barrier(&mut cx, &mut inbound, &mut net_recv, &clock, &mut recorder, delta).await {
+            Some(passed) => {
+                // Everyone past the barrier starts a delta from now
+                begin = passed + delta;
+                epoch_started = passed;
+                phase_end.reset(begin);
+                watchdog.reset(begin + stall_margin);
+            }
+            None => {
+                log::error!(target:"node", "Protocol message channel closed");
+                return;
+            }
+        }
+    }
+    if let Some(path) = config.admin_socket.as_ref() {
+        tokio::spawn(admin::serve(path.into(), admin_send));
+    } else {
+        drop(admin_send);
+    }
     loop {
         tokio::select! {
-            pmsg_opt = net_recv.recv() => {
+            pmsg_opt = inbound.next(&mut net_recv) => {
                 log::debug!(target:"consensus", "Got {:?}", pmsg_opt);
                 // Received a protocol message
                 if let None = pmsg_opt {
                     log::error!(target:"node", "Protocol message channel closed");
-                    std::process::exit(0);
+                    return;
                 }
-                let (_, pmsg) = pmsg_opt.unwrap();
-                let s = pmsg.to_string();
-                println!("{}: Received {:?}.", myid, s);
-                let time_before = time::Instant::now();
-                match pmsg {
-                    ProtocolMsg::Certificate(p) => {
-                        if myid == cx.last_leader && phase == Phase::Propose {
-                            // Check that the certificate is valid.
-                            for vote in p.votes.iter() {
-                                if !cx.pub_key_map.get(&vote.origin).unwrap().verify(&vote.msg, &vote.auth) {
-                                    println!("[WARN] Cannot verify the certificate.")
-                                }
-                            }
-                            let hash = if p.votes.len() == 0 { EMPTY_HASH.to_vec() } else { p.votes[0].msg.clone() };
-                            if let Some(block) = cx.storage.committed_blocks_by_hash.get(&TryInto::<[u8; 32]>::try_into(hash).unwrap()) {
-                                if block.header.height > cx.highest_height {
-                                    cx.highest_cert = p;
-                                    cx.highest_height = block.header.height;
-                                }
-                            }
-                        }
-                    },
-                    ProtocolMsg::Propose(mut p, z) => {
-                        let mut is_valid = true;
-                        p.new_block.update_hash();
-                        let hash = p.new_block.hash.to_vec();
-                        for cert in p.certificate.votes.iter() {
-                            if cert.msg != hash {
-                                println!("[WARN] The hash of the certification does not match block.");
-                                is_valid = false;
-                            }
-                            if !cx.pub_key_map.get(&cert.origin).unwrap().verify(&cert.msg, &cert.auth) {
-                                println!("[WARN] The auth of the certification does not match block.");
-                                is_valid = false;
-                            }
-                        }
-                        let commit_hash = crypto::hash::ser_and_hash(&p.new_block.body.data.commits);
-                        for cert in p.new_block.body.data.acks.iter() {
-                            if cert.msg != commit_hash {
-                                println!("[WARN] The hash of the certification does not match commit.");
-                                is_valid = false;
-                            }
-                            if !cx.pub_key_map.get(&cert.origin).unwrap().verify(&cert.msg, &cert.auth) {
-                                println!("[WARN] The auth of the certification does not match commit.");
-                                is_valid = false;
-                            }
-                        }
-                        if is_valid {
-                            cx.received_propose = Some(p);
-                            cx.received_propose_sign = Some(z);
-                        }
-                    },
-                    ProtocolMsg::Vote(p) => {
-                        cx.received_vote.push(p);
-                        if cx.received_vote.len() == (cx.num_faults + 1) as usize {
-                            let certificate = Certificate {
-                                votes: cx.received_vote.clone(),
-                            };
-                            let sign = get_acc(&cx, &certificate).1;
-                            cx.net_send.send((cx.num_nodes, Arc::new(ProtocolMsg::VoteCert(certificate.clone(), sign.clone())))).unwrap();
-                            cx.received_certificate = Some(certificate);
-                            cx.received_certificate_sign = Some(sign);
-                            deliver_vote_cert(&mut cx, myid);
-                            phase = Phase::Commit;
-                            phase_end.as_mut().reset(time::Instant::now() + Duration::from_millis(delta * 2));
-                        }
-                    },
-                    ProtocolMsg::VoteCert(c, z) => {
-                        cx.received_certificate = Some(c);
-                        cx.received_certificate_sign = Some(z);
-                        deliver_vote_cert(&mut cx, myid);
-                        phase = Phase::Commit;
-                        phase_end.as_mut().reset(time::Instant::now() + Duration::from_millis(delta * 2));
-                    },
-                    ProtocolMsg::DeliverPropose(sh, n, z) => {
-                        if !cx.propose_share_sent && n == myid {
-                            cx.net_send
-                                .send((
-                                    cx.num_nodes,
-                                    Arc::new(ProtocolMsg::DeliverPropose(
-                                        sh.clone(),
-                                        myid,
-                                        z.clone(),
-                                    )),
-                                ))
-                                .unwrap();
-                            cx.propose_share_sent = true;
-                        }
-                        cx.propose_gatherer.add_share(sh, n, cx.accumulator_pub_params_map.get(&cx.last_leader).unwrap(), cx.pub_key_map.get(&cx.last_leader).unwrap(), z);
-                    }
-                    ProtocolMsg::DeliverVoteCert(sh, n, z) => {
-                        if !cx.vote_cert_share_sent && n == myid {
-                            cx.net_send
-                                .send((
-                                    cx.num_nodes,
-                                    Arc::new(ProtocolMsg::DeliverVoteCert(
-                                        sh.clone(),
-                                        myid,
-                                        z.clone(),
-                                    )),
-                                ))
-                                .unwrap();
-                            cx.vote_cert_share_sent = true;
-                        }
-                        cx.vote_cert_gatherer.add_share(sh, n, cx.accumulator_pub_params_map.get(&cx.last_leader).unwrap(), cx.pub_key_map.get(&cx.last_leader).unwrap(), z);
-                    }
-                    ProtocolMsg::Reconstruct(sh, e) => {
-                        let last = cx.reconstruct_queue.back();
-                        if last.is_none() || e >= last.unwrap().1 {
-                            cx.reconstruct_queue.push_back((sh, e));
+                let (origin, pmsg) = pmsg_opt.unwrap();
+                if let Some(Err(e)) = recorder.as_mut().map(|r| r.record(origin, &pmsg)) {
+                    log::error!(target:"consensus", "Stopped recording the message trace: {}", e);
+                    recorder = None;
+                }
+                let signatures = if verifier.is_enabled() { cx.signatures_of(&pmsg) } else { Vec::new() };
+                if signatures.is_empty() {
+                    take_in(&mut cx, myid, &timing, &mut phase, &mut phase_end, origin, pmsg);
+                } else {
+                    verifier.submit(origin, pmsg, signatures);
+                }
+            },
+            (origin, pmsg, signatures) = verifier.next() => {
+                // Every signature in it checked out
+                cx.remember_verified(pmsg.epoch(), signatures);
+                take_in(&mut cx, myid, &timing, &mut phase, &mut phase_end, origin, pmsg);
+            },
+            _ = tokio::task::yield_now(), if cx.handler_budget.has_deferred() => {
+                // A message of a slow kind, taken in turn with whatever
+                // else is ready
+                if let Some((origin, pmsg)) = cx.handler_budget.next_deferred(cx.epoch) {
+                    on_message(&mut cx, myid, &timing, &mut phase, &mut phase_end, origin, pmsg);
+                }
+            },
+            permit = cx.net_send.reserve(), if cx.has_outbound() => {
+                // Room freed up in the network channel
+                match permit {
+                    Ok(permit) => {
+                        if let Some(out) = cx.next_outbound() {
+                            permit.send(out);
                         }
                     }
-                    ProtocolMsg::Commit(mut sh, c, z) => {
-                        let mut is_valid = true;
-                        let rng = &mut crypto::rand::rngs::StdRng::from_entropy();
-                        for i in 0..cx.num_nodes as usize {
-                            is_valid = is_valid && crypto::EVSS381::check(&cx.rand_beacon_parameter.get_public_params(), &c[i], &sh[i], rng).unwrap();
-                        }
-                        if is_valid {
-                            cx.rand_beacon_queue.get_mut(&cx.next_leader()).unwrap().append(&mut sh);
-                            cx.received_commit = Some(c);
-                            cx.received_commit_sign = Some(z);
-                        } else {
-                            println!("[WARN] Received invalid commit.")
-                        }
+                    Err(_) => cx.shutdown = true,
+                }
+            },
+            _ = cx.commit_gatherer.ready() => {
+                // Enough commitment shards, whichever path they came in on
+                cx.ack_commit();
+            },
+            tx_opt = cli_recv.recv(), if cli_open && cx.mempool_has_room() => {
+                // We received a message from the client
+                match tx_opt {
+                    Some(tx) => {
+                        cx.emit(NodeEvent::TransactionReceived(tx.clone()));
+                        cx.queue_transaction(tx);
                     }
-                    ProtocolMsg::DeliverCommit(sh, n, z) => {
-                        if !cx.commit_share_sent && n == myid {
-                            cx.net_send
-                                .send((
-                                    cx.num_nodes,
-                                    Arc::new(ProtocolMsg::DeliverCommit(
-                                        sh.clone(),
-                                        myid,
-                                        z.clone(),
-                                    )),
-                                ))
-                                .unwrap();
-                            cx.commit_share_sent = true;
-                        }
-                        cx.commit_gatherer.add_share(sh, n, cx.accumulator_pub_params_map.get(&cx.next_leader()).unwrap(), cx.pub_key_map.get(&cx.next_leader()).unwrap(), z);
-                        if cx.commit_gatherer.shard_num == cx.num_nodes - cx.num_faults {
-                            let reconstructed_commit = commit_from_bytes(&cx.commit_gatherer.reconstruct(cx.num_nodes, cx.num_faults).unwrap());
-                            let vote = Vote {
-                                msg: crypto::hash::ser_and_hash(&reconstructed_commit).to_vec(),
-                                origin: myid,
-                                auth: cx.my_secret_key.sign(&crypto::hash::ser_and_hash(&reconstructed_commit)).unwrap(),
-                            };
-                            if myid != cx.next_leader() {
-                                cx.net_send.send((cx.next_leader(), Arc::new(ProtocolMsg::Ack(vote)))).unwrap();
-                            }
-                        }
+                    // Nothing feeds the channel anymore
+                    None => cli_open = false,
+                }
+            },
+            (block, then) = hashes.next() => {
+                // A large block is hashed
+                on_hashed(&mut cx, myid, block, then);
+            },
+            (check, res) = checker.next() => {
+                // A shard or commit was checked off the reactor
+                on_checked(&mut cx, check, res);
+            },
+            value = entropy.recv(), if entropy_open => {
+                match value {
+                    Some((e, value)) => cx.add_entropy(e, value),
+                    // No external source, or it is done
+                    None => entropy_open = false,
+                }
+            },
+            req = admin_recv.recv(), if admin_open => {
+                match req {
+                    Some(req) => {
+                        let _ = req.reply.send(admin::answer(&cx, phase.to_string(), req.query));
                     }
-                    ProtocolMsg::Ack(v) => {
-                        cx.received_ack.push(v);
+                    // The socket could not be opened
+                    None => admin_open = false,
+                }
+            },
+            _ = std::future::ready(()), if phase == Phase::End && hashes.is_empty() && cx.prefetch_due() => {
+                // Waiting out the epoch before our turn to lead: build the
+                // proposal on the tip we will have by then
+                let cert = cx.last_seen_cert.clone();
+                cx.update_highest_cert(cert);
+                let (batch, builder) = cx.assemble_proposal(cx.epoch + 1);
+                let then = Hashed::Prefetch(cx.prefetch_key(cx.epoch + 1), batch);
+                let built = hashes.build(builder, then).expect("every part of the proposal is set");
+                if let Some((block, then)) = built {
+                    on_hashed(&mut cx, myid, block, then);
+                }
+            },
+            _ = std::future::ready(()), if cx.quarantine.is_some() => {
+                // Skip to where the cluster is and wait out that epoch
+                // without taking part in it
+                if let Some(target) = cx.fast_forward() {
+                    println!("{}: Caught up to epoch {}.", myid, target);
+                    // Our schedule fell behind the cluster's; restart it so
+                    // that the epoch we skipped to started now
+                    if let Some(b) = clock.now().checked_sub(Duration::from_millis(timing.epoch * (target - 1))) {
+                        begin = b;
                     }
-                };
-                let time_after = time::Instant::now();
-                println!("{}: Message {:?} took {} ms.", myid, s, (time_after - time_before).as_millis());
+                    phase = Phase::End;
+                    phase_end.reset(begin + Duration::from_millis(timing.epoch * target));
+                    watchdog.reset(begin + Duration::from_millis(timing.epoch * target) + stall_margin);
+                }
             },
-            _tx_opt = cli_recv.recv() => {
-                // We received a message from the client
+            _ = &mut watchdog, if cx.quarantine.is_none() => {
+                let s = phase.to_string();
+                println!("[WARN] {}: Phase {} of epoch {} stalled; moving on to the next epoch.", myid, s, cx.epoch);
+                cx.emit(NodeEvent::PhaseStalled(cx.epoch, s));
+                // Whatever the epoch was waiting for is of no use to the next
+                cx.awaiting_acks = false;
+                cx.received_propose = None;
+                cx.received_propose_sign = None;
+                cx.received_certificate = None;
+                cx.received_certificate_sign = None;
+                phase = Phase::End;
+                phase_end.reset(clock.now());
+                // In case the end of the epoch stalls as well
+                watchdog.reset(clock.now() + stall_margin);
             },
-            _ = &mut phase_end => {
+            _ = &mut phase_end, if cx.quarantine.is_none() => {
                 let s = phase.to_string();
                 println!("{}: Phase {:?}", myid, s);
-                let time_before = time::Instant::now();
+                let time_before = clock.now();
                 match phase {
+                    Phase::Propose if !cx.awaiting_acks && cx.received_ack.len() < cx.ack_threshold => {
+                        cx.awaiting_acks = true;
+                        phase_end.reset(clock.now() + Duration::from_millis(timing.ack_wait));
+                    }
                     Phase::Propose => {
-                        let mut new_block = Block::new();
-                        if cx.highest_cert.votes.len() == 0 {
-                            new_block.header.prev = EMPTY_HASH;
+                        if cx.received_ack.len() < cx.ack_threshold {
+                            println!("[WARN] Proposing with {} of {} acks.", cx.received_ack.len(), cx.ack_threshold);
+                        }
+                        cx.awaiting_acks = false;
+                        if cx.solo {
+                            let (batch, builder) = cx.assemble_proposal(cx.epoch);
+                            cx.received_ack.clear();
+                            let new_block = builder.build().expect("every part of the proposal is set");
+                            cx.emit(NodeEvent::BlockProposed(cx.epoch, new_block.hash));
+                            let propose = Propose {
+                                new_block: new_block,
+                                certificate: cx.highest_cert.clone(),
+                                epoch: cx.epoch,
+                                batch,
+                            };
+                            commit_solo(&mut cx, myid, propose);
+                            phase = Phase::End;
+                            phase_end.reset(begin + Duration::from_millis(timing.epoch * cx.epoch));
                         } else {
-                            new_block.header.prev = cx.highest_cert.votes[0].msg.clone().try_into().unwrap();
-                        };
-                        new_block.header.author = myid;
-                        new_block.header.height = cx.highest_height + 1;
-                        // TODO: Maybe add something to body?
-                        let content = Content {
-                            commits: cx.commits.clone(),
-                            acks: cx.received_ack.clone(),
-                        };
-                        new_block.body.data = content;
-                        cx.received_ack.clear();
-                        new_block.update_hash();
-                        let propose = Propose {
-                            new_block: new_block,
-                            certificate: cx.highest_cert.clone(),
-                            epoch: cx.epoch,
-                        };
-                        let sign = get_acc(&cx, &propose).1;
-                        cx.net_send.send((cx.num_nodes, Arc::new(ProtocolMsg::Propose(propose.clone(), sign.clone())))).unwrap();
-                        cx.received_propose = Some(propose);
-                        cx.received_propose_sign = Some(sign);
-                        phase = Phase::DeliverCommit;
-                        phase_end.as_mut().reset(begin + Duration::from_millis(delta * 11 * (cx.epoch - 1) + delta * 8));
+                            let built = match cx.take_prefetched() {
+                                // Built while we waited for our turn, on the
+                                // same tip
+                                Some((batch, block)) => {
+                                    Some((block, Hashed::Propose(cx.epoch, cx.highest_cert.clone(), batch)))
+                                }
+                                None => {
+                                    let (batch, builder) = cx.assemble_proposal(cx.epoch);
+                                    let then = Hashed::Propose(cx.epoch, cx.highest_cert.clone(), batch);
+                                    hashes.build(builder, then).expect("every part of the proposal is set")
+                                }
+                            };
+                            cx.received_ack.clear();
+                            if let Some((block, then)) = built {
+                                on_hashed(&mut cx, myid, block, then);
+                            }
+                            phase = Phase::DeliverCommit;
+                            phase_end.reset(begin + Duration::from_millis(timing.epoch * (cx.epoch - 1) + timing.deliver_commit));
+                        }
                     }
                     Phase::DeliverPropose => {
-                        deliver_propose(&mut cx, myid);
+                        if cx.received_propose.is_some() {
+                            deliver_propose(&mut cx, myid);
+                        }
                         phase = Phase::DeliverCommit;
-                        phase_end.as_mut().reset(begin + Duration::from_millis(delta * 11 * (cx.epoch - 1) + delta * 8));
+                        phase_end.reset(begin + Duration::from_millis(timing.epoch * (cx.epoch - 1) + timing.deliver_commit));
                     }
                     Phase::DeliverCommit => {
                         if cx.received_commit.is_some() {
                             deliver_commit(&mut cx, myid);
                         }
+                        // Someone who should have sent their share did not,
+                        // so ask the replicas holding theirs back
+                        if cx.lazy_reconstruct && cx.reconstruct_shares(cx.epoch) < (cx.num_nodes - cx.num_faults) as usize {
+                            cx.broadcast(ProtocolMsg::ReconstructRequest(myid, cx.epoch));
+                        }
                         if myid == cx.last_leader {
                             phase = Phase::End;
-                            phase_end.as_mut().reset(begin + Duration::from_millis(delta * 11 * cx.epoch));
+                            phase_end.reset(begin + Duration::from_millis(timing.epoch * cx.epoch));
                         } else {
+                            // Shards lost on the way can still come in
+                            // before we rebuild the proposal to vote
+                            cx.pull_missing_shares(Gatherer::Propose);
                             phase = Phase::Vote;
-                            phase_end.as_mut().reset(time::Instant::now() + Duration::from_millis(delta * 1));
+                            phase_end.reset(clock.now() + Duration::from_millis(timing.vote));
                         }
                     }
                     Phase::Vote => {
-                        let propose = Propose::from_bytes(&cx.propose_gatherer.reconstruct(cx.num_nodes, cx.num_faults).unwrap()[..]);
-                        let mut block = propose.new_block;
-                        block.update_hash();
-                        let vote = Vote {
-                            msg: block.hash.to_vec(),
-                            origin: myid,
-                            auth: cx.my_secret_key.sign(&block.hash).unwrap(),
-                        };
-                        cx.net_send.send((cx.last_leader, Arc::new(ProtocolMsg::Vote(vote)))).unwrap();
+                        if let Some(propose) = rebuild_propose(&mut cx) {
+                            if let Some((block, then)) = hashes.hash(propose.new_block, Hashed::Vote(cx.epoch)) {
+                                on_hashed(&mut cx, myid, block, then);
+                            }
+                        } else {
+                            println!("[WARN] Could not rebuild the proposal to vote.");
+                        }
+                        // The commitments we ack can come in until the epoch ends
+                        cx.pull_missing_shares(Gatherer::Commit);
                         phase = Phase::End;
-                        phase_end.as_mut().reset(begin + Duration::from_millis(delta * 11 * cx.epoch));
+                        phase_end.reset(begin + Duration::from_millis(timing.epoch * cx.epoch));
                     }
                     Phase::Commit => {
-                        let propose = Propose::from_bytes(&cx.propose_gatherer.reconstruct(cx.num_nodes, cx.num_faults).unwrap()[..]);
-                        let new_block = Arc::new(propose.new_block);
-                        cx.storage
-                            .committed_blocks_by_hash
-                            .insert(new_block.hash.clone(), Arc::clone(&new_block));
-                        cx.storage
-                            .committed_blocks_by_ht
-                            .insert(new_block.header.height, Arc::clone(&new_block));
+                        if let Some(propose) = rebuild_propose(&mut cx) {
+                            let (batch, block) = into_batch(propose);
+                            let then = Hashed::Commit(cx.epoch, cx.received_certificate.clone(), batch);
+                            if let Some((block, then)) = hashes.hash(block, then) {
+                                on_hashed(&mut cx, myid, block, then);
+                            }
+                        } else {
+                            println!("[WARN] Could not rebuild the proposal to commit.");
+                        }
                         cx.received_propose = None;
                         cx.received_propose_sign = None;
                         cx.received_certificate = None;
                         cx.received_certificate_sign = None;
+                        invariants::check_committed(&cx);
                         phase = Phase::End;
-                        phase_end.as_mut().reset(begin + Duration::from_millis(delta * 11 * cx.epoch));
+                        phase_end.reset(begin + Duration::from_millis(timing.epoch * cx.epoch));
                     }
                     Phase::End => {
-                        let mut vec = Vec::with_capacity(cx.num_nodes as usize);
-                        while !cx.reconstruct_queue.is_empty() && cx.reconstruct_queue.front().unwrap().1 < cx.epoch {
-                            cx.reconstruct_queue.pop_front();
+                        if cx.epoch > 0 {
+                            let sample = cx.metrics.record(cx.epoch, clock.now() - epoch_started, cx.bytes_sent);
+                            println!("{}: Epoch {} took {} ms{}.", myid, sample.epoch, sample.duration.as_millis(), if sample.warmup { " (warm-up)" } else { "" });
+                            println!("DP[EpochBytes]: {}", sample.bytes_sent);
+                            if let Some(summary) = cx.metrics.summary() {
+                                println!("DP[EpochLatency]: {}", summary.mean.as_millis());
+                            }
+                        }
+                        epoch_started = clock.now();
+                        cx.bytes_sent = 0;
+                        let reconstruct_participants = cx.reconstruct_shares(cx.epoch);
+                        let hash = cx.take_beacon();
+                        let hash = cx.mix_entropy(hash);
+                        let hash = cx.delay_beacon(hash);
+                        println!("Rand Beacon: {:x?}", hash);
+                        cx.emit(NodeEvent::BeaconProduced(cx.epoch, hash));
+                        if hash != NO_BEACON {
+                            if let Err(e) = cx.beacons.append(cx.epoch, hash) {
+                                log::error!(target:"consensus", "Cannot append the beacon of epoch {} to the beacon log: {}", cx.epoch, e);
+                            }
                         }
-                        while !cx.reconstruct_queue.is_empty() && cx.reconstruct_queue.front().unwrap().1 == cx.epoch {
-                            vec.push(cx.reconstruct_queue.pop_front().unwrap().0);
+                        let summary = std::mem::take(&mut cx.epoch_summary);
+                        if cx.epoch > 0 {
+                            cx.emit(NodeEvent::EpochSummary(EpochSummary {
+                                epoch: cx.epoch,
+                                leader: cx.last_leader,
+                                reconstruct_participants,
+                                beacon_produced: hash != NO_BEACON,
+                                ..summary
+                            }));
                         }
-                        let mut hash = [0 as u8; 32];
-                        if vec.len() >= (cx.num_nodes - cx.num_faults) as usize {
-                            let mut buf = Vec::new();
-                            crypto::EVSS381::reconstruct(&vec).serialize(&mut buf).unwrap();
-                            hash = crypto::hash::ser_and_hash(&buf);
+                        if cx.epoch_done && cx.epoch > 0 {
+                            cx.record_epoch_done(myid, cx.epoch, hash);
+                            cx.broadcast(ProtocolMsg::EpochDone(cx.epoch, hash));
                         }
-                        println!("Rand Beacon: {:x?}", hash);
+                        // Finalized key rotations take effect for everyone here
+                        cx.apply_reconfig();
+                        let prev_leader = cx.last_leader;
                         cx.last_leader = cx.next_leader();
                         cx.epoch += 1;
+                        cx.emit(NodeEvent::ViewChange(cx.epoch));
+                        if cx.epoch == cx.metrics.warmup_epochs + 1 {
+                            cx.emit(NodeEvent::WarmupEnded(cx.epoch));
+                        }
+                        cx.emit(NodeEvent::LeaderChanged(cx.epoch, cx.last_leader));
                         println!("{}: cx.epoch {}. Leader is {}.", myid, cx.epoch, cx.last_leader);
                         cx.propose_gatherer.clear();
                         cx.vote_cert_gatherer.clear();
                         cx.commit_gatherer.clear();
-                        cx.received_vote.clear();
-                        cx.received_ack.clear();
+                        cx.gossip_seen.clear();
+                        let epoch = cx.epoch;
+                        cx.verified_signatures.retain(|_, e| *e >= epoch);
+                        // Stragglers from the epoch that ended no longer count
+                        cx.received_vote = cx.received_vote.split_off(&cx.epoch);
+                        if myid != cx.last_leader {
+                            // The acks are for the commitments we propose now
+                            cx.received_ack.clear();
+                        }
                         cx.propose_share_sent = false;
                         cx.vote_cert_share_sent = false;
                         cx.commit_share_sent = false;
+                        cx.ack_sent = false;
+                        invariants::check_epoch_start(&cx, prev_leader);
+                        if let Some(from) = cx.bootstrap {
+                            // Until a snapshot arrives, start from it
+                            cx.send_to(from, ProtocolMsg::SnapshotRequest(cx.epoch));
+                        }
                         if myid != cx.last_leader {
                             // Send the certification.
-                            cx.net_send.send((cx.last_leader, Arc::new(ProtocolMsg::Certificate(cx.last_seen_block.certificate.clone())))).unwrap();
+                            cx.send_to(cx.last_leader, ProtocolMsg::Certificate(cx.last_seen_cert.clone(), cx.epoch, wall_clock_ms()));
                             println!("{}: Certification sent.", myid);
                             phase = Phase::DeliverPropose;
-                            phase_end.as_mut().reset(begin + Duration::from_millis(delta * 11 * (cx.epoch - 1) + delta * 7));
+                            phase_end.reset(begin + Duration::from_millis(timing.epoch * (cx.epoch - 1) + timing.deliver_propose));
                             if myid == cx.next_leader() {
                                 cx.shards = cx.rand_beacon_shares[cx.epoch as usize % 100].0.clone();
                                 cx.commits = cx.rand_beacon_shares[cx.epoch as usize % 100].1.clone();
-                                let sign = get_acc(&cx, &cx.commits).1;
-                                cx.rand_beacon_queue.get_mut(&myid).unwrap().append(&mut cx.shards[myid as usize].clone());
-                                for i in 0..cx.num_nodes {
-                                    if myid != i {
-                                        cx.net_send.send((i, Arc::new(ProtocolMsg::Commit(cx.shards[i as usize].clone(), cx.commits.clone(), sign.clone())))).unwrap();
+                                match get_acc(&cx, &cx.commits, COMMIT_DOMAIN) {
+                                    Ok((_, sign)) => {
+                                        let commits = cx.commits.clone();
+                                        // Shard `n` is for the `n`th active replica
+                                        for (n, i) in cx.active_replicas.clone().into_iter().enumerate() {
+                                            if myid == i {
+                                                let shards = cx.shards[n].clone();
+                                                cx.queue_dealt(myid, cx.epoch, shards, &commits);
+                                                continue;
+                                            }
+                                            if cx.commit_chunk == 0 {
+                                                cx.send_to(i, ProtocolMsg::Commit(cx.shards[n].clone(), cx.commits.clone(), sign.clone(), cx.epoch));
+                                            } else {
+                                                for chunk in split_commit(&cx.shards[n], &cx.commits, &sign, cx.epoch, cx.commit_chunk) {
+                                                    cx.send_to(i, chunk);
+                                                }
+                                            }
+                                        }
+                                        cx.received_commit = Some(cx.commits.clone());
+                                        cx.received_commit_sign = Some(sign);
                                     }
+                                    Err(e) => log::error!(target:"consensus", "Not dealing the commitments of epoch {}: cannot serialize them: {}", cx.epoch, e),
                                 }
-                                cx.received_commit = Some(cx.commits.clone());
-                                cx.received_commit_sign = Some(sign);
                             }
                         } else {
+                            // What we committed counts like what the others send us
+                            let cert = cx.last_seen_cert.clone();
+                            cx.update_highest_cert(cert);
+                            if cx.solo {
+                                // We are the next leader as well, and the
+                                // only one to deal to, at the first shard
+                                let shards = cx.rand_beacon_shares[cx.epoch as usize % 100].0[0].clone();
+                                cx.commits = cx.rand_beacon_shares[cx.epoch as usize % 100].1.clone();
+                                let commits = cx.commits.clone();
+                                cx.queue_dealt(myid, cx.epoch, shards, &commits);
+                            }
                             phase = Phase::Propose;
-                            phase_end.as_mut().reset(time::Instant::now() + Duration::from_millis(delta * 2));
+                            phase_end.reset(clock.now() + Duration::from_millis(timing.propose));
                         }
                         // Reconstruction Shards
-                        let mut sum = crypto::EVSSShare381 {
-                            point: crypto::F381::zero(),
-                            value: crypto::F381::zero(),
-                            challenge: crypto::F381::zero(),
-                            proof: crypto::EVSSProof381 {
-                                w: crypto::EVSSG1Affine381::zero(),
-                                random_v: None,
-                            }
-                        };
-                        for i in 0..cx.num_nodes {
-                            let shard = cx.rand_beacon_queue.get_mut(&(i as Replica)).unwrap().pop_front();
-                            if shard.is_some() {
-                                let u = shard.unwrap();
-                                sum.point += u.point;
-                                sum.value += u.value;
-                                sum.challenge += u.challenge;
-                                sum.proof.w += &u.proof.w;
-                            }
+                        let share = cx.reconstruct_share();
+                        // Our own share counts towards the beacon as well,
+                        // otherwise `num_faults` silent replicas would
+                        // already leave us one share short
+                        cx.accept_reconstruct(myid, share.clone(), cx.epoch);
+                        if cx.reconstructs_eagerly() {
+                            cx.withheld_share = None;
+                            cx.broadcast(ProtocolMsg::Reconstruct(share, cx.epoch));
+                        } else {
+                            cx.withheld_share = Some((share, cx.epoch));
+                        }
+                        // Messages that arrived ahead of us can be processed now
+                        for (origin, pmsg) in cx.take_ready() {
+                            cx.relay_shard(origin, &pmsg);
+                            on_message(&mut cx, myid, &timing, &mut phase, &mut phase_end, origin, pmsg);
                         }
-                        cx.net_send.send((cx.num_nodes, Arc::new(ProtocolMsg::Reconstruct(sum, cx.epoch)))).unwrap();
                     }
                 };
-                let time_after = time::Instant::now();
+                invariants::check(&cx);
+                watchdog.reset(begin + Duration::from_millis(timing.epoch * cx.epoch) + stall_margin);
+                let time_after = clock.now();
                 println!("{}: Phase {:?} took {} ms.", myid, s, (time_after - time_before).as_millis());
             },
         }
+        while let Some(pmsg) = cx.self_inbox.pop_front() {
+            on_message(&mut cx, myid, &timing, &mut phase, &mut phase_end, myid, pmsg);
+        }
+        while let Some(check) = cx.pending_checks.pop_front() {
+            checker.submit(check);
+        }
+        if cx.epoch_summary.block_hash.is_some() && cx.epoch_summary.commit_latency_ms.is_none() {
+            // Committed during this turn of the loop
+            cx.epoch_summary.commit_latency_ms = Some((clock.now() - epoch_started).as_millis() as u64);
+        }
+        if cx.shutdown {
+            log::error!(target:"consensus", "Network channel closed after {} failed sends; shutting down", cx.send_failures);
+            return;
+        }
     }
 }
@@ -0,0 +1,131 @@
+// Encryption of what a node keeps on disk. The key is derived from a
+// passphrase the operator supplies, with a random salt stored next to the
+// data, and every write gets a fresh nonce. A sealed file is
+//
+//   MAGIC | KDF rounds (u32 LE) | salt | nonce | AES-256-GCM ciphertext
+//
+// Deriving the key is slow on purpose, so it is done once per salt and
+// reused for every later write. Only files sealed with the rounds of the
+// sealer opening them are read.
+
+use aes_gcm::aead::{Aead, NewAead};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use crypto::rand::{rngs::OsRng, RngCore};
+use hmac::Hmac;
+use sha2::Sha256;
+use std::convert::TryInto;
+
+pub const MAGIC: &[u8; 8] = b"RPSEAL01";
+/// PBKDF2 rounds new files are sealed with
+pub const KDF_ROUNDS: u32 = 100_000;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 4 + SALT_LEN + NONCE_LEN;
+
+#[derive(Debug, PartialEq)]
+pub enum SealError {
+    /// The data is not sealed, or its header is cut off
+    NotSealed,
+    /// Wrong passphrase, or the data was tampered with
+    Unreadable,
+    /// Sealed with another number of KDF rounds than the one this sealer
+    /// uses; the rounds found and expected
+    Rounds(u32, u32),
+}
+
+impl std::fmt::Display for SealError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SealError::NotSealed => write!(f, "the data is not sealed"),
+            SealError::Unreadable => write!(f, "wrong passphrase or tampered data"),
+            SealError::Rounds(found, expected) => {
+                write!(f, "sealed with {} KDF rounds instead of {}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SealError {}
+
+/// Whether `bytes` were written by a `Sealer`
+pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub struct Sealer {
+    passphrase: Vec<u8>,
+    rounds: u32,
+    /// The last key derived, with the salt and rounds it was derived for
+    key: Option<([u8; SALT_LEN], u32, Aes256Gcm)>,
+}
+
+impl Sealer {
+    pub fn new(passphrase: &[u8]) -> Self {
+        Self::with_rounds(passphrase, KDF_ROUNDS)
+    }
+
+    /// Seals with `rounds` of key derivation instead of `KDF_ROUNDS`
+    pub fn with_rounds(passphrase: &[u8], rounds: u32) -> Self {
+        Sealer {
+            passphrase: passphrase.to_vec(),
+            rounds,
+            key: None,
+        }
+    }
+
+    fn cipher(&mut self, salt: [u8; SALT_LEN], rounds: u32) -> &Aes256Gcm {
+        let fresh = match &self.key {
+            Some((s, r, _)) => *s != salt || *r != rounds,
+            None => true,
+        };
+        if fresh {
+            let mut key = [0u8; 32];
+            pbkdf2::pbkdf2::<Hmac<Sha256>>(&self.passphrase, &salt, rounds, &mut key);
+            self.key = Some((salt, rounds, Aes256Gcm::new(Key::from_slice(&key))));
+        }
+        &self.key.as_ref().unwrap().2
+    }
+
+    pub fn seal(&mut self, plain: &[u8]) -> Vec<u8> {
+        // Keep the salt of the key we have, so that it is not derived again
+        let (salt, rounds) = match &self.key {
+            Some((salt, rounds, _)) if *rounds == self.rounds => (*salt, *rounds),
+            _ => {
+                let mut salt = [0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                (salt, self.rounds)
+            }
+        };
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let sealed = self
+            .cipher(salt, rounds)
+            .encrypt(Nonce::from_slice(&nonce), plain)
+            .expect("encrypting in memory does not fail");
+        let mut out = Vec::with_capacity(HEADER_LEN + sealed.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&rounds.to_le_bytes());
+        out.extend_from_slice(&salt);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&sealed);
+        out
+    }
+
+    pub fn open(&mut self, sealed: &[u8]) -> Result<Vec<u8>, SealError> {
+        if !is_sealed(sealed) || sealed.len() < HEADER_LEN {
+            return Err(SealError::NotSealed);
+        }
+        let rest = &sealed[MAGIC.len()..];
+        let rounds = u32::from_le_bytes(rest[..4].try_into().unwrap());
+        // The header is not authenticated until the key is derived, so it
+        // does not get to choose how long that takes
+        if rounds != self.rounds {
+            return Err(SealError::Rounds(rounds, self.rounds));
+        }
+        let salt: [u8; SALT_LEN] = rest[4..4 + SALT_LEN].try_into().unwrap();
+        let nonce = &rest[4 + SALT_LEN..4 + SALT_LEN + NONCE_LEN];
+        self.cipher(salt, rounds)
+            .decrypt(Nonce::from_slice(nonce), &rest[4 + SALT_LEN + NONCE_LEN..])
+            .map_err(|_| SealError::Unreadable)
+    }
+}
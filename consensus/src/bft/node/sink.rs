@@ -0,0 +1,139 @@
+// Streaming the transactions of committed blocks to a system outside the
+// cluster, such as an audit log. The sink runs on a thread of its own and
+// the reactor only hands it blocks through a bounded buffer: a slow or stuck
+// sink loses blocks, which are counted, but never holds up consensus.
+
+use config::Node;
+use serde_json::to_writer;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use types::{Height, Transaction};
+
+/// The transactions of the block committed at a height
+pub type Committed = (Height, Vec<Transaction>);
+
+pub trait CommitSink: Send {
+    /// Takes the transactions of the block committed at `height`. Called
+    /// once per block, in commit order.
+    fn write(&mut self, height: Height, transactions: Vec<Transaction>) -> io::Result<()>;
+}
+
+/// Hands committed blocks to a consumer in the same process
+pub struct ChannelSink(UnboundedSender<Committed>);
+
+impl ChannelSink {
+    pub fn new() -> (ChannelSink, UnboundedReceiver<Committed>) {
+        let (send, recv) = unbounded_channel();
+        (ChannelSink(send), recv)
+    }
+}
+
+impl CommitSink for ChannelSink {
+    fn write(&mut self, height: Height, transactions: Vec<Transaction>) -> io::Result<()> {
+        self.0
+            .send((height, transactions))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the consumer is gone"))
+    }
+}
+
+/// Writes every committed block as a line of JSON, `[height, transactions]`,
+/// to a file or to anything else that takes bytes, such as a producer for a
+/// Kafka topic
+pub struct WriterSink<W: Write + Send>(W);
+
+impl<W: Write + Send> WriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        WriterSink(writer)
+    }
+}
+
+impl WriterSink<BufWriter<File>> {
+    /// Appends to the file at `path`, creating it if needed
+    pub fn append(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(WriterSink(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write + Send> CommitSink for WriterSink<W> {
+    fn write(&mut self, height: Height, transactions: Vec<Transaction>) -> io::Result<()> {
+        to_writer(&mut self.0, &(height, transactions))?;
+        self.0.write_all(b"\n")?;
+        self.0.flush()
+    }
+}
+
+/// The sink the config asks for, if any. A commit log that cannot be
+/// opened is fatal, since the operator expects every commit in it.
+pub fn from_config(config: &Node) -> Option<Box<dyn CommitSink>> {
+    let path = config.commit_log.as_ref()?;
+    match WriterSink::append(Path::new(path)) {
+        Ok(sink) => Some(Box::new(sink)),
+        Err(e) => panic!("Failed to open the commit log at {}: {}", path, e),
+    }
+}
+
+/// The reactor's end of a commit sink
+pub struct SinkFeed {
+    send: Option<SyncSender<Committed>>,
+    overflows: u64,
+}
+
+impl SinkFeed {
+    /// A feed into `sink`, run on a thread of its own, with room for
+    /// `capacity` blocks; or one that drops everything for no sink
+    pub fn new(sink: Option<Box<dyn CommitSink>>, capacity: usize) -> Self {
+        let mut sink = match sink {
+            Some(sink) => sink,
+            None => return SinkFeed::none(),
+        };
+        let (send, recv) = sync_channel::<Committed>(capacity);
+        std::thread::Builder::new()
+            .name("commit-sink".into())
+            .spawn(move || {
+                // Ends once the reactor drops the feed
+                while let Ok((height, transactions)) = recv.recv() {
+                    if let Err(e) = sink.write(height, transactions) {
+                        log::error!(target:"consensus", "The commit sink failed on height {}: {}", height, e);
+                    }
+                }
+            })
+            .expect("failed to start the commit sink");
+        SinkFeed {
+            send: Some(send),
+            overflows: 0,
+        }
+    }
+
+    pub fn none() -> Self {
+        SinkFeed {
+            send: None,
+            overflows: 0,
+        }
+    }
+
+    /// Queues the block committed at `height` for the sink. Dropped and
+    /// counted if the sink is too far behind.
+    pub fn push(&mut self, height: Height, transactions: Vec<Transaction>) {
+        let send = match self.send.as_ref() {
+            Some(send) => send,
+            None => return,
+        };
+        match send.try_send((height, transactions)) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => {
+                self.overflows += 1;
+                log::warn!(target:"consensus", "The commit sink is behind; dropping height {}", height);
+            }
+            Err(TrySendError::Disconnected(_)) => log::error!(target:"consensus", "The commit sink is gone"),
+        }
+    }
+
+    /// Committed blocks dropped because the sink was behind
+    pub fn overflows(&self) -> u64 {
+        self.overflows
+    }
+}
@@ -0,0 +1,177 @@
+// Recording the protocol messages a node takes in, so that a run seen in the
+// field can be seen again. The recorder appends every message the reactor
+// takes off the network, with its origin and when it was taken, to a file;
+// `replay` feeds such a trace to a fresh reactor on a mock clock, handing it
+// every message at the time it was recorded.
+//
+// What is replayed is the node's view of the network only. Client
+// transactions and external entropy are not recorded, so a trace reproduces
+// a run only if the node had neither, or if what is being looked at does not
+// depend on them. Gossip picks its peers at random, and a message taken in
+// the same instant a deadline passed may have been handled before the
+// deadline where the replay handles it after; neither changes what gets
+// committed.
+//
+// A trace is a sequence of records, each a little-endian u32 length followed
+// by the bincode encoding of a `Record`. A record cut short, as by a crash
+// while it was written, ends the trace.
+
+use super::clock::{Clock, MockClock};
+use super::entropy::entropy_channel;
+use super::events::EventSender;
+use super::intake::tx_channel;
+use super::reactor::reactor;
+use config::Node;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{channel, unbounded_channel, Receiver};
+use tokio::time::Instant;
+use types::{Destination, ProtocolMsg, Replica};
+use util::io::to_frame;
+
+/// How far the mock clock moves at a time while replaying. Deadlines in
+/// between are met at most this late.
+pub const REPLAY_STEP: Duration = Duration::from_millis(1);
+
+/// Times the replay lets the reactor run after every step of the clock and
+/// every message, so that it is done with one before the next comes
+const SETTLE_YIELDS: usize = 64;
+
+/// A message as the reactor took it in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    /// Microseconds since the reactor started
+    pub at_us: u64,
+    pub origin: Replica,
+    pub msg: ProtocolMsg,
+}
+
+/// A recorder writing to a file, as the reactor uses
+pub type FileRecorder = Recorder<BufWriter<File>>;
+
+pub struct Recorder<W: Write> {
+    out: W,
+    clock: Arc<dyn Clock>,
+    start: Instant,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Records to `out`, with times from `clock` counted from now
+    pub fn new(out: W, clock: Arc<dyn Clock>) -> Self {
+        let start = clock.now();
+        Recorder { out, clock, start }
+    }
+
+    /// Appends `msg` from `origin`, taken in now. Every record is flushed,
+    /// so that a trace is complete up to a crash.
+    pub fn record(&mut self, origin: Replica, msg: &ProtocolMsg) -> io::Result<()> {
+        let at_us = (self.clock.now() - self.start).as_micros() as u64;
+        let bytes = to_frame(&(at_us, origin, msg))?;
+        self.out.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        self.out.write_all(&bytes)?;
+        self.out.flush()
+    }
+}
+
+impl Recorder<BufWriter<File>> {
+    /// Starts a new trace at `path`, replacing any trace already there
+    pub fn create(path: &Path, clock: Arc<dyn Clock>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
+        Ok(Recorder::new(BufWriter::new(file), clock))
+    }
+}
+
+/// The recorder the config asks for, if any. A trace that cannot be
+/// created is fatal, since the operator is counting on it.
+pub fn from_config(config: &Node, clock: Arc<dyn Clock>) -> Option<FileRecorder> {
+    let path = config.message_trace.as_ref()?;
+    match Recorder::create(Path::new(path), clock) {
+        Ok(recorder) => Some(recorder),
+        Err(e) => panic!("Failed to create the message trace at {}: {}", path, e),
+    }
+}
+
+/// The records of the trace `input`
+pub fn read_from<R: Read>(mut input: R) -> io::Result<Vec<Record>> {
+    let mut records = Vec::new();
+    let mut len = [0; 4];
+    loop {
+        match input.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(records),
+            Err(e) => return Err(e),
+        }
+        let mut bytes = vec![0; u32::from_le_bytes(len) as usize];
+        match input.read_exact(&mut bytes) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(records),
+            Err(e) => return Err(e),
+        }
+        let record = bincode::deserialize(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        records.push(record);
+    }
+}
+
+/// The records of the trace at `path`
+pub fn read(path: &Path) -> io::Result<Vec<Record>> {
+    read_from(BufReader::new(File::open(path)?))
+}
+
+/// Lets the reactor run until it is done with what it was handed, dropping
+/// whatever it sends
+async fn settle(net_out: &mut Receiver<(Destination, Arc<ProtocolMsg>)>) {
+    for _ in 0..SETTLE_YIELDS {
+        tokio::task::yield_now().await;
+        while net_out.try_recv().is_ok() {}
+    }
+}
+
+/// Moves `clock` on to `to` since it started, a step at a time
+async fn advance_to(clock: &MockClock, to: Duration, net_out: &mut Receiver<(Destination, Arc<ProtocolMsg>)>) {
+    while clock.elapsed() < to {
+        clock.advance(std::cmp::min(REPLAY_STEP, to - clock.elapsed()));
+        settle(net_out).await;
+    }
+}
+
+/// Runs a fresh reactor for `config` on a mock clock, handing it the
+/// messages of `trace` at the times they were recorded, and stops it once it
+/// has had `linger` to act on the last one. What it sends is dropped; what
+/// it does shows in `events`.
+pub async fn replay(config: &Node, trace: Vec<Record>, events: EventSender, linger: Duration) {
+    let mock = MockClock::new();
+    let clock: Arc<dyn Clock> = Arc::new(mock.clone());
+    let (net_send, mut net_out) = channel(config.consensus_channel_size());
+    let (in_send, net_recv) = unbounded_channel();
+    let (cli_send, _cli_recv) = unbounded_channel();
+    // Kept open so that the reactor waits for clients like the recorded one
+    let (_tx_send, cli_recv) = tx_channel(config.mempool_capacity());
+    let (_, entropy) = entropy_channel();
+    let mut config = config.clone();
+    // The replay must not write over the trace it replays
+    config.message_trace = None;
+
+    let driver = async {
+        let mut end = Duration::from_micros(0);
+        for record in trace {
+            end = Duration::from_micros(record.at_us);
+            advance_to(&mock, end, &mut net_out).await;
+            if in_send.send((record.origin, record.msg)).is_err() {
+                return;
+            }
+            settle(&mut net_out).await;
+        }
+        advance_to(&mock, end + linger, &mut net_out).await;
+        // The reactor stops once its network channel closes
+        drop(in_send);
+        settle(&mut net_out).await;
+    };
+    tokio::join!(
+        reactor(&config, false, net_send, net_recv, cli_send, cli_recv, events, clock, entropy, None),
+        driver
+    );
+}
@@ -0,0 +1,104 @@
+use super::context::{certified_hash, Context};
+use crypto::hash::Hash;
+use types::{Block, Certificate, Height};
+
+#[derive(Debug, PartialEq)]
+pub enum BlockError {
+    // The block does not extend a block we know about
+    UnknownParent(Hash),
+    // The parent is not the block certified by the block's certificate
+    ParentNotCertified,
+    // expected, actual
+    WrongHeight(Height, Height),
+    // The hash does not match the block's contents
+    HashMismatch,
+    // The block is not the one the vote certificate is for
+    NotVotedFor,
+}
+
+impl std::fmt::Display for BlockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BlockError::UnknownParent(prev) => write!(f, "unknown parent {:x?}", prev),
+            BlockError::ParentNotCertified => {
+                write!(f, "parent is not the block the certificate is for")
+            }
+            BlockError::WrongHeight(expected, actual) => {
+                write!(f, "expected height {} but got {}", expected, actual)
+            }
+            BlockError::HashMismatch => write!(f, "hash does not match the contents"),
+            BlockError::NotVotedFor => write!(f, "block is not the one that was voted for"),
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+/// Checks that `block` can be committed on top of what `cx` has committed:
+/// it extends a known block by exactly one, that block is the one its
+/// certificate certifies, its hash is intact and, if we hold a vote
+/// certificate for this epoch, it is the block that was voted for.
+pub fn validate_block(block: &Block, cx: &Context) -> Result<(), BlockError> {
+    let mut rehashed = block.clone();
+    rehashed.update_hash();
+    if rehashed.hash != block.hash {
+        return Err(BlockError::HashMismatch);
+    }
+    check_block(block, cx, cx.received_certificate.as_ref())
+}
+
+/// Like `validate_block` for a block we hashed ourselves, checked against
+/// `voted`, the vote certificate we hold for it if any
+pub fn check_block(block: &Block, cx: &Context, voted: Option<&Certificate>) -> Result<(), BlockError> {
+    let parent = cx
+        .storage
+        .committed_blocks_by_hash
+        .get(&block.header.prev)
+        .ok_or(BlockError::UnknownParent(block.header.prev))?;
+    if certified_hash(&block.certificate) != Some(block.header.prev) {
+        return Err(BlockError::ParentNotCertified);
+    }
+    if block.header.height != parent.header.height + 1 {
+        return Err(BlockError::WrongHeight(
+            parent.header.height + 1,
+            block.header.height,
+        ));
+    }
+    if let Some(cert) = voted {
+        if certified_hash(cert) != Some(block.hash) {
+            return Err(BlockError::NotVotedFor);
+        }
+    }
+    Ok(())
+}
+
+/// Like `check_block` for a batch: `batch`, oldest first, and then `head`,
+/// the block `voted` is for. The first block is checked like any other; each
+/// block after extends the one before by exactly one, and is certified only
+/// through the votes on `head`.
+pub fn check_batch(batch: &[Block], head: &Block, cx: &Context, voted: Option<&Certificate>) -> Result<(), BlockError> {
+    let first = match batch.first() {
+        Some(first) => first,
+        None => return check_block(head, cx, voted),
+    };
+    check_block(first, cx, None)?;
+    let mut parent = first;
+    for block in batch[1..].iter().chain(std::iter::once(head)) {
+        if block.header.prev != parent.hash {
+            return Err(BlockError::UnknownParent(block.header.prev));
+        }
+        if block.header.height != parent.header.height + 1 {
+            return Err(BlockError::WrongHeight(
+                parent.header.height + 1,
+                block.header.height,
+            ));
+        }
+        parent = block;
+    }
+    if let Some(cert) = voted {
+        if certified_hash(cert) != Some(head.hash) {
+            return Err(BlockError::NotVotedFor);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+// Checking the signatures of incoming votes, proposals, certificates and
+// reconfigurations on a pool of threads before the reactor handles them.
+// The reactor hands over each message with the keys its signatures may be
+// under, taken from its own state, and only gets back the messages whose
+// every signature checked out. Those signatures are remembered as verified,
+// so that the handlers' own checks find them there rather than doing the
+// crypto again on the reactor. A message with a bad signature is dropped by
+// the workers and counted; the reactor never sees it.
+
+use crypto_lib::PublicKey;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::{self, error::TrySendError, UnboundedReceiver};
+use types::{ProtocolMsg, Replica};
+
+/// Messages that may wait for one worker at once; beyond this they are
+/// dropped
+pub const MAX_PENDING_VERIFIES: usize = 10_000;
+
+/// One signature in a message
+#[derive(Clone)]
+pub struct Signature {
+    /// Who it claims to be from
+    pub origin: Replica,
+    /// The keys of `origin` it may be under
+    pub keys: Vec<PublicKey>,
+    /// The signed bytes, domain tag included
+    pub msg: Vec<u8>,
+    pub auth: Vec<u8>,
+}
+
+impl Signature {
+    fn check(&self) -> bool {
+        self.keys.iter().any(|pk| pk.verify(&self.msg, &self.auth))
+    }
+}
+
+struct Job {
+    origin: Replica,
+    msg: ProtocolMsg,
+    signatures: Vec<Signature>,
+}
+
+pub struct SignatureVerifier {
+    workers: Vec<mpsc::Sender<Job>>,
+    next_worker: usize,
+    done: UnboundedReceiver<(Replica, ProtocolMsg, Vec<Signature>)>,
+    invalid: Arc<AtomicU64>,
+}
+
+impl SignatureVerifier {
+    /// A pool of `workers` threads, or none that verifies nothing for 0.
+    /// Messages dropped for a bad signature are counted in `invalid`.
+    pub fn new(workers: usize, invalid: Arc<AtomicU64>) -> Self {
+        let (done_send, done) = mpsc::unbounded_channel();
+        let workers = (0..workers)
+            .map(|i| {
+                let (jobs, mut jobs_recv) = mpsc::channel::<Job>(MAX_PENDING_VERIFIES);
+                let done_send = done_send.clone();
+                let invalid = Arc::clone(&invalid);
+                std::thread::Builder::new()
+                    .name(format!("verify-{}", i))
+                    .spawn(move || {
+                        // Ends once the reactor drops the verifier
+                        while let Some(job) = jobs_recv.blocking_recv() {
+                            if !job.signatures.iter().all(Signature::check) {
+                                invalid.fetch_add(1, Ordering::Relaxed);
+                                log::warn!(target:"consensus", "Dropping a {} from {}: a signature does not verify", job.msg.to_string(), job.origin);
+                                continue;
+                            }
+                            if done_send.send((job.origin, job.msg, job.signatures)).is_err() {
+                                return;
+                            }
+                        }
+                    })
+                    .expect("failed to start a signature verifier");
+                jobs
+            })
+            .collect();
+        SignatureVerifier {
+            workers,
+            next_worker: 0,
+            done,
+            invalid,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.workers.is_empty()
+    }
+
+    /// Hands `msg` from `origin` to the next worker in turn, to come back
+    /// once all of `signatures` verify. Dropped with a warning if that
+    /// worker is too far behind.
+    pub fn submit(&mut self, origin: Replica, msg: ProtocolMsg, signatures: Vec<Signature>) {
+        let worker = &self.workers[self.next_worker];
+        self.next_worker = (self.next_worker + 1) % self.workers.len();
+        match worker.try_send(Job { origin, msg, signatures }) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => println!("[WARN] A signature verifier is behind; dropping a message."),
+            Err(TrySendError::Closed(_)) => log::error!(target:"consensus", "A signature verifier is gone"),
+        }
+    }
+
+    /// The messages whose signatures verified, with those signatures, as
+    /// they are done. Never returns while there are none.
+    pub async fn next(&mut self) -> (Replica, ProtocolMsg, Vec<Signature>) {
+        match self.done.recv().await {
+            Some(done) => done,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Messages dropped so far for a signature that does not verify
+    pub fn invalid(&self) -> u64 {
+        self.invalid.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use bytes::Bytes;
+use consensus::bft::node::accumulator::{get_acc, get_sign};
+use consensus::bft::node::context::Context;
+use crypto::hash::COMMIT_DOMAIN;
+use types::ProtocolMsg;
+
+#[test]
+fn commitments_are_acked_once_per_epoch() {
+    let configs = common::generate_configs(4, 1, 1);
+    let leader = common::context_and_sent(&configs[0]).0.next_leader() as usize;
+    let (dealer, _dealer_out) = common::context_and_sent(&configs[leader]);
+    let (mut cx, mut net_recv) = common::context_and_sent(&configs[(leader + 1) % 4]);
+
+    let (shards, acc) = get_acc(&dealer, &dealer.commits, COMMIT_DOMAIN).unwrap();
+    let pp = cx.accumulator_pub_params_map[&(leader as u16)].clone();
+    let pk = cx.pub_key_map[&(leader as u16)].clone();
+    let fill = |cx: &mut Context| {
+        // Every shard, some twice, acking as each comes in
+        for &n in [0u16, 1, 2, 3, 2, 3].iter() {
+            let sh = Bytes::from(shards[n as usize].clone());
+            cx.commit_gatherer.add_share(sh, n, &pp, &pk, get_sign(&acc, n));
+            cx.ack_commit();
+        }
+    };
+    fill(&mut cx);
+    // The gatherer is refilled within the same epoch
+    cx.commit_gatherer.clear();
+    fill(&mut cx);
+
+    let mut acks = Vec::new();
+    while let Ok((to, msg)) = net_recv.try_recv() {
+        if let ProtocolMsg::Ack(_, _) = msg.as_ref() {
+            acks.push(to);
+        }
+    }
+    assert_eq!(acks.len(), 1);
+    assert!(cx.ack_sent);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_stream::StreamExt;
+
+const PROPOSE: u64 = 50;
+const ACK_WAIT: u64 = 250;
+
+/// How long replica 2, the leader of epoch 2, takes to propose once the
+/// epoch starts. It collects acks for its commitments during epoch 1.
+async fn proposal_delay(ack_threshold: usize) -> Duration {
+    let mut configs = common::generate_configs(4, 1, 4);
+    for config in configs.iter_mut() {
+        config.ack_threshold = ack_threshold;
+        config.timing.propose = Some(PROPOSE);
+        config.timing.ack_wait = Some(ACK_WAIT);
+    }
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[2]);
+    let measure = async {
+        let mut started = None;
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::ViewChange(2) => started = Some(Instant::now()),
+                NodeEvent::BlockProposed(2, _) if started.is_some() => {
+                    return started.unwrap().elapsed()
+                }
+                _ => (),
+            }
+        }
+        panic!("event channel closed");
+    };
+    tokio::time::timeout(Duration::from_secs(30), measure)
+        .await
+        .expect("epoch 2 was not proposed")
+}
+
+#[tokio::test]
+async fn leader_waits_for_the_configured_acks() {
+    let wait = Duration::from_millis(PROPOSE + ACK_WAIT);
+    // Not waiting for acks at all
+    assert!(proposal_delay(0).await < wait);
+    // The three other replicas ack during epoch 1, so this is already met
+    assert!(proposal_delay(3).await < wait);
+    // A leader never acks itself, so this waits out the whole window
+    assert!(proposal_delay(4).await >= wait);
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio_stream::StreamExt;
+
+#[tokio::test]
+async fn status_of_a_running_node() {
+    let path = std::env::temp_dir().join(format!("randpiper-admin-{}.sock", std::process::id()));
+    let mut configs = common::generate_configs(4, 1, 3);
+    configs[0].admin_socket = Some(path.to_str().unwrap().to_string());
+    let cluster = common::spawn_cluster(configs);
+
+    let mut stream = subscribe(&cluster.events[0]);
+    let epoch_two = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            if let NodeEvent::ViewChange(2) = ev {
+                return;
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(30), epoch_two)
+        .await
+        .expect("epoch 2 not reached");
+
+    let (read, mut write) = UnixStream::connect(&path).await.unwrap().into_split();
+    let mut lines = BufReader::new(read).lines();
+    write.write_all(b"status\n").await.unwrap();
+    let status: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(status["id"], 0);
+    assert!(status["epoch"]["epoch"].as_u64().unwrap() >= 2);
+    assert!(status["epoch"]["phase"].is_string());
+    assert!(status["height"].as_u64().unwrap() >= 1);
+    assert_eq!(status["gatherers"]["threshold"], 3);
+    let peers = status["peers"].as_array().unwrap();
+    assert_eq!(peers.len(), 3);
+    assert!(peers.iter().all(|p| p["last_heard"].as_u64().is_some()));
+
+    write.write_all(b"shutdown\n").await.unwrap();
+    let error: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert!(error["error"].is_string());
+    let _ = std::fs::remove_file(&path);
+}
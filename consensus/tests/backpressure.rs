@@ -0,0 +1,73 @@
+mod common;
+
+use consensus::bft::node::context::MAX_BACKLOG;
+use types::{ProtocolMsg, Vote};
+
+#[tokio::test]
+async fn stalled_network_bounds_backlog() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    configs[0].consensus_channel = Some(16);
+    // Nobody ever reads from the network side.
+    let (mut cx, _net_send_recv) = common::context_and_sent(&configs[0]);
+    for i in 0..(3 * MAX_BACKLOG) {
+        cx.send_to(
+            1,
+            ProtocolMsg::Vote(
+                Vote {
+                    msg: (i as u64).to_be_bytes().to_vec(),
+                    origin: 0,
+                    auth: Vec::new(),
+                },
+                0,
+            ),
+        );
+    }
+    assert_eq!(cx.backlog.len(), MAX_BACKLOG);
+    assert_eq!(cx.dropped_sends as usize, 3 * MAX_BACKLOG - 16 - MAX_BACKLOG);
+    assert!(!cx.shutdown);
+
+    // Best effort traffic is dropped outright instead of displacing votes.
+    let dropped = cx.dropped_sends;
+    cx.broadcast(ProtocolMsg::Certificate(Default::default(), 0, 0));
+    assert_eq!(cx.dropped_sends, dropped + 1);
+    assert_eq!(cx.backlog.len(), MAX_BACKLOG);
+}
+
+#[tokio::test]
+async fn stale_votes_are_dropped_instead_of_sent_late() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    configs[0].send_ttl = 1;
+    configs[0].consensus_channel = Some(16);
+    let (mut cx, mut net_send_recv) = common::context_and_sent(&configs[0]);
+    cx.epoch = 1;
+    let vote = |e| {
+        ProtocolMsg::Vote(
+            Vote {
+                msg: Vec::new(),
+                origin: 0,
+                auth: Vec::new(),
+            },
+            e,
+        )
+    };
+    // The channel fills up, the rest of epoch 1's votes wait in the backlog
+    for _ in 0..20 {
+        cx.send_to(1, vote(1));
+    }
+    cx.send_to(1, ProtocolMsg::ReconstructRequest(0, 1));
+    assert_eq!(cx.backlog.len(), 5);
+
+    // By the time there is room again the epoch is over
+    cx.epoch = 2;
+    cx.send_to(1, vote(2));
+    for _ in 0..16 {
+        net_send_recv.recv().await.unwrap();
+    }
+    let (_, msg) = cx.next_outbound().unwrap();
+    assert_eq!(msg.to_string(), "ReconstructRequest");
+    let (_, msg) = cx.next_outbound().unwrap();
+    assert_eq!(msg.epoch(), 2);
+    assert!(cx.next_outbound().is_none());
+    assert_eq!(cx.dropped_stale.get("Vote"), Some(&4));
+    assert_eq!(cx.dropped_sends, 0);
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use consensus::bft::node::validate::{check_batch, BlockError};
+use crypto::hash::empty_hash;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use types::{Block, BlockBody, BlockBuilder};
+
+/// `len` blocks by replica 1, each extending the one before, the first
+/// extending genesis
+fn chain(len: u64) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    for height in 1..=len {
+        let prev = blocks.last().map_or_else(empty_hash, |b| b.hash);
+        let block = BlockBuilder::new()
+            .prev(prev)
+            .author(1)
+            .height(height)
+            .body(BlockBody::new())
+            .build()
+            .unwrap();
+        blocks.push(block);
+    }
+    blocks
+}
+
+#[test]
+fn a_batch_commits_as_a_linked_chain() {
+    let mut cx = common::context(&common::generate_configs(4, 1, 1)[0]);
+    let mut batch = chain(4);
+    let head = batch.pop().unwrap();
+    assert_eq!(check_batch(&batch, &head, &cx, None), Ok(()));
+    let head = cx.commit_batch(batch, head);
+
+    assert_eq!(head.header.height, 4);
+    let mut prev = empty_hash();
+    for height in 1..=4 {
+        let block = cx.storage.committed_blocks_by_ht.get(&height).expect("block not stored");
+        assert_eq!(block.header.prev, prev, "height {}", height);
+        assert!(cx.storage.committed_blocks_by_hash.contains_key(&block.hash));
+        prev = block.hash;
+    }
+    assert_eq!(prev, head.hash);
+}
+
+#[test]
+fn a_batch_with_a_broken_link_is_refused() {
+    let cx = common::context(&common::generate_configs(4, 1, 1)[0]);
+    let mut batch = chain(4);
+    let head = batch.pop().unwrap();
+    batch.remove(1);
+    let dangling = batch[1].header.prev;
+    assert_eq!(
+        check_batch(&batch, &head, &cx, None),
+        Err(BlockError::UnknownParent(dangling))
+    );
+    assert!(cx.storage.committed_blocks_by_ht.get(&1).is_none());
+}
+
+#[tokio::test]
+async fn every_epoch_commits_a_batch_of_four() {
+    let mut configs = common::generate_configs(4, 1, 4);
+    for config in configs.iter_mut() {
+        config.batch_size = 4;
+    }
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+    let mut epoch = 0;
+    let mut committed = Vec::new();
+    let collect = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::ViewChange(3) => break,
+                NodeEvent::ViewChange(e) => epoch = e,
+                NodeEvent::BlockCommitted(h, _) if h > 0 => committed.push((epoch, h)),
+                _ => (),
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(60), collect)
+        .await
+        .expect("did not finish two epochs");
+
+    // The second batch builds on the head of the first
+    let expected: Vec<_> = (1..=8).map(|h| ((h + 3) / 4, h)).collect();
+    assert_eq!(committed, expected);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use consensus::bft::node::beacons::{BeaconLog, BeaconOutput};
+use consensus::bft::node::context::NO_BEACON;
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio_stream::StreamExt;
+use types::Height;
+
+#[test]
+fn only_the_newest_beacons_stay_in_memory() {
+    let mut log = BeaconLog::in_memory(3);
+    for e in 1..=6 {
+        log.append(e, [e as u8; 32]).unwrap();
+    }
+    assert_eq!(log.len(), 3);
+    assert_eq!(log.get_beacon(3), None);
+    assert_eq!(log.get_beacon(5), Some(BeaconOutput { epoch: 5, value: [5; 32] }));
+    let epochs: Vec<Height> = log.range(..).iter().map(|b| b.epoch).collect();
+    assert_eq!(epochs, vec![4, 5, 6]);
+}
+
+#[tokio::test]
+async fn past_beacons_can_be_looked_up_after_many_epochs() {
+    let dir = std::env::temp_dir();
+    let socket = dir.join(format!("randpiper-beacons-{}.sock", std::process::id()));
+    let path = dir.join(format!("randpiper-beacons-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut configs = common::generate_configs(4, 1, 10);
+    configs[0].admin_socket = Some(socket.to_str().unwrap().to_string());
+    configs[0].beacon_log = Some(path.to_str().unwrap().to_string());
+    configs[0].beacon_history = Some(3);
+    let cluster = common::spawn_cluster(configs);
+
+    let mut stream = subscribe(&cluster.events[0]);
+    let mut produced = BTreeMap::new();
+    let collect = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::ViewChange(10) => break,
+                NodeEvent::BeaconProduced(e, beacon) if beacon != NO_BEACON => {
+                    produced.insert(e, beacon);
+                }
+                _ => (),
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(120), collect)
+        .await
+        .expect("did not get through nine epochs");
+    assert!(produced.len() >= 6, "only {} beacons", produced.len());
+
+    // Through the admin socket, from memory and from the file
+    let (read, mut write) = UnixStream::connect(&socket).await.unwrap().into_split();
+    let mut lines = BufReader::new(read).lines();
+    for (e, beacon) in produced.iter().take(3) {
+        write.write_all(format!("beacon {}\n", e).as_bytes()).await.unwrap();
+        let answer: BeaconOutput = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(answer, BeaconOutput { epoch: *e, value: *beacon });
+    }
+    write.write_all(b"beacons 0 9\n").await.unwrap();
+    let answer: Vec<BeaconOutput> = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    let expected: Vec<BeaconOutput> = produced.iter().map(|(e, v)| BeaconOutput { epoch: *e, value: *v }).collect();
+    assert_eq!(answer, expected);
+    write.write_all(b"beacon 1000\n").await.unwrap();
+    assert_eq!(lines.next_line().await.unwrap().unwrap(), "null");
+
+    // The file alone is enough to look them up
+    let log = BeaconLog::open(&path, 3).unwrap();
+    for (e, beacon) in produced.iter() {
+        assert_eq!(log.get_beacon(*e).map(|b| b.value), Some(*beacon), "epoch {}", e);
+    }
+    assert_eq!(log.range(2..5), expected.iter().filter(|b| (2..5).contains(&b.epoch)).cloned().collect::<Vec<_>>());
+    let _ = std::fs::remove_file(&socket);
+    let _ = std::fs::remove_file(&path);
+}
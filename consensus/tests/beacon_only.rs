@@ -0,0 +1,53 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use crypto::hash::HASH_SIZE;
+use std::net::TcpListener;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use types::Transaction;
+
+#[tokio::test]
+async fn a_node_without_clients_still_produces_beacons() {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let mut configs = common::generate_configs(4, 1, 4);
+    for config in configs.iter_mut() {
+        config.disable_clients = true;
+        config.client_port = port;
+    }
+    let client_ip = configs[0].client_ip();
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+
+    // Nothing takes transactions
+    let tx = Transaction {
+        data: vec![1],
+        request: Vec::new(),
+    };
+    assert!(cluster.clients[0].send(tx).await.is_err());
+
+    let run = async {
+        let mut beacons = Vec::new();
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::TransactionReceived(_) => panic!("a node without clients took a transaction"),
+                NodeEvent::BeaconProduced(e, beacon) if e > 0 => {
+                    beacons.push(beacon);
+                    if beacons.len() == 2 {
+                        return beacons;
+                    }
+                }
+                _ => (),
+            }
+        }
+        panic!("event channel closed");
+    };
+    let beacons = tokio::time::timeout(Duration::from_secs(30), run)
+        .await
+        .expect("no beacons were produced");
+    for beacon in beacons {
+        assert_ne!(beacon, [0; HASH_SIZE]);
+    }
+    // The client port was never taken
+    assert!(TcpListener::bind(client_ip).is_ok());
+}
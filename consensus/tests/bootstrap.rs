@@ -0,0 +1,193 @@
+mod common;
+
+use consensus::bft::node::context::{certified_hash, Context};
+use crypto::hash::{with_domain, Hash, SNAPSHOT_DOMAIN, VOTE_DOMAIN};
+use crypto_lib::ed25519;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc::Receiver;
+use types::{Block, Certificate, Destination, Height, ProtocolMsg, Snapshot, Vote, genesis_block};
+
+/// How far the cluster got before the new node joins
+const TIP: Height = 1000;
+
+fn block_on(prev: Hash, height: Height) -> Block {
+    let mut block = Block::new();
+    block.header.prev = prev;
+    block.header.height = height;
+    block.update_hash();
+    block
+}
+
+fn certify(block: &Block, voters: &[&Context]) -> Certificate {
+    let votes = voters
+        .iter()
+        .map(|cx| common::vote(cx, &block.hash))
+        .collect();
+    Certificate { votes }
+}
+
+/// Replica 1 with `TIP` blocks committed in epoch `TIP`, the certificate
+/// of the last one, and the snapshot it hands out
+fn running_cluster(
+    configs: &[config::Node],
+) -> (Context, Receiver<(Destination, Arc<ProtocolMsg>)>, Vec<Context>, Block) {
+    let (mut peer, peer_out) = common::context_and_sent(&configs[1]);
+    let voters: Vec<Context> = configs[2..].iter().map(|c| common::context_and_sent(c).0).collect();
+    let mut tip = genesis_block();
+    for height in 1..=TIP {
+        tip = block_on(tip.hash, height);
+        peer.commit_block(Arc::new(tip.clone()));
+    }
+    let cert = certify(&tip, &[&peer, &voters[0]]);
+    peer.update_highest_cert(cert);
+    peer.epoch = TIP;
+    (peer, peer_out, voters, tip)
+}
+
+fn snapshot_response(peer: &mut Context, out: &mut Receiver<(Destination, Arc<ProtocolMsg>)>) -> (Snapshot, Vec<u8>) {
+    peer.answer_snapshot_request(0, 1);
+    match out.try_recv().unwrap() {
+        (Destination::One(0), msg) => match msg.as_ref() {
+            ProtocolMsg::SnapshotResponse(snapshot, auth, 1) => (snapshot.clone(), auth.clone()),
+            m => panic!("expected a snapshot response, got {}", m.to_string()),
+        },
+        _ => panic!("the response did not go to replica 0"),
+    }
+}
+
+#[tokio::test]
+async fn a_new_node_starts_from_the_snapshot_and_builds_on_it() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    configs[0].bootstrap_from = Some(1);
+    let (mut peer, mut peer_out, voters, tip) = running_cluster(&configs);
+    let (mut fresh, _) = common::context_and_sent(&configs[0]);
+    let (snapshot, auth) = snapshot_response(&mut peer, &mut peer_out);
+    assert_eq!(snapshot.block.header.height, TIP);
+
+    assert!(fresh.install_snapshot(1, snapshot, &auth));
+    assert_eq!(fresh.bootstrap, None);
+    assert_eq!(fresh.highest_height, TIP);
+    assert_eq!(certified_hash(&fresh.highest_cert), Some(tip.hash));
+    assert_eq!(fresh.proposal_parent(), (tip.hash, TIP + 1));
+    // It skips ahead to the cluster's epoch instead of replaying it
+    assert_eq!(fresh.quarantine, Some(TIP));
+
+    // Only what comes after the snapshot is left to sync
+    let next = block_on(tip.hash, TIP + 1);
+    fresh.commit_block(Arc::new(next.clone()));
+    fresh.update_highest_cert(certify(&next, &[&peer, &voters[1]]));
+    assert_eq!(fresh.highest_height, TIP + 1);
+    assert_eq!(fresh.proposal_parent(), (next.hash, TIP + 2));
+}
+
+#[tokio::test]
+async fn snapshots_that_do_not_check_out_are_not_installed() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    configs[0].bootstrap_from = Some(1);
+    let (mut peer, mut peer_out, _voters, tip) = running_cluster(&configs);
+    let (mut fresh, _) = common::context_and_sent(&configs[0]);
+    let (snapshot, auth) = snapshot_response(&mut peer, &mut peer_out);
+
+    // Only the replica we trust may hand us a snapshot
+    assert!(fresh.check_snapshot(2, &snapshot, &auth).is_err());
+    // Nor may anyone change what it signed
+    let mut forged = snapshot.clone();
+    forged.finalized_height += 1;
+    assert!(fresh.check_snapshot(1, &forged, &auth).is_err());
+
+    // A trusted replica cannot vouch for a block without a quorum of votes
+    let mut thin = snapshot.clone();
+    thin.cert.votes.truncate(1);
+    let bytes = util::io::canonical_bytes(&thin).unwrap();
+    let thin_auth = peer
+        .my_secret_key
+        .sign(&with_domain(SNAPSHOT_DOMAIN, &bytes))
+        .unwrap();
+    assert!(fresh.check_snapshot(1, &thin, &thin_auth).is_err());
+    // Nor for another block than the certified one
+    let mut swapped = snapshot.clone();
+    swapped.block = block_on(tip.hash, TIP + 1);
+    let bytes = util::io::canonical_bytes(&swapped).unwrap();
+    let swapped_auth = peer
+        .my_secret_key
+        .sign(&with_domain(SNAPSHOT_DOMAIN, &bytes))
+        .unwrap();
+    assert!(fresh.check_snapshot(1, &swapped, &swapped_auth).is_err());
+
+    assert!(!fresh.install_snapshot(1, thin, &thin_auth));
+    assert_eq!(fresh.highest_height, 0);
+    assert_eq!(fresh.bootstrap, Some(1));
+    assert!(fresh.check_snapshot(1, &snapshot, &auth).is_ok());
+}
+
+#[tokio::test]
+async fn the_signed_snapshot_does_not_depend_on_insertion_order() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (mut peer, mut peer_out, _voters, _tip) = running_cluster(&configs);
+    let (snapshot, auth) = snapshot_response(&mut peer, &mut peer_out);
+
+    // The same members, inserted the other way round
+    let mut keys: Vec<_> = peer.member_keys.iter().map(|(r, pk)| (*r, pk.clone())).collect();
+    keys.sort();
+    let mut reversed = HashMap::with_capacity(64);
+    for (r, pk) in keys.into_iter().rev() {
+        reversed.insert(r, pk);
+    }
+    peer.member_keys = reversed;
+    let (again, again_auth) = snapshot_response(&mut peer, &mut peer_out);
+
+    assert_eq!(
+        util::io::canonical_bytes(&again).unwrap(),
+        util::io::canonical_bytes(&snapshot).unwrap()
+    );
+    assert_eq!(again_auth, auth);
+}
+
+#[tokio::test]
+async fn a_snapshot_cannot_bring_its_own_members() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    configs[0].bootstrap_from = Some(1);
+    let (peer, _peer_out, _voters, tip) = running_cluster(&configs);
+    let (mut fresh, _) = common::context_and_sent(&configs[0]);
+    let keys = fresh.pub_key_map.clone();
+    let members = fresh.member_keys.clone();
+
+    // Replica 1 makes up keys for replicas 2 and 3, and with them
+    // certifies a block nobody else voted for
+    let block = block_on(tip.hash, TIP + 1);
+    let mut votes = vec![Vote {
+        msg: block.hash.to_vec(),
+        origin: 1,
+        auth: peer.my_secret_key.sign(&with_domain(VOTE_DOMAIN, &block.hash)).unwrap(),
+    }];
+    let mut forged_members: Vec<_> = members.iter().map(|(r, pk)| (*r, pk.clone())).collect();
+    forged_members.sort();
+    for r in 2..4 {
+        let kp = ed25519::Keypair::generate();
+        forged_members[r as usize].1 = kp.public().encode().to_vec();
+        let kp = crypto_lib::Keypair::Ed25519(kp);
+        votes.push(Vote {
+            msg: block.hash.to_vec(),
+            origin: r,
+            auth: kp.sign(&with_domain(VOTE_DOMAIN, &block.hash)).unwrap(),
+        });
+    }
+    let snapshot = Snapshot {
+        epoch: TIP + 1,
+        block,
+        cert: Certificate { votes },
+        finalized_height: TIP,
+        members: forged_members,
+        beacons: Vec::new(),
+    };
+    let bytes = util::io::canonical_bytes(&snapshot).unwrap();
+    let auth = peer.my_secret_key.sign(&with_domain(SNAPSHOT_DOMAIN, &bytes)).unwrap();
+
+    assert!(fresh.check_snapshot(1, &snapshot, &auth).is_err());
+    assert!(!fresh.install_snapshot(1, snapshot, &auth));
+    assert_eq!(fresh.highest_height, 0);
+    assert_eq!(fresh.pub_key_map.len(), keys.len());
+    assert_eq!(fresh.member_keys, members);
+    assert_eq!(fresh.active_replicas, vec![0, 1, 2, 3]);
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use consensus::bft::node::context::{certified_hash, Context, MAX_PENDING_CERTS, PENDING_CERT_EPOCHS};
+use std::sync::Arc;
+use types::{Block, Certificate, Destination, ProtocolMsg, Vote, genesis_block};
+
+#[tokio::test]
+async fn certificate_for_an_unknown_block_is_synced_and_adopted() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    configs[0].cert_sync = true;
+    let (mut lagging, mut lagging_out) = common::context_and_sent(&configs[0]);
+    let (mut peer, mut peer_out) = common::context_and_sent(&configs[1]);
+    let (voter, _) = common::context_and_sent(&configs[2]);
+
+    // Replica 1 committed a block replica 0 never saw
+    let mut block = Block::new();
+    block.header.prev = genesis_block().hash;
+    block.header.height = 1;
+    block.update_hash();
+    peer.commit_block(Arc::new(block.clone()));
+    let votes = [&peer, &voter]
+        .iter()
+        .map(|cx| common::vote(cx, &block.hash))
+        .collect();
+    let cert = Certificate { votes };
+
+    lagging.accept_certificate(1, cert.clone());
+    assert_eq!(lagging.highest_height, 0);
+    let (hash, e) = match lagging_out.try_recv().unwrap() {
+        (Destination::One(1), msg) => match msg.as_ref() {
+            ProtocolMsg::BlockRequest(hash, e) => (*hash, *e),
+            m => panic!("expected a block request, got {}", m.to_string()),
+        },
+        _ => panic!("the request did not go to replica 1"),
+    };
+    assert_eq!(hash, block.hash);
+
+    peer.answer_block_request(0, hash, e);
+    let synced = match peer_out.try_recv().unwrap().1.as_ref() {
+        ProtocolMsg::BlockResponse(b, _) => b.clone(),
+        m => panic!("expected a block response, got {}", m.to_string()),
+    };
+    lagging.accept_synced_block(1, synced);
+    assert_eq!(lagging.highest_height, 1);
+    assert_eq!(certified_hash(&lagging.highest_cert), Some(block.hash));
+    assert_eq!(lagging.proposal_parent(), (block.hash, 2));
+}
+
+#[tokio::test]
+async fn certificate_for_an_unknown_block_is_dropped_without_sync() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (mut cx, mut out) = common::context_and_sent(&configs[0]);
+    let mut block = Block::new();
+    block.header.height = 1;
+    block.update_hash();
+    let cert = Certificate {
+        votes: vec![Vote {
+            msg: block.hash.to_vec(),
+            origin: 1,
+            auth: Vec::new(),
+        }],
+    };
+    cx.accept_certificate(1, cert);
+    assert_eq!(cx.highest_height, 0);
+    assert!(out.try_recv().is_err());
+}
+
+fn certify(height: u64, voters: &[&Context]) -> Certificate {
+    let mut block = Block::new();
+    block.header.height = height;
+    block.update_hash();
+    let votes = voters
+        .iter()
+        .map(|cx| common::vote(cx, &block.hash))
+        .collect();
+    Certificate { votes }
+}
+
+#[tokio::test]
+async fn forged_and_stale_certificates_do_not_use_up_cert_sync() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    configs[0].cert_sync = true;
+    let (mut lagging, mut out) = common::context_and_sent(&configs[0]);
+    let (peer, _) = common::context_and_sent(&configs[1]);
+    let (voter, _) = common::context_and_sent(&configs[2]);
+    lagging.epoch = 1;
+
+    // Votes nobody signed are neither kept nor asked about
+    for height in 1..=2 * MAX_PENDING_CERTS as u64 {
+        let mut cert = certify(height, &[&peer, &voter]);
+        for vote in cert.votes.iter_mut() {
+            vote.auth = vec![0; 64];
+        }
+        lagging.accept_certificate(1, cert);
+    }
+    assert!(lagging.pending_certs.is_empty());
+    assert!(out.try_recv().is_err());
+
+    // Valid ones for blocks that never come fill up the waiting room
+    for height in 1..=MAX_PENDING_CERTS as u64 {
+        lagging.accept_certificate(1, certify(height, &[&peer, &voter]));
+    }
+    let late = certify(MAX_PENDING_CERTS as u64 + 1, &[&peer, &voter]);
+    lagging.accept_certificate(1, late.clone());
+    assert_eq!(lagging.pending_certs.len(), MAX_PENDING_CERTS);
+
+    // but only for a while
+    lagging.epoch += PENDING_CERT_EPOCHS + 1;
+    lagging.accept_certificate(1, late);
+    assert_eq!(lagging.pending_certs.len(), 1);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use std::sync::Arc;
+use types::{Block, Certificate, Vote};
+
+fn block_at(height: u64, author: u16) -> Arc<Block> {
+    let mut block = Block::new();
+    block.header.height = height;
+    block.header.author = author;
+    block.update_hash();
+    Arc::new(block)
+}
+
+fn cert_for(block: &Block) -> Certificate {
+    Certificate {
+        votes: vec![Vote {
+            msg: block.hash.to_vec(),
+            origin: 1,
+            auth: Vec::new(),
+        }],
+    }
+}
+
+#[tokio::test]
+async fn equal_height_certificates_resolve_the_same_way() {
+    let configs = common::generate_configs(4, 1, 1);
+    let a = block_at(1, 1);
+    let b = block_at(1, 2);
+    assert_ne!(a.hash, b.hash);
+
+    let mut picked = Vec::new();
+    for order in vec![[&a, &b], [&b, &a]] {
+        let (mut cx, _net_recv) = common::context_and_sent(&configs[0]);
+        for block in order.iter() {
+            cx.storage
+                .committed_blocks_by_hash
+                .insert(block.hash, Arc::clone(block));
+        }
+        for block in order.iter() {
+            cx.update_highest_cert(cert_for(block));
+        }
+        assert_eq!(cx.highest_height, 1);
+        picked.push(cx.highest_cert.votes[0].msg.clone());
+    }
+    assert_eq!(picked[0], picked[1]);
+    assert_eq!(picked[0], std::cmp::min(a.hash, b.hash).to_vec());
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+#[tokio::test]
+async fn every_epoch_builds_on_the_last_committed_block() {
+    let cluster = common::spawn_cluster(common::generate_configs(4, 1, 6));
+    let mut stream = subscribe(&cluster.events[0]);
+    let mut epoch = 0;
+    let mut heights = HashMap::new();
+    let collect = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::ViewChange(5) => break,
+                NodeEvent::ViewChange(e) => epoch = e,
+                NodeEvent::BlockCommitted(h, _) => {
+                    heights.insert(epoch, h);
+                }
+                _ => (),
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(60), collect)
+        .await
+        .expect("did not finish four epochs");
+
+    // Had the replicas kept handing the leaders the genesis certificate,
+    // every block would be at height 1
+    for e in 1..5 {
+        assert_eq!(heights.get(&e), Some(&e), "epoch {}", e);
+    }
+}
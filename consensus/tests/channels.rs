@@ -0,0 +1,41 @@
+mod common;
+
+use config::{DEFAULT_CHANNEL_SIZE, MAX_CHANNEL_SIZE};
+use types::{ProtocolMsg, Vote};
+
+/// Sends 100 votes to nobody through a channel sized by `config` and
+/// returns how many had to wait in the backlog
+fn backlog_after_burst(config: &config::Node) -> usize {
+    let (mut cx, _net_send_recv) = common::context_and_sent(config);
+    for _ in 0..100 {
+        cx.send_to(
+            1,
+            ProtocolMsg::Vote(
+                Vote {
+                    msg: Vec::new(),
+                    origin: 0,
+                    auth: Vec::new(),
+                },
+                0,
+            ),
+        );
+    }
+    cx.backlog.len()
+}
+
+#[test]
+fn channel_size_is_configurable_and_validated() {
+    let mut config = common::generate_configs(4, 1, 1).remove(0);
+    assert!(config.validate().is_ok());
+    assert_eq!(config.consensus_channel_size(), DEFAULT_CHANNEL_SIZE);
+    assert_eq!(backlog_after_burst(&config), 0);
+
+    config.consensus_channel = Some(10);
+    assert!(config.validate().is_ok());
+    assert_eq!(backlog_after_burst(&config), 90);
+
+    config.consensus_channel = Some(0);
+    assert!(config.validate().is_err());
+    config.consensus_channel = Some(MAX_CHANNEL_SIZE + 1);
+    assert!(config.validate().is_err());
+}
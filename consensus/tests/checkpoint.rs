@@ -0,0 +1,83 @@
+mod common;
+
+use consensus::bft::node::checkpoint::{self, Checkpoint};
+use consensus::bft::node::context::Context;
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use consensus::bft::node::sealing::{self, SealError, Sealer};
+use crypto::hash::empty_hash;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+fn restart(config: &config::Node) -> Context {
+    common::context_and_sent(config).0
+}
+
+#[tokio::test]
+async fn restarted_node_proposes_on_the_saved_tip() {
+    let path = std::env::temp_dir().join(format!("randpiper-checkpoint-{}", std::process::id()));
+    let mut configs = common::generate_configs(4, 1, 6);
+    configs[0].checkpoint = Some(path.to_string_lossy().into_owned());
+    let config = configs[0].clone();
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+    let mut tip = None;
+    let run = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                // Cut replica 0 off so that it commits nothing more, as if
+                // it went down
+                NodeEvent::ViewChange(4) => cluster.partition.split(&[&[1, 2, 3]], 4),
+                NodeEvent::ViewChange(5) => break,
+                NodeEvent::BlockCommitted(h, hash) => tip = Some((h, hash)),
+                _ => (),
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(60), run)
+        .await
+        .expect("did not finish four epochs");
+    let (height, hash) = tip.expect("nothing committed");
+    assert!(height >= 3);
+
+    // Back up, e.g. to take its turn as leader, it extends the tip it had
+    let cx = restart(&config);
+    assert_eq!(cx.proposal_parent(), (hash, height + 1));
+    assert_eq!(cx.highest_height, height);
+
+    // Without the checkpoint it would have started over from genesis
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(restart(&config).proposal_parent(), (empty_hash(), 1));
+}
+
+#[test]
+fn encrypted_checkpoints_need_the_passphrase() {
+    let path = std::env::temp_dir().join(format!("randpiper-sealed-{}", std::process::id()));
+    let mut block = types::Block::new();
+    block.header.height = 7;
+    block.header.extra = b"a secret transaction".to_vec();
+    let saved = Checkpoint {
+        cert: types::Certificate::empty_cert(),
+        block,
+    };
+    // Few rounds, to keep the test quick
+    let sealer = || Sealer::with_rounds(b"correct horse", 1_000);
+    checkpoint::save(&path, &saved, Some(&mut sealer())).unwrap();
+
+    let on_disk = std::fs::read(&path).unwrap();
+    assert!(!on_disk.windows(18).any(|w| w == b"secret transaction"));
+    assert!(checkpoint::load(&path, None).is_none());
+    let mut wrong = Sealer::with_rounds(b"battery staple", 1_000);
+    assert!(checkpoint::load(&path, Some(&mut wrong)).is_none());
+
+    let loaded = checkpoint::load(&path, Some(&mut sealer())).expect("did not decrypt");
+    assert_eq!(
+        bincode::serialize(&loaded).unwrap(),
+        bincode::serialize(&saved).unwrap()
+    );
+
+    // A header asking for more rounds is turned down before deriving a key
+    let mut forged = on_disk.clone();
+    forged[sealing::MAGIC.len()..sealing::MAGIC.len() + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(sealer().open(&forged), Err(SealError::Rounds(u32::MAX, 1_000)));
+    let _ = std::fs::remove_file(&path);
+}
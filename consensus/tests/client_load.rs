@@ -0,0 +1,67 @@
+mod common;
+
+use consensus::bft::node::client::ClientRuntime;
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::time::Instant;
+use tokio_stream::StreamExt;
+use types::Transaction;
+
+/// Transactions each client sends every millisecond
+const LOAD: usize = 20;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn epochs_keep_their_pace_under_client_load() {
+    let configs = common::generate_configs(4, 1, 5);
+    let epoch = Duration::from_millis(configs[0].phase_durations().epoch);
+    let client_rt = ClientRuntime::new(configs[0].client_threads(), Handle::current()).unwrap();
+    assert!(client_rt.is_dedicated());
+
+    let cluster = common::spawn_cluster(configs);
+    // Replica 0 is watched and gets no client load, so that its events are
+    // not drowned out by transactions. It still needs the loaded replicas to
+    // get through every phase.
+    let mut stream = subscribe(&cluster.events[0]);
+    for client in cluster.clients.iter().skip(1).cloned() {
+        client_rt.handle().spawn(async move {
+            let mut tick = tokio::time::interval(Duration::from_millis(1));
+            loop {
+                tick.tick().await;
+                for _ in 0..LOAD {
+                    let tx = Transaction {
+                        data: vec![0; 64],
+                        request: Vec::new(),
+                    };
+                    if client.send(tx).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    let changes = async {
+        let mut changes = Vec::new();
+        while let Some(Ok(ev)) = stream.next().await {
+            if let NodeEvent::ViewChange(e) = ev {
+                changes.push(Instant::now());
+                if e == 4 {
+                    return changes;
+                }
+            }
+        }
+        panic!("event channel closed");
+    };
+    let changes = tokio::time::timeout(Duration::from_secs(30), changes)
+        .await
+        .expect("epoch 4 never started");
+    for pair in changes.windows(2) {
+        let took = pair[1] - pair[0];
+        assert!(took < epoch * 3 / 2, "an epoch took {:?} under load", took);
+    }
+
+    if let ClientRuntime::Dedicated(rt) = client_rt {
+        rt.shutdown_background();
+    }
+}
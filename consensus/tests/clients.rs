@@ -0,0 +1,155 @@
+mod common;
+
+use config::ClientOverflow;
+use consensus::bft::node::client;
+use consensus::bft::node::intake::{tx_channel, TxReceiver};
+use futures::StreamExt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio_util::codec::FramedRead;
+use types::{Block, ClientMsg};
+use util::codec::{block, tx::submit};
+
+const CLIENTS: u64 = 16;
+
+fn block_at(height: u64) -> Arc<Block> {
+    let mut block = Block::new();
+    block.header.height = height;
+    block.update_hash();
+    Arc::new(block)
+}
+
+/// A client server for `config`, with where it listens, the transactions
+/// its clients send, and where blocks go in
+async fn serve(config: &config::Node) -> (SocketAddr, TxReceiver, UnboundedSender<Arc<Block>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx_send, txs) = tx_channel(CLIENTS as usize);
+    let blocks = client::start(config, listener, tx_send).await;
+    (addr, txs, blocks)
+}
+
+/// `n` clients of the server at `addr`, each served once what it sent came
+/// through
+async fn connect(addr: SocketAddr, txs: &mut TxReceiver, n: u64) -> Vec<TcpStream> {
+    let mut clients = Vec::new();
+    for i in 0..n {
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        submit(&mut client, vec![util::new_dummy_tx(i, 16)], 1).await.unwrap();
+        assert_eq!(txs.recv().await.unwrap().data, i.to_be_bytes().to_vec());
+        clients.push(client);
+    }
+    clients
+}
+
+#[test]
+fn committed_blocks_go_to_the_clients() {
+    let config = common::generate_configs(4, 1, 1).remove(0);
+    let mut cx = common::context_and_sent(&config).0;
+    let (cli_send, mut cli_recv) = unbounded_channel();
+    cx.cli_send = cli_send;
+    cx.commit_block(block_at(1));
+    // A height committed again is not sent twice
+    cx.commit_block(block_at(1));
+    assert_eq!(cli_recv.try_recv().unwrap().header.height, 1);
+    assert!(cli_recv.try_recv().is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn every_client_gets_every_block() {
+    let mut config = common::generate_configs(4, 1, 1).remove(0);
+    config.client_fanout = 2;
+    let (addr, mut txs, blocks) = serve(&config).await;
+    let clients = connect(addr, &mut txs, CLIENTS).await;
+
+    let sent: Vec<_> = (1..4).map(block_at).collect();
+    for block in sent.iter() {
+        blocks.send(Arc::clone(block)).unwrap();
+    }
+    for client in clients {
+        let mut framed = FramedRead::new(client, block::Codec::new());
+        for block in sent.iter() {
+            assert_eq!(framed.next().await.unwrap().unwrap().hash, block.hash);
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn a_client_that_does_not_read_is_dropped() {
+    const BLOCKS: usize = 32;
+    let mut config = common::generate_configs(4, 1, 1).remove(0);
+    config.client_write_timeout = Some(200);
+    let (addr, mut txs, blocks) = serve(&config).await;
+    let mut clients = connect(addr, &mut txs, 2).await;
+    let reader = clients.pop().unwrap();
+    let stuck = clients.pop().unwrap();
+
+    // Blocks of 1 MiB, far more than the socket buffers take
+    let mut big = Block::new();
+    big.body.data.transactions = vec![util::new_dummy_tx(0, 1 << 20)];
+    big.update_hash();
+    let hash = big.hash;
+    let big = Arc::new(big);
+    let reading = tokio::spawn(async move {
+        let mut framed = FramedRead::new(reader, block::Codec::new());
+        for _ in 0..BLOCKS {
+            assert_eq!(framed.next().await.unwrap().unwrap().hash, hash);
+        }
+    });
+    for _ in 0..BLOCKS {
+        blocks.send(Arc::clone(&big)).unwrap();
+    }
+    // The other client is not held up
+    reading.await.unwrap();
+
+    // The one that did not read gets what fit before it was dropped, and
+    // then the end of the connection
+    let mut framed = FramedRead::new(stuck, block::Codec::new());
+    let mut got = 0;
+    while let Some(Ok(_)) = framed.next().await {
+        got += 1;
+    }
+    assert!(got < BLOCKS, "a client that did not read got all {} blocks", got);
+}
+
+#[tokio::test]
+async fn a_client_over_the_limit_is_told_the_node_is_busy() {
+    let mut config = common::generate_configs(4, 1, 1).remove(0);
+    config.max_clients = Some(2);
+    config.client_overflow = ClientOverflow::Reject;
+    let (addr, mut txs, blocks) = serve(&config).await;
+    let clients = connect(addr, &mut txs, 2).await;
+
+    let mut extra = TcpStream::connect(addr).await.unwrap();
+    let mut reply = Vec::new();
+    extra.read_to_end(&mut reply).await.unwrap();
+    assert_eq!(ClientMsg::from_bytes(&reply), ClientMsg::Busy);
+
+    // The ones served before are still served
+    blocks.send(block_at(1)).unwrap();
+    for client in clients {
+        let mut framed = FramedRead::new(client, block::Codec::new());
+        assert_eq!(framed.next().await.unwrap().unwrap().header.height, 1);
+    }
+}
+
+#[tokio::test]
+async fn a_client_over_the_limit_takes_the_place_of_the_one_idle_longest() {
+    let mut config = common::generate_configs(4, 1, 1).remove(0);
+    config.max_clients = Some(2);
+    config.client_overflow = ClientOverflow::EvictIdle;
+    let (addr, mut txs, blocks) = serve(&config).await;
+    let mut clients = connect(addr, &mut txs, 2).await;
+    clients.extend(connect(addr, &mut txs, 1).await);
+
+    blocks.send(block_at(1)).unwrap();
+    let mut clients = clients.into_iter().map(|c| FramedRead::new(c, block::Codec::new()));
+    // The first one sent last longest ago, and is closed without the block
+    assert!(clients.next().unwrap().next().await.is_none());
+    for mut framed in clients {
+        assert_eq!(framed.next().await.unwrap().unwrap().header.height, 1);
+    }
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use consensus::bft::node::clock::MockClock;
+use consensus::bft::node::events::NodeEvent;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::TryRecvError;
+
+const STEP: Duration = Duration::from_micros(500);
+
+#[tokio::test]
+async fn epochs_follow_virtual_time() {
+    let configs = common::generate_configs(4, 1, 4);
+    let delta = Duration::from_millis(configs[0].delta);
+    let epoch = Duration::from_millis(configs[0].phase_durations().epoch);
+    let clock = MockClock::new();
+    let cluster = common::spawn_cluster_with_clock(configs, Arc::new(clock.clone()));
+    let mut events = cluster.events[0].subscribe();
+
+    let mut view_changes = Vec::new();
+    let mut committed = 0;
+    while view_changes.len() < 4 {
+        assert!(
+            clock.elapsed() < delta + epoch * 4,
+            "epochs did not advance"
+        );
+        clock.advance(STEP);
+        // Let the replicas handle everything that is due before moving on
+        for _ in 0..64 {
+            tokio::task::yield_now().await;
+        }
+        loop {
+            match events.try_recv() {
+                Ok(NodeEvent::ViewChange(e)) => view_changes.push((e, clock.elapsed())),
+                Ok(NodeEvent::BlockCommitted(..)) => committed += 1,
+                Ok(_) => {}
+                Err(TryRecvError::Empty) => break,
+                Err(e) => panic!("event stream failed: {}", e),
+            }
+        }
+    }
+
+    // Epoch k starts exactly (k - 1) epochs after the boot delay.
+    for (i, (e, at)) in view_changes.into_iter().enumerate() {
+        assert_eq!(e, i as u64 + 1);
+        assert_eq!(at, delta + epoch * i as u32);
+    }
+    assert!(committed > 0, "no block committed");
+}
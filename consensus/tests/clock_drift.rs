@@ -0,0 +1,88 @@
+mod common;
+
+use consensus::bft::node::clock::{wall_clock_ms, RealClock};
+use consensus::bft::node::entropy::entropy_channel;
+use consensus::bft::node::events::{event_channel, subscribe, NodeEvent};
+use consensus::bft::node::intake::tx_channel;
+use consensus::bft::node::reactor;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{channel, unbounded_channel};
+use tokio_stream::StreamExt;
+use types::{Certificate, ProtocolMsg};
+
+const MAX_DRIFT: u64 = 500;
+
+#[test]
+fn a_skewed_peer_is_warned_about_once() {
+    let mut config = common::generate_configs(4, 1, 1).remove(0);
+    config.max_clock_drift_ms = MAX_DRIFT;
+    let (mut cx, _net_recv) = common::context_and_sent(&config);
+    let mut events = cx.events.subscribe();
+    let now = 1_600_000_000_000;
+
+    // Replica 2 is in step, give or take the time in transit
+    assert!(!cx.observe_clock(2, now - 20, now));
+    assert!(!cx.observe_clock(2, now + 30, now));
+    // Replica 1 is two seconds ahead
+    assert!(cx.observe_clock(1, now + 2_000, now));
+    assert!(!cx.observe_clock(1, now + 2_000, now));
+    assert_eq!(cx.clock_drift[&1], 2_000);
+    match events.try_recv() {
+        Ok(NodeEvent::ClockDrift(1, 2_000)) => (),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(events.try_recv().is_err());
+
+    // Once it is back in step, a new skew is warned about again
+    for _ in 0..20 {
+        assert!(!cx.observe_clock(1, now, now));
+    }
+    assert!(cx.drifting.is_empty());
+    assert!(cx.observe_clock(1, now - 5_000, now));
+    assert!(cx.clock_drift[&1] < -(MAX_DRIFT as i64));
+}
+
+#[tokio::test]
+async fn a_certificate_from_a_skewed_peer_raises_a_warning() {
+    let mut config = common::generate_configs(4, 1, 4).remove(0);
+    config.max_clock_drift_ms = MAX_DRIFT;
+    let (net_send, mut net_send_recv) = channel(util::CHANNEL_SIZE);
+    let (net_recv_send, net_recv) = unbounded_channel();
+    let (cli_send, _cli_send_recv) = unbounded_channel();
+    let (_cli_recv_send, cli_recv) = tx_channel(16);
+    let events = event_channel();
+    let mut stream = subscribe(&events);
+    tokio::spawn(async move { while net_send_recv.recv().await.is_some() {} });
+    tokio::spawn(async move {
+        reactor(
+            &config,
+            false,
+            net_send,
+            net_recv,
+            cli_send,
+            cli_recv,
+            events,
+            Arc::new(RealClock),
+            entropy_channel().1,
+            None,
+        )
+        .await
+    });
+
+    let ahead = wall_clock_ms() + 60_000;
+    net_recv_send.send((2, ProtocolMsg::Certificate(Certificate::empty_cert(), 0, ahead))).unwrap();
+    let follow = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            if let NodeEvent::ClockDrift(origin, drift) = ev {
+                return (origin, drift);
+            }
+        }
+        panic!("event channel closed");
+    };
+    let (origin, drift) = tokio::time::timeout(Duration::from_secs(10), follow)
+        .await
+        .expect("no drift warning");
+    assert_eq!(origin, 2);
+    assert!(drift > 50_000, "estimated {} ms", drift);
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use consensus::bft::node::accumulator::get_acc;
+use crypto::hash::COMMIT_DOMAIN;
+
+#[test]
+fn shares_that_do_not_open_the_commitments_are_rejected() {
+    let configs = common::generate_configs(4, 1, 2);
+    let leader = common::context(&configs[0]).next_leader();
+    let me = (leader + 1) % 4;
+    let dealer = common::context(&configs[leader as usize]);
+    let mut cx = common::context(&configs[me as usize]);
+    let (shards, commits) = dealer.rand_beacon_shares[1].clone();
+    let sign = get_acc(&dealer, &commits, COMMIT_DOMAIN).unwrap().1;
+    let mine = shards[me as usize].clone();
+    assert_eq!(cx.check_commit(leader, &mine, &commits, &sign), Ok(()));
+
+    // Two of our shares swapped, so neither matches its commitment
+    let mut swapped = mine.clone();
+    swapped.swap(0, 1);
+    let mismatch = Err("Received a share that does not match its commitment");
+    assert_eq!(cx.check_commit(leader, &swapped, &commits, &sign), mismatch);
+    // The shares of another replica
+    let theirs = &shards[leader as usize];
+    assert_eq!(cx.check_commit(leader, theirs, &commits, &sign), mismatch);
+
+    // Commitments swapped along with the shares match, but were not signed
+    let mut reordered = commits.clone();
+    reordered.swap(0, 1);
+    assert_eq!(
+        cx.check_commit(leader, &swapped, &reordered, &sign),
+        Err("The signature of the commit does not match")
+    );
+    // Signed by someone other than the next leader
+    let forged = get_acc(&cx, &commits, COMMIT_DOMAIN).unwrap().1;
+    assert_eq!(
+        cx.check_commit(leader, &mine, &commits, &forged),
+        Err("The signature of the commit does not match")
+    );
+    assert!(cx.check_commit(me, &mine, &commits, &sign).is_err());
+
+    cx.trust_commits = true;
+    assert_eq!(cx.check_commit(leader, &swapped, &commits, &sign), Ok(()));
+}
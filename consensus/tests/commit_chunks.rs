@@ -0,0 +1,67 @@
+use consensus::bft::node::chunks::{split_commit, CommitAssembler};
+use crypto::rand::{rngs::StdRng, SeedableRng};
+use crypto::UniformRand;
+use std::collections::VecDeque;
+use types::{DataWithAcc, ProtocolMsg};
+use util::io::to_bytes;
+
+const N: usize = 64;
+
+#[test]
+fn chunks_reassemble_to_the_single_message() {
+    let rng = &mut StdRng::seed_from_u64(7);
+    let params = crypto::EVSS381::setup((N - 1) / 2, rng).unwrap();
+    let mut shares = VecDeque::with_capacity(N);
+    let mut commits = Vec::with_capacity(N);
+    for _ in 0..N {
+        let poly = crypto::EVSS381::commit(&params, crypto::F381::rand(rng), rng).unwrap();
+        commits.push(poly.get_commit());
+        shares.push_back(
+            crypto::EVSS381::get_share(crypto::F381::from(3 as u16), &params, &poly, rng).unwrap(),
+        );
+    }
+    let sign = DataWithAcc {
+        sign: vec![1; 64],
+        tree: vec![vec![2; 32]; 8],
+        size: 3,
+    };
+    let whole = to_bytes(&ProtocolMsg::Commit(shares.clone(), commits.clone(), sign.clone(), 5)).unwrap();
+
+    let chunks = split_commit(&shares, &commits, &sign, 5, 5);
+    assert_eq!(chunks.len(), 13);
+    for chunk in chunks.iter() {
+        assert!(to_bytes(chunk).unwrap().len() < whole.len());
+    }
+
+    let mut assembler = CommitAssembler::new(N);
+    let mut done = None;
+    // Out of order, with a repeat
+    let mut order: Vec<_> = chunks.into_iter().rev().collect();
+    order.insert(3, order[1].clone());
+    for chunk in order {
+        if let ProtocolMsg::CommitChunk(e, index, total, sh, c, z) = chunk {
+            assert!(done.is_none());
+            done = assembler.add(e, index, total, sh, c, z);
+        } else {
+            panic!("not a chunk");
+        }
+    }
+    let (sh, c, z) = done.expect("commit was not reassembled");
+    assert_eq!(to_bytes(&ProtocolMsg::Commit(sh, c, z, 5)).unwrap(), whole);
+}
+
+#[test]
+fn incomplete_commit_is_not_used() {
+    let sign = DataWithAcc {
+        sign: vec![1; 64],
+        tree: Vec::new(),
+        size: 0,
+    };
+    let mut assembler = CommitAssembler::new(N);
+    // Every chunk of an empty commit arrives, but it does not have N of each
+    for chunk in split_commit(&VecDeque::new(), &Vec::new(), &sign, 1, 5) {
+        if let ProtocolMsg::CommitChunk(e, index, total, sh, c, z) = chunk {
+            assert!(assembler.add(e, index, total, sh, c, z).is_none());
+        }
+    }
+}
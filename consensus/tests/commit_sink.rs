@@ -0,0 +1,87 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use types::{Height, Transaction};
+
+/// Transactions every replica's client sends
+const PER_CLIENT: usize = 4;
+
+fn tx(client: usize, i: usize) -> Transaction {
+    Transaction {
+        data: vec![client as u8, i as u8],
+        request: Vec::new(),
+    }
+}
+
+fn read_log(path: &PathBuf) -> Vec<(Height, Vec<Transaction>)> {
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    text.lines()
+        .map(|line| serde_json::from_str(line).expect("a commit log line is not JSON"))
+        .collect()
+}
+
+#[tokio::test]
+async fn committed_transactions_are_written_once_in_commit_order() {
+    let mut configs = common::generate_configs(4, 1, 12);
+    let paths: Vec<PathBuf> = (0..configs.len())
+        .map(|i| std::env::temp_dir().join(format!("randpiper-commits-{}-{}", std::process::id(), i)))
+        .collect();
+    for (config, path) in configs.iter_mut().zip(paths.iter()) {
+        let _ = std::fs::remove_file(path);
+        config.block_size = PER_CLIENT;
+        config.commit_log = Some(path.to_string_lossy().into_owned());
+    }
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+    // Every client's transactions only reach its own replica, so each is
+    // proposed by one leader
+    for (client, send) in cluster.clients.iter().enumerate() {
+        for i in 0..PER_CLIENT {
+            send.send(tx(client, i)).await.unwrap();
+        }
+    }
+
+    let expected = PER_CLIENT * cluster.clients.len();
+    let run = async {
+        loop {
+            if let Some(Ok(NodeEvent::ViewChange(_))) = stream.next().await {
+                let written: usize = read_log(&paths[0]).iter().map(|(_, txs)| txs.len()).sum();
+                if written >= expected {
+                    return;
+                }
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(60), run)
+        .await
+        .expect("not every transaction was committed");
+
+    let log = read_log(&paths[0]);
+    for pair in log.windows(2) {
+        assert!(pair[0].0 < pair[1].0, "height {} was written after {}", pair[1].0, pair[0].0);
+    }
+    let written: Vec<Transaction> = log.iter().flat_map(|(_, txs)| txs.iter().cloned()).collect();
+    let unique: HashSet<Vec<u8>> = written.iter().map(|t| t.data.clone()).collect();
+    assert_eq!(unique.len(), written.len(), "a transaction was written twice");
+    assert_eq!(written.len(), expected);
+    for client in 0..cluster.clients.len() {
+        let sent: Vec<Transaction> = written.iter().filter(|t| t.data[0] == client as u8).cloned().collect();
+        let order: Vec<Transaction> = (0..PER_CLIENT).map(|i| tx(client, i)).collect();
+        assert_eq!(sent, order, "client {}", client);
+    }
+    // Every replica committed the same block at a height
+    for path in paths.iter().skip(1) {
+        for (height, txs) in read_log(path) {
+            if let Some((_, ours)) = log.iter().find(|(h, _)| *h == height) {
+                assert_eq!(&txs, ours, "height {}", height);
+            }
+        }
+    }
+    for path in paths.iter() {
+        let _ = std::fs::remove_file(path);
+    }
+}
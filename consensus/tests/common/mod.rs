@@ -0,0 +1,389 @@
+// Helpers shared by the integration tests. Mirrors what genconfig does, but
+// keeps everything in memory and only generates `epochs` rounds of beacon
+// shares.
+
+#![allow(dead_code)]
+
+use config::{Node, ParseError};
+use consensus::bft::node::clock::{Clock, RealClock};
+use consensus::bft::node::context::Context;
+use consensus::bft::node::entropy::{entropy_channel, EntropySender};
+use consensus::bft::node::events::{event_channel, subscribe, EventSender, NodeEvent};
+use consensus::bft::node::intake::{tx_channel, TxSender};
+use consensus::bft::node::reactor;
+use consensus::bft::node::sink;
+use crypto::hash::{with_domain, Hash, VOTE_DOMAIN};
+use crypto::rand::{rngs::StdRng, SeedableRng};
+use crypto::Algorithm;
+use crypto::UniformRand;
+use crypto_lib::ed25519;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{channel, unbounded_channel, Receiver, UnboundedSender};
+use tokio_stream::StreamExt;
+use types::{Certificate, Destination, Height, ProtocolMsg, Replica, Vote};
+
+pub const SEED: u64 = 42;
+
+pub fn generate_configs(num_nodes: usize, num_faults: usize, epochs: usize) -> Vec<Node> {
+    let rng = &mut StdRng::seed_from_u64(SEED);
+    let mut node: Vec<Node> = Vec::with_capacity(num_nodes);
+    let mut pk = HashMap::new();
+    let mut ip = HashMap::new();
+    let mut bi_pp = HashMap::new();
+
+    for i in 0..num_nodes {
+        node.push(Node::new());
+        node[i].delta = 50;
+        node[i].id = i as Replica;
+        node[i].num_nodes = num_nodes;
+        node[i].num_faults = num_faults;
+        node[i].block_size = 1;
+        node[i].crypto_alg = Algorithm::ED25519;
+        let kp = ed25519::Keypair::generate();
+        pk.insert(i as Replica, kp.public().encode().to_vec());
+        node[i].secret_key_bytes = kp.encode().to_vec();
+        ip.insert(i as Replica, format!("127.0.0.1:{}", 16000 + i));
+        node[i].bi_p = Some(crypto::Biaccumulator381::setup(num_nodes, rng).unwrap());
+        bi_pp.insert(
+            i as Replica,
+            node[i].bi_p.as_ref().unwrap().get_public_params(),
+        );
+    }
+
+    let rand_beacon_parameter = crypto::EVSS381::setup(num_faults, rng).unwrap();
+    for i in 0..num_nodes {
+        node[i].pk_map = pk.clone();
+        node[i].net_map = ip.clone();
+        node[i].bi_pp_map = bi_pp.clone();
+        node[i].rand_beacon_parameter = Some(rand_beacon_parameter.clone());
+    }
+
+    for i in 0..num_nodes {
+        for j in 0..num_nodes {
+            node[j]
+                .rand_beacon_queue
+                .insert(i as Replica, VecDeque::with_capacity(num_nodes + num_faults));
+            node[j]
+                .rand_beacon_commits
+                .insert(i as Replica, VecDeque::with_capacity(num_nodes + num_faults));
+        }
+        for _ in 0..num_nodes + num_faults {
+            let poly =
+                crypto::EVSS381::commit(&rand_beacon_parameter, crypto::F381::rand(rng), rng)
+                    .unwrap();
+            for k in 0..num_nodes {
+                node[k]
+                    .rand_beacon_commits
+                    .get_mut(&(i as Replica))
+                    .unwrap()
+                    .push_back(poly.get_commit());
+                node[k]
+                    .rand_beacon_queue
+                    .get_mut(&(i as Replica))
+                    .unwrap()
+                    .push_back(
+                        crypto::EVSS381::get_share(
+                            crypto::F381::from((k + 1) as u16),
+                            &rand_beacon_parameter,
+                            &poly,
+                            rng,
+                        )
+                        .unwrap(),
+                    );
+            }
+        }
+    }
+
+    for i in 0..num_nodes {
+        let mut vec = Vec::with_capacity(epochs);
+        for _ in 0..epochs {
+            let mut shares = vec![VecDeque::with_capacity(num_nodes); num_nodes];
+            let mut commits = Vec::with_capacity(num_nodes);
+            for _ in 0..num_nodes {
+                let poly =
+                    crypto::EVSS381::commit(&rand_beacon_parameter, crypto::F381::rand(rng), rng)
+                        .unwrap();
+                commits.push(poly.get_commit());
+                for j in 0..num_nodes {
+                    shares[j].push_back(
+                        crypto::EVSS381::get_share(
+                            crypto::F381::from((j + 1) as u16),
+                            &rand_beacon_parameter,
+                            &poly,
+                            rng,
+                        )
+                        .unwrap(),
+                    );
+                }
+            }
+            vec.push((shares, commits));
+        }
+        node[i].rand_beacon_shares = vec;
+    }
+    node
+}
+
+/// Gives the replicas of `configs` the increasing ids `ids` instead of 0 to
+/// n - 1, so that every replica keeps its place among the others and with it
+/// its shards
+pub fn with_ids(mut configs: Vec<Node>, ids: &[Replica]) -> Vec<Node> {
+    fn relabel<T>(map: &mut HashMap<Replica, T>, ids: &[Replica]) {
+        *map = map.drain().map(|(r, v)| (ids[r as usize], v)).collect();
+    }
+    for config in configs.iter_mut() {
+        config.id = ids[config.id as usize];
+        relabel(&mut config.net_map, ids);
+        relabel(&mut config.pk_map, ids);
+        relabel(&mut config.bi_pp_map, ids);
+        relabel(&mut config.rand_beacon_queue, ids);
+        relabel(&mut config.rand_beacon_commits, ids);
+        relabel(&mut config.threshold_shares, ids);
+        relabel(&mut config.weights, ids);
+    }
+    configs
+}
+
+/// The context of the replica `config` is for, outside any reactor, with
+/// what it sends to the others through a channel as large as the reactor's
+pub fn try_context(config: &Node) -> Result<(Context, Receiver<(Destination, Arc<ProtocolMsg>)>), ParseError> {
+    let (net_send, net_recv) = channel(config.consensus_channel_size());
+    let (cli_send, _cli_recv) = unbounded_channel();
+    Ok((Context::new(config, net_send, cli_send)?, net_recv))
+}
+
+/// Like `try_context`, for a config that is known to be good
+pub fn context_and_sent(config: &Node) -> (Context, Receiver<(Destination, Arc<ProtocolMsg>)>) {
+    try_context(config).expect("the config cannot make a context")
+}
+
+/// Like `context_and_sent`, in epoch 1 and without anyone listening
+pub fn context(config: &Node) -> Context {
+    let mut cx = context_and_sent(config).0;
+    cx.epoch = 1;
+    cx
+}
+
+/// The vote of `cx` for `hash`
+pub fn vote(cx: &Context, hash: &[u8]) -> Vote {
+    Vote {
+        msg: hash.to_vec(),
+        origin: cx.myid,
+        auth: cx.my_secret_key.sign(&with_domain(VOTE_DOMAIN, hash)).unwrap(),
+    }
+}
+
+/// Decides which replicas can currently reach each other. By default every
+/// replica can reach every other one.
+#[derive(Clone, Default)]
+pub struct Partition(Arc<Mutex<Option<Vec<usize>>>>);
+
+impl Partition {
+    /// Only lets messages through between replicas in the same group.
+    /// Replicas not listed in any group are isolated.
+    pub fn split(&self, groups: &[&[Replica]], num_nodes: usize) {
+        let mut side: Vec<usize> = (0..num_nodes).map(|i| groups.len() + i).collect();
+        for (g, group) in groups.iter().enumerate() {
+            for r in group.iter() {
+                side[*r as usize] = g;
+            }
+        }
+        *self.0.lock().unwrap() = Some(side);
+    }
+
+    pub fn heal(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+
+    pub fn allows(&self, from: usize, to: usize) -> bool {
+        match self.0.lock().unwrap().as_ref() {
+            None => true,
+            Some(side) => side[from] == side[to],
+        }
+    }
+}
+
+/// Decides which messages are lost on the way, on top of the partition. By
+/// default none are.
+#[derive(Clone, Default)]
+pub struct Losses(Arc<Mutex<Option<Box<dyn Fn(usize, usize, &ProtocolMsg) -> bool + Send>>>>);
+
+impl Losses {
+    /// Loses every message `lost` says is, given its sender and recipient
+    pub fn set(&self, lost: impl Fn(usize, usize, &ProtocolMsg) -> bool + Send + 'static) {
+        *self.0.lock().unwrap() = Some(Box::new(lost));
+    }
+
+    pub fn clear(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+
+    pub fn loses(&self, from: usize, to: usize, msg: &ProtocolMsg) -> bool {
+        match self.0.lock().unwrap().as_ref() {
+            None => false,
+            Some(lost) => lost(from, to, msg),
+        }
+    }
+}
+
+/// A set of reactors wired together through in-memory channels
+pub struct Cluster {
+    pub events: Vec<EventSender>,
+    pub clients: Vec<TxSender>,
+    pub partition: Partition,
+    pub losses: Losses,
+    /// Frames each replica put on the wire
+    pub frames_sent: Arc<Vec<AtomicUsize>>,
+    /// Frames put on the wire by all replicas, by message kind
+    pub frames_by_kind: Arc<Mutex<HashMap<&'static str, usize>>>,
+    /// (epoch, sender) of every proposal put on the wire
+    pub proposals: Arc<Mutex<Vec<(Height, Replica)>>>,
+    /// External randomness for each replica's beacon
+    pub entropy: Vec<EntropySender>,
+    /// Each replica's network inbox, to inject messages from any sender
+    pub inbound: Vec<UnboundedSender<(Replica, ProtocolMsg)>>,
+}
+
+impl Cluster {
+    pub fn frames_sent(&self, id: usize) -> usize {
+        self.frames_sent[id].load(Ordering::SeqCst)
+    }
+
+    pub fn frames_of(&self, kind: &str) -> usize {
+        *self.frames_by_kind.lock().unwrap().get(kind).unwrap_or(&0)
+    }
+}
+
+/// Starts one reactor per config and routes their messages to each other.
+pub fn spawn_cluster(configs: Vec<Node>) -> Cluster {
+    spawn_cluster_with_clock(configs, Arc::new(RealClock))
+}
+
+/// Like `spawn_cluster`, but every reactor reads time from `clock`.
+pub fn spawn_cluster_with_clock(configs: Vec<Node>, clock: Arc<dyn Clock>) -> Cluster {
+    let delays = vec![Duration::from_millis(0); configs.len()];
+    spawn(configs, clock, delays)
+}
+
+/// Like `spawn_cluster`, but replica `i` only starts once `delays[i]` has
+/// passed. Whatever is sent to it before then waits in its inbox.
+pub fn spawn_cluster_staggered(configs: Vec<Node>, delays: Vec<Duration>) -> Cluster {
+    spawn(configs, Arc::new(RealClock), delays)
+}
+
+fn spawn(configs: Vec<Node>, clock: Arc<dyn Clock>, delays: Vec<Duration>) -> Cluster {
+    let n = configs.len();
+    let ids: Arc<Vec<Replica>> = Arc::new(configs.iter().map(|c| c.id).collect());
+    let mut inbound = Vec::with_capacity(n);
+    let mut outbound = Vec::with_capacity(n);
+    let mut cluster = Cluster {
+        events: Vec::with_capacity(n),
+        clients: Vec::with_capacity(n),
+        partition: Partition::default(),
+        losses: Losses::default(),
+        frames_sent: Arc::new((0..n).map(|_| AtomicUsize::new(0)).collect()),
+        frames_by_kind: Arc::default(),
+        proposals: Arc::default(),
+        entropy: Vec::with_capacity(n),
+        inbound: Vec::new(),
+    };
+    for (config, delay) in configs.into_iter().zip(delays) {
+        let (net_send, out_recv) = channel(config.consensus_channel_size());
+        let (in_send, net_recv) = unbounded_channel();
+        let (cli_send, _) = unbounded_channel();
+        let (tx_send, cli_recv) = tx_channel(config.mempool_capacity());
+        // Like the node binary, nothing feeds the reactor without clients
+        let tx_send = if config.disable_clients { tx_channel(1).0 } else { tx_send };
+        let events = event_channel();
+        let (entropy_send, entropy) = entropy_channel();
+        inbound.push(in_send);
+        outbound.push(out_recv);
+        cluster.events.push(events.clone());
+        cluster.clients.push(tx_send);
+        cluster.entropy.push(entropy_send);
+        let clock = Arc::clone(&clock);
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            reactor(
+                &config,
+                false,
+                net_send,
+                net_recv,
+                cli_send,
+                cli_recv,
+                events,
+                clock,
+                entropy,
+                sink::from_config(&config),
+            )
+            .await;
+        });
+    }
+    for (i, mut out_recv) in outbound.into_iter().enumerate() {
+        let peers = inbound.clone();
+        let partition = cluster.partition.clone();
+        let losses = cluster.losses.clone();
+        let frames_sent = Arc::clone(&cluster.frames_sent);
+        let frames_by_kind = Arc::clone(&cluster.frames_by_kind);
+        let proposals = Arc::clone(&cluster.proposals);
+        let ids = Arc::clone(&ids);
+        tokio::spawn(async move {
+            while let Some((to, msg)) = out_recv.recv().await {
+                if let ProtocolMsg::Propose(p, _) = msg.as_ref() {
+                    proposals.lock().unwrap().push((p.epoch, ids[i]));
+                }
+                for id in to.peers(ids[i], &ids) {
+                    // Replicas are numbered by their place in `configs`
+                    let j = ids.iter().position(|r| *r == id).unwrap();
+                    frames_sent[i].fetch_add(1, Ordering::SeqCst);
+                    *frames_by_kind.lock().unwrap().entry(msg.to_string()).or_insert(0) += 1;
+                    if partition.allows(i, j) && !losses.loses(i, j, &msg) {
+                        let _ = peers[j].send((ids[i], (*msg).clone()));
+                    }
+                }
+            }
+        });
+    }
+    cluster.inbound = inbound;
+    cluster
+}
+
+/// What one replica observed during epoch 1
+pub struct EpochOutcome {
+    pub committed: Option<(Height, Hash)>,
+    pub certificate: Option<Certificate>,
+    pub beacon: Option<Hash>,
+}
+
+/// Follows every replica of a freshly spawned cluster through epoch 1. Call
+/// it right after `spawn_cluster`, before anything else is awaited.
+pub async fn first_epoch(cluster: &Cluster) -> Vec<EpochOutcome> {
+    let mut streams: Vec<_> = cluster.events.iter().map(subscribe).collect();
+    let mut outcomes = Vec::with_capacity(streams.len());
+    for stream in streams.iter_mut() {
+        let epoch = async {
+            let mut outcome = EpochOutcome {
+                committed: None,
+                certificate: None,
+                beacon: None,
+            };
+            while let Some(Ok(ev)) = stream.next().await {
+                match ev {
+                    NodeEvent::BlockCommitted(h, hash) => outcome.committed = Some((h, hash)),
+                    NodeEvent::VoteCertified(1, c) => outcome.certificate = Some(c),
+                    NodeEvent::BeaconProduced(1, b) => outcome.beacon = Some(b),
+                    NodeEvent::ViewChange(2) => break,
+                    _ => (),
+                }
+            }
+            outcome
+        };
+        let outcome = tokio::time::timeout(Duration::from_secs(30), epoch)
+            .await
+            .expect("epoch 1 did not finish");
+        outcomes.push(outcome);
+    }
+    outcomes
+}
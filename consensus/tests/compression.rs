@@ -0,0 +1,81 @@
+mod common;
+
+use bytes::Bytes;
+use consensus::bft::node::accumulator::{get_acc, get_sign, ShareGatherer};
+use consensus::bft::node::checkpoint::{self, Checkpoint};
+use crypto::hash::{empty_hash, PROPOSE_DOMAIN};
+use types::{Block, BlockBody, BlockBuilder, Certificate, Content, Propose, Transaction};
+
+/// A block whose body is a thousand copies of the same transaction
+fn block(compressed: bool) -> Block {
+    let tx = Transaction {
+        data: vec![0x5a; 256],
+        request: b"client".to_vec(),
+    };
+    let content = Content {
+        transactions: vec![tx; 1000],
+        ..Content::new()
+    };
+    BlockBuilder::new()
+        .prev(empty_hash())
+        .author(1)
+        .height(1)
+        .body(BlockBody { data: content })
+        .compressed(compressed)
+        .build()
+        .unwrap()
+}
+
+fn propose(compressed: bool) -> Propose {
+    Propose {
+        new_block: block(compressed),
+        certificate: Certificate::empty_cert(),
+        epoch: 1,
+        batch: Vec::new(),
+    }
+}
+
+#[test]
+fn a_compressed_proposal_survives_sharding() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (cx, _net_recv) = common::context_and_sent(&configs[1]);
+    let pp = cx.accumulator_pub_params_map.get(&1).unwrap();
+    let pk = cx.pub_key_map.get(&1).unwrap();
+
+    let plain = get_acc(&cx, &propose(false), PROPOSE_DOMAIN).unwrap().0;
+    let (shards, acc) = get_acc(&cx, &propose(true), PROPOSE_DOMAIN).unwrap();
+    assert!(shards[0].len() * 10 < plain[0].len(), "{} bytes compressed, {} plain", shards[0].len(), plain[0].len());
+
+    // Shard 0 never arrives
+    let mut gatherer = ShareGatherer::new(4, 3, PROPOSE_DOMAIN);
+    for i in 1..4 {
+        gatherer.add_share(Bytes::from(shards[i as usize].clone()), i, pp, pk, get_sign(&acc, i));
+    }
+    let bytes = gatherer.reconstruct(4, 1).unwrap();
+    let mut rebuilt = Propose::try_from_bytes(&bytes).expect("the proposal does not decode").new_block;
+    let sent = block(true);
+    assert!(rebuilt.header.compressed);
+    assert_eq!(rebuilt.body.data.transactions.len(), 1000);
+    assert_eq!(rebuilt.body.data.transactions[999].data, vec![0x5a; 256]);
+    rebuilt.update_hash();
+    assert_eq!(rebuilt.hash, sent.hash);
+    // How the body is encoded is not part of the block
+    assert_eq!(block(false).hash, sent.hash);
+}
+
+#[test]
+fn a_compressed_block_is_saved_and_loaded() {
+    let path = std::env::temp_dir().join(format!("randpiper-compressed-{}", std::process::id()));
+    let saved = Checkpoint {
+        cert: Certificate::empty_cert(),
+        block: block(true),
+    };
+    checkpoint::save(&path, &saved, None).unwrap();
+    let on_disk = std::fs::metadata(&path).unwrap().len() as usize;
+    assert!(on_disk < bincode::serialized_size(&block(false)).unwrap() as usize / 10);
+
+    let loaded = checkpoint::load(&path, None).expect("the checkpoint does not load");
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.block.body.data.transactions.len(), 1000);
+    assert_eq!(loaded.block.header.body_root, saved.block.header.body_root);
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use bytes::Bytes;
+use consensus::bft::node::accumulator::{get_acc, get_sign};
+use consensus::bft::node::context::Context;
+use consensus::bft::node::deadletter::{DeadLetters, Outbox};
+use crypto::hash::PROPOSE_DOMAIN;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use tokio::sync::mpsc::{channel, unbounded_channel};
+use types::{Destination, ProtocolMsg};
+
+/// What a `Ready` says, as only those are sent here
+fn ready(msg: &ProtocolMsg) -> bool {
+    match msg {
+        ProtocolMsg::Ready(past) => *past,
+        other => panic!("unexpected {}", other.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn messages_for_a_disconnected_peer_are_redelivered_on_reconnect() {
+    let (link1, mut recv1) = channel(8);
+    let (link2, recv2) = channel(8);
+    // Peer 2's connection is gone
+    drop(recv2);
+    let links = vec![(1, link1), (2, link2)].into_iter().collect();
+    let mut outbox = Outbox::new(0, vec![0, 1, 2], links, DeadLetters::new(16, Arc::new(AtomicU64::new(0))));
+
+    outbox.send(&Destination::All, Arc::new(ProtocolMsg::Ready(false))).await;
+    outbox.send(&Destination::One(2), Arc::new(ProtocolMsg::Ready(true))).await;
+    assert!(!ready(&recv1.try_recv().unwrap()));
+    assert_eq!(outbox.dead.kept(2), 2);
+    assert_eq!(outbox.dead.undeliverable(), 2);
+
+    let (link2, mut recv2) = channel(8);
+    outbox.reconnect(2, link2).await;
+    assert_eq!(outbox.dead.kept(2), 0);
+    outbox.send(&Destination::One(2), Arc::new(ProtocolMsg::Ready(false))).await;
+    let got: Vec<bool> = std::iter::from_fn(|| recv2.try_recv().ok()).map(|m| ready(&m)).collect();
+    // What it missed comes first, in order
+    assert_eq!(got, vec![false, true, false]);
+}
+
+#[test]
+fn only_the_latest_messages_are_kept() {
+    let mut dead = DeadLetters::new(2, Arc::new(AtomicU64::new(0)));
+    for past in [false, true, true].iter() {
+        dead.bury(1, Arc::new(ProtocolMsg::Ready(*past)));
+    }
+    assert_eq!(dead.undeliverable(), 3);
+    assert_eq!(dead.discarded(), 1);
+    let kept: Vec<bool> = dead.exhume(1).iter().map(|m| ready(m)).collect();
+    assert_eq!(kept, vec![true, true]);
+}
+
+#[tokio::test]
+async fn without_capacity_nothing_is_kept() {
+    let mut outbox = Outbox::new(0, vec![0, 1], HashMap::new(), DeadLetters::new(0, Arc::new(AtomicU64::new(0))));
+    outbox.send(&Destination::One(1), Arc::new(ProtocolMsg::Ready(false))).await;
+    assert_eq!(outbox.dead.undeliverable(), 1);
+    assert_eq!(outbox.dead.kept(1), 0);
+}
+
+#[tokio::test]
+async fn the_outbox_redelivers_when_a_connection_comes_in() {
+    let (send, recv) = channel(8);
+    let (reconnect, reconnects) = unbounded_channel();
+    let outbox = Outbox::new(0, vec![0, 1], HashMap::new(), DeadLetters::new(8, Arc::new(AtomicU64::new(0))));
+    let running = tokio::spawn(outbox.run(recv, reconnects));
+
+    send.send((Destination::One(1), Arc::new(ProtocolMsg::Ready(true)))).await.unwrap();
+    tokio::task::yield_now().await;
+    let (link, mut link_recv) = channel(8);
+    reconnect.send((1, link)).unwrap();
+    assert!(ready(&link_recv.recv().await.unwrap()));
+
+    drop(send);
+    running.await.unwrap();
+}
+
+#[tokio::test]
+async fn redelivery_gives_up_after_the_retries() {
+    let dead = DeadLetters::new(8, Arc::new(AtomicU64::new(0))).retrying(2, 0);
+    let mut outbox = Outbox::new(0, vec![0, 1], HashMap::new(), dead);
+    outbox.send(&Destination::One(1), Arc::new(ProtocolMsg::Ready(true))).await;
+    // Every new connection drops before the message is written
+    for attempt in 0..2 {
+        let (link, recv) = channel(8);
+        drop(recv);
+        outbox.reconnect(1, link).await;
+        assert_eq!(outbox.dead.kept(1), 1, "after attempt {}", attempt);
+    }
+    let (link, mut recv) = channel(8);
+    outbox.reconnect(1, link).await;
+    assert!(recv.try_recv().is_err());
+    assert_eq!(outbox.dead.kept(1), 0);
+    assert_eq!(outbox.dead.expired(), 1);
+    assert_eq!(outbox.dead.undeliverable(), 3);
+}
+
+#[tokio::test]
+async fn shares_past_their_phase_are_not_redelivered() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (cx, _net_recv) = common::context_and_sent(&configs[0]);
+    let (shards, acc) = get_acc(&cx, &"proposal", PROPOSE_DOMAIN).unwrap();
+    let share = |e| Arc::new(ProtocolMsg::DeliverPropose(Bytes::from(shards[0].clone()), 0, get_sign(&acc, 0), e));
+
+    let (link1, _recv1) = channel(8);
+    let links = vec![(1, link1)].into_iter().collect();
+    let dead = DeadLetters::new(8, Arc::new(AtomicU64::new(0))).retrying(0, 1);
+    let mut outbox = Outbox::new(0, vec![0, 1, 2], links, dead);
+    outbox.send(&Destination::One(2), share(1)).await;
+    outbox.send(&Destination::One(2), Arc::new(ProtocolMsg::Ready(true))).await;
+    // The node moves on to epoch 2 before peer 2 is back
+    outbox.send(&Destination::One(1), Arc::new(ProtocolMsg::SnapshotRequest(2))).await;
+    outbox.send(&Destination::One(2), share(2)).await;
+    assert_eq!(outbox.dead.kept(2), 3);
+
+    let (link2, mut recv2) = channel(8);
+    outbox.reconnect(2, link2).await;
+    let got: Vec<String> = std::iter::from_fn(|| recv2.try_recv().ok())
+        .map(|m| format!("{}@{}", m.to_string(), m.epoch()))
+        .collect();
+    assert_eq!(got, vec!["Ready@0", "DeliverPropose@2"]);
+    assert_eq!(outbox.dead.expired(), 1);
+}
+
+#[tokio::test]
+async fn a_peer_unreachable_during_deliver_still_reconstructs() {
+    let configs = common::generate_configs(4, 1, 1);
+    let mut cxs: Vec<Context> = configs.iter().map(|config| common::context_and_sent(config).0).collect();
+    let leader = cxs[0].last_leader;
+    let (shards, acc) = get_acc(&cxs[leader as usize], &"proposal", PROPOSE_DOMAIN).unwrap();
+    let pp = cxs[0].accumulator_pub_params_map[&leader].clone();
+    let pk = cxs[0].pub_key_map[&leader].clone();
+    let cx = &mut cxs[3];
+    cx.propose_gatherer
+        .add_share(Bytes::from(shards[3].clone()), 3, &pp, &pk, get_sign(&acc, 3));
+
+    // Replicas 0 to 2 deliver their shards while replica 3 is unreachable
+    let mut receivers = Vec::new();
+    let mut outboxes: Vec<Outbox> = (0..3)
+        .map(|me| {
+            let links = (0..3)
+                .filter(|id| *id != me)
+                .map(|id| {
+                    let (link, recv) = channel(8);
+                    receivers.push(recv);
+                    (id, link)
+                })
+                .collect();
+            let dead = DeadLetters::from_config(&configs[me as usize], Arc::new(AtomicU64::new(0)));
+            Outbox::new(me, vec![0, 1, 2, 3], links, dead)
+        })
+        .collect();
+    for (me, outbox) in outboxes.iter_mut().enumerate() {
+        let n = me as u16;
+        let msg = ProtocolMsg::DeliverPropose(Bytes::from(shards[me].clone()), n, get_sign(&acc, n), 1);
+        outbox.send(&Destination::All, Arc::new(msg)).await;
+        assert_eq!(outbox.dead.kept(3), 1);
+        assert_eq!(outbox.dead.undeliverable(), 1);
+    }
+    assert!(cx.propose_gatherer.reconstruct(4, 1).is_none());
+
+    // It is back within the epoch, and gets what it missed
+    for outbox in outboxes.iter_mut() {
+        let (link, mut recv) = channel(8);
+        outbox.reconnect(3, link).await;
+        match recv.try_recv().unwrap().as_ref() {
+            ProtocolMsg::DeliverPropose(sh, n, sign, 1) => {
+                cx.propose_gatherer.add_share(sh.clone(), *n, &pp, &pk, sign.clone())
+            }
+            other => panic!("unexpected {}", other.to_string()),
+        }
+    }
+    assert_eq!(cx.propose_gatherer.shard_num, 4);
+    let rebuilt: String = bincode::deserialize(&cx.propose_gatherer.reconstruct(4, 1).unwrap()).unwrap();
+    assert_eq!(rebuilt, "proposal");
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use types::{Destination, ProtocolMsg};
+
+#[tokio::test]
+async fn each_destination_reaches_the_right_peers() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (mut cx, mut net_recv) = common::context_and_sent(&configs[1]);
+
+    let cases = vec![
+        (Destination::All, vec![0, 2, 3]),
+        (Destination::One(2), vec![2]),
+        (Destination::Subset(vec![3, 1, 0, 3]), vec![0, 3]),
+    ];
+    for (to, expected) in cases {
+        cx.send(to.clone(), ProtocolMsg::Certificate(Default::default(), 0, 0));
+        let (sent_to, _) = net_recv.try_recv().unwrap();
+        assert_eq!(sent_to, to);
+        assert_eq!(sent_to.peers(cx.myid, &cx.active_replicas), expected);
+    }
+
+    // The old helpers map onto the explicit destinations.
+    cx.broadcast(ProtocolMsg::Certificate(Default::default(), 0, 0));
+    assert_eq!(net_recv.try_recv().unwrap().0, Destination::All);
+    cx.send_to(3, ProtocolMsg::Certificate(Default::default(), 0, 0));
+    assert_eq!(net_recv.try_recv().unwrap().0, Destination::One(3));
+}
+
+#[test]
+fn only_a_broadcast_uses_the_broadcast_id() {
+    // The network layer takes id 4 of 4 to mean everyone
+    assert_eq!(Destination::All.wire_ids(0, 4), vec![4]);
+    // The highest real id is just that peer
+    assert_eq!(Destination::One(3).wire_ids(0, 4), vec![3]);
+    assert_eq!(Destination::Subset(vec![3, 2]).wire_ids(0, 4), vec![2, 3]);
+    // An id past the cluster, as after a reconfiguration, reaches no one
+    // rather than everyone
+    assert!(Destination::One(4).wire_ids(0, 4).is_empty());
+    assert_eq!(Destination::Subset(vec![4, 1]).wire_ids(0, 4), vec![1]);
+}
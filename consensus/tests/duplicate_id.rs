@@ -0,0 +1,25 @@
+mod common;
+
+use config::ParseError;
+
+#[test]
+fn copied_entries_are_caught_by_validate() {
+    let mut config = common::generate_configs(4, 1, 1).remove(0);
+    assert!(config.validate().is_ok());
+
+    // Replica 3 was set up from a copy of replica 1's config
+    let addr = config.net_map[&1].clone();
+    config.net_map.insert(3, addr);
+    match config.validate() {
+        Err(ParseError::DuplicateAddress(1, 3)) => (),
+        r => panic!("expected a duplicate address, got {:?}", r),
+    }
+
+    let mut config = common::generate_configs(4, 1, 1).remove(0);
+    let pk = config.pk_map[&2].clone();
+    config.pk_map.insert(0, pk);
+    match config.validate() {
+        Err(ParseError::DuplicatePk(0, 2)) => (),
+        r => panic!("expected a duplicate public key, got {:?}", r),
+    }
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use types::{ProtocolMsg, Vote};
+use util::io::{encoded_len, to_bytes};
+
+fn vote() -> ProtocolMsg {
+    ProtocolMsg::Vote(
+        Vote {
+            msg: vec![7; 32],
+            origin: 0,
+            auth: vec![1; 64],
+        },
+        1,
+    )
+}
+
+#[tokio::test]
+async fn egress_is_counted_and_the_cap_holds_back_reconstruction() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    let broadcast_len = (encoded_len(&vote()) * 3) as u64;
+    configs[0].epoch_byte_cap = 2 * broadcast_len;
+    let (mut cx, mut net_recv) = common::context_and_sent(&configs[0]);
+    cx.epoch = 1;
+    let share = cx.reconstruct_share();
+
+    // Under the cap reconstruction shares go out like anything else
+    cx.broadcast(ProtocolMsg::Reconstruct(share.clone(), 1));
+    cx.broadcast(vote());
+    cx.broadcast(vote());
+    let mut wire = 0;
+    for _ in 0..3 {
+        let (to, msg) = net_recv.recv().await.unwrap();
+        wire += (to_bytes(msg.as_ref()).unwrap().len() * to.peers(0, &cx.active_replicas).len()) as u64;
+    }
+    assert_eq!(cx.bytes_sent, wire);
+    assert!(cx.over_byte_cap());
+
+    // Over it they wait behind later traffic
+    cx.broadcast(ProtocolMsg::Reconstruct(share, 1));
+    cx.send_to(2, vote());
+    let (_, msg) = net_recv.recv().await.unwrap();
+    assert_eq!(msg.to_string(), "Vote");
+    assert!(net_recv.try_recv().is_err());
+    assert!(cx.has_outbound());
+    let (_, msg) = cx.next_outbound().unwrap();
+    assert_eq!(msg.to_string(), "Reconstruct");
+    let reconstruct_len = (encoded_len(msg.as_ref()) * 3) as u64;
+    assert_eq!(cx.bytes_sent, wire + broadcast_len / 3 + reconstruct_len);
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use consensus::bft::node::entropy::{mix, parse_line, ENTROPY_LEAD};
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use crypto::hash::Hash;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use types::Height;
+
+// The first epoch whose beacon a value can be mixed into
+const MIXED: Height = 1 + ENTROPY_LEAD;
+
+/// The beacons every replica of a freshly spawned cluster produces in
+/// epochs 1 to `MIXED`
+async fn beacons(cluster: &common::Cluster) -> Vec<Vec<Hash>> {
+    let mut streams: Vec<_> = cluster.events.iter().map(subscribe).collect();
+    let mut all = Vec::with_capacity(streams.len());
+    for stream in streams.iter_mut() {
+        let run = async {
+            let mut beacons = Vec::new();
+            while let Some(Ok(ev)) = stream.next().await {
+                if let NodeEvent::BeaconProduced(e, beacon) = ev {
+                    if e >= 1 {
+                        beacons.push(beacon);
+                    }
+                    if e == MIXED {
+                        break;
+                    }
+                }
+            }
+            beacons
+        };
+        all.push(
+            tokio::time::timeout(Duration::from_secs(60), run)
+                .await
+                .expect("did not get through the epochs"),
+        );
+    }
+    all
+}
+
+fn cluster() -> common::Cluster {
+    common::spawn_cluster(common::generate_configs(4, 1, MIXED as usize + 1))
+}
+
+#[tokio::test]
+async fn same_external_input_gives_same_beacon_everywhere() {
+    let plain = beacons(&cluster()).await.remove(0);
+    assert!(plain.iter().all(|b| *b != [0; 32]));
+
+    let external = [7; 32];
+    let cluster = cluster();
+    for entropy in cluster.entropy.iter() {
+        // Too late for epoch 1 to be committed ahead of its beacon
+        entropy.send((1, external)).unwrap();
+        entropy.send((MIXED, external)).unwrap();
+    }
+    for beacons in beacons(&cluster).await {
+        assert_eq!(beacons[0], plain[0]);
+        assert_eq!(beacons[MIXED as usize - 1], mix(&plain[MIXED as usize - 1], &external));
+    }
+}
+
+#[tokio::test]
+async fn the_committed_external_value_is_mixed_in_whatever_each_source_says() {
+    let plain = beacons(&cluster()).await.remove(0);
+    let plain = plain[MIXED as usize - 1];
+
+    // Every replica's source hands it another value
+    let cluster = cluster();
+    for (i, entropy) in cluster.entropy.iter().enumerate() {
+        entropy.send((MIXED, [i as u8 + 1; 32])).unwrap();
+    }
+    let mixed: Vec<Hash> = beacons(&cluster).await.iter().map(|b| b[MIXED as usize - 1]).collect();
+    assert!(mixed.iter().all(|b| *b == mixed[0]));
+    // The one the leader of epoch 1 proposed
+    assert!((1..=4).any(|i| mixed[0] == mix(&plain, &[i; 32])), "mixed in none of the values");
+}
+
+#[test]
+fn source_lines_are_an_epoch_and_a_hex_value() {
+    let hex = "0a".repeat(32);
+    assert_eq!(parse_line(&format!("3 {}", hex)), Some((3, [10; 32])));
+    assert_eq!(parse_line(&format!("  3\t{}  ", hex.to_uppercase())), Some((3, [10; 32])));
+    assert_eq!(parse_line(&format!("3 {}", &hex[1..])), None);
+    assert_eq!(parse_line(&format!("3 {} extra", hex)), None);
+    assert_eq!(parse_line(&format!("three {}", hex)), None);
+    assert_eq!(parse_line(&format!("3 {}", "zz".repeat(32))), None);
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+#[tokio::test]
+async fn different_beacon_is_detected() {
+    let mut configs = common::generate_configs(4, 1, 2);
+    // Replica 0 puts the beacon through a delay function the others do not
+    // run, so it ends every epoch with a beacon that differs from theirs
+    configs[0].vdf_iterations = 16;
+    for config in configs.iter_mut() {
+        config.epoch_done = true;
+    }
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+
+    let detect = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            if let NodeEvent::BeaconDisagreement(e, origin, beacon) = ev {
+                return (e, origin, beacon);
+            }
+        }
+        panic!("event channel closed");
+    };
+    let (e, _, beacon) = tokio::time::timeout(Duration::from_secs(30), detect)
+        .await
+        .expect("disagreement not detected");
+    assert_eq!(e, 1);
+    assert_ne!(beacon, [0; 32]);
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use consensus::bft::node::clock::RealClock;
+use consensus::bft::node::context::NO_BEACON;
+use consensus::bft::node::entropy::entropy_channel;
+use consensus::bft::node::events::{event_channel, subscribe, NodeEvent};
+use consensus::bft::node::intake::tx_channel;
+use consensus::bft::node::reactor;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{channel, unbounded_channel};
+use tokio_stream::StreamExt;
+use types::{ProtocolMsg, Vote};
+
+const SKEW: u64 = 3;
+
+#[test]
+fn one_faulty_replica_cannot_force_a_catch_up() {
+    let mut config = common::generate_configs(4, 1, 1).remove(0);
+    config.max_epoch_skew = SKEW;
+    let (mut cx, _net_recv) = common::context_and_sent(&config);
+    cx.epoch = 1;
+    cx.received_vote.entry(1).or_default().push(Vote {
+        msg: Vec::new(),
+        origin: 2,
+        auth: Vec::new(),
+    });
+
+    // Replica 1 alone claims to be far ahead
+    assert!(!cx.observe_epoch(1, 1_000));
+    assert!(!cx.observe_epoch(3, 1 + SKEW));
+    assert!(cx.quarantine.is_none());
+    // Replica 2 backs it up, though not as far
+    assert!(cx.observe_epoch(2, 50));
+    assert_eq!(cx.quarantine, Some(50));
+    // Nothing changes until we skip ahead
+    assert_eq!(cx.epoch, 1);
+    assert_eq!(cx.votes_for(1).len(), 1);
+
+    let leader = cx.leader_for_epoch(50);
+    assert_eq!(cx.fast_forward(), Some(50));
+    assert_eq!(cx.epoch, 50);
+    assert_eq!(Some(cx.current_leader()), leader);
+    assert!(cx.received_vote.is_empty());
+    assert!(cx.quarantine.is_none());
+    assert!(!cx.observe_epoch(1, 50 + SKEW));
+}
+
+#[tokio::test]
+async fn far_future_messages_make_the_node_catch_up() {
+    let mut config = common::generate_configs(4, 1, 4).remove(0);
+    config.max_epoch_skew = SKEW;
+    let (net_send, mut net_send_recv) = channel(util::CHANNEL_SIZE);
+    let (net_recv_send, net_recv) = unbounded_channel();
+    let (cli_send, _cli_send_recv) = unbounded_channel();
+    let (_cli_recv_send, cli_recv) = tx_channel(16);
+    let events = event_channel();
+    let mut stream = subscribe(&events);
+    tokio::spawn(async move { while net_send_recv.recv().await.is_some() {} });
+    tokio::spawn(async move {
+        reactor(
+            &config,
+            false,
+            net_send,
+            net_recv,
+            cli_send,
+            cli_recv,
+            events,
+            Arc::new(RealClock),
+            entropy_channel().1,
+            None,
+        )
+        .await
+    });
+
+    for origin in 1..3 {
+        net_recv_send.send((origin, ProtocolMsg::EpochDone(100, [1; 32]))).unwrap();
+    }
+    let follow = async {
+        let mut seen = Vec::new();
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::Quarantined(_, 100) | NodeEvent::CaughtUp(100) => seen.push(ev),
+                NodeEvent::ViewChange(101) => return seen,
+                // Nothing of the epochs in between is run
+                NodeEvent::ViewChange(e) if e > 1 => panic!("ran epoch {}", e),
+                NodeEvent::BeaconDisagreement(_, _, _) => panic!("took in a report while catching up"),
+                _ => (),
+            }
+        }
+        panic!("event channel closed");
+    };
+    let seen = tokio::time::timeout(Duration::from_secs(10), follow)
+        .await
+        .expect("the node did not take part again after catching up");
+    match seen.as_slice() {
+        [NodeEvent::Quarantined(_, 100), NodeEvent::CaughtUp(100)] => (),
+        other => panic!("unexpected events {:?}", other),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn a_replica_that_fell_behind_takes_part_in_the_beacon_and_the_chain_again() {
+    const LATE: usize = 3;
+    let mut configs = common::generate_configs(4, 1, 24);
+    for config in configs.iter_mut() {
+        config.max_epoch_skew = SKEW;
+    }
+    let epoch = Duration::from_millis(configs[0].phase_durations().epoch);
+    // It only starts once the others are well past where it could follow
+    let delays = (0..4)
+        .map(|i| if i == LATE { epoch * (SKEW as u32 + 3) } else { Duration::from_millis(0) })
+        .collect();
+    let cluster = common::spawn_cluster_staggered(configs, delays);
+
+    let mut stream = subscribe(&cluster.events[0])
+        .map(|ev| (0, ev))
+        .merge(subscribe(&cluster.events[LATE]).map(|ev| (LATE, ev)));
+    let mut caught_up = None;
+    let mut beacons: [BTreeMap<u64, [u8; 32]>; 2] = Default::default();
+    let mut blocks: [BTreeMap<u64, [u8; 32]>; 2] = Default::default();
+    let follow = async {
+        while let Some((i, Ok(ev))) = stream.next().await {
+            let i = if i == LATE { 1 } else { 0 };
+            match ev {
+                NodeEvent::CaughtUp(e) if i == 1 => caught_up = Some(e),
+                NodeEvent::BeaconProduced(e, beacon) if beacon != NO_BEACON => {
+                    beacons[i].insert(e, beacon);
+                }
+                NodeEvent::BlockCommitted(h, hash) if h > 0 => {
+                    blocks[i].insert(h, hash);
+                }
+                NodeEvent::ViewChange(e) if i == 0 && caught_up.map_or(false, |c| e > c + 6) => return,
+                _ => (),
+            }
+        }
+        panic!("event channel closed");
+    };
+    tokio::time::timeout(epoch * 40, follow)
+        .await
+        .expect("the late replica never caught up");
+    let caught_up = caught_up.unwrap();
+
+    // The shares it used after skipping are those of the epochs it is in
+    let after: Vec<_> = beacons[1].range(caught_up + 1..).collect();
+    assert!(!after.is_empty(), "no beacon after catching up to epoch {}", caught_up);
+    for (e, beacon) in after {
+        assert_eq!(beacons[0].get(e), Some(beacon), "epoch {}", e);
+    }
+    // And it commits the blocks the others do, on top of the synced tip
+    assert!(blocks[1].len() >= 2, "only committed {:?}", blocks[1].keys());
+    for (h, hash) in blocks[1].iter() {
+        assert_eq!(blocks[0].get(h), Some(hash), "height {}", h);
+    }
+}
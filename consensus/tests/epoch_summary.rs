@@ -0,0 +1,79 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, EpochSummary, NodeEvent};
+use crypto::hash::HASH_SIZE;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use types::Height;
+
+const EPOCHS: Height = 3;
+
+#[tokio::test]
+async fn summaries_match_what_happened_in_each_epoch() {
+    let configs = common::generate_configs(4, 1, 8);
+    let epoch_ms = configs[0].phase_durations().epoch;
+    let quorum = configs[0].num_nodes - configs[0].num_faults;
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+    let mut seen = Vec::new();
+    let collect = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            let done = matches!(&ev, NodeEvent::EpochSummary(s) if s.epoch == EPOCHS);
+            seen.push(ev);
+            if done {
+                break;
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(30), collect)
+        .await
+        .expect("did not finish the epochs");
+
+    let summaries: Vec<&EpochSummary> = seen
+        .iter()
+        .filter_map(|ev| match ev {
+            NodeEvent::EpochSummary(s) => Some(s),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        summaries.iter().map(|s| s.epoch).collect::<Vec<_>>(),
+        (1..=EPOCHS).collect::<Vec<_>>()
+    );
+
+    for summary in summaries {
+        let e = summary.epoch;
+        // The events from the start of the epoch up to its summary
+        let start = seen
+            .iter()
+            .position(|ev| matches!(ev, NodeEvent::ViewChange(v) if *v == e))
+            .unwrap();
+        let end = seen
+            .iter()
+            .position(|ev| matches!(ev, NodeEvent::EpochSummary(s) if s.epoch == e))
+            .unwrap();
+        let mut leader = None;
+        let mut committed = None;
+        let mut votes = 0;
+        let mut beacon = None;
+        for ev in &seen[start..end] {
+            match ev {
+                NodeEvent::LeaderChanged(_, l) => leader = Some(*l),
+                NodeEvent::BlockCommitted(_, hash) => committed = Some(*hash),
+                NodeEvent::VoteCertified(_, cert) => votes = cert.votes.len(),
+                NodeEvent::BeaconProduced(_, hash) => beacon = Some(*hash),
+                _ => (),
+            }
+        }
+        assert_eq!(Some(summary.leader), leader, "epoch {}", e);
+        assert!(committed.is_some(), "nothing committed in epoch {}", e);
+        assert_eq!(summary.block_hash, committed, "epoch {}", e);
+        let latency = summary.commit_latency_ms.expect("no commit latency");
+        assert!(latency < epoch_ms, "epoch {} committed after {} ms", e, latency);
+        assert_eq!(summary.votes_collected, votes, "epoch {}", e);
+        assert!(votes > 0, "no vote certificate in epoch {}", e);
+        assert_eq!(summary.reconstruct_participants, quorum, "epoch {}", e);
+        assert!(summary.beacon_produced, "epoch {}", e);
+        assert_ne!(beacon, Some([0; HASH_SIZE]), "epoch {}", e);
+    }
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+#[tokio::test]
+async fn events_over_two_epochs() {
+    let cluster = common::spawn_cluster(common::generate_configs(4, 1, 8));
+    let mut stream = subscribe(&cluster.events[0]);
+    let mut seen = Vec::new();
+    let collect = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            let done = matches!(ev, NodeEvent::LeaderChanged(2, _));
+            seen.push(ev);
+            if done {
+                break;
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(30), collect)
+        .await
+        .expect("did not finish two epochs");
+
+    let pos = |f: &dyn Fn(&NodeEvent) -> bool| seen.iter().position(|e| f(e)).unwrap();
+    let first_view = pos(&|e| matches!(e, NodeEvent::ViewChange(1)));
+    let first_leader = pos(&|e| matches!(e, NodeEvent::LeaderChanged(1, 1)));
+    let proposed = pos(&|e| matches!(e, NodeEvent::BlockProposed(1, _)));
+    let committed = pos(&|e| matches!(e, NodeEvent::BlockCommitted(1, _)));
+    let beacon = pos(&|e| matches!(e, NodeEvent::BeaconProduced(1, _)));
+    let second_view = pos(&|e| matches!(e, NodeEvent::ViewChange(2)));
+    let second_leader = pos(&|e| matches!(e, NodeEvent::LeaderChanged(2, 2)));
+    assert!(first_view < first_leader);
+    assert!(first_leader < proposed);
+    assert!(proposed < committed);
+    assert!(committed < beacon);
+    assert!(beacon < second_view);
+    assert!(second_view < second_leader);
+
+    // A late subscriber only observes what happens after it subscribed.
+    let mut late = subscribe(&cluster.events[0]);
+    loop {
+        match late.next().await {
+            Some(Ok(NodeEvent::ViewChange(e))) => {
+                assert!(e >= 3);
+                break;
+            }
+            Some(Ok(NodeEvent::LeaderChanged(e, _))) => assert!(e >= 3),
+            Some(_) => (),
+            None => panic!("event channel closed"),
+        }
+    }
+}
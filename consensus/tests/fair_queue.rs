@@ -0,0 +1,91 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use consensus::bft::node::fair::{FairQueue, MAX_QUEUED_PER_ORIGIN};
+use std::time::Duration;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::time::Instant;
+use tokio_stream::StreamExt;
+use types::{ProtocolMsg, Replica, Vote};
+
+fn vote(origin: Replica, epoch: u64) -> ProtocolMsg {
+    ProtocolMsg::Vote(
+        Vote {
+            msg: epoch.to_be_bytes().to_vec(),
+            origin,
+            auth: Vec::new(),
+        },
+        epoch,
+    )
+}
+
+#[tokio::test]
+async fn every_origin_takes_its_turn() {
+    let (send, mut recv) = unbounded_channel();
+    for i in 0..1000 {
+        send.send((3, vote(3, i))).unwrap();
+    }
+    send.send((1, vote(1, 7))).unwrap();
+    send.send((2, vote(2, 7))).unwrap();
+    send.send((1, vote(1, 8))).unwrap();
+
+    let mut queue = FairQueue::new();
+    let mut order = Vec::new();
+    for _ in 0..6 {
+        let (origin, msg) = queue.next(&mut recv).await.unwrap();
+        order.push((origin, msg.epoch()));
+    }
+    assert_eq!(order, vec![(3, 0), (1, 7), (2, 7), (3, 1), (1, 8), (3, 2)]);
+    assert_eq!(queue.len(), 997);
+
+    // What was waiting is still handed out once the channel is closed
+    drop(send);
+    for _ in 0..997 {
+        assert_eq!(queue.next(&mut recv).await.unwrap().0, 3);
+    }
+    assert!(queue.next(&mut recv).await.is_none());
+}
+
+#[test]
+fn a_flooding_origin_is_capped() {
+    let mut queue = FairQueue::new();
+    for i in 0..MAX_QUEUED_PER_ORIGIN as u64 + 10 {
+        queue.push(3, vote(3, i));
+    }
+    queue.push(1, vote(1, 0));
+    assert_eq!(queue.len(), MAX_QUEUED_PER_ORIGIN + 1);
+    assert_eq!(queue.dropped(), 10);
+    assert_eq!(queue.pop().unwrap().0, 3);
+    assert_eq!(queue.pop().unwrap().0, 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn a_flooding_peer_does_not_hold_up_the_others() {
+    let configs = common::generate_configs(4, 1, 6);
+    let epoch = Duration::from_millis(configs[0].phase_durations().epoch);
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+    let inbox = cluster.inbound[0].clone();
+
+    let certified = async {
+        let mut started = None;
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::ViewChange(2) => {
+                    // Replica 3 buries replica 0 in stale votes
+                    for _ in 0..MAX_QUEUED_PER_ORIGIN {
+                        let _ = inbox.send((3, vote(3, 0)));
+                    }
+                    started = Some(Instant::now());
+                }
+                NodeEvent::VoteCertified(2, _) => return started.unwrap().elapsed(),
+                _ => (),
+            }
+        }
+        panic!("event channel closed");
+    };
+    let took = tokio::time::timeout(Duration::from_secs(30), certified)
+        .await
+        .expect("epoch 2 was never certified");
+    assert!(took < epoch, "certifying epoch 2 took {:?} under the flood", took);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use types::Replica;
+
+const N: usize = 7;
+const F: usize = 2;
+
+/// Runs epoch 1 with the replicas in `silent` cut off from everyone else.
+/// Replica 1 leads epoch 1 and replica 2 hands out the commitments, so both
+/// stay connected.
+async fn epoch_without(silent: &[Replica]) -> Vec<common::EpochOutcome> {
+    let cluster = common::spawn_cluster(common::generate_configs(N, F, 2));
+    let connected: Vec<Replica> = (0..N as Replica).filter(|i| !silent.contains(i)).collect();
+    cluster.partition.split(&[&connected], N);
+    common::first_epoch(&cluster).await
+}
+
+#[tokio::test]
+async fn num_faults_silent_replicas_are_tolerated() {
+    let reference = common::first_epoch(&common::spawn_cluster(common::generate_configs(N, F, 2))).await;
+    let (_, block) = reference[0].committed.expect("no block committed");
+    let beacon = reference[0].beacon.expect("no beacon produced");
+
+    let silent = [5, 6];
+    let outcomes = epoch_without(&silent).await;
+    for (i, outcome) in outcomes.iter().enumerate() {
+        if silent.contains(&(i as Replica)) {
+            assert!(outcome.committed.is_none());
+            continue;
+        }
+        // Proposal shards, vote certificate, commitment shards and beacon
+        // shares all still reach the threshold, with the same results.
+        assert_eq!(outcome.committed, Some((1, block)));
+        assert_eq!(outcome.certificate.as_ref().unwrap().votes.len(), F + 1);
+        assert_eq!(outcome.beacon, Some(beacon));
+    }
+}
+
+#[tokio::test]
+async fn one_more_silent_replica_stalls_without_a_wrong_value() {
+    let outcomes = epoch_without(&[4, 5, 6]).await;
+    for outcome in outcomes.iter() {
+        assert!(outcome.committed.is_none());
+        assert!(outcome.certificate.is_none());
+        assert_eq!(outcome.beacon, Some([0; 32]));
+    }
+}
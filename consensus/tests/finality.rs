@@ -0,0 +1,29 @@
+mod common;
+
+use consensus::bft::node::events::NodeEvent;
+use tokio_stream::StreamExt;
+
+#[tokio::test]
+async fn finalized_trails_commits_by_depth() {
+    const K: u64 = 2;
+    let mut configs = common::generate_configs(4, 1, 1);
+    configs[0].finality_depth = K;
+    let (mut cx, _net_send_recv) = common::context_and_sent(&configs[0]);
+    let mut stream = cx.subscribe();
+    for height in 1..=5 {
+        cx.mark_committed(height);
+        assert_eq!(cx.finalized_height, height.saturating_sub(K));
+    }
+    // Re-committing an old height changes nothing.
+    cx.mark_committed(4);
+    assert_eq!(cx.finalized_height, 3);
+    drop(cx);
+
+    let mut finalized = Vec::new();
+    while let Some(Ok(ev)) = stream.next().await {
+        if let NodeEvent::Finalized(h) = ev {
+            finalized.push(h);
+        }
+    }
+    assert_eq!(finalized, vec![1, 2, 3]);
+}
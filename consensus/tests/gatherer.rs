@@ -0,0 +1,72 @@
+mod common;
+
+use bytes::Bytes;
+use consensus::bft::node::accumulator::{get_acc, get_sign, ShareGatherer};
+use consensus::bft::node::admin::{self, Query};
+use consensus::bft::node::offload::Gatherer;
+use crypto::hash::COMMIT_DOMAIN;
+use futures::FutureExt;
+
+#[tokio::test]
+async fn ready_fires_once_at_the_threshold() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (cx, _net_recv) = common::context_and_sent(&configs[0]);
+    let (shards, acc) = get_acc(&cx, &"commitments", COMMIT_DOMAIN).unwrap();
+    let pp = cx.accumulator_pub_params_map.get(&0).unwrap();
+    let pk = cx.pub_key_map.get(&0).unwrap();
+
+    let mut gatherer = ShareGatherer::new(4, 3, COMMIT_DOMAIN);
+    let add = |g: &mut ShareGatherer, i: u16| {
+        g.add_share(Bytes::from(shards[i as usize].clone()), i, pp, pk, get_sign(&acc, i))
+    };
+
+    add(&mut gatherer, 0);
+    add(&mut gatherer, 1);
+    assert!(gatherer.ready().now_or_never().is_none());
+    add(&mut gatherer, 2);
+    assert!(gatherer.ready().now_or_never().is_some());
+    // Neither more shards nor repeated ones fire it again
+    add(&mut gatherer, 3);
+    add(&mut gatherer, 2);
+    assert!(gatherer.ready().now_or_never().is_none());
+
+    // A cleared gatherer starts a new round
+    gatherer.clear();
+    assert!(gatherer.ready().now_or_never().is_none());
+    let ready = gatherer.ready();
+    for i in 1..4 {
+        add(&mut gatherer, i);
+    }
+    ready.await;
+    assert!(gatherer.ready().now_or_never().is_none());
+}
+
+#[test]
+fn progress_lists_the_contributors() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (mut cx, _net_recv) = common::context_and_sent(&configs[0]);
+    let (shards, acc) = get_acc(&cx, &"commitments", COMMIT_DOMAIN).unwrap();
+    let pp = cx.accumulator_pub_params_map.get(&0).unwrap().clone();
+    let pk = cx.pub_key_map.get(&0).unwrap().clone();
+
+    for &i in [3u16, 0, 2, 0].iter() {
+        let sh = Bytes::from(shards[i as usize].clone());
+        cx.commit_gatherer.add_share(sh, i, &pp, &pk, get_sign(&acc, i));
+    }
+    let status = cx.commit_gatherer.progress();
+    assert_eq!(status.received, 3);
+    assert_eq!(status.threshold, cx.commit_gatherer.threshold as usize);
+    assert_eq!(status.contributors, vec![0, 2, 3]);
+    assert_eq!(cx.gatherer_progress(Gatherer::Commit), status);
+    assert!(cx.gatherer_progress(Gatherer::Propose).contributors.is_empty());
+
+    let answer: serde_json::Value =
+        serde_json::from_str(&admin::answer(&cx, "Commit", Query::Gatherers)).unwrap();
+    assert_eq!(answer["commit"]["contributors"], serde_json::json!([0, 2, 3]));
+    assert_eq!(answer["commit"]["missing"], serde_json::json!([1]));
+    assert_eq!(answer["propose"]["received"], 0);
+
+    cx.commit_gatherer.clear();
+    assert_eq!(cx.commit_gatherer.progress().received, 0);
+    assert!(cx.commit_gatherer.progress().contributors.is_empty());
+}
@@ -0,0 +1,27 @@
+mod common;
+
+async fn run_epoch(gossip_fanout: usize) -> (Vec<usize>, Vec<common::EpochOutcome>) {
+    let mut configs = common::generate_configs(32, 10, 1);
+    for config in configs.iter_mut() {
+        config.gossip_fanout = gossip_fanout;
+    }
+    let cluster = common::spawn_cluster(configs);
+    let outcomes = common::first_epoch(&cluster).await;
+    let frames = (0..outcomes.len()).map(|i| cluster.frames_sent(i)).collect();
+    (frames, outcomes)
+}
+
+#[tokio::test]
+async fn gossip_sends_less_and_still_reconstructs() {
+    let (direct, _) = run_epoch(0).await;
+    let (gossip, outcomes) = run_epoch(2).await;
+
+    let (_, expected_hash) = outcomes[0].committed.expect("no block committed");
+    for outcome in outcomes.iter() {
+        assert_eq!(outcome.committed, Some((1, expected_hash)));
+        assert_ne!(outcome.beacon.expect("no beacon produced"), [0; 32]);
+    }
+    for (i, (d, g)) in direct.iter().zip(gossip.iter()).enumerate() {
+        assert!(g < d, "replica {} sent {} frames with gossip, {} without", i, g, d);
+    }
+}
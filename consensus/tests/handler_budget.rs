@@ -0,0 +1,78 @@
+use consensus::bft::node::budget::HandlerBudget;
+use crypto::hash::empty_hash;
+use std::time::{Duration, Instant};
+use types::{ProtocolMsg, Replica};
+
+// Stands in for the reactor: a `ReconstructRequest` is an artificially slow
+// handler, everything else is instant
+fn handle(msg: &ProtocolMsg) {
+    if let ProtocolMsg::ReconstructRequest(_, _) = msg {
+        std::thread::sleep(Duration::from_millis(30));
+    }
+}
+
+fn run(budget: &mut HandlerBudget, handled: &mut Vec<&'static str>, origin: Replica, msg: ProtocolMsg) {
+    let started = Instant::now();
+    handle(&msg);
+    budget.record(msg.to_string(), origin, started.elapsed());
+    handled.push(msg.to_string());
+}
+
+#[test]
+fn slow_messages_do_not_hold_up_the_rest() {
+    let mut budget = HandlerBudget::new(Some(Duration::from_millis(10)));
+    let mut handled = Vec::new();
+    let incoming = (0..3).flat_map(|i| {
+        vec![
+            (1, ProtocolMsg::ReconstructRequest(1, 1)),
+            (i, ProtocolMsg::EpochDone(1, empty_hash())),
+        ]
+    });
+    for (origin, msg) in incoming {
+        if let Some((origin, msg)) = budget.admit(origin, msg) {
+            run(&mut budget, &mut handled, origin, msg);
+        }
+    }
+    assert_eq!(budget.deferred(), 2);
+    while let Some((origin, msg)) = budget.next_deferred(1) {
+        run(&mut budget, &mut handled, origin, msg);
+    }
+
+    // Only the first slow message went ahead of the others
+    assert_eq!(
+        handled,
+        vec![
+            "ReconstructRequest",
+            "EpochDone",
+            "EpochDone",
+            "EpochDone",
+            "ReconstructRequest",
+            "ReconstructRequest",
+        ]
+    );
+}
+
+#[test]
+fn deferred_messages_of_past_epochs_are_dropped() {
+    let mut budget = HandlerBudget::new(Some(Duration::from_millis(10)));
+    budget.record("EpochDone", 1, Duration::from_millis(20));
+    for e in 1..4 {
+        assert!(budget.admit(1, ProtocolMsg::EpochDone(e, empty_hash())).is_none());
+    }
+    match budget.next_deferred(3) {
+        Some((1, ProtocolMsg::EpochDone(3, _))) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(!budget.has_deferred());
+
+    // Coming in under the budget again lets the kind through right away
+    budget.record("EpochDone", 1, Duration::from_millis(1));
+    assert!(budget.admit(1, ProtocolMsg::EpochDone(4, empty_hash())).is_some());
+}
+
+#[test]
+fn no_budget_defers_nothing() {
+    let mut budget = HandlerBudget::new(None);
+    budget.record("ReconstructRequest", 1, Duration::from_secs(1));
+    assert!(budget.admit(1, ProtocolMsg::ReconstructRequest(1, 1)).is_some());
+}
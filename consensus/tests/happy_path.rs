@@ -0,0 +1,39 @@
+mod common;
+
+use crypto::hash::{with_domain, VOTE_DOMAIN};
+use std::collections::HashSet;
+
+#[tokio::test]
+async fn four_honest_nodes_finish_an_epoch() {
+    let configs = common::generate_configs(4, 1, 4);
+    let num_faults = configs[0].num_faults;
+    // Only used to check signatures against the configured keys.
+    let (checker, _net_recv) = common::context_and_sent(&configs[0]);
+
+    let cluster = common::spawn_cluster(configs);
+    let outcomes = common::first_epoch(&cluster).await;
+
+    let (_, expected_hash) = outcomes[0].committed.expect("no block committed");
+    let expected_beacon = outcomes[0].beacon.expect("no beacon produced");
+    assert_ne!(expected_beacon, [0; 32]);
+    for outcome in outcomes {
+        let (height, hash) = outcome.committed.expect("no block committed");
+        assert_eq!(height, 1);
+        assert_eq!(hash, expected_hash);
+
+        let certificate = outcome.certificate.expect("no vote certificate");
+        assert_eq!(certificate.votes.len(), num_faults + 1);
+        let mut origins = HashSet::new();
+        for vote in certificate.votes.iter() {
+            assert_eq!(vote.msg, hash.to_vec());
+            assert!(checker.verify(
+                vote.origin,
+                &with_domain(VOTE_DOMAIN, &vote.msg),
+                &vote.auth
+            ));
+            assert!(origins.insert(vote.origin));
+        }
+
+        assert_eq!(outcome.beacon, Some(expected_beacon));
+    }
+}
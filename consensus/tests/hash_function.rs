@@ -0,0 +1,37 @@
+// The hash function is chosen once per process, so this file holds a single
+// test.
+
+mod common;
+
+use crypto::hash::{self, empty_hash, HashAlgorithm, HashFunction};
+use types::genesis_block;
+
+#[tokio::test]
+async fn a_cluster_agreeing_on_sha3_commits_and_produces_beacons() {
+    // As the node does at startup, before anything is hashed
+    assert_eq!(hash::select(HashAlgorithm::SHA3_256.function()), Ok(()));
+    let mut configs = common::generate_configs(4, 1, 2);
+    for config in configs.iter_mut() {
+        config.hash_alg = HashAlgorithm::SHA3_256;
+    }
+    let cluster = common::spawn_cluster(configs);
+    let outcomes = common::first_epoch(&cluster).await;
+
+    let committed = outcomes[0].committed.expect("replica 0 committed nothing");
+    let beacon = outcomes[0].beacon.expect("replica 0 produced no beacon");
+    assert_ne!(beacon, [0; hash::HASH_SIZE]);
+    for (i, outcome) in outcomes.iter().enumerate() {
+        assert_eq!(outcome.committed, Some(committed), "replica {}", i);
+        assert_eq!(outcome.beacon, Some(beacon), "replica {}", i);
+    }
+
+    // Genesis and empty bodies are hashed with the selected function too
+    let sha3 = HashAlgorithm::SHA3_256.digest(&[]);
+    assert_eq!(empty_hash(), sha3);
+    assert_eq!(genesis_block().hash, sha3);
+    assert_ne!(sha3, HashAlgorithm::SHA256.digest(&[]));
+
+    // Once in use, the function cannot be changed
+    assert_eq!(hash::select(HashAlgorithm::SHA3_256.function()), Ok(()));
+    assert_eq!(hash::select(HashAlgorithm::SHA256.function()), Err("SHA3_256"));
+}
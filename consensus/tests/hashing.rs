@@ -0,0 +1,70 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use consensus::bft::node::hashing::HashQueue;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio_stream::StreamExt;
+use types::{Block, Vote};
+
+#[tokio::test]
+async fn messages_keep_flowing_while_a_large_block_is_hashed() {
+    let mut block = Block::new();
+    block.body.data.acks = (0..200_000)
+        .map(|i| Vote {
+            msg: vec![i as u8; 32],
+            origin: 0,
+            auth: vec![1; 64],
+        })
+        .collect();
+    let mut expected = block.clone();
+    expected.update_hash();
+
+    let (send, mut recv) = unbounded_channel();
+    for i in 0..1000 {
+        send.send(i).unwrap();
+    }
+    let mut hashes = HashQueue::new(64 * 1024);
+    assert!(hashes.hash(block, ()).is_none());
+    let mut received = 0;
+    let hashed = loop {
+        tokio::select! {
+            (hashed, ()) = hashes.next() => break hashed,
+            Some(_) = recv.recv() => received += 1,
+        }
+    };
+    assert!(received > 0, "no message was taken while hashing");
+    assert_eq!(hashed.hash, expected.hash);
+    assert!(hashes.is_empty());
+}
+
+#[tokio::test]
+async fn blocks_hashed_off_the_reactor_still_commit() {
+    let mut configs = common::generate_configs(4, 1, 6);
+    for config in configs.iter_mut() {
+        config.inline_hash_limit = Some(0);
+    }
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+    let mut epoch = 0;
+    let mut heights = HashMap::new();
+    let collect = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::ViewChange(5) => break,
+                NodeEvent::ViewChange(e) => epoch = e,
+                NodeEvent::BlockCommitted(h, _) => {
+                    heights.insert(epoch, h);
+                }
+                _ => (),
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(60), collect)
+        .await
+        .expect("did not finish four epochs");
+    for e in 1..5 {
+        assert_eq!(heights.get(&e), Some(&e), "epoch {}", e);
+    }
+}
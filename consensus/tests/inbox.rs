@@ -0,0 +1,42 @@
+mod common;
+
+use consensus::bft::node::context::MAX_EPOCHS_AHEAD;
+use types::{ProtocolMsg, Vote};
+
+fn vote_at(epoch: u64) -> ProtocolMsg {
+    ProtocolMsg::Vote(
+        Vote {
+            msg: epoch.to_be_bytes().to_vec(),
+            origin: 1,
+            auth: Vec::new(),
+        },
+        epoch,
+    )
+}
+
+#[tokio::test]
+async fn early_messages_wait_for_their_epoch() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (mut cx, _net_send_recv) = common::context_and_sent(&configs[0]);
+    cx.epoch = 3;
+
+    assert!(cx.hold_if_early(1, vote_at(3)).is_some());
+    assert!(cx.hold_if_early(1, vote_at(2)).is_some());
+    assert!(cx.hold_if_early(1, vote_at(4)).is_none());
+    assert!(cx.hold_if_early(2, vote_at(5)).is_none());
+    // Too far ahead to be worth keeping
+    assert!(cx.hold_if_early(1, vote_at(4 + MAX_EPOCHS_AHEAD)).is_none());
+    assert!(cx.take_ready().is_empty());
+
+    cx.epoch = 4;
+    let ready = cx.take_ready();
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].0, 1);
+    assert_eq!(ready[0].1.epoch(), 4);
+
+    cx.epoch = 10;
+    let ready = cx.take_ready();
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].0, 2);
+    assert!(cx.future_msgs.is_empty());
+}
@@ -0,0 +1,65 @@
+// The checks are debug assertions, so the violations only panic in debug
+// builds.
+#![cfg(debug_assertions)]
+
+mod common;
+
+use consensus::bft::node::invariants;
+use types::{Propose, Vote};
+
+#[test]
+fn a_fresh_context_holds() {
+    let cx = common::context(&common::generate_configs(4, 1, 1)[0]);
+    invariants::check(&cx);
+    invariants::check_epoch_start(&cx, 3);
+    invariants::check_committed(&cx);
+}
+
+#[test]
+#[should_panic(expected = "gatherer counts")]
+fn overcounted_shards_are_caught() {
+    let mut cx = common::context(&common::generate_configs(4, 1, 1)[0]);
+    cx.commit_gatherer.shard_num = cx.num_nodes + 1;
+    invariants::check(&cx);
+}
+
+#[test]
+#[should_panic(expected = "was not cleared")]
+fn uncleared_gatherers_are_caught() {
+    let mut cx = common::context(&common::generate_configs(4, 1, 1)[0]);
+    cx.propose_gatherer.reference = Some((Vec::new(), Vec::new()));
+    invariants::check_epoch_start(&cx, 3);
+}
+
+#[test]
+#[should_panic(expected = "votes carried into epoch")]
+fn leftover_votes_are_caught() {
+    let mut cx = common::context(&common::generate_configs(4, 1, 1)[0]);
+    cx.received_vote.entry(0).or_default().push(Vote {
+        msg: Vec::new(),
+        origin: 1,
+        auth: Vec::new(),
+    });
+    invariants::check_epoch_start(&cx, 3);
+}
+
+#[test]
+#[should_panic(expected = "leader went from")]
+fn a_skipped_leader_is_caught() {
+    let mut cx = common::context(&common::generate_configs(4, 1, 1)[0]);
+    cx.last_leader = 2;
+    invariants::check_epoch_start(&cx, 0);
+}
+
+#[test]
+#[should_panic(expected = "outlived its commit")]
+fn a_proposal_kept_after_commit_is_caught() {
+    let mut cx = common::context(&common::generate_configs(4, 1, 1)[0]);
+    cx.received_propose = Some(Propose {
+        new_block: types::Block::new(),
+        certificate: types::Certificate::empty_cert(),
+        epoch: 1,
+        batch: Vec::new(),
+    });
+    invariants::check_committed(&cx);
+}
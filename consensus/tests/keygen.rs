@@ -0,0 +1,48 @@
+mod common;
+
+use config::{Client, Node};
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use crypto::rand::{rngs::StdRng, SeedableRng};
+use genconfig::{generate, write_configs, Setup};
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+#[tokio::test]
+async fn generated_configs_form_a_cluster() {
+    let hosts: Vec<String> = (1..=4).map(|i| format!("10.0.0.{}", i)).collect();
+    let mut setup = Setup::with_hosts(4, 1, &hosts, 16000, 15000);
+    setup.epochs = 4;
+    let (node, client) = generate(&setup, &mut StdRng::seed_from_u64(common::SEED));
+
+    let target = std::env::temp_dir().join(format!("genconfig-{}", std::process::id()));
+    std::fs::create_dir_all(&target).unwrap();
+    let target = target.to_str().unwrap().to_string();
+    write_configs(&node, &client, "json", &target);
+
+    let client = Client::from_json(format!("{}/client.json", target));
+    client.validate().expect("generated client config is invalid");
+    let mut configs = Vec::new();
+    for i in 0..4 {
+        let config = Node::from_json(format!("{}/nodes-{}.json", target, i));
+        config.validate().expect("generated node config is invalid");
+        assert_eq!(config.net_map[&1], "10.0.0.2:16001");
+        assert_eq!(config.pk_map, client.server_pk);
+        configs.push(config);
+    }
+    assert_eq!(client.net_map[&3], "10.0.0.4:15003");
+    std::fs::remove_dir_all(&target).unwrap();
+
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+    let committed = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            if let NodeEvent::BlockCommitted(1, _) = ev {
+                return;
+            }
+        }
+        panic!("event channel closed");
+    };
+    tokio::time::timeout(Duration::from_secs(30), committed)
+        .await
+        .expect("cluster did not commit a block");
+}
@@ -0,0 +1,29 @@
+mod common;
+
+async fn run_epoch(lazy_reconstruct: bool) -> (usize, Vec<common::EpochOutcome>) {
+    let mut configs = common::generate_configs(7, 2, 1);
+    for config in configs.iter_mut() {
+        config.lazy_reconstruct = lazy_reconstruct;
+    }
+    let cluster = common::spawn_cluster(configs);
+    let outcomes = common::first_epoch(&cluster).await;
+    (cluster.frames_of("Reconstruct"), outcomes)
+}
+
+#[tokio::test]
+async fn lazy_reconstruct_sends_fewer_shares() {
+    let (eager_frames, eager) = run_epoch(false).await;
+    let (lazy_frames, lazy) = run_epoch(true).await;
+
+    let expected = eager[0].beacon.expect("no beacon produced");
+    assert_ne!(expected, [0; 32]);
+    for outcome in eager.iter().chain(lazy.iter()) {
+        assert_eq!(outcome.beacon, Some(expected));
+    }
+    assert!(
+        lazy_frames < eager_frames,
+        "{} reconstruct frames with lazy reconstruction, {} without",
+        lazy_frames,
+        eager_frames
+    );
+}
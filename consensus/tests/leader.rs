@@ -0,0 +1,42 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+#[tokio::test]
+async fn reported_leaders_are_the_proposers() {
+    let configs = common::generate_configs(4, 1, 6);
+    let (cx, _net_recv) = common::context_and_sent(&configs[0]);
+    assert_eq!(cx.current_leader(), 0);
+    assert_eq!(cx.next_leader(), 1);
+    assert_eq!(cx.leader_for_epoch(5), Some(1));
+
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+    let mut reported = HashMap::new();
+    let collect = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::LeaderChanged(e, l) => {
+                    reported.insert(e, l);
+                }
+                NodeEvent::ViewChange(5) => break,
+                _ => (),
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(60), collect)
+        .await
+        .expect("did not finish four epochs");
+
+    let proposals = cluster.proposals.lock().unwrap().clone();
+    for e in 1..5 {
+        let leader = cx.leader_for_epoch(e);
+        assert_eq!(reported.get(&e).cloned(), leader);
+        let proposers: Vec<_> = proposals.iter().filter(|(pe, _)| *pe == e).map(|(_, id)| *id).collect();
+        assert!(!proposers.is_empty(), "nobody proposed in epoch {}", e);
+        assert!(proposers.iter().all(|id| Some(*id) == leader));
+    }
+}
@@ -22,7 +22,7 @@ fn tree_get_dummy_acc<T: Serialize>(
     data: &T,
 ) -> (Vec<Vec<u8>>, DataWithAcc) {
     let shards = accumulator::to_shards(
-        &to_bytes(data),
+        &to_bytes(data).unwrap(),
         cx_num_nodes as usize,
         cx_num_faults as usize,
     );
@@ -64,6 +64,8 @@ fn generate_propose() -> HashMap<usize, Propose> {
         let content = Content {
             acks: certificate.votes.clone(),
             commits: vec![poly.get_commit(); *test],
+            reconfig: Vec::new(),
+            transactions: Vec::new(),
         };
         let mut block = Block::new();
         block.body.data = content;
@@ -72,6 +74,7 @@ fn generate_propose() -> HashMap<usize, Propose> {
             new_block: block,
             certificate: certificate,
             epoch: 0,
+            batch: Vec::new(),
         };
         propose_map.insert(*test, propose);
     }
@@ -99,7 +102,7 @@ mod tests {
             println!(
                 "Certificate,{},{}",
                 n,
-                to_bytes(&ProtocolMsg::Certificate(data)).len()
+                to_bytes(&ProtocolMsg::Certificate(data, 0, 0)).unwrap().len()
             );
         }
     }
@@ -112,7 +115,7 @@ mod tests {
             println!(
                 "Propose,{},{}",
                 n,
-                to_bytes(&ProtocolMsg::Propose(p.get(&n).unwrap().clone(), acc)).len()
+                to_bytes(&ProtocolMsg::Propose(p.get(&n).unwrap().clone(), acc)).unwrap().len()
             );
         }
     }
@@ -123,7 +126,7 @@ mod tests {
             println!(
                 "Vote,{},{}",
                 n,
-                to_bytes(&ProtocolMsg::Vote(empty_vote())).len()
+                to_bytes(&ProtocolMsg::Vote(empty_vote(), 0)).unwrap().len()
             );
         }
     }
@@ -138,7 +141,7 @@ mod tests {
             println!(
                 "VoteCert,{},{}",
                 n,
-                to_bytes(&ProtocolMsg::VoteCert(data, acc)).len()
+                to_bytes(&ProtocolMsg::VoteCert(data, acc, 0)).unwrap().len()
             );
         }
     }
@@ -152,10 +155,12 @@ mod tests {
                 "DeliverPropose,{},{}",
                 n,
                 to_bytes(&ProtocolMsg::DeliverPropose(
-                    acc.0[0].clone(),
+                    bytes::Bytes::from(acc.0[0].clone()),
                     0,
-                    accumulator::get_sign(&acc.1, 0)
+                    accumulator::get_sign(&acc.1, 0),
+                    0
                 ))
+                .unwrap()
                 .len()
             );
         }
@@ -172,10 +177,12 @@ mod tests {
                 "DeliverVoteCert,{},{}",
                 n,
                 to_bytes(&ProtocolMsg::DeliverPropose(
-                    acc.0[0].clone(),
+                    bytes::Bytes::from(acc.0[0].clone()),
                     0,
-                    accumulator::get_sign(&acc.1, 0)
+                    accumulator::get_sign(&acc.1, 0),
+                    0
                 ))
+                .unwrap()
                 .len()
             );
         }
@@ -184,7 +191,7 @@ mod tests {
     #[test]
     fn reconstruct_length() {
         for n in TEST_POINTS.iter() {
-            let _num_nodes = *n;
+            let num_nodes = *n;
             let num_faults = (n - 1) / 2;
             let rng = &mut StdRng::from_entropy();
             let rand_beacon_parameter = crypto::EVSS381::setup(num_faults, rng).unwrap();
@@ -201,7 +208,7 @@ mod tests {
             println!(
                 "Reconstruct,{},{}",
                 n,
-                to_bytes(&ProtocolMsg::Reconstruct(share, 0)).len()
+                to_bytes(&ProtocolMsg::Reconstruct(vec![share; num_nodes], 0)).unwrap().len()
             );
         }
     }
@@ -232,11 +239,11 @@ mod tests {
                     );
                 }
             }
-            let acc = tree_get_dummy_acc(*n as u16, ((n - 1) / 2) as u16, &to_bytes(&commits));
+            let acc = tree_get_dummy_acc(*n as u16, ((n - 1) / 2) as u16, &to_bytes(&commits).unwrap());
             println!(
                 "Commit,{},{}",
                 n,
-                to_bytes(&ProtocolMsg::Commit(shares[0].clone(), commits, acc.1)).len()
+                to_bytes(&ProtocolMsg::Commit(shares[0].clone(), commits, acc.1, 0)).unwrap().len()
             );
         }
     }
@@ -267,15 +274,17 @@ mod tests {
                     );
                 }
             }
-            let acc = tree_get_dummy_acc(*n as u16, ((n - 1) / 2) as u16, &to_bytes(&commits));
+            let acc = tree_get_dummy_acc(*n as u16, ((n - 1) / 2) as u16, &to_bytes(&commits).unwrap());
             println!(
                 "DeliverCommit,{},{}",
                 n,
                 to_bytes(&ProtocolMsg::DeliverCommit(
-                    acc.0[0].clone(),
+                    bytes::Bytes::from(acc.0[0].clone()),
                     0,
-                    accumulator::get_sign(&acc.1, 0)
+                    accumulator::get_sign(&acc.1, 0),
+                    0
                 ))
+                .unwrap()
                 .len()
             );
         }
@@ -287,7 +296,7 @@ mod tests {
             println!(
                 "Ack,{},{}",
                 n,
-                to_bytes(&ProtocolMsg::Ack(empty_vote())).len()
+                to_bytes(&ProtocolMsg::Ack(empty_vote(), 0)).unwrap().len()
             );
         }
     }
@@ -0,0 +1,22 @@
+mod common;
+
+#[tokio::test]
+async fn loopback_matches_direct_delivery() {
+    let direct = common::generate_configs(4, 1, 4);
+    let mut looped = direct.clone();
+    for config in looped.iter_mut() {
+        config.loopback = true;
+    }
+
+    let cluster = common::spawn_cluster(direct);
+    let expected = common::first_epoch(&cluster).await;
+    let cluster = common::spawn_cluster(looped);
+    let outcomes = common::first_epoch(&cluster).await;
+
+    for (want, got) in expected.iter().zip(outcomes.iter()) {
+        assert!(got.committed.is_some());
+        assert_eq!(got.committed, want.committed);
+        assert!(got.certificate.is_some());
+        assert_eq!(got.beacon, want.beacon);
+    }
+}
@@ -0,0 +1,54 @@
+use consensus::bft::node::intake::tx_channel;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::error::TrySendError;
+use types::Transaction;
+
+const CAPACITY: usize = 64;
+
+fn tx(i: usize) -> Transaction {
+    Transaction {
+        data: i.to_be_bytes().to_vec(),
+        request: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn a_flood_waits_for_the_reactor() {
+    let (send, mut recv) = tx_channel(CAPACITY);
+    let sent = Arc::new(AtomicUsize::new(0));
+    let flood = {
+        let send = send.clone();
+        let sent = Arc::clone(&sent);
+        tokio::spawn(async move {
+            for i in 0..100 * CAPACITY {
+                send.send(tx(i)).await.unwrap();
+                sent.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Nothing was taken, so the flood stopped at the capacity
+    assert_eq!(sent.load(Ordering::SeqCst), CAPACITY);
+    assert!(send.depth() <= CAPACITY + 1);
+    match send.try_send(tx(0)) {
+        Err(TrySendError::Full(_)) => (),
+        r => panic!("expected a full mempool, got {:?}", r.map_err(|e| e.to_string())),
+    }
+    assert!(send.depth() <= CAPACITY + 1);
+
+    // It goes on as the reactor takes transactions, in order
+    let mut watch = send.watch();
+    for i in 0..100 * CAPACITY {
+        let got = recv.recv().await.unwrap();
+        assert_eq!(got.data, i.to_be_bytes().to_vec());
+        assert!(*watch.borrow() <= CAPACITY + 1);
+    }
+    flood.await.unwrap();
+    while *watch.borrow() != 0 {
+        watch.changed().await.unwrap();
+    }
+    assert_eq!(send.depth(), 0);
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use types::{Block, BodyItem, Vote};
+
+fn block() -> Block {
+    let configs = common::generate_configs(4, 1, 1);
+    let mut block = Block::new();
+    block.body.data.commits = configs[0].rand_beacon_shares[0].1.clone();
+    block.body.data.acks = (0..3)
+        .map(|i| Vote {
+            msg: vec![i; 32],
+            origin: i as u16,
+            auth: vec![i; 64],
+        })
+        .collect();
+    block.update_hash();
+    block
+}
+
+#[test]
+fn every_item_is_provable_against_the_header() {
+    let block = block();
+    let content = &block.body.data;
+    let root = block.header.body_root;
+    assert_eq!(root, content.merkle_root());
+    // 4 commits and 3 acks, so one leaf has no sibling
+    let leaves = content.leaves();
+    assert_eq!(leaves.len(), 7);
+    for (i, leaf) in leaves.iter().enumerate() {
+        let proof = content.prove(i).unwrap();
+        assert!(proof.verify(&root, leaf));
+    }
+    let proof = content.prove_ack(1).unwrap();
+    assert!(proof.verify(&root, &BodyItem::Ack(&content.acks[1]).leaf()));
+    let proof = content.prove_commit(3).unwrap();
+    assert!(proof.verify(&root, &BodyItem::Commit(&content.commits[3]).leaf()));
+    assert!(content.prove(7).is_none());
+    assert!(content.prove_ack(3).is_none());
+}
+
+#[test]
+fn forged_proofs_are_rejected() {
+    let block = block();
+    let content = &block.body.data;
+    let root = block.header.body_root;
+    let leaves = content.leaves();
+    let proof = content.prove(5).unwrap();
+
+    // An item that is not in the block
+    let mut forged = content.acks[1].clone();
+    forged.origin = 3;
+    assert!(!proof.verify(&root, &BodyItem::Ack(&forged).leaf()));
+    // Another item of the block
+    assert!(!proof.verify(&root, &leaves[4]));
+    // A different root
+    let mut other = block.clone();
+    other.body.data.acks.pop();
+    other.update_hash();
+    assert!(!proof.verify(&other.header.body_root, &leaves[5]));
+
+    let mut bad = proof.clone();
+    bad.index = 4;
+    assert!(!bad.verify(&root, &leaves[5]));
+    let mut bad = proof.clone();
+    bad.path[0][0] ^= 1;
+    assert!(!bad.verify(&root, &leaves[5]));
+    let mut bad = proof.clone();
+    bad.path.pop();
+    assert!(!bad.verify(&root, &leaves[5]));
+    let mut bad = proof.clone();
+    bad.path.push(leaves[0]);
+    assert!(!bad.verify(&root, &leaves[5]));
+    let mut bad = proof.clone();
+    bad.leaves = 6;
+    assert!(!bad.verify(&root, &leaves[5]));
+
+    // An inner node does not pass for a leaf
+    let inner = content.prove(0).unwrap();
+    assert!(!inner.verify(&root, &root));
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use config::MessageHandling;
+use consensus::bft::node::clock::RealClock;
+use consensus::bft::node::entropy::entropy_channel;
+use consensus::bft::node::events::event_channel;
+use consensus::bft::node::intake::tx_channel;
+use consensus::bft::node::reactor;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{channel, unbounded_channel};
+use types::{DataWithAcc, ProtocolMsg};
+
+/// Hands replica 0 a commit without any shares from replica 1 and waits for
+/// up to a second. Returns whether the reactor panicked.
+async fn feed_incomplete_commit(handling: MessageHandling) -> bool {
+    let mut config = common::generate_configs(4, 1, 4).remove(0);
+    config.message_handling = handling;
+    let (net_send, mut net_send_recv) = channel(util::CHANNEL_SIZE);
+    let (net_recv_send, net_recv) = unbounded_channel();
+    let (cli_send, _cli_send_recv) = unbounded_channel();
+    let (_cli_recv_send, cli_recv) = tx_channel(16);
+    tokio::spawn(async move { while net_send_recv.recv().await.is_some() {} });
+    let node = tokio::spawn(async move {
+        reactor(
+            &config,
+            false,
+            net_send,
+            net_recv,
+            cli_send,
+            cli_recv,
+            event_channel(),
+            Arc::new(RealClock),
+            entropy_channel().1,
+            None,
+        )
+        .await
+    });
+    let commit = ProtocolMsg::Commit(
+        VecDeque::new(),
+        Vec::new(),
+        DataWithAcc {
+            sign: Vec::new(),
+            tree: Vec::new(),
+            size: 0,
+        },
+        0,
+    );
+    net_recv_send.send((1, commit)).unwrap();
+    match tokio::time::timeout(Duration::from_secs(1), node).await {
+        Ok(res) => res.expect_err("reactor stopped").is_panic(),
+        Err(_) => false,
+    }
+}
+
+#[tokio::test]
+async fn strict_aborts_on_a_malformed_message() {
+    assert!(feed_incomplete_commit(MessageHandling::Strict).await);
+}
+
+#[tokio::test]
+async fn lenient_skips_a_malformed_message() {
+    assert!(!feed_incomplete_commit(MessageHandling::Lenient).await);
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use consensus::bft::node::accumulator::get_acc;
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use crypto::hash::COMMIT_DOMAIN;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_stream::StreamExt;
+use types::ProtocolMsg;
+
+/// A commit from each replica, properly signed, whose last two shares for
+/// replica 0 are swapped. Checking one takes nearly every EVSS check before
+/// it fails.
+fn bad_commits(configs: &[config::Node]) -> Vec<ProtocolMsg> {
+    configs
+        .iter()
+        .map(|config| {
+            let (cx, _net_recv) = common::context_and_sent(config);
+            let (shards, commits) = cx.rand_beacon_shares[1].clone();
+            let sign = get_acc(&cx, &commits, COMMIT_DOMAIN).unwrap().1;
+            let mut sh = shards[0].clone();
+            let last = sh.len() - 1;
+            sh.swap(last - 1, last);
+            ProtocolMsg::Commit(sh, commits, sign, 0)
+        })
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn votes_keep_their_pace_under_a_flood_of_commits() {
+    let mut configs = common::generate_configs(4, 1, 6);
+    for config in configs.iter_mut() {
+        config.offload_shares = true;
+    }
+    let epoch = Duration::from_millis(configs[0].phase_durations().epoch);
+    let flood = bad_commits(&configs);
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+
+    let inbox = cluster.inbound[0].clone();
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(Duration::from_millis(1));
+        loop {
+            tick.tick().await;
+            for (origin, msg) in flood.iter().enumerate() {
+                if inbox.send((origin as u16, msg.clone())).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    let certified = async {
+        let mut started = HashMap::new();
+        let mut took = Vec::new();
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::ViewChange(e) => {
+                    started.insert(e, Instant::now());
+                    if e == 5 {
+                        return took;
+                    }
+                }
+                NodeEvent::VoteCertified(e, _) if e >= 2 => {
+                    took.push((e, started[&e].elapsed()));
+                }
+                _ => (),
+            }
+        }
+        panic!("event channel closed");
+    };
+    let took = tokio::time::timeout(Duration::from_secs(30), certified)
+        .await
+        .expect("epoch 5 never started");
+    let epochs: Vec<_> = took.iter().map(|(e, _)| *e).collect();
+    assert_eq!(epochs, vec![2, 3, 4], "not every epoch was certified");
+    for (e, t) in took {
+        assert!(t < epoch, "epoch {} was certified after {:?}", e, t);
+    }
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use crypto::hash::Hash;
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use types::Height;
+
+/// What one replica committed
+#[derive(Default)]
+struct Commits {
+    /// The block committed at each height
+    by_height: HashMap<Height, Hash>,
+    /// The epoch of the last commit
+    last_epoch: Height,
+}
+
+// Collects the blocks one replica commits until `last_epoch` is over
+async fn commits_until(events: &consensus::bft::node::events::EventSender, last_epoch: Height) -> Commits {
+    let mut stream = subscribe(events);
+    let mut epoch = 0;
+    let mut commits = Commits::default();
+    while let Some(Ok(ev)) = stream.next().await {
+        match ev {
+            NodeEvent::ViewChange(e) if e > last_epoch => break,
+            NodeEvent::ViewChange(e) => epoch = e,
+            NodeEvent::BlockCommitted(h, hash) => {
+                if let Some(before) = commits.by_height.insert(h, hash) {
+                    assert_eq!(before, hash, "committed two blocks at height {}", h);
+                }
+                commits.last_epoch = epoch;
+            }
+            _ => (),
+        }
+    }
+    commits
+}
+
+#[tokio::test]
+async fn partition_then_heal_is_safe() {
+    const N: usize = 7;
+    const LAST_EPOCH: Height = 8;
+    // How long the cluster stays split
+    const SPLIT_EPOCHS: u64 = 4;
+    let configs = common::generate_configs(N, 3, 12);
+    let split_for = Duration::from_millis(configs[0].phase_durations().epoch * SPLIT_EPOCHS);
+    let cluster = common::spawn_cluster(configs);
+    let watchers: Vec<_> = cluster
+        .events
+        .iter()
+        .cloned()
+        .map(|ev| tokio::spawn(async move { commits_until(&ev, LAST_EPOCH).await }))
+        .collect();
+
+    cluster.partition.split(&[&[0, 1, 2, 3], &[4, 5, 6]], N);
+    tokio::time::sleep(split_for).await;
+    cluster.partition.heal();
+
+    let mut all = Vec::with_capacity(N);
+    for w in watchers {
+        all.push(
+            tokio::time::timeout(Duration::from_secs(60), w)
+                .await
+                .expect("cluster stopped making progress")
+                .unwrap(),
+        );
+    }
+    // No two replicas commit different blocks at the same height.
+    let heights: BTreeSet<Height> = all.iter().flat_map(|c| c.by_height.keys().cloned()).collect();
+    for height in heights {
+        let mut hashes = all.iter().filter_map(|c| c.by_height.get(&height));
+        let first = hashes.next().unwrap();
+        assert!(hashes.all(|h| h == first), "conflicting commits at height {}", height);
+    }
+    // Progress resumes once the partition is healed.
+    assert!(all.iter().all(|c| c.last_epoch >= LAST_EPOCH - 1));
+}
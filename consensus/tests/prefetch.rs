@@ -0,0 +1,101 @@
+mod common;
+
+use consensus::bft::node::context::Context;
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use consensus::bft::node::prefetch::Prefetched;
+use std::time::{Duration, Instant};
+use tokio_stream::StreamExt;
+
+const PENDING: u64 = 20_000;
+
+/// A replica with a full mempool, about to propose
+fn leader() -> Context {
+    let mut config = common::generate_configs(4, 1, 1).remove(1);
+    config.block_size = PENDING as usize;
+    config.prefetch_proposal = true;
+    let (mut cx, _net_recv) = common::context_and_sent(&config);
+    for i in 0..PENDING {
+        cx.queue_transaction(util::new_dummy_tx(i, 64));
+    }
+    cx
+}
+
+fn prefetch(cx: &mut Context) {
+    let (batch, builder) = cx.assemble_proposal(cx.epoch);
+    cx.prefetched = Some(Prefetched {
+        key: cx.prefetch_key(cx.epoch),
+        batch,
+        block: builder.build().unwrap(),
+    });
+}
+
+#[test]
+fn a_prefetched_proposal_takes_the_building_off_the_propose_phase() {
+    let mut cx = leader();
+    let started = Instant::now();
+    let (_, builder) = cx.assemble_proposal(cx.epoch);
+    let built = builder.build().unwrap();
+    let cold = started.elapsed();
+
+    prefetch(&mut cx);
+    let started = Instant::now();
+    let (_, block) = cx.take_prefetched().expect("nothing changed since it was built");
+    let warm = started.elapsed();
+    assert_eq!(block.hash, built.hash);
+    assert_eq!(block.body.data.transactions.len(), PENDING as usize);
+    assert!(warm < cold, "proposing took {:?} prefetched and {:?} built", warm, cold);
+    // It is only proposed once
+    assert!(cx.take_prefetched().is_none());
+}
+
+#[test]
+fn a_prefetched_proposal_is_dropped_once_its_inputs_change() {
+    let mut cx = leader();
+    // The tip moved on
+    prefetch(&mut cx);
+    cx.highest_height += 1;
+    assert!(cx.take_prefetched().is_none());
+
+    // A transaction it carries was committed meanwhile
+    let mut cx = leader();
+    prefetch(&mut cx);
+    let first = *cx.storage.pending_tx.keys().next().unwrap();
+    cx.storage.pending_tx.remove(&first);
+    assert!(cx.take_prefetched().is_none());
+
+    // New transactions only wait for the next proposal
+    let mut cx = leader();
+    prefetch(&mut cx);
+    cx.queue_transaction(util::new_dummy_tx(PENDING, 64));
+    assert!(cx.take_prefetched().is_some());
+}
+
+#[tokio::test]
+async fn leaders_propose_what_they_prefetched() {
+    let mut configs = common::generate_configs(4, 1, 6);
+    for config in configs.iter_mut() {
+        config.prefetch_proposal = true;
+    }
+    let cluster = common::spawn_cluster(configs);
+    // Whoever leads the epoch
+    let mut stream = futures::stream::select_all(cluster.events.iter().map(subscribe));
+    let mut used = 0;
+    let mut heights = Vec::new();
+    let collect = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::ViewChange(6) => break,
+                NodeEvent::ProposalPrefetched(_, true) => used += 1,
+                NodeEvent::BlockCommitted(h, _) if h > 0 && !heights.contains(&h) => heights.push(h),
+                _ => (),
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(90), collect)
+        .await
+        .expect("did not get through five epochs");
+    assert!(used > 0, "no leader proposed a prefetched block");
+    heights.sort();
+    assert_eq!(heights, (1..=heights.len() as u64).collect::<Vec<_>>());
+    assert!(heights.len() >= 4, "only committed {:?}", heights);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use types::{Block, Certificate, Propose, Replica, Vote};
+
+fn propose(author: Replica, epoch: u64) -> Propose {
+    let mut new_block = Block::new();
+    new_block.header.author = author;
+    new_block.header.height = epoch;
+    new_block.update_hash();
+    Propose {
+        new_block,
+        certificate: Default::default(),
+        epoch,
+        batch: Vec::new(),
+    }
+}
+
+#[test]
+fn only_the_leaders_first_proposal_is_admitted() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (mut cx, _net_recv) = common::context_and_sent(&configs[3]);
+    cx.epoch = 1;
+    cx.last_leader = 1;
+
+    // Not the leader of the epoch
+    assert!(cx.admit_propose(2, &propose(2, 1)).is_err());
+    // The leader relaying a block someone else authored
+    assert!(cx.admit_propose(1, &propose(2, 1)).is_err());
+    // The leader proposing for an epoch it does not lead
+    assert!(cx.admit_propose(1, &propose(1, 0)).is_err());
+    assert!(cx.admit_propose(1, &propose(1, 2)).is_err());
+
+    assert!(cx.admit_propose(1, &propose(1, 1)).is_ok());
+    assert!(cx.verify_propose(&propose(1, 1)).is_ok());
+    // A second, different proposal in the same epoch
+    let mut again = propose(1, 1);
+    again.new_block.header.extra = vec![1];
+    assert!(cx.admit_propose(1, &again).is_err());
+
+    // The next leader gets its turn in the next epoch
+    cx.epoch = 2;
+    cx.last_leader = 2;
+    assert!(cx.admit_propose(2, &propose(2, 2)).is_ok());
+}
+
+#[test]
+fn a_proposal_that_does_not_check_out_does_not_keep_out_the_real_one() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (mut cx, _net_recv) = common::context_and_sent(&configs[3]);
+    cx.epoch = 1;
+    cx.last_leader = 1;
+
+    // Sent as the leader's, with a certificate nobody signed
+    let mut forged = propose(1, 1);
+    forged.new_block.header.extra = vec![1];
+    forged.new_block.update_hash();
+    forged.certificate = Certificate {
+        votes: vec![Vote {
+            msg: forged.new_block.hash.to_vec(),
+            origin: 2,
+            auth: vec![0; 64],
+        }],
+    };
+    assert!(cx.admit_propose(1, &forged).is_ok());
+    assert!(cx.verify_propose(&forged).is_err());
+
+    let real = propose(1, 1);
+    assert!(cx.admit_propose(1, &real).is_ok());
+    assert!(cx.verify_propose(&real).is_ok());
+    // Only now is the epoch's proposal taken
+    assert!(cx.admit_propose(1, &forged).is_err());
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+#[test]
+fn flooded_shares_are_capped_at_the_threshold() {
+    let configs = common::generate_configs(4, 1, 1);
+    let mut cxs: Vec<_> = configs.iter().map(common::context).collect();
+    let shares: Vec<_> = cxs.iter_mut().map(|cx| cx.reconstruct_share()).collect();
+
+    // What an undisturbed replica makes of the honest shares
+    let reference = &mut cxs[3];
+    for i in 0..3 {
+        assert!(reference.accept_reconstruct(i, shares[i as usize].clone(), 1));
+    }
+    let expected = reference.take_beacon();
+    assert_ne!(expected, [0; 32]);
+
+    let cx = &mut cxs[0];
+    // Replica 1 keeps resending, and replays replica 2's share as its own
+    assert!(cx.accept_reconstruct(1, shares[1].clone(), 1));
+    for _ in 0..100 {
+        assert!(!cx.accept_reconstruct(1, shares[1].clone(), 1));
+        assert!(!cx.accept_reconstruct(1, shares[2].clone(), 1));
+    }
+    // Replica 3 passes off replica 2's share as its own first, which uses
+    // up its one try for the epoch
+    assert!(!cx.accept_reconstruct(3, shares[2].clone(), 1));
+    assert!(!cx.accept_reconstruct(3, shares[3].clone(), 1));
+    assert!(cx.accept_reconstruct(2, shares[2].clone(), 1));
+    assert!(cx.accept_reconstruct(0, shares[0].clone(), 1));
+    assert_eq!(cx.reconstruct_shares(1), 3);
+
+    assert_eq!(cx.take_beacon(), expected);
+    assert_eq!(cx.reconstruct_shares(1), 0);
+}
+
+#[test]
+fn a_flood_of_invalid_shares_does_not_keep_out_the_valid_ones() {
+    let configs = common::generate_configs(7, 2, 1);
+    let mut cxs: Vec<_> = configs.iter().map(common::context).collect();
+    let shares: Vec<_> = cxs.iter_mut().map(|cx| cx.reconstruct_share()).collect();
+
+    let reference = &mut cxs[6];
+    for i in 1..6 {
+        assert!(reference.accept_reconstruct(i, shares[i as usize].clone(), 1));
+    }
+    let expected = reference.take_beacon();
+    assert_ne!(expected, [0; 32]);
+
+    // Faulty replicas 5 and 6 get their shares in before anyone else:
+    // one that does not open its commitment, one short of a dealer, and
+    // replays of valid shares of others
+    let cx = &mut cxs[0];
+    let mut tampered = shares[5].clone();
+    tampered[0].value += crypto::F381::from(1 as u16);
+    assert!(!cx.accept_reconstruct(5, tampered, 1));
+    let short = shares[6][1..].to_vec();
+    assert!(!cx.accept_reconstruct(6, short, 1));
+    for i in 1..5 {
+        for _ in 0..50 {
+            assert!(!cx.accept_reconstruct(5, shares[i].clone(), 1));
+            assert!(!cx.accept_reconstruct(6, shares[i].clone(), 1));
+        }
+    }
+    assert_eq!(cx.reconstruct_shares(1), 0);
+
+    // None of it took a slot from the honest replicas
+    for i in 0..5 {
+        assert!(cx.accept_reconstruct(i, shares[i as usize].clone(), 1));
+    }
+    assert_eq!(cx.take_beacon(), expected);
+}
+
+#[test]
+fn skipped_epochs_do_not_change_the_beacon() {
+    let configs = common::generate_configs(4, 1, 1);
+    let mut cxs: Vec<_> = configs.iter().map(common::context).collect();
+    // Every replica's shares for epochs 1 to 4, by epoch
+    let mut shares = BTreeMap::new();
+    for e in 1..5 {
+        let epoch: Vec<_> = cxs
+            .iter_mut()
+            .map(|cx| {
+                cx.epoch = e;
+                cx.reconstruct_share()
+            })
+            .collect();
+        shares.insert(e, epoch);
+    }
+
+    let reference = &mut cxs[3];
+    let mut expected = BTreeMap::new();
+    for e in [1, 3].iter() {
+        reference.epoch = *e;
+        for i in 0..3 {
+            assert!(reference.accept_reconstruct(i, shares[e][i as usize].clone(), *e));
+        }
+        expected.insert(*e, reference.take_beacon());
+    }
+    assert_ne!(expected[&1], expected[&3]);
+
+    // Shares for epochs 1 to 4 arrive before replica 0 gets to any of them
+    let cx = &mut cxs[0];
+    cx.epoch = 1;
+    for e in 1..5 {
+        for i in 1..4 {
+            assert!(cx.accept_reconstruct(i, shares[&e][i as usize].clone(), e));
+        }
+    }
+    // A share for an epoch behind the newest one queued is too late
+    assert!(!cx.accept_reconstruct(0, shares[&2][0].clone(), 2));
+
+    assert_eq!(cx.take_beacon(), expected[&1]);
+    assert_eq!(cx.reconstruct_shares(1), 0);
+    assert_eq!(cx.reconstruct_shares(2), 3);
+
+    // Skipping to epoch 3 drops epoch 2 and keeps epoch 4
+    cx.epoch = 3;
+    assert_eq!(cx.take_beacon(), expected[&3]);
+    assert_eq!(cx.reconstruct_shares(2), 0);
+    assert_eq!(cx.reconstruct_shares(3), 0);
+    assert_eq!(cx.reconstruct_shares(4), 3);
+}
+
+#[test]
+fn shares_that_do_not_open_their_commitment_are_rejected() {
+    let configs = common::generate_configs(4, 1, 1);
+    let mut cxs: Vec<_> = configs.iter().map(common::context).collect();
+    let mut shares: Vec<_> = cxs.iter_mut().map(|cx| cx.reconstruct_share()).collect();
+    // Replica 2's share no longer lies on the dealt polynomial, but any two
+    // shares would still rebuild some value
+    shares[2][0].value += crypto::F381::from(1 as u16);
+
+    let mut beacons = Vec::new();
+    for (r, senders) in [(0, [2, 1, 0, 3]), (1, [0, 2, 1, 3]), (3, [3, 0, 2, 1])].iter() {
+        let cx = &mut cxs[*r];
+        for i in senders.iter() {
+            assert_eq!(cx.accept_reconstruct(*i, shares[*i as usize].clone(), 1), *i != 2);
+        }
+        beacons.push(cx.take_beacon());
+    }
+    // It never takes a slot, so the beacon is rebuilt everywhere
+    assert_ne!(beacons[0], [0; 32]);
+    assert!(beacons.iter().all(|b| *b == beacons[0]));
+}
+
+#[test]
+fn our_own_share_is_left_out_if_it_does_not_open_its_commitment() {
+    let configs = common::generate_configs(4, 1, 1);
+    let mut cxs: Vec<_> = configs.iter().map(common::context).collect();
+    let mut shares: Vec<_> = cxs.iter_mut().map(|cx| cx.reconstruct_share()).collect();
+
+    let reference = &mut cxs[3];
+    for i in 0..3 {
+        assert!(reference.accept_reconstruct(i, shares[i as usize].clone(), 1));
+    }
+    let expected = reference.take_beacon();
+
+    // Replica 0's queue is out of step with the dealers'
+    shares[0][1].value += crypto::F381::from(1 as u16);
+    let cx = &mut cxs[0];
+    assert!(!cx.accept_reconstruct(0, shares[0].clone(), 1));
+    for i in 1..4 {
+        assert!(cx.accept_reconstruct(i, shares[i as usize].clone(), 1));
+    }
+    assert_eq!(cx.take_beacon(), expected);
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use bytes::Bytes;
+use consensus::bft::node::accumulator::{acc_from_shards, get_sign, to_shards, ShareGatherer};
+use crypto::hash::PROPOSE_DOMAIN;
+use types::{Block, Certificate, Propose};
+use util::io::to_bytes;
+
+#[test]
+fn a_bad_signed_shard_is_left_out_on_retry() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (cx, _net_recv) = common::context_and_sent(&configs[0]);
+    let propose = Propose {
+        new_block: Block::new(),
+        certificate: Certificate::empty_cert(),
+        epoch: 1,
+        batch: Vec::new(),
+    };
+
+    // The leader signs an accumulator over a shard that does not belong to
+    // the proposal, so it verifies like the others
+    let mut shards = to_shards(&to_bytes(&propose).unwrap(), 4, 1);
+    for b in shards[0].iter_mut() {
+        *b = 0xff;
+    }
+    let acc = acc_from_shards(&cx, &shards, PROPOSE_DOMAIN);
+    let pp = cx.accumulator_pub_params_map.get(&0).unwrap();
+    let pk = cx.pub_key_map.get(&0).unwrap();
+    let mut gatherer = ShareGatherer::new(4, 3, PROPOSE_DOMAIN);
+    for i in 0..4 {
+        gatherer.add_share(Bytes::from(shards[i as usize].clone()), i, pp, pk, get_sign(&acc, i));
+    }
+    assert_eq!(gatherer.shard_num, 4);
+
+    let check = |bytes: &[u8]| Propose::try_from_bytes(bytes).filter(|p| p.epoch == 1);
+    assert!(gatherer.reconstruct_checked(4, 1, 0, check).is_none());
+    // Every set of three but the last one has the bad shard in it
+    assert!(gatherer.reconstruct_checked(4, 1, 3, check).is_none());
+    let rebuilt = gatherer.reconstruct_checked(4, 1, 4, check).expect("no retry succeeded");
+    assert_eq!(to_bytes(&rebuilt).unwrap(), to_bytes(&propose).unwrap());
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use consensus::bft::node::events::{event_channel, subscribe, NodeEvent};
+use consensus::bft::node::trace::{self, replay};
+use crypto::hash::Hash;
+use std::time::Duration;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::StreamExt;
+use types::Height;
+
+/// What the node committed and the beacons it produced, up to the start of
+/// `until`
+async fn outcome(stream: &mut BroadcastStream<NodeEvent>, until: Height) -> (Vec<(Height, Hash)>, Vec<(Height, Hash)>) {
+    let mut committed = Vec::new();
+    let mut beacons = Vec::new();
+    while let Some(Ok(ev)) = stream.next().await {
+        match ev {
+            NodeEvent::BlockCommitted(h, hash) => committed.push((h, hash)),
+            NodeEvent::BeaconProduced(e, beacon) => beacons.push((e, beacon)),
+            NodeEvent::ViewChange(e) if e == until => break,
+            _ => (),
+        }
+    }
+    (committed, beacons)
+}
+
+#[tokio::test]
+async fn a_recorded_run_replays_to_the_same_state() {
+    let path = std::env::temp_dir().join(format!("randpiper-trace-{}", std::process::id()));
+    let mut configs = common::generate_configs(4, 1, 6);
+    configs[0].message_trace = Some(path.to_string_lossy().into_owned());
+    let config = configs[0].clone();
+    let epoch = Duration::from_millis(config.phase_durations().epoch);
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+    let recorded = tokio::time::timeout(Duration::from_secs(30), outcome(&mut stream, 4))
+        .await
+        .expect("the cluster did not get through the epochs");
+    assert!(recorded.0.len() >= 3, "only committed {:?}", recorded.0);
+
+    let records = trace::read(&path).unwrap();
+    assert!(!records.is_empty());
+    let events = event_channel();
+    let mut replayed = subscribe(&events);
+    replay(&config, records, events.clone(), epoch).await;
+    let replayed = tokio::time::timeout(Duration::from_secs(5), outcome(&mut replayed, 4))
+        .await
+        .expect("the replay did not get as far");
+    assert_eq!(replayed, recorded);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use consensus::bft::node::context::KEY_GRACE_EPOCHS;
+use crypto::hash::{with_domain, VOTE_DOMAIN};
+use crypto_lib::ed25519;
+use std::sync::Arc;
+use types::{Block, Destination, ProtocolMsg};
+
+#[tokio::test]
+async fn rotated_key_takes_over_after_finality() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (mut follower, mut net_recv) = common::context_and_sent(&configs[1]);
+    let (mut leader, _net_recv) = common::context_and_sent(&configs[0]);
+
+    let msg = with_domain(VOTE_DOMAIN, &[7; 32]);
+    let old_auth = follower.my_secret_key.sign(&msg).unwrap();
+
+    follower.rotate_key(&ed25519::Keypair::generate().encode());
+    let (to, req) = net_recv.recv().await.unwrap();
+    assert_eq!(to, Destination::All);
+    match req.as_ref() {
+        ProtocolMsg::Reconfig(change, auth, _) => {
+            leader.queue_reconfig(change.clone(), auth.clone())
+        }
+        _ => panic!("expected a reconfiguration request"),
+    }
+    assert_eq!(leader.pending_reconfig.len(), 1);
+    // Nothing changes until the request is finalized.
+    assert_eq!(follower.my_secret_key.sign(&msg).unwrap(), old_auth);
+
+    let mut block = Block::new();
+    block.header.height = 1;
+    block.body.data.reconfig = leader.pending_reconfig.clone();
+    block.update_hash();
+    let block = Arc::new(block);
+    for cx in vec![&mut leader, &mut follower] {
+        cx.commit_block(Arc::clone(&block));
+        assert!(cx.pending_reconfig.is_empty());
+        assert_eq!(cx.scheduled_reconfig.len(), 1);
+        cx.apply_reconfig();
+        cx.epoch += 1;
+    }
+
+    let new_auth = follower.my_secret_key.sign(&msg).unwrap();
+    assert_ne!(new_auth, old_auth);
+    assert!(leader.verify(1, &msg, &new_auth));
+    // Votes signed before the switch still verify during the grace window.
+    assert!(leader.verify(1, &msg, &old_auth));
+    leader.epoch += KEY_GRACE_EPOCHS;
+    assert!(!leader.verify(1, &msg, &old_auth));
+    assert!(leader.verify(1, &msg, &new_auth));
+}
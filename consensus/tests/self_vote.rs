@@ -0,0 +1,74 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_stream::StreamExt;
+use types::Replica;
+
+/// Followers other than the fast one vote this long after delivering the
+/// commitments, late in the epoch
+const SLOW_VOTE: u64 = 250;
+
+fn leader_of_epoch_one(config: &config::Node) -> Replica {
+    common::context_and_sent(config).0.leader_for_epoch(1).unwrap()
+}
+
+/// How long after epoch 1 started its leader had a certificate, and the
+/// replicas that voted for it
+async fn time_to_certificate(self_vote: bool) -> (Duration, Vec<Replica>) {
+    let mut configs = common::generate_configs(4, 1, 4);
+    let leader = leader_of_epoch_one(&configs[0]);
+    // Only one follower votes on time, so without the leader's own vote the
+    // certificate waits for a slow one
+    let fast = (leader + 1) % 4;
+    for config in configs.iter_mut() {
+        config.self_vote = self_vote;
+        config.timing.epoch = Some(config.delta * 14);
+        if config.id != leader && config.id != fast {
+            config.timing.vote = Some(SLOW_VOTE);
+        }
+    }
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[leader as usize]);
+    let certified = async {
+        let mut started = None;
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::ViewChange(1) => started = Some(Instant::now()),
+                NodeEvent::VoteCertified(1, cert) => {
+                    let took = started.expect("certified before the epoch started").elapsed();
+                    return (took, cert.votes.iter().map(|v| v.origin).collect());
+                }
+                NodeEvent::ViewChange(2) => panic!("epoch 1 was not certified"),
+                _ => (),
+            }
+        }
+        panic!("event channel closed");
+    };
+    tokio::time::timeout(Duration::from_secs(10), certified)
+        .await
+        .expect("epoch 1 was not certified")
+}
+
+#[tokio::test]
+async fn self_voting_leaders_certify_sooner() {
+    let leader = leader_of_epoch_one(&common::generate_configs(4, 1, 1)[0]);
+    let ((without, voters), (with, self_voters)) =
+        tokio::join!(time_to_certificate(false), time_to_certificate(true));
+
+    assert!(!voters.contains(&leader));
+    // The leader's vote is signed and counted once, like anyone else's
+    assert!(self_voters.contains(&leader));
+    let mut distinct = self_voters.clone();
+    distinct.sort();
+    distinct.dedup();
+    assert_eq!(distinct.len(), self_voters.len());
+
+    assert!(
+        with + Duration::from_millis(SLOW_VOTE / 2) < without,
+        "certified after {:?} with the leader's vote and {:?} without",
+        with,
+        without
+    );
+}
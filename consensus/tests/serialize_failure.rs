@@ -0,0 +1,32 @@
+mod common;
+
+use consensus::bft::node::accumulator::{check_acc, get_acc, shards_of};
+use crypto::hash::PROPOSE_DOMAIN;
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+
+/// A sequence that does not say how long it is, which bincode refuses
+struct Unsized;
+
+impl Serialize for Unsized {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        seq.serialize_element(&0u8)?;
+        seq.end()
+    }
+}
+
+#[test]
+fn unserializable_data_fails_only_its_own_action() {
+    let config = common::generate_configs(4, 1, 1).remove(0);
+    let (cx, _net_recv) = common::context_and_sent(&config);
+    let pk = cx.pub_key_map.get(&0).unwrap();
+
+    assert!(shards_of(&Unsized, cx.num_nodes, cx.num_faults).is_err());
+    assert!(get_acc(&cx, &Unsized, PROPOSE_DOMAIN).is_err());
+
+    // The same replica goes on sharding and signing what does serialize
+    let (shards, acc) = get_acc(&cx, &"proposal", PROPOSE_DOMAIN).expect("failed to shard");
+    assert_eq!(shards.len(), cx.num_nodes as usize);
+    assert!(check_acc(cx.num_nodes, cx.num_faults, &"proposal", &acc, pk, PROPOSE_DOMAIN));
+    assert!(!check_acc(cx.num_nodes, cx.num_faults, &Unsized, &acc, pk, PROPOSE_DOMAIN));
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use bytes::Bytes;
+use consensus::bft::node::accumulator::{get_acc, get_sign, ShareGatherer};
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use crypto::hash::PROPOSE_DOMAIN;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use types::{Height, Replica};
+use util::io::to_bytes;
+
+#[test]
+fn sparse_ids_get_and_rebuild_their_shards() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (mut cx, _net_recv) = common::context_and_sent(&configs[0]);
+    cx.set_active_replicas(vec![42, 0, 17, 5]);
+
+    let ids: Vec<Replica> = vec![0, 5, 17, 42];
+    for (pos, id) in ids.iter().enumerate() {
+        assert_eq!(cx.shard_index(*id), Some(pos as Replica));
+        assert_eq!(cx.shard_owner(pos as Replica), Some(*id));
+    }
+    assert_eq!(cx.shard_index(3), None);
+    assert_eq!(cx.shard_owner(4), None);
+    assert_eq!(cx.shard_recipients(), vec![5, 17, 42]);
+
+    let data = (0..1000u32).collect::<Vec<_>>();
+    let (shards, acc) = get_acc(&cx, &data, PROPOSE_DOMAIN).unwrap();
+    let pp = cx.accumulator_pub_params_map.get(&0).unwrap();
+    let pk = cx.pub_key_map.get(&0).unwrap();
+    // Every replica but 17 forwards the shard the leader handed it
+    let mut gatherer = ShareGatherer::new(4, 3, PROPOSE_DOMAIN);
+    for id in [0, 5, 42].iter() {
+        let n = cx.shard_index(*id).unwrap();
+        gatherer.add_share(Bytes::from(shards[n as usize].clone()), n, pp, pk, get_sign(&acc, n));
+    }
+    assert_eq!(gatherer.shard_num, 3);
+    assert_eq!(gatherer.reconstruct(4, 1), Some(to_bytes(&data).unwrap()));
+}
+
+#[tokio::test]
+async fn a_cluster_with_sparse_ids_commits_and_agrees_on_beacons() {
+    // Far enough that the beacons come from shares dealt during the run,
+    // not just from those every replica started with
+    const EPOCHS: Height = 7;
+    let ids: Vec<Replica> = vec![3, 8, 21, 40];
+    let configs = common::with_ids(common::generate_configs(4, 1, EPOCHS as usize + 1), &ids);
+    for config in configs.iter() {
+        config.validate().unwrap();
+    }
+    let cluster = common::spawn_cluster(configs);
+    let mut streams: Vec<_> = cluster.events.iter().map(subscribe).collect();
+
+    let mut runs = Vec::with_capacity(streams.len());
+    for (id, stream) in ids.iter().zip(streams.iter_mut()) {
+        let run = async {
+            let (mut committed, mut beacons) = (Vec::new(), Vec::new());
+            while let Some(Ok(ev)) = stream.next().await {
+                match ev {
+                    NodeEvent::BlockCommitted(h, hash) if h > 0 => committed.push((h, hash)),
+                    NodeEvent::BeaconProduced(e, beacon) if e > 0 => {
+                        beacons.push(beacon);
+                        if e == EPOCHS {
+                            break;
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            (committed, beacons)
+        };
+        runs.push(
+            tokio::time::timeout(Duration::from_secs(60), run)
+                .await
+                .unwrap_or_else(|_| panic!("replica {} did not get through {} epochs", id, EPOCHS)),
+        );
+    }
+
+    let (committed, beacons) = &runs[0];
+    assert!(!committed.is_empty());
+    assert_eq!(beacons.len(), EPOCHS as usize);
+    assert!(beacons.iter().all(|b| *b != [0; 32]), "a beacon was not rebuilt");
+    for (id, run) in ids.iter().zip(runs.iter()) {
+        let n = run.0.len().min(committed.len());
+        assert_eq!(run.0[..n], committed[..n], "replica {}", id);
+        assert_eq!(&run.1, beacons, "replica {}", id);
+    }
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use bytes::Bytes;
+use consensus::bft::node::accumulator::{get_acc, get_sign};
+use consensus::bft::node::context::Context;
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use crypto::hash::PROPOSE_DOMAIN;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use types::{Destination, Gatherer, ProtocolMsg};
+
+#[test]
+fn missing_shards_are_asked_of_their_owners_and_of_replicas_heard_from() {
+    let configs = common::generate_configs(4, 1, 1);
+    let mut cxs: Vec<_> = configs.iter().map(common::context_and_sent).collect();
+    let leader = cxs[0].0.last_leader;
+    let (shards, acc) = get_acc(&cxs[leader as usize].0, &"proposal", PROPOSE_DOMAIN).unwrap();
+    let pp = cxs[0].0.accumulator_pub_params_map[&leader].clone();
+    let pk = cxs[0].0.pub_key_map[&leader].clone();
+    let add = |cx: &mut Context, n: u16| {
+        cx.propose_gatherer
+            .add_share(Bytes::from(shards[n as usize].clone()), n, &pp, &pk, get_sign(&acc, n));
+    };
+    // Replica 1 has every shard; replica 3 lost all but its own and 1's
+    for n in 0..4 {
+        add(&mut cxs[1].0, n);
+    }
+    add(&mut cxs[3].0, 1);
+    add(&mut cxs[3].0, 3);
+
+    assert_eq!(cxs[3].0.pull_missing_shares(Gatherer::Propose), 2);
+    let mut requests = Vec::new();
+    while let Ok((to, msg)) = cxs[3].1.try_recv() {
+        match (to, msg.as_ref()) {
+            (Destination::Subset(to), ProtocolMsg::RequestShare(_, Gatherer::Propose, n)) => requests.push((*n, to)),
+            (_, other) => panic!("unexpected {}", other.to_string()),
+        }
+    }
+    assert_eq!(requests, vec![(0, vec![1, 0]), (2, vec![1, 2])]);
+
+    // Replica 1 answers with the shards it has
+    for (n, _) in requests.iter() {
+        cxs[1].0.answer_share_request(3, 0, Gatherer::Propose, *n);
+    }
+    while let Ok((to, msg)) = cxs[1].1.try_recv() {
+        assert_eq!(to, Destination::One(3));
+        match msg.as_ref() {
+            ProtocolMsg::DeliverPropose(sh, n, sign, _) => {
+                cxs[3].0.propose_gatherer.add_share(sh.clone(), *n, &pp, &pk, sign.clone())
+            }
+            other => panic!("unexpected {}", other.to_string()),
+        }
+    }
+    let cx = &mut cxs[3].0;
+    assert_eq!(cx.propose_gatherer.shard_num, 4);
+    let rebuilt: String = bincode::deserialize(&cx.propose_gatherer.reconstruct(4, 1).unwrap()).unwrap();
+    assert_eq!(rebuilt, "proposal");
+    // Nothing more to ask for
+    assert_eq!(cx.pull_missing_shares(Gatherer::Propose), 0);
+}
+
+#[tokio::test]
+async fn a_replica_that_lost_most_shards_pulls_them_and_commits() {
+    let cluster = common::spawn_cluster(common::generate_configs(4, 1, 4));
+    // Replica 3 never hears the shards of replicas 1 and 2 from them
+    cluster
+        .losses
+        .set(|from, to, msg| to == 3 && (from == 1 || from == 2) && msg.to_string() == "DeliverPropose");
+    let mut stream = subscribe(&cluster.events[3]);
+    let mut heights = Vec::new();
+    let collect = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::ViewChange(3) => break,
+                NodeEvent::BlockCommitted(h, _) if h > 0 => heights.push(h),
+                _ => (),
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(60), collect)
+        .await
+        .expect("did not finish two epochs");
+    assert!(cluster.frames_of("RequestShare") > 0);
+    assert_eq!(heights, vec![1, 2]);
+}
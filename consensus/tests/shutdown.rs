@@ -0,0 +1,38 @@
+mod common;
+
+use consensus::bft::node::clock::RealClock;
+use consensus::bft::node::entropy::entropy_channel;
+use consensus::bft::node::events::event_channel;
+use consensus::bft::node::intake::tx_channel;
+use consensus::bft::node::reactor;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{channel, unbounded_channel};
+
+#[tokio::test]
+async fn closed_net_channel_stops_reactor() {
+    let configs = common::generate_configs(4, 1, 4);
+    let (net_send, net_send_recv) = channel(util::CHANNEL_SIZE);
+    let (_net_recv_send, net_recv) = unbounded_channel();
+    let (cli_send, _cli_send_recv) = unbounded_channel();
+    let (_cli_recv_send, cli_recv) = tx_channel(16);
+    // The network task is gone before the reactor sends anything.
+    drop(net_send_recv);
+    let res = tokio::time::timeout(
+        Duration::from_secs(10),
+        reactor(
+            &configs[0],
+            false,
+            net_send,
+            net_recv,
+            cli_send,
+            cli_recv,
+            event_channel(),
+            Arc::new(RealClock),
+            entropy_channel().1,
+            None,
+        ),
+    )
+    .await;
+    assert!(res.is_ok(), "reactor did not shut down");
+}
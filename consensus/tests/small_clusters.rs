@@ -0,0 +1,59 @@
+mod common;
+
+use config::ParseError;
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+#[test]
+fn two_replicas_are_rejected() {
+    let config = common::generate_configs(2, 0, 1).remove(0);
+    match config.validate() {
+        Err(ParseError::TooFewNodes(2)) => (),
+        r => panic!("expected too few nodes, got {:?}", r),
+    }
+    match common::try_context(&config) {
+        Err(ParseError::TooFewNodes(2)) => (),
+        Err(e) => panic!("expected too few nodes, got {}", e),
+        Ok(_) => panic!("a context was made for two replicas"),
+    }
+}
+
+#[tokio::test]
+async fn solo_replica_commits_and_produces_beacons() {
+    let mut config = common::generate_configs(1, 0, 8).remove(0);
+    assert!(config.validate().is_err());
+    config.solo = true;
+    config.validate().expect("solo config is invalid");
+
+    let cluster = common::spawn_cluster(vec![config]);
+    let mut stream = subscribe(&cluster.events[0]);
+    let mut heights = HashMap::new();
+    let mut beacons = HashMap::new();
+    let mut epoch = 0;
+    let collect = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::ViewChange(5) => break,
+                NodeEvent::ViewChange(e) => epoch = e,
+                NodeEvent::BlockCommitted(h, _) => {
+                    heights.insert(epoch, h);
+                }
+                NodeEvent::BeaconProduced(e, b) => {
+                    beacons.insert(e, b);
+                }
+                _ => (),
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(30), collect)
+        .await
+        .expect("did not finish four epochs");
+
+    for e in 1..5 {
+        assert_eq!(heights.get(&e), Some(&e), "epoch {}", e);
+        assert_ne!(beacons[&e], [0; 32], "epoch {}", e);
+    }
+    assert_ne!(beacons[&1], beacons[&2]);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_stream::StreamExt;
+
+/// Starts replica `i` of 4 after `150 * i` ms and returns how far apart the
+/// first three started epoch 1
+async fn epoch_one_spread(startup_barrier: bool) -> Duration {
+    let mut configs = common::generate_configs(4, 1, 4);
+    for config in configs.iter_mut() {
+        config.startup_barrier = startup_barrier;
+    }
+    let delays = (0..4).map(|i| Duration::from_millis(150 * i)).collect();
+    let cluster = common::spawn_cluster_staggered(configs, delays);
+    let started = (0..3).map(|i| {
+        let mut stream = subscribe(&cluster.events[i]);
+        async move {
+            while let Some(Ok(ev)) = stream.next().await {
+                if let NodeEvent::ViewChange(1) = ev {
+                    return Instant::now();
+                }
+            }
+            panic!("event channel closed");
+        }
+    });
+    let started = tokio::time::timeout(Duration::from_secs(10), futures::future::join_all(started))
+        .await
+        .expect("epoch 1 never started");
+    let first = *started.iter().min().unwrap();
+    let last = *started.iter().max().unwrap();
+    last - first
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn replicas_past_the_barrier_start_together() {
+    // Replica 2 completes the quorum, and 0, 1 and 2 start as one
+    let spread = epoch_one_spread(true).await;
+    assert!(spread < Duration::from_millis(20), "epoch 1 started {:?} apart", spread);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn without_the_barrier_replicas_start_as_they_boot() {
+    let spread = epoch_one_spread(false).await;
+    assert!(spread >= Duration::from_millis(250), "epoch 1 started {:?} apart", spread);
+}
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use types::{Block, Storage};
+use util::io::to_bytes;
+
+fn block_at(height: u64) -> Arc<Block> {
+    let mut block = Block::new();
+    block.header.height = height;
+    block.update_hash();
+    Arc::new(block)
+}
+
+#[test]
+fn eviction_keeps_the_newest_window() {
+    let mut storage = Storage::bounded(0, 5, 3);
+    assert_eq!(storage.capacity(), 5);
+    for h in 0..20 {
+        storage.insert_committed(block_at(h));
+        assert!(storage.len() <= 5);
+    }
+    assert_eq!(storage.len(), 5);
+    assert_eq!(storage.evictions(), 15);
+    for h in 0..15 {
+        assert!(storage.committed_blocks_by_ht.get(&h).is_none());
+    }
+    for h in 15..20 {
+        let block = storage.committed_blocks_by_ht.get(&h).expect("block evicted");
+        assert!(storage.committed_blocks_by_hash.contains_key(&block.hash));
+    }
+    assert_eq!(storage.committed_blocks_by_hash.len(), 5);
+    let expected: usize = (15..20)
+        .map(|h| to_bytes(&*block_at(h)).unwrap().len())
+        .sum();
+    assert_eq!(storage.bytes_used(), expected);
+}
+
+#[test]
+fn retained_window_outlasts_capacity() {
+    let mut storage = Storage::bounded(0, 2, 4);
+    for h in 0..10 {
+        storage.insert_committed(block_at(h));
+    }
+    // The newest four heights stay even though only two fit
+    assert_eq!(storage.len(), 4);
+    assert_eq!(storage.evictions(), 6);
+    for h in 6..10 {
+        assert!(storage.committed_blocks_by_ht.contains_key(&h));
+    }
+}
+
+#[test]
+fn unbounded_storage_never_evicts() {
+    let mut storage = Storage::new(0);
+    for h in 0..50 {
+        storage.insert_committed(block_at(h));
+    }
+    assert_eq!(storage.len(), 50);
+    assert_eq!(storage.evictions(), 0);
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use config::Node;
+use consensus::bft::node::context::{is_aggregate, Context};
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use crypto::rand::{rngs::StdRng, SeedableRng};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use types::{Certificate, Replica, Vote};
+use util::io::to_bytes;
+
+/// Deals a threshold key for `configs` like genconfig does
+fn with_threshold(mut configs: Vec<Node>) -> Vec<Node> {
+    let (n, f) = (configs[0].num_nodes, configs[0].num_faults);
+    let rng = &mut StdRng::seed_from_u64(common::SEED);
+    let (public, shares) = crypto::threshold::deal(n, f + 1, rng);
+    let public_shares: HashMap<Replica, Vec<u8>> = shares
+        .iter()
+        .map(|(share, pk)| (share.index(), pk.to_bytes()))
+        .collect();
+    for (config, (share, _)) in configs.iter_mut().zip(shares.iter()) {
+        config.threshold_certificates = true;
+        config.threshold_secret = share.to_bytes();
+        config.threshold_public = public.to_bytes();
+        config.threshold_shares = public_shares.clone();
+    }
+    configs
+}
+
+fn contexts(configs: &[Node]) -> Vec<Context> {
+    configs.iter().map(|config| common::context_and_sent(config).0).collect()
+}
+
+/// The certificate replica 0 forms from the votes of the first `f + 1`
+/// replicas for `hash`
+fn certify(cxs: &[Context], hash: &[u8; 32]) -> Certificate {
+    let votes = cxs
+        .iter()
+        .take(cxs[0].num_faults as usize + 1)
+        .map(|cx| Vote {
+            msg: hash.to_vec(),
+            origin: cx.myid,
+            auth: cx.vote_auth(hash),
+        })
+        .collect();
+    cxs[0].certificate_of(votes)
+}
+
+#[test]
+fn votes_aggregate_into_one_constant_size_signature() {
+    let hash = [7; 32];
+    let mut sizes = Vec::new();
+    for &(n, f) in [(4, 1), (7, 3)].iter() {
+        let configs = common::generate_configs(n, f, 1);
+        let plain = certify(&contexts(&configs), &hash);
+        let cxs = contexts(&with_threshold(configs));
+        let aggregated = certify(&cxs, &hash);
+
+        assert!(!is_aggregate(&plain));
+        assert_eq!(plain.votes.len(), f + 1);
+        assert!(is_aggregate(&aggregated));
+        // Any replica checks it against the one threshold key
+        for cx in cxs.iter() {
+            assert!(cx.certifies(&aggregated, &hash));
+            assert!(!cx.certifies(&aggregated, &[8; 32]));
+        }
+        let mut forged = aggregated.clone();
+        forged.votes[0].auth = cxs[1].vote_auth(&hash);
+        assert!(!cxs[2].certifies(&forged, &hash));
+
+        let (plain, aggregated) = (to_bytes(&plain).unwrap().len(), to_bytes(&aggregated).unwrap().len());
+        println!("n = {}, f = {}: {} bytes with every vote, {} aggregated", n, f, plain, aggregated);
+        assert!(aggregated < plain);
+        sizes.push(aggregated);
+    }
+    // The aggregated certificate does not grow with the threshold
+    assert_eq!(sizes[0], sizes[1]);
+}
+
+#[test]
+fn votes_below_the_threshold_do_not_certify() {
+    let cxs = contexts(&with_threshold(common::generate_configs(4, 1, 1)));
+    let hash = [7; 32];
+    let vote = Vote {
+        msg: hash.to_vec(),
+        origin: 1,
+        auth: cxs[1].vote_auth(&hash),
+    };
+    assert!(cxs[0].verify_vote(&vote));
+    let single = cxs[0].certificate_of(vec![vote]);
+    assert!(is_aggregate(&single));
+    assert!(!cxs[0].certifies(&single, &hash));
+}
+
+#[tokio::test]
+async fn a_cluster_certifies_with_threshold_signatures() {
+    let cluster = common::spawn_cluster(with_threshold(common::generate_configs(4, 1, 4)));
+    let mut stream = subscribe(&cluster.events[1]);
+    let mut certified = 0;
+    let mut heights = Vec::new();
+    let collect = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            match ev {
+                NodeEvent::ViewChange(3) => break,
+                NodeEvent::VoteCertified(_, cert) => {
+                    assert!(is_aggregate(&cert), "a certificate is not aggregated");
+                    certified += 1;
+                }
+                NodeEvent::BlockCommitted(h, _) if h > 0 => heights.push(h),
+                _ => (),
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(60), collect)
+        .await
+        .expect("did not finish two epochs");
+    assert!(certified >= 2);
+    assert_eq!(heights, vec![1, 2]);
+}
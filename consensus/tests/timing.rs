@@ -0,0 +1,103 @@
+mod common;
+
+use config::Timing;
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use crypto::hash::Hash;
+use std::time::{Duration, Instant};
+use tokio_stream::StreamExt;
+use types::Height;
+
+const EPOCHS: Height = 3;
+
+/// Windows that are no multiple of delta, with a longer epoch than 11Δ
+fn timing() -> Timing {
+    Timing {
+        propose: Some(100),
+        deliver_propose: Some(400),
+        deliver_commit: Some(500),
+        vote: Some(70),
+        commit: Some(130),
+        epoch: Some(800),
+        ack_wait: Some(100),
+        stall_margin: Some(150),
+    }
+}
+
+/// What one replica went through, in the order it did
+#[derive(Default)]
+struct Run {
+    proposed: Vec<Height>,
+    certified: Vec<Height>,
+    committed: Vec<(Height, Hash)>,
+    /// When each epoch after the first started
+    started: Vec<(Height, Instant)>,
+}
+
+#[tokio::test]
+async fn a_cluster_with_its_own_timing_runs_its_epochs_in_order() {
+    let mut configs = common::generate_configs(4, 1, EPOCHS as usize + 1);
+    for config in configs.iter_mut() {
+        config.timing = timing();
+        config.phase_durations().validate().unwrap();
+    }
+    let durations = configs[0].phase_durations();
+    assert_ne!(durations, Timing::default().resolve(configs[0].delta));
+    let cluster = common::spawn_cluster(configs);
+    let streams: Vec<_> = cluster.events.iter().map(subscribe).collect();
+
+    // All followed at once, so that epochs are timed as they end
+    let follow = streams.into_iter().enumerate().map(|(i, mut stream)| async move {
+        let run = async {
+            let mut run = Run::default();
+            while let Some(Ok(ev)) = stream.next().await {
+                match ev {
+                    NodeEvent::BlockProposed(e, _) if run.proposed.last() != Some(&e) => run.proposed.push(e),
+                    NodeEvent::VoteCertified(e, _) if run.certified.last() != Some(&e) => {
+                        // A certificate follows the proposal of its epoch
+                        assert_eq!(run.proposed.last(), Some(&e), "replica {}", i);
+                        run.certified.push(e);
+                    }
+                    NodeEvent::BlockCommitted(h, hash) if h > 0 => {
+                        // Nothing is committed before it is certified
+                        assert!(!run.certified.is_empty(), "replica {} committed {} uncertified", i, h);
+                        run.committed.push((h, hash));
+                    }
+                    NodeEvent::PhaseStalled(e, phase) => panic!("replica {} stalled in {} of epoch {}", i, phase, e),
+                    NodeEvent::ViewChange(e) if e > 1 => {
+                        // The epoch before was certified before it ended
+                        assert_eq!(run.certified.last(), Some(&(e - 1)), "replica {}", i);
+                        run.started.push((e, Instant::now()));
+                        if e == EPOCHS + 1 {
+                            break;
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            run
+        };
+        tokio::time::timeout(Duration::from_secs(60), run)
+            .await
+            .unwrap_or_else(|_| panic!("replica {} did not get through {} epochs", i, EPOCHS))
+    });
+    let runs = futures::future::join_all(follow).await;
+
+    let expected = &runs[0].committed;
+    let heights: Vec<_> = expected.iter().map(|(h, _)| *h).collect();
+    assert_eq!(heights, (1..=EPOCHS).collect::<Vec<_>>());
+    for (i, run) in runs.iter().enumerate() {
+        assert_eq!(&run.committed, expected, "replica {}", i);
+        assert_eq!(run.certified, (1..=EPOCHS).collect::<Vec<_>>(), "replica {}", i);
+        // Epochs last as long as configured, not 11Δ
+        for pair in run.started.windows(2) {
+            let took = pair[1].1 - pair[0].1;
+            assert!(
+                took >= Duration::from_millis(durations.epoch - durations.vote),
+                "replica {} went through epoch {} in {:?}",
+                i,
+                pair[0].0,
+                took
+            );
+        }
+    }
+}
@@ -0,0 +1,297 @@
+mod common;
+
+use bytes::Bytes;
+use config::Node;
+use consensus::bft::node::accumulator::{get_acc, get_sign};
+use consensus::bft::node::network::{self, Credentials};
+use crypto::hash::PROPOSE_DOMAIN;
+use crypto_lib::ed25519;
+use futures::{SinkExt, StreamExt};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
+use tokio_util::codec::{FramedRead, FramedWrite};
+use types::{Destination, ProtocolMsg, Vote};
+use util::codec::{proto, EnCodec};
+use util::sequence::Sequenced;
+
+/// How long the reactor's channel has to stay full to count as held up
+const STALLED: Duration = Duration::from_millis(500);
+
+/// A vote of 64 KiB that says which one it is
+fn vote(i: u64) -> Arc<ProtocolMsg> {
+    Arc::new(ProtocolMsg::Vote(
+        Vote {
+            msg: i.to_be_bytes().to_vec(),
+            origin: 0,
+            auth: vec![0; 64 * 1024],
+        },
+        1,
+    ))
+}
+
+fn frame(seq: u64, msg: ProtocolMsg) -> Sequenced<Arc<ProtocolMsg>> {
+    Sequenced {
+        seq,
+        frame: Arc::new(msg),
+    }
+}
+
+/// Listeners for replicas 0 and 1, as the only replicas in `config`
+async fn bind(config: &mut Node) -> (TcpListener, TcpListener) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let peer = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    config.net_map = vec![
+        (0, listener.local_addr().unwrap().to_string()),
+        (1, peer.local_addr().unwrap().to_string()),
+    ]
+    .into_iter()
+    .collect();
+    (listener, peer)
+}
+
+/// Replica 1 connecting to replica 0 at `ours`, with the connection it
+/// dialed, the one it accepted on `peer`, and `peer` to take the next one.
+/// Replica 0 is told to send from its first frame.
+fn replica_one(config: &Node, ours: String, peer: TcpListener) -> JoinHandle<(TcpStream, TcpStream, TcpListener)> {
+    let creds = Credentials::from_config(config);
+    tokio::spawn(async move {
+        let mut dialed = TcpStream::connect(ours).await.unwrap();
+        creds.hello(0, &mut dialed).await.unwrap();
+        let (mut accepted, _) = peer.accept().await.unwrap();
+        assert_eq!(creds.admit(&mut accepted).await.unwrap(), 0);
+        accepted.write_u64(0).await.unwrap();
+        (dialed, accepted, peer)
+    })
+}
+
+/// The frame replica 0 expects next on `dialed`, and the connection to send
+/// it frames on
+async fn resume(mut dialed: TcpStream) -> (u64, FramedWrite<TcpStream, EnCodec>) {
+    let next = dialed.read_u64().await.unwrap();
+    (next, FramedWrite::new(dialed, EnCodec::new()))
+}
+
+/// Lets in the connection replica 0 dialed to replica 1 again, telling it
+/// to resume from frame `next`
+async fn let_in(config: &Node, mut accepted: TcpStream, next: u64) -> FramedRead<TcpStream, proto::SeqCodec> {
+    assert_eq!(Credentials::from_config(config).admit(&mut accepted).await.unwrap(), 0);
+    accepted.write_u64(next).await.unwrap();
+    FramedRead::new(accepted, proto::SeqCodec(proto::Codec::new()))
+}
+
+/// Which vote replica 0 sent as frame `seq`
+async fn next_vote(framed: &mut FramedRead<TcpStream, proto::SeqCodec>, seq: u64) -> u64 {
+    match framed.next().await.unwrap().unwrap() {
+        Sequenced {
+            seq: s,
+            frame: Some(ProtocolMsg::Vote(v, 1)),
+        } if s == seq => {
+            let mut i = [0; 8];
+            i.copy_from_slice(&v.msg);
+            u64::from_be_bytes(i)
+        }
+        other => panic!("unexpected {:?} as frame {}", other.frame.map(|m| m.to_string()), seq),
+    }
+}
+
+#[tokio::test]
+async fn a_peer_that_stops_reading_holds_up_the_reactor() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    configs[0].consensus_channel = Some(16);
+    let (listener, peer) = bind(&mut configs[0]).await;
+    let one = replica_one(&configs[1], configs[0].net_map[&0].clone(), peer);
+    let (net_send, _net_recv) = network::start(&configs[0], listener).await;
+    let (_dialed, conn, _) = one.await.unwrap();
+
+    // Replica 1 reads nothing, so once the socket and the queues are full
+    // the reactor cannot hand over anything more
+    let mut sent = 0;
+    let mut full_since = None;
+    loop {
+        match net_send.try_send((Destination::One(1), vote(sent))) {
+            Ok(()) => {
+                sent += 1;
+                full_since = None;
+            }
+            Err(TrySendError::Full(_)) => {
+                let since = *full_since.get_or_insert_with(Instant::now);
+                if since.elapsed() > STALLED {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Err(TrySendError::Closed(_)) => panic!("the network went away"),
+        }
+        assert!(sent < 1024, "handed over {} votes of 64 KiB to a peer that reads nothing", sent);
+    }
+
+    // Nothing was dropped on the way: once it reads, it gets every vote in order
+    let mut framed = FramedRead::new(conn, proto::SeqCodec(proto::Codec::new()));
+    for i in 0..sent {
+        assert_eq!(next_vote(&mut framed, i).await, i);
+    }
+    net_send.try_send((Destination::One(1), vote(sent))).unwrap();
+}
+
+#[tokio::test]
+async fn a_peer_is_only_taken_with_the_key_of_its_id() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    let (listener, peer) = bind(&mut configs[0]).await;
+    let ours = configs[0].net_map[&0].clone();
+    let config = configs[0].clone();
+    let node = tokio::spawn(async move { network::start(&config, listener).await });
+
+    // Replica 2 claiming to be replica 1, then a key the cluster does not know
+    let mut impostor = configs[2].clone();
+    impostor.id = 1;
+    let mut stranger = configs[1].clone();
+    stranger.secret_key_bytes = ed25519::Keypair::generate().encode().to_vec();
+    for config in [impostor, stranger].iter() {
+        let mut stream = TcpStream::connect(&ours).await.unwrap();
+        assert!(Credentials::from_config(config).hello(0, &mut stream).await.is_err());
+    }
+
+    // The real replica 1 gets in, and what it sends is taken as its own
+    let (dialed, _accepted, _) = replica_one(&configs[1], ours, peer).await.unwrap();
+    let (_net_send, mut net_recv) = node.await.unwrap();
+    let (next, mut framed) = resume(dialed).await;
+    assert_eq!(next, 0);
+    framed.send(frame(0, ProtocolMsg::Ready(true))).await.unwrap();
+    match net_recv.recv().await.unwrap() {
+        (1, ProtocolMsg::Ready(true)) => (),
+        (from, msg) => panic!("unexpected {} from {}", msg.to_string(), from),
+    }
+}
+
+#[tokio::test]
+async fn a_peer_that_reconnects_is_heard_again() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    let (listener, peer) = bind(&mut configs[0]).await;
+    let ours = configs[0].net_map[&0].clone();
+    let one = replica_one(&configs[1], ours.clone(), peer);
+    let (_net_send, mut net_recv) = network::start(&configs[0], listener).await;
+    let (dialed, _accepted, _) = one.await.unwrap();
+    let (_, mut framed) = resume(dialed).await;
+    framed.send(frame(0, ProtocolMsg::Ready(true))).await.unwrap();
+    assert!(matches!(net_recv.recv().await.unwrap(), (1, ProtocolMsg::Ready(true))));
+
+    // Its connection breaks, and it dials again. It is told to go on after
+    // what was delivered, and what it sends twice is only taken once.
+    drop(framed);
+    let mut dialed = TcpStream::connect(ours).await.unwrap();
+    Credentials::from_config(&configs[1]).hello(0, &mut dialed).await.unwrap();
+    let (next, mut framed) = resume(dialed).await;
+    assert_eq!(next, 1);
+    framed.send(frame(0, ProtocolMsg::Ready(true))).await.unwrap();
+    framed.send(frame(1, ProtocolMsg::Ready(false))).await.unwrap();
+    match net_recv.recv().await.unwrap() {
+        (1, ProtocolMsg::Ready(false)) => (),
+        (from, msg) => panic!("unexpected {} from {}", msg.to_string(), from),
+    }
+}
+
+#[tokio::test]
+async fn what_a_peer_did_not_acknowledge_is_sent_again() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    let (listener, peer) = bind(&mut configs[0]).await;
+    let one = replica_one(&configs[1], configs[0].net_map[&0].clone(), peer);
+    let (net_send, _net_recv) = network::start(&configs[0], listener).await;
+    let (_dialed, accepted, peer) = one.await.unwrap();
+
+    // Replica 1 reads three votes but only delivers the first before the
+    // connection breaks
+    let mut framed = FramedRead::new(accepted, proto::SeqCodec(proto::Codec::new()));
+    for i in 0..3 {
+        net_send.send((Destination::One(1), vote(i))).await.unwrap();
+        assert_eq!(next_vote(&mut framed, i).await, i);
+    }
+    framed.get_mut().write_u64(1).await.unwrap();
+    drop(framed);
+
+    // Replica 0 dials again and goes on from the second
+    let (accepted, _) = peer.accept().await.unwrap();
+    let mut framed = let_in(&configs[1], accepted, 1).await;
+    for i in 1..3 {
+        assert_eq!(next_vote(&mut framed, i).await, i);
+    }
+}
+
+#[tokio::test]
+async fn what_is_sent_to_a_peer_that_is_away_waits_for_it() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    configs[0].dead_letter_capacity = 8;
+    let (listener, peer) = bind(&mut configs[0]).await;
+    let one = replica_one(&configs[1], configs[0].net_map[&0].clone(), peer);
+    let (net_send, _net_recv) = network::start(&configs[0], listener).await;
+    let (_dialed, accepted, peer) = one.await.unwrap();
+
+    // Replica 1 goes away. Replica 0 notices and is dialing it again when
+    // the votes are sent.
+    drop(accepted);
+    let (accepted, _) = peer.accept().await.unwrap();
+    for i in 0..2 {
+        net_send.send((Destination::One(1), vote(i))).await.unwrap();
+    }
+
+    // Once it is back it gets them
+    let mut framed = let_in(&configs[1], accepted, 0).await;
+    for i in 0..2 {
+        assert_eq!(next_vote(&mut framed, i).await, i);
+    }
+}
+
+#[tokio::test]
+async fn a_peer_unreachable_during_deliver_gets_its_shard_when_back() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    configs[0].dead_letter_capacity = 8;
+    configs[0].send_ttl = 1;
+    let cx = common::context_and_sent(&configs[0]).0;
+    let (shards, acc) = get_acc(&cx, &"proposal", PROPOSE_DOMAIN).unwrap();
+    let share = |e| Arc::new(ProtocolMsg::DeliverPropose(Bytes::from(shards[1].clone()), 1, get_sign(&acc, 1), e));
+
+    let (listener, peer) = bind(&mut configs[0]).await;
+    let one = replica_one(&configs[1], configs[0].net_map[&0].clone(), peer);
+    let (net_send, _net_recv) = network::start(&configs[0], listener).await;
+    let (_dialed, accepted, peer) = one.await.unwrap();
+
+    // Replica 1 is away while replica 0 delivers its shards of epochs 1 and
+    // 2, so by the time it is back the deliver phase of epoch 1 is over
+    drop(accepted);
+    let (accepted, _) = peer.accept().await.unwrap();
+    for e in 1..3 {
+        net_send.send((Destination::One(1), share(e))).await.unwrap();
+    }
+
+    // It only gets the shard it can still use
+    let mut framed = let_in(&configs[1], accepted, 0).await;
+    match framed.next().await.unwrap().unwrap() {
+        Sequenced {
+            seq: 0,
+            frame: Some(ProtocolMsg::DeliverPropose(sh, 1, _, 2)),
+        } => assert_eq!(sh, Bytes::from(shards[1].clone())),
+        other => panic!("unexpected {:?}", other.frame.map(|m| m.to_string())),
+    }
+}
+
+#[tokio::test]
+async fn a_peer_hears_where_to_resume_before_the_others_are_up() {
+    let mut configs = common::generate_configs(4, 1, 1);
+    let (listener, peer) = bind(&mut configs[0]).await;
+    // Replica 2 is in the config, but nothing listens where it should
+    let away = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    configs[0].net_map.insert(2, away.local_addr().unwrap().to_string());
+    drop(away);
+    let ours = configs[0].net_map[&0].clone();
+    let config = configs[0].clone();
+    tokio::spawn(async move { network::start(&config, listener).await });
+
+    let (dialed, _accepted, _) = replica_one(&configs[1], ours, peer).await.unwrap();
+    let (next, _) = tokio::time::timeout(Duration::from_secs(1), resume(dialed))
+        .await
+        .expect("replica 1 was only told where to resume once every peer was up");
+    assert_eq!(next, 0);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use consensus::bft::node::validate::{validate_block, BlockError};
+use crypto::hash::empty_hash;
+use std::sync::Arc;
+use types::{Block, Certificate, Vote};
+
+fn child_of_genesis() -> Block {
+    let mut block = Block::new();
+    block.header.prev = empty_hash();
+    block.header.height = 1;
+    block.header.author = 1;
+    block.update_hash();
+    block
+}
+
+#[tokio::test]
+async fn invalid_blocks_are_refused() {
+    let configs = common::generate_configs(4, 1, 1);
+    let (mut cx, _net_recv) = common::context_and_sent(&configs[0]);
+
+    let good = child_of_genesis();
+    assert_eq!(validate_block(&good, &cx), Ok(()));
+
+    let mut wrong_prev = child_of_genesis();
+    wrong_prev.header.prev = [1; 32];
+    wrong_prev.update_hash();
+    assert_eq!(
+        validate_block(&wrong_prev, &cx),
+        Err(BlockError::UnknownParent([1; 32]))
+    );
+
+    let mut wrong_height = child_of_genesis();
+    wrong_height.header.height = 2;
+    wrong_height.update_hash();
+    assert_eq!(
+        validate_block(&wrong_height, &cx),
+        Err(BlockError::WrongHeight(1, 2))
+    );
+
+    let mut tampered = child_of_genesis();
+    tampered.header.extra = vec![1, 2, 3];
+    assert_eq!(
+        validate_block(&tampered, &cx),
+        Err(BlockError::HashMismatch)
+    );
+
+    // A block on top of a known block has to carry that block's certificate.
+    cx.commit_block(Arc::new(good.clone()));
+    let mut uncertified = Block::new();
+    uncertified.header.prev = good.hash;
+    uncertified.header.height = 2;
+    uncertified.update_hash();
+    assert_eq!(
+        validate_block(&uncertified, &cx),
+        Err(BlockError::ParentNotCertified)
+    );
+    uncertified.certificate = Certificate {
+        votes: vec![Vote {
+            msg: good.hash.to_vec(),
+            origin: 1,
+            auth: Vec::new(),
+        }],
+    };
+    assert_eq!(validate_block(&uncertified, &cx), Ok(()));
+
+    // Once we hold a vote certificate, only the block it is for commits.
+    cx.received_certificate = Some(uncertified.certificate.clone());
+    assert_eq!(
+        validate_block(&uncertified, &cx),
+        Err(BlockError::NotVotedFor)
+    );
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use consensus::bft::node::entropy::{delay, verify_beacon};
+use consensus::bft::node::events::{subscribe, NodeEvent};
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+const ITERATIONS: u64 = 64;
+
+#[tokio::test]
+async fn the_delayed_beacon_verifies_and_tampering_does_not() {
+    let cluster = common::spawn_cluster(common::generate_configs(4, 1, 1));
+    let plain = common::first_epoch(&cluster).await[0]
+        .beacon
+        .expect("no beacon produced");
+
+    let mut configs = common::generate_configs(4, 1, 1);
+    for config in configs.iter_mut() {
+        config.vdf_iterations = ITERATIONS;
+    }
+    let cluster = common::spawn_cluster(configs);
+    let mut stream = subscribe(&cluster.events[0]);
+    let outcomes = common::first_epoch(&cluster).await;
+    let delayed = outcomes[0].beacon.expect("no beacon produced");
+    assert_ne!(delayed, plain);
+    for outcome in outcomes.iter() {
+        assert_eq!(outcome.beacon, Some(delayed));
+    }
+
+    let proof = async {
+        while let Some(Ok(ev)) = stream.next().await {
+            if let NodeEvent::BeaconDelayed(1, beacon, proof) = ev {
+                return (beacon, proof);
+            }
+        }
+        panic!("event channel closed");
+    };
+    let (beacon, proof) = tokio::time::timeout(Duration::from_secs(5), proof)
+        .await
+        .expect("the beacon was not delayed");
+    assert_eq!(beacon, plain);
+    assert_eq!(delay(&plain, ITERATIONS), (delayed, proof.clone()));
+    assert!(verify_beacon(&plain, &delayed, ITERATIONS, &proof));
+
+    // Another output, another input, another value or another number of
+    // steps all fail
+    let mut forged = proof.clone();
+    forged.output[40] ^= 1;
+    assert!(!verify_beacon(&plain, &forged.value(), ITERATIONS, &forged));
+    let mut other = plain;
+    other[0] ^= 1;
+    assert!(!verify_beacon(&other, &delayed, ITERATIONS, &proof));
+    let mut wrong = delayed;
+    wrong[0] ^= 1;
+    assert!(!verify_beacon(&plain, &wrong, ITERATIONS, &proof));
+    assert!(!verify_beacon(&plain, &delayed, ITERATIONS - 1, &proof));
+    let mut shortened = proof.clone();
+    shortened.iterations -= 1;
+    assert!(!verify_beacon(&plain, &shortened.value(), ITERATIONS - 1, &shortened));
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use config::MessageHandling;
+use consensus::bft::node::clock::RealClock;
+use consensus::bft::node::context::Context;
+use consensus::bft::node::entropy::entropy_channel;
+use consensus::bft::node::events::event_channel;
+use consensus::bft::node::intake::tx_channel;
+use consensus::bft::node::reactor;
+use consensus::bft::node::verifier::SignatureVerifier;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{channel, unbounded_channel};
+use types::{Certificate, ProtocolMsg, Vote};
+
+fn forged(mut vote: Vote) -> Vote {
+    vote.auth[0] ^= 1;
+    vote
+}
+
+/// Hands a strict replica 0 votes from replica 1 whose signatures do not
+/// verify, and waits for up to a second. Returns whether the reactor
+/// panicked, as it does on handling one.
+async fn feed_forged_votes(verify_workers: usize) -> bool {
+    let configs = common::generate_configs(4, 1, 4);
+    let bad = forged(common::vote(&common::context(&configs[1]), &[7; 32]));
+    let mut config = configs[0].clone();
+    config.message_handling = MessageHandling::Strict;
+    config.verify_workers = verify_workers;
+    let (net_send, mut net_send_recv) = channel(util::CHANNEL_SIZE);
+    let (net_recv_send, net_recv) = unbounded_channel();
+    let (cli_send, _cli_send_recv) = unbounded_channel();
+    let (_cli_recv_send, cli_recv) = tx_channel(16);
+    tokio::spawn(async move { while net_send_recv.recv().await.is_some() {} });
+    let node = tokio::spawn(async move {
+        reactor(
+            &config,
+            false,
+            net_send,
+            net_recv,
+            cli_send,
+            cli_recv,
+            event_channel(),
+            Arc::new(RealClock),
+            entropy_channel().1,
+            None,
+        )
+        .await
+    });
+    for e in 0..2 {
+        net_recv_send.send((1, ProtocolMsg::Vote(bad.clone(), e))).unwrap();
+    }
+    match tokio::time::timeout(Duration::from_secs(1), node).await {
+        Ok(res) => res.expect_err("reactor stopped").is_panic(),
+        Err(_) => false,
+    }
+}
+
+#[tokio::test]
+async fn the_reactor_never_handles_a_forged_signature() {
+    // Checked on the reactor, the forged vote is handled and aborts it
+    assert!(feed_forged_votes(0).await);
+    // The pool drops it before the reactor sees it
+    assert!(!feed_forged_votes(2).await);
+}
+
+#[tokio::test]
+async fn only_messages_that_verify_come_back() {
+    let configs = common::generate_configs(4, 1, 1);
+    let cxs: Vec<Context> = configs.iter().map(common::context).collect();
+    let leader = &cxs[0];
+    let invalid = Arc::new(AtomicU64::new(0));
+    let mut verifier = SignatureVerifier::new(4, Arc::clone(&invalid));
+
+    let good: Vec<Vote> = cxs.iter().map(|cx| common::vote(cx, &[7; 32])).collect();
+    for (i, v) in good.iter().enumerate() {
+        let msg = ProtocolMsg::Vote(v.clone(), 1);
+        verifier.submit(i as u16, msg.clone(), leader.signatures_of(&msg));
+        let msg = ProtocolMsg::Vote(forged(v.clone()), 1);
+        verifier.submit(i as u16, msg.clone(), leader.signatures_of(&msg));
+    }
+    // One bad vote spoils the whole certificate
+    let mut votes = good.clone();
+    votes[2] = forged(votes[2].clone());
+    let msg = ProtocolMsg::Certificate(Certificate { votes }, 1, 0);
+    verifier.submit(1, msg.clone(), leader.signatures_of(&msg));
+
+    let mut passed = Vec::new();
+    while let Ok((origin, msg, signatures)) = tokio::time::timeout(Duration::from_millis(500), verifier.next()).await {
+        match msg {
+            ProtocolMsg::Vote(v, _) => {
+                assert_eq!(signatures.len(), 1);
+                passed.push((origin, v.auth));
+            }
+            m => panic!("{} came back from the verifier", m.to_string()),
+        }
+    }
+    passed.sort();
+    let expected: Vec<_> = good.iter().map(|v| (v.origin, v.auth.clone())).collect();
+    assert_eq!(passed, expected);
+    assert_eq!(invalid.load(Ordering::Relaxed), 5);
+    assert_eq!(verifier.invalid(), 5);
+}
+
+/// How long `workers` take to verify a batch of certificates
+async fn verify_batch(cxs: &[Context], workers: usize) -> Duration {
+    let votes: Vec<Vote> = cxs.iter().map(|cx| common::vote(cx, &[7; 32])).collect();
+    let msg = ProtocolMsg::Certificate(Certificate { votes }, 1, 0);
+    let signatures = cxs[0].signatures_of(&msg);
+    let mut verifier = SignatureVerifier::new(workers, Arc::new(AtomicU64::new(0)));
+    let started = Instant::now();
+    for _ in 0..1000 {
+        verifier.submit(1, msg.clone(), signatures.clone());
+    }
+    for _ in 0..1000 {
+        verifier.next().await;
+    }
+    started.elapsed()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn verification_is_spread_over_the_workers() {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if cores < 2 {
+        return;
+    }
+    let configs = common::generate_configs(4, 1, 1);
+    let cxs: Vec<Context> = configs.iter().map(common::context).collect();
+    let one = verify_batch(&cxs, 1).await;
+    let many = verify_batch(&cxs, cores.min(4)).await;
+    assert!(
+        many.as_secs_f64() < one.as_secs_f64() * 0.8,
+        "{} workers took {:?}, one took {:?}",
+        cores.min(4),
+        many,
+        one
+    );
+}
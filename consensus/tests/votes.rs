@@ -0,0 +1,68 @@
+mod common;
+
+#[test]
+fn duplicate_and_late_votes_form_one_certificate() {
+    let configs = common::generate_configs(4, 1, 1);
+    let mut cxs: Vec<_> = configs.iter().map(common::context).collect();
+    let hash = [7; 32];
+    let votes: Vec<_> = cxs.iter().map(|cx| common::vote(cx, &hash)).collect();
+    let leader = &mut cxs[0];
+    let mut certificates = Vec::new();
+
+    // A vote for an earlier epoch does not count
+    assert!(leader.accept_vote(1, votes[1].clone(), 0).is_none());
+    // Replica 1 votes three times, once replaying replica 2's vote
+    assert!(leader.accept_vote(1, votes[1].clone(), 1).is_none());
+    assert!(leader.accept_vote(1, votes[1].clone(), 1).is_none());
+    assert!(leader.accept_vote(1, votes[2].clone(), 1).is_none());
+    // A vote that does not verify does not count either
+    let mut forged = votes[3].clone();
+    forged.auth = votes[1].auth.clone();
+    assert!(leader.accept_vote(3, forged, 1).is_none());
+    assert_eq!(leader.votes_for(1).len(), 1);
+
+    for (origin, v) in votes.iter().enumerate().skip(2) {
+        certificates.extend(leader.accept_vote(origin as u16, v.clone(), 1));
+    }
+    assert_eq!(certificates.len(), 1);
+    let origins: Vec<_> = certificates[0].votes.iter().map(|v| v.origin).collect();
+    assert_eq!(origins, vec![1, 2]);
+
+    // Replica 3 was too late
+    assert_eq!(leader.votes_for(1).len(), 2);
+}
+
+#[test]
+fn interleaved_votes_certify_the_right_proposal_of_each_epoch() {
+    let configs = common::generate_configs(4, 1, 1);
+    let mut cxs: Vec<_> = configs.iter().map(common::context).collect();
+    let (first, second, other) = ([1; 32], [2; 32], [3; 32]);
+    let first_votes: Vec<_> = cxs.iter().map(|cx| common::vote(cx, &first)).collect();
+    let second_votes: Vec<_> = cxs.iter().map(|cx| common::vote(cx, &second)).collect();
+    let stray = common::vote(&cxs[3], &other);
+    let leader = &mut cxs[0];
+    let msgs = |cert: &types::Certificate| -> Vec<Vec<u8>> { cert.votes.iter().map(|v| v.msg.clone()).collect() };
+    let origins = |cert: &types::Certificate| -> Vec<u16> { cert.votes.iter().map(|v| v.origin).collect() };
+
+    // Votes for the next epoch come in among those for this one
+    assert!(leader.accept_vote(1, second_votes[1].clone(), 2).is_none());
+    assert!(leader.accept_vote(3, stray, 1).is_none());
+    assert!(leader.accept_vote(2, second_votes[2].clone(), 2).is_none());
+    assert!(leader.accept_vote(1, first_votes[1].clone(), 1).is_none());
+    let cert = leader.accept_vote(2, first_votes[2].clone(), 1).expect("no certificate for epoch 1");
+    assert_eq!(origins(&cert), vec![1, 2]);
+    assert_eq!(msgs(&cert), vec![first.to_vec(); 2]);
+
+    // The next epoch starts as the reactor starts it
+    leader.epoch = 2;
+    leader.received_vote = leader.received_vote.split_off(&2);
+    // A straggler from epoch 1 does not count
+    assert!(leader.accept_vote(3, first_votes[3].clone(), 1).is_none());
+    // The early votes were a quorum already; the next vote brings out their
+    // certificate without being counted itself
+    let cert = leader.accept_vote(3, second_votes[3].clone(), 2).expect("no certificate for epoch 2");
+    assert_eq!(origins(&cert), vec![1, 2]);
+    assert_eq!(msgs(&cert), vec![second.to_vec(); 2]);
+    assert_eq!(leader.votes_for(2).len(), 2);
+    assert!(leader.accept_vote(3, second_votes[3].clone(), 2).is_none());
+}
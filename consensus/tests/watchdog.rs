@@ -0,0 +1,81 @@
+mod common;
+
+use consensus::bft::node::clock::{Clock, MockClock, Sleep};
+use consensus::bft::node::events::NodeEvent;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::TryRecvError;
+use tokio::time::Instant;
+
+const STEP: Duration = Duration::from_micros(500);
+const STALL_MARGIN: u64 = 100;
+
+/// A mock clock on which one deadline never comes
+struct StarvingClock {
+    inner: MockClock,
+    starved: Instant,
+}
+
+impl Clock for StarvingClock {
+    fn now(&self) -> Instant {
+        self.inner.now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Sleep {
+        if deadline == self.starved {
+            Box::pin(std::future::pending())
+        } else {
+            self.inner.sleep_until(deadline)
+        }
+    }
+}
+
+#[tokio::test]
+async fn a_starved_phase_is_abandoned_and_the_node_moves_on() {
+    let mut configs = common::generate_configs(4, 1, 6);
+    for config in configs.iter_mut() {
+        config.timing.stall_margin = Some(STALL_MARGIN);
+    }
+    let delta = Duration::from_millis(configs[0].delta);
+    let epoch = Duration::from_millis(configs[0].phase_durations().epoch);
+    let margin = Duration::from_millis(STALL_MARGIN);
+    let clock = MockClock::new();
+    // The end of epoch 2 never comes on any replica
+    let starving = StarvingClock {
+        inner: clock.clone(),
+        starved: clock.now() + delta + epoch * 2,
+    };
+    let cluster = common::spawn_cluster_with_clock(configs, Arc::new(starving));
+    let mut events = cluster.events[0].subscribe();
+
+    let mut view_changes = Vec::new();
+    let mut stalls = Vec::new();
+    let mut committed_since_stall = 0;
+    while view_changes.len() < 4 {
+        assert!(
+            clock.elapsed() < delta + epoch * 4 + margin,
+            "the node did not recover from the stall"
+        );
+        clock.advance(STEP);
+        for _ in 0..64 {
+            tokio::task::yield_now().await;
+        }
+        loop {
+            match events.try_recv() {
+                Ok(NodeEvent::ViewChange(e)) => view_changes.push((e, clock.elapsed())),
+                Ok(NodeEvent::PhaseStalled(e, phase)) => stalls.push((e, phase)),
+                Ok(NodeEvent::BlockCommitted(..)) if !stalls.is_empty() => committed_since_stall += 1,
+                Ok(_) => {}
+                Err(TryRecvError::Empty) => break,
+                Err(e) => panic!("event stream failed: {}", e),
+            }
+        }
+    }
+
+    assert_eq!(stalls, vec![(2, "End")]);
+    // Epoch 3 starts once the margin is over, and the schedule goes on as
+    // before from there
+    assert_eq!(view_changes[2], (3, delta + epoch * 2 + margin));
+    assert_eq!(view_changes[3], (4, delta + epoch * 3));
+    assert!(committed_since_stall > 0, "nothing was committed after the stall");
+}
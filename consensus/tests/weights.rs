@@ -0,0 +1,79 @@
+mod common;
+
+use config::ParseError;
+use std::collections::HashMap;
+use types::Replica;
+
+// Replica 3 is the heaviest, with a bit over a third of the weight
+const WEIGHTS: [u64; 4] = [2, 2, 3, 4];
+
+fn weighted_configs() -> Vec<config::Node> {
+    let weights: HashMap<Replica, u64> =
+        WEIGHTS.iter().enumerate().map(|(r, w)| (r as Replica, *w)).collect();
+    let mut configs = common::generate_configs(4, 1, 1);
+    for config in configs.iter_mut() {
+        config.weights = weights.clone();
+    }
+    configs
+}
+
+#[test]
+fn certificates_outweigh_the_heaviest_faulty_replicas() {
+    let configs = weighted_configs();
+    assert!(configs[0].validate().is_ok());
+    let cxs: Vec<_> = configs.iter().map(common::context).collect();
+    let hash = [7; 32];
+    let votes: Vec<_> = cxs.iter().map(|cx| common::vote(cx, &hash)).collect();
+    // The one faulty replica may be replica 3
+    assert_eq!(cxs[0].fault_weight(), 4);
+
+    // Replicas 0 and 1 together only hold as much as replica 3
+    let mut leader = common::context(&configs[0]);
+    assert!(leader.accept_vote(0, votes[0].clone(), 1).is_none());
+    assert!(leader.accept_vote(1, votes[1].clone(), 1).is_none());
+    let cert = leader.accept_vote(2, votes[2].clone(), 1).expect("no certificate");
+    assert_eq!(cert.votes.len(), 3);
+    assert!(leader.accept_vote(3, votes[3].clone(), 1).is_none());
+
+    // Replica 3 may be the faulty one, so it cannot certify on its own
+    let mut leader = common::context(&configs[0]);
+    assert!(leader.accept_vote(3, votes[3].clone(), 1).is_none());
+    let cert = leader.accept_vote(0, votes[0].clone(), 1).expect("no certificate");
+    assert_eq!(cert.votes.len(), 2);
+}
+
+#[test]
+fn leaders_take_turns_in_proportion_to_weight() {
+    let configs = weighted_configs();
+    let mut cx = common::context(&configs[0]);
+    cx.epoch = 0;
+    let cycle: u64 = WEIGHTS.iter().sum();
+    let leaders: Vec<_> = (0..2 * cycle)
+        .map(|e| cx.leader_for_epoch(e).unwrap())
+        .collect();
+    for (r, w) in WEIGHTS.iter().enumerate() {
+        let turns = leaders.iter().filter(|l| **l == r as Replica).count();
+        assert_eq!(turns as u64, 2 * w, "replica {} led {} times", r, turns);
+    }
+    for pair in leaders.windows(2) {
+        assert_ne!(pair[0], pair[1], "a replica led twice in a row");
+    }
+    // The rotation the reactor does at every epoch boundary agrees
+    assert_eq!(cx.next_leader(), leaders[1]);
+    assert_eq!(cx.current_leader(), leaders[0]);
+}
+
+#[test]
+fn weights_are_validated() {
+    let mut config = weighted_configs().remove(0);
+    config.weights.insert(3, 8);
+    match config.validate() {
+        Err(ParseError::InvalidWeight(3)) => (),
+        r => panic!("expected an invalid weight, got {:?}", r),
+    }
+    config.weights.remove(&3);
+    match config.validate() {
+        Err(ParseError::InvalidWeight(3)) => (),
+        r => panic!("expected an invalid weight, got {:?}", r),
+    }
+}
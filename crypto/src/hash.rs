@@ -1,16 +1,129 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use sha3::Sha3_256;
+use std::str::FromStr;
+use std::sync::OnceLock;
 
+/// Every hash function has digests of this size; it is part of the wire
+/// format of blocks and messages.
 pub const HASH_SIZE: usize = 32;
 pub type Hash = [u8; HASH_SIZE];
 
-pub const EMPTY_HASH: Hash = [0 as u8; 32];
+// Domain tags mixed into every signed message so that a signature produced in
+// one context cannot be replayed in another.
+pub const VOTE_DOMAIN: &[u8] = b"VOTE";
+pub const ACK_DOMAIN: &[u8] = b"ACK";
+pub const PROPOSE_DOMAIN: &[u8] = b"PROPOSE";
+pub const CERT_DOMAIN: &[u8] = b"CERT";
+pub const COMMIT_DOMAIN: &[u8] = b"COMMIT";
+pub const RECONFIG_DOMAIN: &[u8] = b"RECONFIG";
+pub const SNAPSHOT_DOMAIN: &[u8] = b"SNAPSHOT";
+pub const VDF_DOMAIN: &[u8] = b"VDF";
+pub const CHAIN_DOMAIN: &[u8] = b"CHAIN";
+pub const PEER_DOMAIN: &[u8] = b"PEER";
+
+/// The function blocks and messages are hashed with. All replicas of a
+/// cluster must use the same one.
+pub trait HashFunction: Send + Sync {
+    /// Tells functions apart, so two selections can be compared
+    fn name(&self) -> &'static str;
+    fn digest(&self, bytes: &[u8]) -> Hash;
+}
+
+/// The hash functions a node can be configured with
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum HashAlgorithm {
+    SHA256,
+    SHA3_256,
+    BLAKE3,
+}
+
+impl Default for HashAlgorithm {
+    fn default() -> Self {
+        HashAlgorithm::SHA256
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "SHA256" => Ok(HashAlgorithm::SHA256),
+            "SHA3_256" => Ok(HashAlgorithm::SHA3_256),
+            "BLAKE3" => Ok(HashAlgorithm::BLAKE3),
+            _ => Err("no match"),
+        }
+    }
+}
+
+impl HashAlgorithm {
+    pub fn function(self) -> &'static dyn HashFunction {
+        match self {
+            HashAlgorithm::SHA256 => &HashAlgorithm::SHA256,
+            HashAlgorithm::SHA3_256 => &HashAlgorithm::SHA3_256,
+            HashAlgorithm::BLAKE3 => &HashAlgorithm::BLAKE3,
+        }
+    }
+}
+
+impl HashFunction for HashAlgorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::SHA256 => "SHA256",
+            HashAlgorithm::SHA3_256 => "SHA3_256",
+            HashAlgorithm::BLAKE3 => "BLAKE3",
+        }
+    }
+
+    fn digest(&self, bytes: &[u8]) -> Hash {
+        match self {
+            HashAlgorithm::SHA256 => Sha256::digest(bytes).into(),
+            HashAlgorithm::SHA3_256 => Sha3_256::digest(bytes).into(),
+            HashAlgorithm::BLAKE3 => *blake3::hash(bytes).as_bytes(),
+        }
+    }
+}
+
+static SELECTED: OnceLock<&'static dyn HashFunction> = OnceLock::new();
+
+/// Makes `f` the hash function of this process. It can only be chosen once,
+/// before anything is hashed; afterwards this fails with the name of the
+/// function in use unless it is `f` again.
+pub fn select(f: &'static dyn HashFunction) -> Result<(), &'static str> {
+    let current = *SELECTED.get_or_init(|| f);
+    if current.name() == f.name() {
+        Ok(())
+    } else {
+        Err(current.name())
+    }
+}
+
+/// The hash function in use, SHA256 unless another was selected before the
+/// first hash
+pub fn selected() -> &'static dyn HashFunction {
+    *SELECTED.get_or_init(|| HashAlgorithm::default().function())
+}
+
+/// The hash of no bytes; the root of an empty body and the hash of the
+/// genesis block
+pub fn empty_hash() -> Hash {
+    do_hash(&[])
+}
 
 pub fn do_hash(bytes: &[u8]) -> Hash {
-    let hash = Sha256::digest(bytes);
-    return hash.into();
+    selected().digest(bytes)
 }
 
 pub fn ser_and_hash(obj: &impl Serialize) -> Hash {
     return do_hash(&bincode::serialize(&obj).unwrap());
 }
+
+/// Returns the bytes that are actually signed for `msg` under `domain`.
+pub fn with_domain(domain: &[u8], msg: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(domain.len() + 1 + msg.len());
+    buf.extend_from_slice(domain);
+    buf.push(b':');
+    buf.extend_from_slice(msg);
+    buf
+}
@@ -7,6 +7,8 @@ mod tests {
 }
 
 pub mod hash;
+pub mod threshold;
+pub mod vdf;
 
 mod crypto;
 pub use crypto::*;
@@ -0,0 +1,191 @@
+// Threshold BLS signatures over BLS12-381. A dealer splits a key into shares
+// with a random polynomial of degree `threshold - 1`; signatures by any
+// `threshold` of the shares on the same message combine into the signature
+// of the key itself. That signature is the size of one share's and checks
+// against the one public key with a single pairing equation, however many
+// shares went into it.
+//
+// Signatures are points of G1, 48 bytes compressed, and public keys points
+// of G2, 96 bytes compressed. Share `i` is the polynomial at `i + 1`, so
+// that shares are numbered from 0 like replicas.
+
+use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+use bls12_381::{multi_miller_loop, G1Affine, G1Projective, G2Affine, G2Prepared, Gt, Scalar};
+use rand::RngCore;
+use std::convert::TryInto;
+
+/// Separates our hashes to the curve from anyone else's
+const DST: &[u8] = b"RANDPIPER-V01-CS01-with-BLS12381G1_XMD:SHA-256_SSWU_RO_";
+
+pub const SIGNATURE_SIZE: usize = 48;
+pub const PUBLIC_KEY_SIZE: usize = 96;
+/// The index, little-endian, and then the key
+pub const SECRET_SHARE_SIZE: usize = 2 + 32;
+
+fn hash_to_g1(msg: &[u8]) -> G1Projective {
+    <G1Projective as HashToCurve<ExpandMsgXmd<sha2::Sha256>>>::hash_to_curve(msg, DST)
+}
+
+fn decode_signature(bytes: &[u8]) -> Option<G1Affine> {
+    let bytes: [u8; SIGNATURE_SIZE] = bytes.try_into().ok()?;
+    Option::from(G1Affine::from_compressed(&bytes))
+}
+
+fn random_scalar<R: RngCore>(rng: &mut R) -> Scalar {
+    let mut bytes = [0u8; 64];
+    rng.fill_bytes(&mut bytes);
+    Scalar::from_bytes_wide(&bytes)
+}
+
+/// Where share `index` is on the polynomial
+fn point_of(index: u16) -> Scalar {
+    Scalar::from(index as u64 + 1)
+}
+
+/// One share of a threshold key
+#[derive(Clone)]
+pub struct SecretShare {
+    index: u16,
+    key: Scalar,
+}
+
+impl SecretShare {
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// This share's signature on `msg`
+    pub fn sign(&self, msg: &[u8]) -> Vec<u8> {
+        G1Affine::from(hash_to_g1(msg) * self.key).to_compressed().to_vec()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.index.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.key.to_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != SECRET_SHARE_SIZE {
+            return None;
+        }
+        let index = u16::from_le_bytes(bytes[..2].try_into().ok()?);
+        let key: [u8; 32] = bytes[2..].try_into().ok()?;
+        let key = Option::from(Scalar::from_bytes(&key))?;
+        Some(SecretShare { index, key })
+    }
+}
+
+/// The public key of a threshold key or of one of its shares
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublicKey(G2Affine);
+
+impl PublicKey {
+    fn of(key: &Scalar) -> Self {
+        PublicKey(G2Affine::from(G2Affine::generator() * key))
+    }
+
+    /// Whether `sig` is the signature on `msg` of the key this is for
+    pub fn verify(&self, msg: &[u8], sig: &[u8]) -> bool {
+        let sig = match decode_signature(sig) {
+            Some(sig) => sig,
+            None => return false,
+        };
+        let hash = G1Affine::from(hash_to_g1(msg));
+        // e(sig, g2) == e(hash, pk), as one product of pairings
+        let terms = [
+            (&sig, &G2Prepared::from(-G2Affine::generator())),
+            (&hash, &G2Prepared::from(self.0)),
+        ];
+        multi_miller_loop(&terms).final_exponentiation() == Gt::identity()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_compressed().to_vec()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: [u8; PUBLIC_KEY_SIZE] = bytes.try_into().ok()?;
+        Option::from(G2Affine::from_compressed(&bytes)).map(PublicKey)
+    }
+}
+
+/// Splits a fresh key into `n` shares, any `threshold` of which sign for
+/// it. Returns the public key and the shares, each with its public key.
+pub fn deal<R: RngCore>(n: usize, threshold: usize, rng: &mut R) -> (PublicKey, Vec<(SecretShare, PublicKey)>) {
+    assert!(threshold >= 1 && threshold <= n, "the threshold must be between 1 and the number of shares");
+    let coefficients: Vec<Scalar> = (0..threshold).map(|_| random_scalar(rng)).collect();
+    let shares = (0..n as u16)
+        .map(|index| {
+            let x = point_of(index);
+            let key = coefficients.iter().rev().fold(Scalar::zero(), |acc, c| acc * x + c);
+            (SecretShare { index, key }, PublicKey::of(&key))
+        })
+        .collect();
+    (PublicKey::of(&coefficients[0]), shares)
+}
+
+/// The signature `shares`, each an index and that share's signature on the
+/// same message, combine into. That is the signature of the key if there
+/// are at least `threshold` of them and they are all valid. `None` if a
+/// share does not decode or two have the same index.
+pub fn combine(shares: &[(u16, &[u8])]) -> Option<Vec<u8>> {
+    let points: Vec<Scalar> = shares.iter().map(|(index, _)| point_of(*index)).collect();
+    let mut sum = G1Projective::identity();
+    for (j, (_, sig)) in shares.iter().enumerate() {
+        let sig = decode_signature(sig)?;
+        // The Lagrange coefficient of share j at 0
+        let mut num = Scalar::one();
+        let mut den = Scalar::one();
+        for (m, x) in points.iter().enumerate() {
+            if m != j {
+                num *= x;
+                den *= x - points[j];
+            }
+        }
+        let lambda = num * Option::<Scalar>::from(den.invert())?;
+        sum += sig * lambda;
+    }
+    Some(G1Affine::from(sum).to_compressed().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn any_threshold_of_shares_sign_for_the_key() {
+        let rng = &mut StdRng::seed_from_u64(7);
+        let (public, shares) = deal(4, 2, rng);
+        let msg = b"block";
+        let sigs: Vec<(u16, Vec<u8>)> = shares.iter().map(|(s, _)| (s.index(), s.sign(msg))).collect();
+        for (i, (share, pk)) in shares.iter().enumerate() {
+            assert!(pk.verify(msg, &sigs[i].1));
+            assert!(!public.verify(msg, &share.sign(msg)));
+        }
+        for pair in [[0, 1], [1, 3], [2, 0]].iter() {
+            let picked: Vec<(u16, &[u8])> = pair.iter().map(|&i| (sigs[i].0, sigs[i].1.as_slice())).collect();
+            let sig = combine(&picked).unwrap();
+            assert_eq!(sig.len(), SIGNATURE_SIZE);
+            assert!(public.verify(msg, &sig));
+            assert!(!public.verify(b"other", &sig));
+        }
+        // Below the threshold the shares say nothing about the key
+        let one = combine(&[(sigs[0].0, sigs[0].1.as_slice())]).unwrap();
+        assert!(!public.verify(msg, &one));
+    }
+
+    #[test]
+    fn keys_survive_encoding() {
+        let rng = &mut StdRng::seed_from_u64(7);
+        let (public, shares) = deal(3, 2, rng);
+        assert_eq!(PublicKey::from_bytes(&public.to_bytes()), Some(public));
+        let (share, pk) = &shares[2];
+        let decoded = SecretShare::from_bytes(&share.to_bytes()).unwrap();
+        assert_eq!(decoded.index(), 2);
+        assert!(pk.verify(b"m", &decoded.sign(b"m")));
+        assert!(SecretShare::from_bytes(&[0; 3]).is_none());
+    }
+}
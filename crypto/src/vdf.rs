@@ -0,0 +1,124 @@
+// A verifiable delay function for the beacon: sloth, after Lenstra and
+// Wesolowski, over the prime 2^521 - 1. Every step takes a modular square
+// root, which costs some 520 squarings, and is undone by a single squaring,
+// so checking an output is hundreds of times faster than computing it. The
+// roots can only be taken one after another, so a replica that learns the
+// input last still cannot tell the output in time to bias it.
+//
+// There is no trusted setup: the modulus is a known prime rather than a
+// product of secret ones.
+
+use crate::hash::{do_hash, with_domain, Hash, VDF_DOMAIN};
+use num_bigint::BigUint;
+use num_traits::One;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Bytes of an encoded field element
+pub const ELEMENT_SIZE: usize = 66;
+
+/// The output of the delay function on some input, which anyone can check
+/// against the input without redoing the work
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proof {
+    /// Steps the function was run for
+    pub iterations: u64,
+    /// The field element the last step ended on
+    pub output: Vec<u8>,
+}
+
+impl Proof {
+    /// The value the output stands for
+    pub fn value(&self) -> Hash {
+        do_hash(&with_domain(VDF_DOMAIN, &self.output))
+    }
+}
+
+fn prime() -> &'static BigUint {
+    static P: OnceLock<BigUint> = OnceLock::new();
+    P.get_or_init(|| (BigUint::one() << 521) - 1u32)
+}
+
+/// Raising to (p + 1) / 4 takes square roots, as p = 3 mod 4
+fn root_exponent() -> &'static BigUint {
+    static E: OnceLock<BigUint> = OnceLock::new();
+    E.get_or_init(|| BigUint::one() << 519)
+}
+
+fn encode(x: &BigUint) -> Vec<u8> {
+    let bytes = x.to_bytes_be();
+    let mut out = vec![0; ELEMENT_SIZE - bytes.len()];
+    out.extend_from_slice(&bytes);
+    out
+}
+
+/// The square root of x + 1, or of its negation if it has none. Which of
+/// the two roots is taken tells the cases apart, so every step can be
+/// undone.
+fn step(x: &BigUint) -> BigUint {
+    let p = prime();
+    let a = (x + 1u32) % p;
+    let r = a.modpow(root_exponent(), p);
+    if &r * &r % p == a {
+        if r.bit(0) {
+            p - r
+        } else {
+            r
+        }
+    } else {
+        let r = (p - &a).modpow(root_exponent(), p);
+        if r.bit(0) {
+            r
+        } else {
+            p - r
+        }
+    }
+}
+
+fn undo(y: &BigUint) -> BigUint {
+    let p = prime();
+    let square = y * y % p;
+    let a = if y.bit(0) { (p - square) % p } else { square };
+    (a + p - 1u32) % p
+}
+
+/// Runs the delay function on `input` for `iterations` steps. This is the
+/// expensive part and takes time in proportion to `iterations`.
+pub fn eval(input: &Hash, iterations: u64) -> Proof {
+    let mut x = BigUint::from_bytes_be(input);
+    for _ in 0..iterations {
+        x = step(&x);
+    }
+    Proof {
+        iterations,
+        output: encode(&x),
+    }
+}
+
+/// Whether `proof` is the output of the delay function on `input`
+pub fn verify(input: &Hash, proof: &Proof) -> bool {
+    if proof.output.len() != ELEMENT_SIZE {
+        return false;
+    }
+    let mut y = BigUint::from_bytes_be(&proof.output);
+    if &y >= prime() {
+        return false;
+    }
+    for _ in 0..proof.iterations {
+        y = undo(&y);
+    }
+    y == BigUint::from_bytes_be(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_step_can_be_undone() {
+        let p = prime();
+        for x in [BigUint::from(0u32), BigUint::from(1u32), BigUint::from(5u32), p - 2u32, p - 1u32].iter() {
+            assert_eq!(&undo(&step(x)), x);
+        }
+    }
+}
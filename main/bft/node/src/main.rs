@@ -1,8 +1,10 @@
 // use futures::prelude::*;
 use clap::{load_yaml, App};
 use config::Node;
+use consensus::bft::node::client::{self, ClientRuntime};
+use consensus::bft::node::intake::tx_channel;
 use std::error::Error;
-use types::{Block, ProtocolMsg, Transaction};
+use std::sync::Arc;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let yaml = load_yaml!("cli.yml");
@@ -26,6 +28,10 @@ fn main() -> Result<(), Box<dyn Error>> {
         _ => panic!("Invalid config file extension"),
     };
     config.validate().expect("The decoded config is not valid");
+    // The hash function is chosen for the whole process, before anything is
+    // hashed
+    crypto::hash::select(config.hash_alg.function())
+        .map_err(|in_use| format!("cannot hash with {:?}, {} is already in use", config.hash_alg, in_use))?;
     if let Some(d) = m.value_of("delta") {
         config.delta = d.parse().unwrap();
     }
@@ -52,40 +58,61 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     log::info!(target:"app","Successfully decoded the config file");
 
-    let cli_net_rt = tokio::runtime::Builder::new_multi_thread()
+    let core_rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
+        .worker_threads(2)
         .build()
         .unwrap();
 
-    // Setup client network
-    let (cli_send, cli_recv) =
-        cli_net_rt.block_on(net::Protocol::<Transaction, Block>::client_setup(
-            config.client_ip(),
-            util::codec::EnCodec::new(),
-            util::codec::tx::Codec::new(),
-        ));
+    // Clients are served on a runtime of their own unless the config says
+    // to share the consensus runtime, or to serve none at all
+    let cli_net_rt = if config.disable_clients {
+        None
+    } else {
+        Some(
+            ClientRuntime::new(config.client_threads(), core_rt.handle().clone())
+                .expect("failed to start the client runtime"),
+        )
+    };
+
+    // Clients are only read from while the mempool has room, so a flood of
+    // transactions waits at the clients instead of in memory
+    let (tx_send, cli_recv) = tx_channel(config.mempool_capacity());
+    let cli_send = match cli_net_rt.as_ref() {
+        Some(cli_net_rt) => {
+            // Setup client network
+            let listener = cli_net_rt
+                .block_on(tokio::net::TcpListener::bind(config.client_ip()))
+                .expect("failed to listen for clients");
+            cli_net_rt.block_on(client::start(&config, listener, tx_send))
+        }
+        None => {
+            // Nothing binds the client port; blocks for clients go nowhere
+            // and no transaction ever arrives
+            log::info!(target:"app", "Clients are disabled");
+            drop(tx_send);
+            tokio::sync::mpsc::unbounded_channel().0
+        }
+    };
 
     let prot_net_rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .unwrap();
 
-    // Setup networking
-    let protocol_network =
-        net::Protocol::<ProtocolMsg, ProtocolMsg>::new(config.id, config.num_nodes as u16);
-
-    // Setup the protocol network
-    let (net_send, net_recv) = prot_net_rt.block_on(protocol_network.server_setup(
-        config.net_map.clone(),
-        util::codec::EnCodec::new(),
-        util::codec::proto::Codec::new(),
-    ));
+    // Setup the protocol network. Sending to a peer that does not keep up
+    // waits on its connection, so the reactor's channel fills up and what
+    // the reactor cannot send waits in its capped backlog.
+    let listener = prot_net_rt
+        .block_on(tokio::net::TcpListener::bind(config.my_ip()))
+        .expect("failed to listen for the other replicas");
+    let (net_send, net_recv) = prot_net_rt.block_on(consensus::bft::node::network::start(&config, listener));
 
-    let core_rt = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .worker_threads(2)
-        .build()
-        .unwrap();
+    // External values to mix into the beacon, if the config names a source
+    let (entropy_send, entropy_recv) = consensus::bft::node::entropy::entropy_channel();
+    if let Some(path) = config.entropy_source.as_ref() {
+        core_rt.spawn(consensus::bft::node::entropy::read_from(path.into(), entropy_send));
+    }
 
     // Start the Apollo consensus protocol
     core_rt.block_on(consensus::bft::node::reactor(
@@ -95,6 +122,10 @@ fn main() -> Result<(), Box<dyn Error>> {
         net_recv,
         cli_send,
         cli_recv,
+        consensus::bft::node::events::event_channel(),
+        Arc::new(consensus::bft::node::clock::RealClock),
+        entropy_recv,
+        consensus::bft::node::sink::from_config(&config),
     ));
     Ok(())
 }
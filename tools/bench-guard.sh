@@ -0,0 +1,21 @@
+#!/bin/sh
+# Fails if a benchmark got slower than its saved criterion baseline by more
+# than MAX_REGRESSION percent (10 by default). Run the benchmarks with
+# `-- --baseline <name>` first so that criterion writes the changes.
+#
+#   tools/bench-guard.sh sig_      checks the groups starting with sig_
+
+prefix=${1:-}
+max=${MAX_REGRESSION:-10}
+status=0
+for f in target/criterion/${prefix}*/*/change/estimates.json; do
+    [ -f "$f" ] || continue
+    # {"mean":{"confidence_interval":{...},"point_estimate":0.0123,...},...}
+    change=$(sed 's/.*"mean":{[^}]*}[^}]*"point_estimate":\([-0-9.e]*\).*/\1/' "$f")
+    bench=$(dirname "$(dirname "$f")")
+    if awk -v c="$change" -v m="$max" 'BEGIN { exit !(c * 100 > m) }'; then
+        echo "REGRESSION ${bench#target/criterion/}: $(awk -v c="$change" 'BEGIN { printf "%+.1f%%", c * 100 }')"
+        status=1
+    fi
+done
+exit $status
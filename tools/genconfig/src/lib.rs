@@ -0,0 +1,271 @@
+// Builds the configs for a cluster: fresh keypairs for every node, a shared
+// beacon setup, and consistent `net_map`/`pk_map` entries across all of them.
+
+use config::{Client, Node};
+use crypto::rand::rngs::StdRng;
+use crypto::Algorithm;
+use crypto::UniformRand;
+use crypto_lib::{ed25519, secp256k1};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use types::Replica;
+use util::io::*;
+
+/// Number of epochs worth of beacon shares genconfig has always generated
+pub const DEFAULT_EPOCHS: usize = 100;
+
+/// Everything needed to generate the configs of one cluster
+pub struct Setup {
+    pub num_nodes: usize,
+    pub num_faults: usize,
+    pub delay: u64,
+    pub block_size: usize,
+    pub payload: usize,
+    pub crypto_alg: Algorithm,
+    /// `ip:port` that node `i` listens to other nodes on
+    pub addresses: Vec<String>,
+    /// `ip:port` that node `i` listens to clients on
+    pub client_addresses: Vec<String>,
+    /// Number of epochs of beacon shares to pre-generate
+    pub epochs: usize,
+    /// Whether to deal a threshold key and certify with it
+    pub threshold_certificates: bool,
+}
+
+impl Setup {
+    /// A setup where every node runs on localhost, node `i` listening on
+    /// `base_port+i` and `client_base_port+i`.
+    pub fn localhost(
+        num_nodes: usize,
+        num_faults: usize,
+        base_port: u16,
+        client_base_port: u16,
+    ) -> Setup {
+        let ips = vec!["127.0.0.1".to_string(); num_nodes];
+        Setup::with_hosts(num_nodes, num_faults, &ips, base_port, client_base_port)
+    }
+
+    /// A setup where node `i` runs on `hosts[i]`, listening on `base_port+i`
+    /// and `client_base_port+i`.
+    pub fn with_hosts(
+        num_nodes: usize,
+        num_faults: usize,
+        hosts: &[String],
+        base_port: u16,
+        client_base_port: u16,
+    ) -> Setup {
+        Setup {
+            num_nodes,
+            num_faults,
+            delay: 50,
+            block_size: 1,
+            payload: 0,
+            crypto_alg: Algorithm::ED25519,
+            addresses: (0..num_nodes)
+                .map(|i| format!("{}:{}", hosts[i], base_port + (i as u16)))
+                .collect(),
+            client_addresses: (0..num_nodes)
+                .map(|i| format!("{}:{}", hosts[i], client_base_port + (i as u16)))
+                .collect(),
+            epochs: DEFAULT_EPOCHS,
+            threshold_certificates: false,
+        }
+    }
+}
+
+fn port_of(addr: &str) -> u16 {
+    addr.split(":")
+        .last()
+        .expect("invalid address found; unable to split at :")
+        .parse()
+        .expect("failed to parse the port after :")
+}
+
+/// Generates the node configs and the client config for `setup`.
+pub fn generate(setup: &Setup, rng: &mut StdRng) -> (Vec<Node>, Client) {
+    let num_nodes = setup.num_nodes;
+    let num_faults = setup.num_faults;
+    assert_eq!(
+        setup.addresses.len(),
+        num_nodes,
+        "expected one address per node"
+    );
+    assert_eq!(
+        setup.client_addresses.len(),
+        num_nodes,
+        "expected one client address per node"
+    );
+
+    let mut client = Client::new();
+    client.block_size = setup.block_size;
+    client.crypto_alg = setup.crypto_alg.clone();
+    client.num_nodes = num_nodes;
+    client.num_faults = num_faults;
+
+    let mut node: Vec<Node> = Vec::with_capacity(num_nodes);
+
+    let mut pk = HashMap::new();
+    let mut ip = HashMap::new();
+    let mut bi_pp = HashMap::new();
+
+    for i in 0..num_nodes {
+        node.push(Node::new());
+
+        node[i].delta = setup.delay;
+        node[i].id = i as Replica;
+        node[i].num_nodes = num_nodes;
+        node[i].num_faults = num_faults;
+        node[i].block_size = setup.block_size;
+        node[i].payload = setup.payload;
+        node[i].client_port = port_of(&setup.client_addresses[i]);
+
+        node[i].crypto_alg = setup.crypto_alg.clone();
+        match setup.crypto_alg {
+            Algorithm::ED25519 => {
+                let kp = ed25519::Keypair::generate();
+                pk.insert(i as Replica, kp.public().encode().to_vec());
+                node[i].secret_key_bytes = kp.encode().to_vec();
+            }
+            Algorithm::SECP256K1 => {
+                let kp = secp256k1::Keypair::generate();
+                pk.insert(i as Replica, kp.public().encode().to_vec());
+                node[i].secret_key_bytes = kp.secret().to_bytes().to_vec();
+            }
+            _ => (),
+        };
+        ip.insert(i as Replica, setup.addresses[i].clone());
+        client
+            .net_map
+            .insert(i as Replica, setup.client_addresses[i].clone());
+
+        node[i].bi_p = Some(crypto::Biaccumulator381::setup(num_nodes, rng).unwrap());
+        bi_pp.insert(
+            i as Replica,
+            node[i].bi_p.as_ref().unwrap().get_public_params(),
+        );
+    }
+
+    let rand_beacon_parameter = crypto::EVSS381::setup(num_faults, rng).unwrap();
+
+    for i in 0..num_nodes {
+        node[i].pk_map = pk.clone();
+        node[i].net_map = ip.clone();
+        node[i].bi_pp_map = bi_pp.clone();
+        node[i].rand_beacon_parameter = Some(rand_beacon_parameter.clone());
+    }
+
+    for i in 0..num_nodes {
+        for j in 0..num_nodes {
+            node[j].rand_beacon_queue.insert(
+                i as Replica,
+                VecDeque::with_capacity(num_nodes + num_faults),
+            );
+            node[j].rand_beacon_commits.insert(
+                i as Replica,
+                VecDeque::with_capacity(num_nodes + num_faults),
+            );
+        }
+        for _ in 0..num_nodes + num_faults {
+            let poly =
+                crypto::EVSS381::commit(&rand_beacon_parameter, crypto::F381::rand(rng), rng)
+                    .unwrap();
+            for k in 0..num_nodes {
+                node[k]
+                    .rand_beacon_commits
+                    .get_mut(&(i as Replica))
+                    .unwrap()
+                    .push_back(poly.get_commit());
+                node[k]
+                    .rand_beacon_queue
+                    .get_mut(&(i as Replica))
+                    .unwrap()
+                    .push_back(
+                        crypto::EVSS381::get_share(
+                            crypto::F381::from((k + 1) as u16),
+                            &rand_beacon_parameter,
+                            &poly,
+                            rng,
+                        )
+                        .unwrap(),
+                    );
+            }
+        }
+    }
+
+    for i in 0..num_nodes {
+        let mut vec = Vec::with_capacity(setup.epochs);
+        for time in 0..setup.epochs {
+            println!("{}:{}", i, time);
+            let mut shares = vec![VecDeque::with_capacity(num_nodes); num_nodes];
+            let mut commits = Vec::with_capacity(num_nodes);
+            for _ in 0..num_nodes {
+                let poly =
+                    crypto::EVSS381::commit(&rand_beacon_parameter, crypto::F381::rand(rng), rng)
+                        .unwrap();
+                commits.push(poly.get_commit());
+                for j in 0..num_nodes {
+                    shares[j].push_back(
+                        crypto::EVSS381::get_share(
+                            crypto::F381::from((j + 1) as u16),
+                            &rand_beacon_parameter,
+                            &poly,
+                            rng,
+                        )
+                        .unwrap(),
+                    );
+                }
+            }
+            vec.push((shares, commits));
+        }
+        node[i].rand_beacon_shares = vec;
+    }
+
+    if setup.threshold_certificates {
+        let (public, shares) = crypto::threshold::deal(num_nodes, num_faults + 1, rng);
+        let public_shares: HashMap<Replica, Vec<u8>> = shares
+            .iter()
+            .map(|(share, pk)| (share.index(), pk.to_bytes()))
+            .collect();
+        for (i, (share, _)) in shares.iter().enumerate() {
+            node[i].threshold_certificates = true;
+            node[i].threshold_secret = share.to_bytes();
+            node[i].threshold_public = public.to_bytes();
+            node[i].threshold_shares = public_shares.clone();
+        }
+    }
+
+    client.server_pk = pk;
+    (node, client)
+}
+
+fn write_as(out: &str, filename: String, obj: &impl Serialize) {
+    match out {
+        "json" => write_json(filename, obj),
+        "binary" => write_bin(filename, obj),
+        "toml" => write_toml(filename, obj),
+        "yaml" => write_yaml(filename, obj),
+        _ => panic!("Invalid output type {}", out),
+    }
+}
+
+/// Writes `nodes-<i>.<ext>` for every node and `client.<ext>` into `target`,
+/// validating each config as it goes. `out` is one of json, binary, toml or
+/// yaml.
+pub fn write_configs(node: &[Node], client: &Client, out: &str, target: &str) {
+    let ext = match out {
+        "binary" => "dat",
+        "yaml" => "yml",
+        _ => out,
+    };
+    for i in 0..node.len() {
+        let filename = format!("{}/nodes-{}.{}", target, i, ext);
+        write_as(out, filename, &node[i]);
+        node[i].validate().expect("failed to validate node config");
+    }
+
+    let filename = format!("{}/client.{}", target, ext);
+    write_as(out, filename, client);
+    client
+        .validate()
+        .expect("failed to validate the client config");
+}
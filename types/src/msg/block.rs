@@ -1,14 +1,31 @@
 use super::Certificate;
 use crate::protocol::{Height, Replica};
-use crate::Vote;
-use crypto::hash::{Hash, EMPTY_HASH};
-use serde::{Deserialize, Serialize};
+use crate::{Reconfig, Transaction, Vote};
+use crypto::hash::{do_hash, empty_hash, with_domain, Hash, CHAIN_DOMAIN};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::{self, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::io::{Read, Write};
 use types_upstream::WireReady;
 
+/// Bytes of the tag naming a chain on every protocol message
+pub const CHAIN_TAG_SIZE: usize = 8;
+
+/// The most a compressed body may expand to, so that a small malicious
+/// body cannot make us allocate without bound
+pub const MAX_BODY_SIZE: u64 = 64 << 20;
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Content {
     pub commits: Vec<crypto::EVSSCommit381>,
     pub acks: Vec<Vote>,
+    /// Reconfiguration requests, each with its requester's signature
+    pub reconfig: Vec<(Reconfig, Vec<u8>)>,
+    /// Client transactions, in the order they are committed
+    pub transactions: Vec<Transaction>,
 }
 
 impl Content {
@@ -16,6 +33,8 @@ impl Content {
         Content {
             commits: Vec::new(),
             acks: Vec::new(),
+            reconfig: Vec::new(),
+            transactions: Vec::new(),
         }
 
     }
@@ -43,6 +62,10 @@ pub struct BlockHeader {
     pub extra: Vec<u8>,
     pub author: Replica,
     pub height: Height,
+    /// Merkle root over the body items, set by `Block::update_hash`
+    pub body_root: Hash,
+    /// Whether the body is encoded compressed
+    pub compressed: bool,
 }
 
 impl std::fmt::Debug for BlockHeader {
@@ -51,6 +74,8 @@ impl std::fmt::Debug for BlockHeader {
             .field("author", &self.author)
             .field("height", &self.height)
             .field("prev", &self.prev)
+            .field("body_root", &self.body_root)
+            .field("compressed", &self.compressed)
             .finish()
     }
 }
@@ -66,26 +91,102 @@ impl std::fmt::Debug for BlockBody {
 impl BlockHeader {
     pub fn new() -> Self {
         BlockHeader {
-            prev: EMPTY_HASH,
+            prev: empty_hash(),
             extra: Vec::new(),
             author: 0,
             height: 0,
+            body_root: empty_hash(),
+            compressed: false,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// `body` deflated, as a compressed block encodes it
+pub fn compress_body(body: &BlockBody) -> bincode::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&bincode::serialize(body)?)?;
+    Ok(encoder.finish()?)
+}
+
+/// The body `bytes` from `compress_body` holds
+pub fn decompress_body(bytes: &[u8]) -> bincode::Result<BlockBody> {
+    let mut plain = Vec::new();
+    DeflateDecoder::new(bytes).take(MAX_BODY_SIZE + 1).read_to_end(&mut plain)?;
+    if plain.len() as u64 > MAX_BODY_SIZE {
+        return Err(Box::new(bincode::ErrorKind::Custom(format!(
+            "the body expands to more than {} bytes",
+            MAX_BODY_SIZE
+        ))));
+    }
+    bincode::deserialize(&plain)
+}
+
+/// A block. Its body is held decoded; if `header.compressed` is set it is
+/// compressed whenever the block is encoded, whether to be sent, sharded or
+/// saved, and decompressed when it is decoded.
+#[derive(Debug, Clone)]
 pub struct Block {
     pub header: BlockHeader,
     pub body: BlockBody,
 
-    #[serde(skip_serializing, skip_deserializing)]
+    /// Not encoded
     pub hash: Hash,
-    // #[serde(skip_serializing, skip_deserializing)]
     pub payload: Vec<u8>,
     pub certificate: Certificate,
 }
 
+impl Serialize for Block {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Block", 4)?;
+        s.serialize_field("header", &self.header)?;
+        if self.header.compressed {
+            let body = compress_body(&self.body).map_err(ser::Error::custom)?;
+            s.serialize_field("body", &body)?;
+        } else {
+            s.serialize_field("body", &self.body)?;
+        }
+        s.serialize_field("payload", &self.payload)?;
+        s.serialize_field("certificate", &self.certificate)?;
+        s.end()
+    }
+}
+
+struct BlockVisitor;
+
+impl<'de> Visitor<'de> for BlockVisitor {
+    type Value = Block;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a block")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Block, A::Error> {
+        let header: BlockHeader = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let body = if header.compressed {
+            let bytes: Vec<u8> = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            decompress_body(&bytes).map_err(de::Error::custom)?
+        } else {
+            seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?
+        };
+        let payload = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        let certificate = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(3, &self))?;
+        Ok(Block {
+            header,
+            body,
+            hash: Hash::default(),
+            payload,
+            certificate,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Block {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Block, D::Error> {
+        const FIELDS: &[&str] = &["header", "body", "payload", "certificate"];
+        deserializer.deserialize_struct("Block", FIELDS, BlockVisitor)
+    }
+}
+
 impl Block {
     pub fn from_bytes(bytes: &[u8]) -> Self {
         let c: Block = bincode::deserialize(&bytes).expect("failed to decode the block");
@@ -96,7 +197,7 @@ impl Block {
         Block {
             header: BlockHeader::new(),
             body: BlockBody::new(),
-            hash: EMPTY_HASH,
+            hash: empty_hash(),
             payload: Vec::new(),
             certificate: Certificate::empty_cert(),
         }
@@ -109,31 +210,172 @@ impl Block {
     }
 
     pub fn update_hash(&mut self) {
+        self.header.body_root = self.body.data.merkle_root();
         let empty_vec = vec![0; 0];
         let old_vec = std::mem::replace(&mut self.payload, empty_vec);
         let empty_cert = Certificate::empty_cert();
         let old_cert = std::mem::replace(&mut self.certificate, empty_cert);
+        // The hash is of the plain encoding, so hashing does not compress
+        // and a block is the same block however its body is encoded
+        let compressed = std::mem::replace(&mut self.header.compressed, false);
         self.hash = crypto::hash::ser_and_hash(&self);
+        self.header.compressed = compressed;
         let _ = std::mem::replace(&mut self.payload, old_vec);
         let _ = std::mem::replace(&mut self.certificate, old_cert);
     }
 }
 
-pub const GENESIS_BLOCK: Block = Block {
-    header: BlockHeader {
-        prev: EMPTY_HASH,
-        extra: Vec::new(),
-        author: 0,
-        height: 0,
-    },
-    body: BlockBody::new(),
-    hash: EMPTY_HASH,
-    payload: vec![],
-    certificate: Certificate::empty_cert(),
-    // cert: Certificate{
-    // votes: vec![],
-    // },
-};
+/// A part of a block that has to be set on a `BlockBuilder` but was not
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockBuildError {
+    MissingPrev,
+    MissingAuthor,
+    MissingHeight,
+    MissingBody,
+}
+
+impl std::fmt::Display for BlockBuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let part = match self {
+            BlockBuildError::MissingPrev => "parent hash",
+            BlockBuildError::MissingAuthor => "author",
+            BlockBuildError::MissingHeight => "height",
+            BlockBuildError::MissingBody => "body",
+        };
+        write!(f, "the block has no {}", part)
+    }
+}
+
+impl std::error::Error for BlockBuildError {}
+
+/// Puts a block together. The parent, author, height and body have to be
+/// set; `build` refuses to make a block without them and hashes the block
+/// it makes, so the hash always matches the contents.
+#[derive(Default)]
+pub struct BlockBuilder {
+    prev: Option<Hash>,
+    author: Option<Replica>,
+    height: Option<Height>,
+    body: Option<BlockBody>,
+    extra: Vec<u8>,
+    payload: usize,
+    certificate: Certificate,
+    compressed: bool,
+}
+
+impl BlockBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn prev(mut self, prev: Hash) -> Self {
+        self.prev = Some(prev);
+        self
+    }
+
+    pub fn author(mut self, author: Replica) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    pub fn height(mut self, height: Height) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    pub fn body(mut self, body: BlockBody) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    pub fn extra(mut self, extra: Vec<u8>) -> Self {
+        self.extra = extra;
+        self
+    }
+
+    /// Bytes of padding, which are not part of the hash
+    pub fn payload(mut self, len: usize) -> Self {
+        self.payload = len;
+        self
+    }
+
+    /// The certificate for the parent, which is not part of the hash
+    pub fn certificate(mut self, certificate: Certificate) -> Self {
+        self.certificate = certificate;
+        self
+    }
+
+    /// Whether the body is compressed when the block is encoded
+    pub fn compressed(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    /// The body set so far
+    pub fn get_body(&self) -> Option<&BlockBody> {
+        self.body.as_ref()
+    }
+
+    /// Whether every required part is set
+    pub fn check(&self) -> Result<(), BlockBuildError> {
+        if self.prev.is_none() {
+            return Err(BlockBuildError::MissingPrev);
+        }
+        if self.author.is_none() {
+            return Err(BlockBuildError::MissingAuthor);
+        }
+        if self.height.is_none() {
+            return Err(BlockBuildError::MissingHeight);
+        }
+        if self.body.is_none() {
+            return Err(BlockBuildError::MissingBody);
+        }
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<Block, BlockBuildError> {
+        self.check()?;
+        let mut block = Block {
+            header: BlockHeader {
+                prev: self.prev.unwrap(),
+                extra: self.extra,
+                author: self.author.unwrap(),
+                height: self.height.unwrap(),
+                body_root: empty_hash(),
+                compressed: self.compressed,
+            },
+            body: self.body.unwrap(),
+            hash: empty_hash(),
+            payload: Vec::new(),
+            certificate: self.certificate,
+        };
+        block.add_payload(self.payload);
+        block.update_hash();
+        Ok(block)
+    }
+}
+
+/// The block every chain starts from. Its hash is `empty_hash()`, so it
+/// depends on the hash function in use.
+pub fn genesis_block() -> Block {
+    Block {
+        header: BlockHeader::new(),
+        body: BlockBody::new(),
+        hash: empty_hash(),
+        payload: vec![],
+        certificate: Certificate::empty_cert(),
+    }
+}
+
+/// The tag of the chain named `chain_id` that starts from the genesis block.
+/// Clusters that differ in either have different tags.
+pub fn chain_tag(chain_id: &str) -> [u8; CHAIN_TAG_SIZE] {
+    let mut named = genesis_block().hash.to_vec();
+    named.extend_from_slice(chain_id.as_bytes());
+    let mut tag = [0; CHAIN_TAG_SIZE];
+    tag.copy_from_slice(&do_hash(&with_domain(CHAIN_DOMAIN, &named))[..CHAIN_TAG_SIZE]);
+    tag
+}
 
 impl types_upstream::WireReady for Block {
     fn init(self) -> Self {
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// What a node tells a client besides the blocks it streams
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum ClientMsg {
+    /// The node serves as many clients as it takes; the connection is
+    /// closed after this
+    Busy,
+}
+
+impl ClientMsg {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        bincode::deserialize(bytes).expect("failed to decode the client message")
+    }
+}
@@ -55,7 +55,7 @@ impl std::default::Default for Certificate {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Transaction {
     pub data: Vec<u8>,
     pub request: Vec<u8>,
@@ -0,0 +1,154 @@
+// A Merkle tree over the items of a block body, so that a light client
+// holding only the header can check that an item is in the block
+
+use super::Content;
+use crate::{Reconfig, Transaction, Vote};
+use crypto::hash::{do_hash, empty_hash, with_domain, Hash};
+use serde::{Deserialize, Serialize};
+
+// Leaves and inner nodes are hashed under different domains so that an inner
+// node can never pass for an item
+const LEAF_DOMAIN: &[u8] = b"LEAF";
+const NODE_DOMAIN: &[u8] = b"NODE";
+
+/// One item of a block body, in the order the leaves are laid out: all the
+/// commits, then the acks, then the reconfiguration requests, then the
+/// client transactions.
+#[derive(Serialize)]
+pub enum BodyItem<'a> {
+    Commit(&'a crypto::EVSSCommit381),
+    Ack(&'a Vote),
+    Reconfig(&'a (Reconfig, Vec<u8>)),
+    Transaction(&'a Transaction),
+}
+
+impl BodyItem<'_> {
+    pub fn leaf(&self) -> Hash {
+        do_hash(&with_domain(LEAF_DOMAIN, &bincode::serialize(self).unwrap()))
+    }
+}
+
+fn node(left: &Hash, right: &Hash) -> Hash {
+    let mut buf = Vec::with_capacity(2 * left.len());
+    buf.extend_from_slice(left);
+    buf.extend_from_slice(right);
+    do_hash(&with_domain(NODE_DOMAIN, &buf))
+}
+
+// A node without a sibling moves up a level unchanged
+fn next_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node(left, right),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Shows that a leaf is at `index` among `leaves` leaves under some root
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InclusionProof {
+    pub index: usize,
+    pub leaves: usize,
+    /// Siblings on the way from the leaf to the root
+    pub path: Vec<Hash>,
+}
+
+impl InclusionProof {
+    /// Checks that `leaf` is the item this proof was made for under `root`
+    pub fn verify(&self, root: &Hash, leaf: &Hash) -> bool {
+        if self.index >= self.leaves {
+            return false;
+        }
+        let mut path = self.path.iter();
+        let mut hash = *leaf;
+        let mut index = self.index;
+        let mut width = self.leaves;
+        while width > 1 {
+            if index ^ 1 < width {
+                let sibling = match path.next() {
+                    Some(sibling) => sibling,
+                    None => return false,
+                };
+                hash = if index & 1 == 0 {
+                    node(&hash, sibling)
+                } else {
+                    node(sibling, &hash)
+                };
+            }
+            index >>= 1;
+            width = (width + 1) >> 1;
+        }
+        path.next().is_none() && hash == *root
+    }
+}
+
+impl Content {
+    pub fn items(&self) -> Vec<BodyItem> {
+        self.commits
+            .iter()
+            .map(BodyItem::Commit)
+            .chain(self.acks.iter().map(BodyItem::Ack))
+            .chain(self.reconfig.iter().map(BodyItem::Reconfig))
+            .chain(self.transactions.iter().map(BodyItem::Transaction))
+            .collect()
+    }
+
+    pub fn leaves(&self) -> Vec<Hash> {
+        self.items().iter().map(BodyItem::leaf).collect()
+    }
+
+    /// The root of the tree over the body items, `empty_hash()` for an empty
+    /// body
+    pub fn merkle_root(&self) -> Hash {
+        let mut level = self.leaves();
+        if level.is_empty() {
+            return empty_hash();
+        }
+        while level.len() > 1 {
+            level = next_level(&level);
+        }
+        level[0]
+    }
+
+    /// Proves the item at `index`, counted as in `items`
+    pub fn prove(&self, index: usize) -> Option<InclusionProof> {
+        let mut level = self.leaves();
+        if index >= level.len() {
+            return None;
+        }
+        let leaves = level.len();
+        let mut path = Vec::new();
+        let mut i = index;
+        while level.len() > 1 {
+            if i ^ 1 < level.len() {
+                path.push(level[i ^ 1]);
+            }
+            level = next_level(&level);
+            i >>= 1;
+        }
+        Some(InclusionProof {
+            index,
+            leaves,
+            path,
+        })
+    }
+
+    /// Proves the `i`th commit of the body
+    pub fn prove_commit(&self, i: usize) -> Option<InclusionProof> {
+        if i >= self.commits.len() {
+            return None;
+        }
+        self.prove(i)
+    }
+
+    /// Proves the `i`th ack of the body
+    pub fn prove_ack(&self, i: usize) -> Option<InclusionProof> {
+        if i >= self.acks.len() {
+            return None;
+        }
+        self.prove(self.commits.len() + i)
+    }
+}
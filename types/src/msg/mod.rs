@@ -13,4 +13,16 @@ pub use storage::*;
 mod propose;
 pub use propose::*;
 
+mod reconfig;
+pub use reconfig::*;
+
+mod client;
+pub use client::*;
+
+mod merkle;
+pub use merkle::*;
+
+mod snapshot;
+pub use snapshot::*;
+
 pub mod synchs;
@@ -7,6 +7,11 @@ pub struct Propose {
     pub new_block: Block,
     pub certificate: Certificate,
     pub epoch: Height,
+    /// The blocks `new_block` builds on within this proposal, oldest first,
+    /// when the leader proposes a batch. The first extends the block
+    /// `certificate` is for and each one after extends the one before; the
+    /// votes on `new_block` commit to all of them through its parent.
+    pub batch: Vec<Block>,
 }
 
 impl Propose {
@@ -14,4 +19,9 @@ impl Propose {
         let c: Propose = bincode::deserialize(&bytes).expect("failed to decode the propose");
         c
     }
+
+    /// Like `from_bytes`, but `None` if the bytes are not a proposal
+    pub fn try_from_bytes(bytes: &[u8]) -> Option<Self> {
+        bincode::deserialize(bytes).ok()
+    }
 }
@@ -1,23 +1,76 @@
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
 use super::Certificate;
-use crate::{Propose, Height, Replica, DataWithAcc, SignedData, Vote};
+use crate::{Block, Propose, Height, Reconfig, Replica, DataWithAcc, SignedData, Snapshot, Vote};
+use crypto::hash::Hash;
 use types_upstream::WireReady;
 
+/// The gatherer a shard is for
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Gatherer {
+    Propose,
+    VoteCert,
+    Commit,
+}
+
+/// Every message carries the epoch its sender was in when it was produced
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum ProtocolMsg {
-    Certificate(Certificate),
+    /// (certificate, epoch, the sender's wall clock in milliseconds since
+    /// the Unix epoch when it sent it)
+    Certificate(Certificate, Height, u64),
     Propose(Propose, DataWithAcc),
-    Vote(Vote),
-    VoteCert(Certificate, DataWithAcc),
-    DeliverPropose(Vec<u8>, Replica, SignedData),
-    DeliverVoteCert(Vec<u8>, Replica, SignedData),
-    Reconstruct(crypto::EVSSShare381, Height),
-    Commit(std::collections::VecDeque<crypto::EVSSShare381>, Vec<crypto::EVSSCommit381>, DataWithAcc),
-    DeliverCommit(Vec<u8>, Replica, SignedData),
-    Ack(Vote),
+    Vote(Vote, Height),
+    VoteCert(Certificate, DataWithAcc, Height),
+    /// (shard, shard index, proof, epoch) The index is the owner's position
+    /// among the active replicas, not its id
+    DeliverPropose(Bytes, Replica, SignedData, Height),
+    DeliverVoteCert(Bytes, Replica, SignedData, Height),
+    /// The sender's share of the beacon from each dealer, in dealer order.
+    /// They are checked one by one and then added up.
+    Reconstruct(Vec<crypto::EVSSShare381>, Height),
+    /// Asks replicas holding back their reconstruction share to send it
+    ReconstructRequest(Replica, Height),
+    Commit(std::collections::VecDeque<crypto::EVSSShare381>, Vec<crypto::EVSSCommit381>, DataWithAcc, Height),
+    /// (epoch, index, total, shares, commits, signature on the first chunk)
+    /// One piece of a `Commit` that was too large to send at once
+    CommitChunk(Height, u16, u16, std::collections::VecDeque<crypto::EVSSShare381>, Vec<crypto::EVSSCommit381>, Option<DataWithAcc>),
+    DeliverCommit(Bytes, Replica, SignedData, Height),
+    Ack(Vote, Height),
+    Reconfig(Reconfig, Vec<u8>, Height),
+    /// The sender finished the epoch with this beacon
+    EpochDone(Height, Hash),
+    /// (block hash, epoch) Asks for a committed block the sender only has a
+    /// certificate for
+    BlockRequest(Hash, Height),
+    /// (block, epoch of the request) The block asked for
+    BlockResponse(Block, Height),
+    /// (epoch) Asks a replica a new node trusts for a snapshot to start from
+    SnapshotRequest(Height),
+    /// (snapshot, the sender's signature on it, epoch of the request)
+    SnapshotResponse(Snapshot, Vec<u8>, Height),
+    /// (whether the sender is past the barrier) The sender is up and waiting
+    /// for enough replicas to start epoch 0 together; one already past the
+    /// barrier answers so that a late replica need not wait for ever
+    Ready(bool),
+    /// (epoch, gatherer, shard index) Asks for a deliver shard the sender
+    /// still lacks; a replica holding it answers with the Deliver* message
+    RequestShare(Height, Gatherer, Replica),
+    /// (epoch) Sent by a replica that skipped ahead to this epoch, asking
+    /// every dealer where its queue of deals stands
+    DealRequest(Height),
+    /// (epoch, queue, deals) What the sender's queue of deals held in the
+    /// epoch asked for, in order, as (epoch dealt in, place in the deal),
+    /// and the recipient's shares of those deals. The ones every replica
+    /// started with, dealt in epoch zero, are not sent again.
+    Deals(Height, Vec<(Height, usize)>, Vec<Deal>),
 }
 
+/// (epoch dealt in, the recipient's shares, commitments, signature) One
+/// deal of beacons, as sent in a `Commit`
+pub type Deal = (Height, std::collections::VecDeque<crypto::EVSSShare381>, Vec<crypto::EVSSCommit381>, DataWithAcc);
+
 pub fn commit_from_bytes(bytes: &[u8]) -> Vec<crypto::EVSSCommit381> {
     let c: Vec<crypto::EVSSCommit381> = bincode::deserialize(&bytes).expect("failed to decode the commit");
     c
@@ -30,20 +83,78 @@ impl ProtocolMsg {
         return c.init();
     }
 
+    /// Like `from_bytes`, but `None` if the bytes are not a protocol message
+    pub fn try_from_bytes(bytes: &[u8]) -> Option<Self> {
+        bincode::deserialize::<ProtocolMsg>(bytes).ok().map(|c| c.init())
+    }
+
     pub fn to_string(&self) -> &'static str {
         match self {
-            ProtocolMsg::Certificate(_) => "Certificate",
+            ProtocolMsg::Certificate(_, _, _) => "Certificate",
             ProtocolMsg::Propose(_, _) => "Propose",
-            ProtocolMsg::Vote(_) => "Vote",
-            ProtocolMsg::VoteCert(_, _) => "VoteCert",
-            ProtocolMsg::DeliverPropose(_, _, _) => "DeliverPropose",
-            ProtocolMsg::DeliverVoteCert(_, _, _) => "DeliverVoteCert",
+            ProtocolMsg::Vote(_, _) => "Vote",
+            ProtocolMsg::VoteCert(_, _, _) => "VoteCert",
+            ProtocolMsg::DeliverPropose(_, _, _, _) => "DeliverPropose",
+            ProtocolMsg::DeliverVoteCert(_, _, _, _) => "DeliverVoteCert",
             ProtocolMsg::Reconstruct(_, _) => "Reconstruct",
-            ProtocolMsg::Commit(_, _, _) => "Commit",
-            ProtocolMsg::DeliverCommit(_, _, _) => "DeliverCommit",
-            ProtocolMsg::Ack(_) => "Ack",
+            ProtocolMsg::ReconstructRequest(_, _) => "ReconstructRequest",
+            ProtocolMsg::Commit(_, _, _, _) => "Commit",
+            ProtocolMsg::CommitChunk(_, _, _, _, _, _) => "CommitChunk",
+            ProtocolMsg::DeliverCommit(_, _, _, _) => "DeliverCommit",
+            ProtocolMsg::Ack(_, _) => "Ack",
+            ProtocolMsg::Reconfig(_, _, _) => "Reconfig",
+            ProtocolMsg::EpochDone(_, _) => "EpochDone",
+            ProtocolMsg::BlockRequest(_, _) => "BlockRequest",
+            ProtocolMsg::BlockResponse(_, _) => "BlockResponse",
+            ProtocolMsg::SnapshotRequest(_) => "SnapshotRequest",
+            ProtocolMsg::SnapshotResponse(_, _, _) => "SnapshotResponse",
+            ProtocolMsg::Ready(_) => "Ready",
+            ProtocolMsg::RequestShare(_, _, _) => "RequestShare",
+            ProtocolMsg::DealRequest(_) => "DealRequest",
+            ProtocolMsg::Deals(_, _, _) => "Deals",
         }
     }
+
+    pub fn epoch(&self) -> Height {
+        match self {
+            ProtocolMsg::Certificate(_, e, _) => *e,
+            ProtocolMsg::Propose(p, _) => p.epoch,
+            ProtocolMsg::Vote(_, e) => *e,
+            ProtocolMsg::VoteCert(_, _, e) => *e,
+            ProtocolMsg::DeliverPropose(_, _, _, e) => *e,
+            ProtocolMsg::DeliverVoteCert(_, _, _, e) => *e,
+            ProtocolMsg::Reconstruct(_, e) => *e,
+            ProtocolMsg::ReconstructRequest(_, e) => *e,
+            ProtocolMsg::Commit(_, _, _, e) => *e,
+            ProtocolMsg::CommitChunk(e, _, _, _, _, _) => *e,
+            ProtocolMsg::DeliverCommit(_, _, _, e) => *e,
+            ProtocolMsg::Ack(_, e) => *e,
+            ProtocolMsg::Reconfig(_, _, e) => *e,
+            ProtocolMsg::EpochDone(e, _) => *e,
+            ProtocolMsg::BlockRequest(_, e) => *e,
+            ProtocolMsg::BlockResponse(_, e) => *e,
+            ProtocolMsg::SnapshotRequest(e) => *e,
+            ProtocolMsg::SnapshotResponse(_, _, e) => *e,
+            ProtocolMsg::Ready(_) => 0,
+            ProtocolMsg::RequestShare(e, _, _) => *e,
+            ProtocolMsg::DealRequest(e) => *e,
+            ProtocolMsg::Deals(e, _, _) => *e,
+        }
+    }
+
+    /// Whether the message only matters in the phase it was made for: votes,
+    /// vote certificates, deliver shards and requests for them
+    pub fn is_phase_bound(&self) -> bool {
+        matches!(
+            self,
+            ProtocolMsg::Vote(_, _)
+                | ProtocolMsg::VoteCert(_, _, _)
+                | ProtocolMsg::DeliverPropose(_, _, _, _)
+                | ProtocolMsg::DeliverVoteCert(_, _, _, _)
+                | ProtocolMsg::DeliverCommit(_, _, _, _)
+                | ProtocolMsg::RequestShare(_, _, _)
+        )
+    }
 }
 
 impl WireReady for ProtocolMsg {
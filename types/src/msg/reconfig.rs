@@ -0,0 +1,11 @@
+use crate::protocol::Replica;
+use serde::{Deserialize, Serialize};
+
+/// A change to the replica set. It is carried in a block together with the
+/// requesting replica's signature and takes effect at the first epoch
+/// boundary after that block is finalized.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Reconfig {
+    /// The replica signs with the (encoded) public key from now on
+    RotateKey(Replica, Vec<u8>),
+}
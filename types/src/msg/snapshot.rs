@@ -0,0 +1,20 @@
+use super::{Block, Certificate};
+use crate::protocol::{Height, Replica};
+use crypto::hash::Hash;
+use serde::{Deserialize, Serialize};
+
+/// What a new node needs to join a running cluster without replaying it:
+/// the tip to build on and who signs for the cluster now
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    /// The epoch the snapshot was taken in
+    pub epoch: Height,
+    /// The highest certified block, and its certificate
+    pub block: Block,
+    pub cert: Certificate,
+    pub finalized_height: Height,
+    /// The (encoded) public key of every replica
+    pub members: Vec<(Replica, Vec<u8>)>,
+    /// Beacons the sender produced in recent epochs
+    pub beacons: Vec<(Height, Hash)>,
+}
@@ -13,16 +13,96 @@ pub struct Storage {
     pub committed_blocks_by_ht: Box<HashMap<Height, Arc<Block>>>,
     pub committed_blocks_by_hash: Box<HashMap<Hash, Arc<Block>>>,
     pub pending_tx: Box<LinkedHashMap<Hash, Transaction>>,
+
+    // Committed blocks kept before the oldest are evicted, 0 for no limit
+    capacity: usize,
+    // Number of most recent heights that are never evicted
+    retain: Height,
+    bytes_used: usize,
+    evictions: u64,
 }
 
 impl Storage {
     pub fn new(space: usize) -> Self {
+        Storage::bounded(space, 0, 0)
+    }
+
+    /// Storage that holds at most `capacity` committed blocks, evicting the
+    /// lowest heights first but never one of the newest `retain` heights.
+    pub fn bounded(space: usize, capacity: usize, retain: Height) -> Self {
         Storage {
             all_delivered_blocks_by_hash: Box::new(HashMap::new()),
             all_delivered_blocks_by_ht: Box::new(HashMap::new()),
             committed_blocks_by_hash: Box::new(HashMap::new()),
             committed_blocks_by_ht: Box::new(HashMap::new()),
             pending_tx: Box::new(LinkedHashMap::with_capacity(space)),
+            capacity,
+            retain,
+            bytes_used: 0,
+            evictions: 0,
+        }
+    }
+
+    /// Stores a committed block, then evicts old blocks if over capacity.
+    pub fn insert_committed(&mut self, block: Arc<Block>) {
+        let height = block.header.height;
+        if let Some(old) = self.committed_blocks_by_ht.insert(height, Arc::clone(&block)) {
+            self.committed_blocks_by_hash.remove(&old.hash);
+            self.bytes_used -= block_size(&old);
         }
+        self.bytes_used += block_size(&block);
+        self.committed_blocks_by_hash.insert(block.hash, block);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        if self.capacity == 0 {
+            return;
+        }
+        let tip = match self.committed_blocks_by_ht.keys().max() {
+            Some(tip) => *tip,
+            None => return,
+        };
+        while self.len() > self.capacity {
+            let oldest = *self.committed_blocks_by_ht.keys().min().unwrap();
+            if oldest + self.retain > tip {
+                // Everything left is in the retained window
+                return;
+            }
+            let block = self.committed_blocks_by_ht.remove(&oldest).unwrap();
+            self.committed_blocks_by_hash.remove(&block.hash);
+            self.all_delivered_blocks_by_hash.remove(&block.hash);
+            self.all_delivered_blocks_by_ht.remove(&oldest);
+            self.bytes_used -= block_size(&block);
+            self.evictions += 1;
+        }
+    }
+
+    /// Number of committed blocks held
+    pub fn len(&self) -> usize {
+        self.committed_blocks_by_ht.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.committed_blocks_by_ht.is_empty()
     }
+
+    /// Committed blocks held before eviction starts, 0 for no limit
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Encoded size of the committed blocks held
+    pub fn bytes_used(&self) -> usize {
+        self.bytes_used
+    }
+
+    /// Number of committed blocks evicted so far
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+}
+
+fn block_size(block: &Block) -> usize {
+    bincode::serialized_size(block).unwrap_or(0) as usize
 }
@@ -1,2 +1,49 @@
 pub type Replica = u16;
 pub type Height = u64;
+
+/// Who an outgoing protocol message is for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Destination {
+    // Every other replica
+    All,
+    One(Replica),
+    Subset(Vec<Replica>),
+}
+
+impl Destination {
+    /// The replicas out of `replicas` that should receive the message when
+    /// `me` sends it. A node never sends to itself.
+    pub fn peers(&self, me: Replica, replicas: &[Replica]) -> Vec<Replica> {
+        let mut peers = match self {
+            Destination::All => replicas.to_vec(),
+            Destination::One(id) => vec![*id],
+            Destination::Subset(ids) => ids.clone(),
+        };
+        peers.retain(|id| *id != me && replicas.contains(id));
+        peers.sort();
+        peers.dedup();
+        peers
+    }
+
+    /// The ids to hand a network layer that takes `num_nodes` to mean every
+    /// replica. Only `All` turns into that id: a single replica or a subset
+    /// goes to each of its peers by their real ids, so that a replica whose
+    /// id reaches `num_nodes`, as after a reconfiguration, is never mistaken
+    /// for a broadcast.
+    pub fn wire_ids(&self, me: Replica, num_nodes: u16) -> Vec<Replica> {
+        match self {
+            Destination::All => vec![num_nodes],
+            _ => self.peers(me, &(0..num_nodes).collect::<Vec<_>>()),
+        }
+    }
+}
+
+impl std::fmt::Display for Destination {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Destination::All => write!(f, "all"),
+            Destination::One(id) => write!(f, "{}", id),
+            Destination::Subset(ids) => write!(f, "{:?}", ids),
+        }
+    }
+}
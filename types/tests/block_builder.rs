@@ -0,0 +1,73 @@
+use types::*;
+
+fn builder() -> BlockBuilder {
+    let mut body = BlockBody::new();
+    body.data.acks.push(Vote {
+        msg: vec![1, 2, 3],
+        origin: 2,
+        auth: vec![9; 4],
+    });
+    BlockBuilder::new()
+        .prev([0xab; 32])
+        .author(1)
+        .height(5)
+        .body(body)
+}
+
+#[test]
+fn built_blocks_have_a_matching_hash() {
+    let block = builder()
+        .payload(16)
+        .certificate(Certificate {
+            votes: vec![Vote {
+                msg: vec![0xab; 32],
+                origin: 3,
+                auth: vec![8; 4],
+            }],
+        })
+        .build()
+        .unwrap();
+    assert_eq!(block.header.prev, [0xab; 32]);
+    assert_eq!(block.header.author, 1);
+    assert_eq!(block.header.height, 5);
+    assert_eq!(block.payload.len(), 16);
+
+    let mut rehashed = block.clone();
+    rehashed.update_hash();
+    assert_eq!(rehashed.hash, block.hash);
+    assert_eq!(rehashed.header.body_root, block.header.body_root);
+
+    // Neither the padding nor the certificate is part of the hash
+    let plain = builder().build().unwrap();
+    assert_eq!(plain.hash, block.hash);
+    // Every header field is
+    let other = builder().height(6).build().unwrap();
+    assert_ne!(other.hash, block.hash);
+}
+
+#[test]
+fn missing_parts_fail_to_build() {
+    let body = || BlockBody::new();
+    let cases = vec![
+        (
+            BlockBuilder::new().author(1).height(1).body(body()),
+            BlockBuildError::MissingPrev,
+        ),
+        (
+            BlockBuilder::new().prev([0; 32]).height(1).body(body()),
+            BlockBuildError::MissingAuthor,
+        ),
+        (
+            BlockBuilder::new().prev([0; 32]).author(1).body(body()),
+            BlockBuildError::MissingHeight,
+        ),
+        (
+            BlockBuilder::new().prev([0; 32]).author(1).height(1),
+            BlockBuildError::MissingBody,
+        ),
+    ];
+    for (builder, missing) in cases {
+        assert_eq!(builder.check(), Err(missing));
+        assert_eq!(builder.build().err(), Some(missing));
+    }
+}
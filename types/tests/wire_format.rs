@@ -0,0 +1,159 @@
+// Pins the bytes replicas exchange. Each test encodes a fixed instance and
+// compares it against tests/snapshots/<name>.hex, then decodes the snapshot
+// and checks it encodes back to the same bytes. A failure here means nodes
+// built before and after the change cannot talk to each other; if that is
+// intended, rerun with UPDATE_SNAPSHOTS=1 and commit the new snapshots.
+
+use serde::Serialize;
+use std::path::PathBuf;
+use types::*;
+
+fn snapshot_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{}.hex", name))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Vec<u8> {
+    let s = s.trim();
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).expect("bad hex in snapshot"))
+        .collect()
+}
+
+/// Checks the encoding of `value` against the snapshot and returns the
+/// snapshot bytes
+fn check<T: Serialize>(name: &str, value: &T) -> Vec<u8> {
+    let path = snapshot_path(name);
+    let encoded = bincode::serialize(value).unwrap();
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, format!("{}\n", to_hex(&encoded))).unwrap();
+    }
+    let golden = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e));
+    assert_eq!(
+        to_hex(&encoded),
+        golden.trim(),
+        "the encoding of {} changed; this breaks compatibility with older nodes",
+        name
+    );
+    from_hex(&golden)
+}
+
+/// Decoding the snapshot gives back something that encodes the same
+fn round_trip<T: Serialize>(golden: &[u8], decoded: &T) {
+    assert_eq!(bincode::serialize(decoded).unwrap(), golden);
+}
+
+fn vote() -> Vote {
+    Vote {
+        msg: vec![1, 2, 3],
+        origin: 2,
+        auth: vec![9; 4],
+    }
+}
+
+fn certificate() -> Certificate {
+    let mut other = vote();
+    other.origin = 3;
+    other.auth = vec![8; 4];
+    Certificate {
+        votes: vec![vote(), other],
+    }
+}
+
+fn reconfig() -> Reconfig {
+    Reconfig::RotateKey(3, vec![4, 5])
+}
+
+fn block() -> Block {
+    let mut block = Block::new();
+    block.header.prev = [0xab; 32];
+    block.header.author = 1;
+    block.header.height = 5;
+    block.body.data.acks.push(vote());
+    block.body.data.reconfig.push((reconfig(), vec![6]));
+    block.payload = vec![0, 1, 2, 3];
+    block
+}
+
+fn propose() -> Propose {
+    Propose {
+        new_block: block(),
+        certificate: certificate(),
+        epoch: 7,
+        batch: Vec::new(),
+    }
+}
+
+#[test]
+fn vote_format() {
+    let golden = check("vote", &vote());
+    let decoded: Vote = bincode::deserialize(&golden).unwrap();
+    assert_eq!(decoded.origin, 2);
+    round_trip(&golden, &decoded);
+}
+
+#[test]
+fn certificate_format() {
+    let golden = check("certificate", &certificate());
+    let decoded = Certificate::from_bytes(&golden);
+    assert_eq!(decoded.votes.len(), 2);
+    round_trip(&golden, &decoded);
+}
+
+#[test]
+fn transaction_format() {
+    let tx = Transaction {
+        data: b"data".to_vec(),
+        request: b"req".to_vec(),
+    };
+    let golden = check("transaction", &tx);
+    let decoded = Transaction::from_bytes(&golden);
+    assert_eq!(decoded.request, b"req");
+    round_trip(&golden, &decoded);
+}
+
+#[test]
+fn propose_format() {
+    let golden = check("propose", &propose());
+    let decoded = Propose::from_bytes(&golden);
+    assert_eq!(decoded.epoch, 7);
+    assert_eq!(decoded.new_block.header.height, 5);
+    assert_eq!(decoded.new_block.body.data.reconfig[0].0, reconfig());
+    round_trip(&golden, &decoded);
+}
+
+#[test]
+fn protocol_msg_format() {
+    let acc = DataWithAcc {
+        sign: vec![1],
+        tree: vec![vec![2, 3]],
+        size: 4,
+    };
+    let msgs = vec![
+        ("msg_certificate", ProtocolMsg::Certificate(certificate(), 7, 1_600_000_000_000)),
+        ("msg_propose", ProtocolMsg::Propose(propose(), acc)),
+        ("msg_vote", ProtocolMsg::Vote(vote(), 7)),
+        ("msg_reconstruct_request", ProtocolMsg::ReconstructRequest(2, 7)),
+        ("msg_reconfig", ProtocolMsg::Reconfig(reconfig(), vec![6], 7)),
+        ("msg_epoch_done", ProtocolMsg::EpochDone(7, [0x11; 32])),
+        ("msg_block_request", ProtocolMsg::BlockRequest([0x22; 32], 7)),
+        ("msg_block_response", ProtocolMsg::BlockResponse(block(), 7)),
+        ("msg_ready", ProtocolMsg::Ready(true)),
+        ("msg_request_share", ProtocolMsg::RequestShare(7, Gatherer::Commit, 2)),
+        ("msg_deal_request", ProtocolMsg::DealRequest(7)),
+    ];
+    for (name, msg) in msgs {
+        let golden = check(name, &msg);
+        let decoded = ProtocolMsg::from_bytes(&golden);
+        assert_eq!(decoded.to_string(), msg.to_string());
+        assert_eq!(decoded.epoch(), msg.epoch());
+        round_trip(&golden, &decoded);
+    }
+}
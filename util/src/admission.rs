@@ -0,0 +1,259 @@
+// Capping the number of client connections. Every accepted connection
+// holds a slot until it is dropped. Once every slot is taken, a new
+// connection is either turned away, for the listener to tell it the node is
+// busy, or takes the slot of the connection idle longest, which is told to
+// close. Connections that have sent nothing yet are evicted before any that
+// have, so that a flood of connections that never send only evicts itself
+// and cannot push out the clients in use.
+
+use config::{ClientOverflow, Node};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::Notify;
+
+struct Slot {
+    connected: Instant,
+    // When the client last sent something, if it has
+    active: Option<Instant>,
+    evict: Arc<Notify>,
+}
+
+struct State {
+    max: usize,
+    policy: ClientOverflow,
+    next_id: u64,
+    slots: HashMap<u64, Slot>,
+    rejected: u64,
+    evicted: u64,
+}
+
+impl State {
+    // The connection to make room by evicting: the one idle longest of
+    // those that never sent anything, or else of all of them
+    fn victim(&self) -> Option<u64> {
+        self.slots
+            .iter()
+            .min_by_key(|(id, slot)| (slot.active.is_some(), slot.active.unwrap_or(slot.connected), **id))
+            .map(|(id, _)| *id)
+    }
+}
+
+/// Hands out the client slots of a node
+#[derive(Clone)]
+pub struct Admission {
+    state: Arc<Mutex<State>>,
+}
+
+/// Whether a new connection may be served
+pub enum Admit {
+    /// It is, for as long as the ticket is kept
+    Accepted(Ticket),
+    /// Every slot is taken; the connection is to be sent `ClientMsg::Busy`
+    /// and closed
+    Busy,
+}
+
+/// A connection's slot, given back when this is dropped
+pub struct Ticket {
+    id: u64,
+    state: Arc<Mutex<State>>,
+    evict: Arc<Notify>,
+}
+
+impl Admission {
+    /// Slots for `max` connections at once, 0 for no limit
+    pub fn new(max: usize, policy: ClientOverflow) -> Self {
+        Admission {
+            state: Arc::new(Mutex::new(State {
+                max,
+                policy,
+                next_id: 0,
+                slots: HashMap::new(),
+                rejected: 0,
+                evicted: 0,
+            })),
+        }
+    }
+
+    pub fn from_config(config: &Node) -> Self {
+        Admission::new(config.max_clients(), config.client_overflow)
+    }
+
+    /// Takes a slot for a new connection, evicting another if the policy
+    /// says so
+    pub fn admit(&self) -> Admit {
+        let mut state = self.state.lock().unwrap();
+        if state.max > 0 && state.slots.len() >= state.max {
+            let victim = match state.policy {
+                ClientOverflow::Reject => None,
+                ClientOverflow::EvictIdle => state.victim(),
+            };
+            match victim.and_then(|id| state.slots.remove(&id)) {
+                Some(slot) => {
+                    log::info!(target:"util", "Serving {} clients; evicting one to make room", state.max);
+                    slot.evict.notify_one();
+                    state.evicted += 1;
+                }
+                None => {
+                    state.rejected += 1;
+                    return Admit::Busy;
+                }
+            }
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        let evict = Arc::new(Notify::new());
+        state.slots.insert(
+            id,
+            Slot {
+                connected: Instant::now(),
+                active: None,
+                evict: Arc::clone(&evict),
+            },
+        );
+        Admit::Accepted(Ticket {
+            id,
+            state: Arc::clone(&self.state),
+            evict,
+        })
+    }
+
+    /// Connections holding a slot
+    pub fn connected(&self) -> usize {
+        self.state.lock().unwrap().slots.len()
+    }
+
+    /// Connections turned away so far
+    pub fn rejected(&self) -> u64 {
+        self.state.lock().unwrap().rejected
+    }
+
+    /// Connections evicted to make room so far
+    pub fn evicted(&self) -> u64 {
+        self.state.lock().unwrap().evicted
+    }
+}
+
+impl Ticket {
+    /// Records that the client sent something
+    pub fn touch(&self) {
+        if let Some(slot) = self.state.lock().unwrap().slots.get_mut(&self.id) {
+            slot.active = Some(Instant::now());
+        }
+    }
+
+    /// Whether the slot was taken away to make room for another connection
+    pub fn is_evicted(&self) -> bool {
+        !self.state.lock().unwrap().slots.contains_key(&self.id)
+    }
+
+    /// Completes once the slot is taken away, when the connection is to be
+    /// closed
+    pub fn evicted(&self) -> impl Future<Output = ()> + 'static {
+        let evict = Arc::clone(&self.evict);
+        async move { evict.notified().await }
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().slots.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use types::ClientMsg;
+
+    fn accepted(admit: Admit) -> Ticket {
+        match admit {
+            Admit::Accepted(ticket) => ticket,
+            Admit::Busy => panic!("turned away below the limit"),
+        }
+    }
+
+    #[test]
+    fn connections_beyond_the_limit_are_turned_away() {
+        let admission = Admission::new(3, ClientOverflow::Reject);
+        let mut tickets: Vec<Ticket> = (0..3).map(|_| accepted(admission.admit())).collect();
+        for _ in 0..2 {
+            assert!(matches!(admission.admit(), Admit::Busy));
+        }
+        assert_eq!(admission.connected(), 3);
+        assert_eq!(admission.rejected(), 2);
+        assert!(tickets.iter().all(|t| !t.is_evicted()));
+        // A slot given back can be taken again
+        tickets.pop();
+        tickets.push(accepted(admission.admit()));
+        assert_eq!(admission.connected(), 3);
+    }
+
+    #[test]
+    fn a_flood_only_evicts_connections_that_never_sent() {
+        let admission = Admission::new(3, ClientOverflow::EvictIdle);
+        let clients: Vec<Ticket> = (0..2).map(|_| accepted(admission.admit())).collect();
+        for client in clients.iter() {
+            client.touch();
+        }
+        let mut flood = Vec::new();
+        for _ in 0..5 {
+            flood.push(accepted(admission.admit()));
+        }
+        assert_eq!(admission.connected(), 3);
+        assert_eq!(admission.evicted(), 4);
+        assert!(clients.iter().all(|c| !c.is_evicted()));
+        // Each newcomer took the place of the one before it
+        assert!(flood[..4].iter().all(|f| f.is_evicted()));
+        assert!(flood[0].evicted().now_or_never().is_some());
+        assert!(!flood[4].is_evicted());
+
+        // Among clients that did send, the one idle longest goes
+        flood[4].touch();
+        clients[1].touch();
+        accepted(admission.admit());
+        assert!(clients[0].is_evicted());
+        assert!(!clients[1].is_evicted());
+    }
+
+    #[test]
+    fn without_a_limit_everyone_is_served() {
+        let admission = Admission::new(0, ClientOverflow::Reject);
+        let tickets: Vec<Ticket> = (0..100).map(|_| accepted(admission.admit())).collect();
+        assert_eq!(admission.connected(), tickets.len());
+    }
+
+    #[tokio::test]
+    async fn a_client_over_the_limit_is_told_the_node_is_busy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let admission = Admission::new(2, ClientOverflow::Reject);
+        tokio::spawn(async move {
+            let mut served = Vec::new();
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                match admission.admit() {
+                    Admit::Accepted(ticket) => served.push((stream, ticket)),
+                    Admit::Busy => {
+                        let busy = bincode::serialize(&ClientMsg::Busy).unwrap();
+                        let _ = stream.write_all(&busy).await;
+                    }
+                }
+            }
+        });
+
+        let mut clients = Vec::new();
+        for _ in 0..2 {
+            clients.push(TcpStream::connect(addr).await.unwrap());
+        }
+        let mut extra = TcpStream::connect(addr).await.unwrap();
+        let mut reply = Vec::new();
+        extra.read_to_end(&mut reply).await.unwrap();
+        assert_eq!(ClientMsg::from_bytes(&reply), ClientMsg::Busy);
+    }
+}
@@ -5,7 +5,7 @@ use std::sync::Arc;
 use tokio_util::codec::{length_delimited::LengthDelimitedCodec, Decoder, Encoder};
 use types::Block;
 
-use crate::io::to_bytes;
+use crate::io::to_frame;
 pub struct Codec(pub LengthDelimitedCodec);
 
 impl Codec {
@@ -18,7 +18,7 @@ impl Encoder<Block> for super::EnCodec {
     type Error = Error;
 
     fn encode(&mut self, item: Block, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let data = to_bytes(&item);
+        let data = to_frame(&item)?;
         let buf = Bytes::from(data);
         return self.0.encode(buf, dst);
     }
@@ -29,7 +29,7 @@ impl Encoder<Arc<Block>> for super::EnCodec {
 
     fn encode(&mut self, item: Arc<Block>, dst: &mut BytesMut) -> Result<(), Self::Error> {
         let b_addr: &Block = item.borrow();
-        let data = to_bytes(b_addr);
+        let data = to_frame(b_addr)?;
         let buf = Bytes::from(data);
         return self.0.encode(buf, dst);
     }
@@ -1,4 +1,5 @@
 use tokio_util::codec::LengthDelimitedCodec;
+use types::CHAIN_TAG_SIZE;
 
 pub mod block;
 pub mod tx;
@@ -6,17 +7,29 @@ pub mod tx;
 pub mod proto;
 pub mod synchs;
 
+/// Frames what is written. Protocol messages are tagged with the chain, if
+/// one is set.
 #[derive(Debug)]
-pub struct EnCodec(pub LengthDelimitedCodec);
+pub struct EnCodec(pub LengthDelimitedCodec, pub Option<[u8; CHAIN_TAG_SIZE]>);
 
 impl EnCodec {
     pub fn new() -> Self {
-        EnCodec(LengthDelimitedCodec::new())
+        EnCodec(LengthDelimitedCodec::new(), None)
+    }
+
+    /// Tagging protocol messages with `chain`, if any
+    pub fn with_chain(mut self, chain: Option<[u8; CHAIN_TAG_SIZE]>) -> Self {
+        self.1 = chain;
+        self
+    }
+
+    pub fn from_config(config: &config::Node) -> Self {
+        EnCodec::new().with_chain(config.chain_id.as_deref().map(types::chain_tag))
     }
 }
 
 impl std::clone::Clone for EnCodec {
     fn clone(&self) -> Self {
-        EnCodec::new()
+        EnCodec::new().with_chain(self.1)
     }
 }
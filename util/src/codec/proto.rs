@@ -1,17 +1,69 @@
 use bytes::{Bytes, BytesMut};
+use config::{MessageHandling, Node};
 use tokio_util::codec::{Decoder, Encoder, LengthDelimitedCodec};
-use types::ProtocolMsg;
+use types::{chain_tag, ProtocolMsg, CHAIN_TAG_SIZE};
 
 use std::{borrow::Borrow, io, sync::Arc};
 
-use crate::io::to_bytes;
+use crate::io::to_frame;
+use crate::sequence::{Seq, Sequenced};
 
+// Bytes of the sequence number in front of a sequenced frame
+const SEQ_SIZE: usize = std::mem::size_of::<Seq>();
+
+/// Reads protocol messages. With a chain set, only messages tagged for it
+/// are taken; the rest were misrouted from another cluster, and are dropped
+/// whatever the message handling.
 #[derive(Debug)]
-pub struct Codec(pub LengthDelimitedCodec);
+pub struct Codec(
+    pub LengthDelimitedCodec,
+    pub MessageHandling,
+    pub Option<[u8; CHAIN_TAG_SIZE]>,
+);
 
 impl Codec {
     pub fn new() -> Self {
-        Codec(LengthDelimitedCodec::new())
+        Codec::with_handling(MessageHandling::default())
+    }
+
+    pub fn with_handling(handling: MessageHandling) -> Self {
+        Codec(LengthDelimitedCodec::new(), handling, None)
+    }
+
+    /// Only taking messages tagged with `chain`, if any
+    pub fn with_chain(mut self, chain: Option<[u8; CHAIN_TAG_SIZE]>) -> Self {
+        self.2 = chain;
+        self
+    }
+
+    pub fn from_config(config: &Node) -> Self {
+        Codec::with_handling(config.message_handling).with_chain(config.chain_id.as_deref().map(chain_tag))
+    }
+}
+
+impl Codec {
+    // The message in the frame `data`, or `None` if it is to be skipped
+    fn message(&self, data: &[u8]) -> Option<ProtocolMsg> {
+        let data = match self.2 {
+            None => data,
+            Some(chain) if data.starts_with(&chain) => &data[CHAIN_TAG_SIZE..],
+            Some(_) => {
+                log::warn!(target:"util", "Dropping a protocol message of {} bytes from another chain", data.len());
+                return None;
+            }
+        };
+        if let Some(msg) = ProtocolMsg::try_from_bytes(data) {
+            return Some(msg);
+        }
+        match self.1 {
+            MessageHandling::Strict => {
+                panic!("Received a malformed protocol message of {} bytes", data.len())
+            }
+            MessageHandling::Lenient => {
+                log::warn!(target:"util", "Skipping a malformed protocol message of {} bytes", data.len());
+                None
+            }
+        }
     }
 }
 
@@ -19,11 +71,54 @@ impl Decoder for Codec {
     type Item = ProtocolMsg;
     type Error = io::Error;
 
+    // A frame that does not decode is skipped in lenient mode, so one bad
+    // message does not cost the whole connection
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let in_data = match self.0.decode(src)? {
+                Some(in_data) => in_data,
+                None => return Ok(None),
+            };
+            if let Some(msg) = self.message(&in_data) {
+                return Ok(Some(msg));
+            }
+        }
+    }
+}
+
+/// Reads sequenced protocol messages. A frame whose message is skipped, as
+/// `Codec` would, still comes out with its sequence number, so that the
+/// frames after it are not taken for a gap.
+#[derive(Debug)]
+pub struct SeqCodec(pub Codec);
+
+impl Decoder for SeqCodec {
+    type Item = Sequenced<Option<ProtocolMsg>>;
+    type Error = io::Error;
+
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        match self.0.decode(src)? {
-            Some(in_data) => Ok(Some(ProtocolMsg::from_bytes(&in_data))),
-            None => Ok(None),
+        let in_data = match self.0 .0.decode(src)? {
+            Some(in_data) => in_data,
+            None => return Ok(None),
+        };
+        if in_data.len() < SEQ_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too short for a sequence number"));
         }
+        let mut seq = [0; SEQ_SIZE];
+        seq.copy_from_slice(&in_data[..SEQ_SIZE]);
+        Ok(Some(Sequenced {
+            seq: Seq::from_be_bytes(seq),
+            frame: self.0.message(&in_data[SEQ_SIZE..]),
+        }))
+    }
+}
+
+impl Encoder<Sequenced<Arc<ProtocolMsg>>> for super::EnCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Sequenced<Arc<ProtocolMsg>>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let data = [&item.seq.to_be_bytes()[..], &tagged(self.1, &item.frame)?].concat();
+        self.0.encode(Bytes::from(data), dst)
     }
 }
 
@@ -31,7 +126,7 @@ impl Encoder<ProtocolMsg> for super::EnCodec {
     type Error = io::Error;
 
     fn encode(&mut self, item: ProtocolMsg, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let data = to_bytes(&item);
+        let data = tagged(self.1, &item)?;
         let buf = Bytes::from(data);
         return self.0.encode(buf, dst);
     }
@@ -42,14 +137,108 @@ impl Encoder<Arc<ProtocolMsg>> for super::EnCodec {
 
     fn encode(&mut self, item: Arc<ProtocolMsg>, dst: &mut BytesMut) -> Result<(), Self::Error> {
         let bor: &ProtocolMsg = item.borrow();
-        let data = to_bytes(bor);
+        let data = tagged(self.1, bor)?;
         let buf = Bytes::from(data);
         return self.0.encode(buf, dst);
     }
 }
 
+// The frame of `msg`, behind the tag of `chain` if there is one
+fn tagged(chain: Option<[u8; CHAIN_TAG_SIZE]>, msg: &ProtocolMsg) -> io::Result<Vec<u8>> {
+    let data = to_frame(msg)?;
+    Ok(match chain {
+        Some(chain) => [&chain[..], &data].concat(),
+        None => data,
+    })
+}
+
 impl std::clone::Clone for Codec {
     fn clone(&self) -> Self {
-        Codec::new()
+        Codec::with_handling(self.1).with_chain(self.2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames() -> BytesMut {
+        let mut src = BytesMut::new();
+        let mut framer = LengthDelimitedCodec::new();
+        framer.encode(Bytes::from_static(&[0xff; 3]), &mut src).unwrap();
+        super::super::EnCodec::new()
+            .encode(ProtocolMsg::EpochDone(7, [1; 32]), &mut src)
+            .unwrap();
+        src
+    }
+
+    #[test]
+    fn lenient_skips_a_malformed_frame() {
+        let mut src = frames();
+        match Codec::with_handling(MessageHandling::Lenient).decode(&mut src) {
+            Ok(Some(ProtocolMsg::EpochDone(7, beacon))) => assert_eq!(beacon, [1; 32]),
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    fn messages_from_another_chain_are_dropped() {
+        let ours = Some(chain_tag("ours"));
+        let mut src = BytesMut::new();
+        super::super::EnCodec::new()
+            .with_chain(Some(chain_tag("theirs")))
+            .encode(ProtocolMsg::EpochDone(6, [2; 32]), &mut src)
+            .unwrap();
+        super::super::EnCodec::new()
+            .encode(ProtocolMsg::EpochDone(7, [2; 32]), &mut src)
+            .unwrap();
+        super::super::EnCodec::new()
+            .with_chain(ours)
+            .encode(ProtocolMsg::EpochDone(8, [2; 32]), &mut src)
+            .unwrap();
+        // Even a strict node only drops what was meant for another chain
+        let mut codec = Codec::with_handling(MessageHandling::Strict).with_chain(ours);
+        match codec.decode(&mut src) {
+            Ok(Some(ProtocolMsg::EpochDone(e, _))) => assert_eq!(e, 8),
+            r => panic!("unexpected {:?}", r),
+        }
+        assert!(codec.decode(&mut src).unwrap().is_none());
+    }
+
+    #[test]
+    fn the_chain_tag_depends_on_the_name() {
+        assert_eq!(chain_tag("ours"), chain_tag("ours"));
+        assert_ne!(chain_tag("ours"), chain_tag("theirs"));
+    }
+
+    #[test]
+    fn a_skipped_sequenced_frame_keeps_its_number() {
+        let mut src = BytesMut::new();
+        let mut framer = LengthDelimitedCodec::new();
+        framer
+            .encode(Bytes::from([&7u64.to_be_bytes()[..], &[0xff; 3]].concat()), &mut src)
+            .unwrap();
+        let msg = Arc::new(ProtocolMsg::EpochDone(7, [1; 32]));
+        super::super::EnCodec::new()
+            .encode(Sequenced { seq: 8, frame: msg }, &mut src)
+            .unwrap();
+
+        let mut codec = SeqCodec(Codec::with_handling(MessageHandling::Lenient));
+        let skipped = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!((skipped.seq, skipped.frame.is_none()), (7, true));
+        match codec.decode(&mut src).unwrap().unwrap() {
+            Sequenced {
+                seq: 8,
+                frame: Some(ProtocolMsg::EpochDone(7, _)),
+            } => (),
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "malformed protocol message")]
+    fn strict_panics_on_a_malformed_frame() {
+        let mut src = frames();
+        let _ = Codec::with_handling(MessageHandling::Strict).decode(&mut src);
     }
 }
@@ -4,7 +4,7 @@ use types::synchs::ProtocolMsg;
 
 use std::{borrow::Borrow, io, sync::Arc};
 
-use crate::io::to_bytes;
+use crate::io::to_frame;
 
 pub struct Codec(pub LengthDelimitedCodec);
 
@@ -30,7 +30,7 @@ impl Encoder<ProtocolMsg> for super::EnCodec {
     type Error = io::Error;
 
     fn encode(&mut self, item: ProtocolMsg, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let data = to_bytes(&item);
+        let data = to_frame(&item)?;
         let buf = Bytes::from(data);
         return self.0.encode(buf, dst);
     }
@@ -41,7 +41,7 @@ impl Encoder<Arc<ProtocolMsg>> for super::EnCodec {
 
     fn encode(&mut self, item: Arc<ProtocolMsg>, dst: &mut BytesMut) -> Result<(), Self::Error> {
         let bor: &ProtocolMsg = item.borrow();
-        let data = to_bytes(bor);
+        let data = to_frame(bor)?;
         let buf = Bytes::from(data);
         return self.0.encode(buf, dst);
     }
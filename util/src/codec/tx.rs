@@ -1,18 +1,28 @@
-use bytes::{Bytes, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 use tokio_util::codec::{Decoder, Encoder, LengthDelimitedCodec};
 use types::Transaction;
 
+use std::collections::VecDeque;
 use std::io::Error;
 use std::{borrow::Borrow, sync::Arc};
 
-use crate::io::to_bytes;
+use crate::io::to_frame;
+
+/// Starts a frame holding many transactions. A single transaction frame
+/// starts with the length of its data, which can never be this large.
+pub const BATCH_MARKER: [u8; 8] = [0xff; 8];
+
+/// Transactions sent together in one frame
+#[derive(Debug, Clone)]
+pub struct TxBatch(pub Vec<Transaction>);
 
 #[derive(Debug)]
-pub struct Codec(pub LengthDelimitedCodec);
+pub struct Codec(pub LengthDelimitedCodec, VecDeque<Transaction>);
 
 impl Codec {
     pub fn new() -> Self {
-        Codec(LengthDelimitedCodec::new())
+        Codec(LengthDelimitedCodec::new(), VecDeque::new())
     }
 }
 
@@ -24,7 +34,7 @@ impl Encoder<Transaction> for super::EnCodec {
     type Error = Error;
 
     fn encode(&mut self, item: Transaction, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let buf = Bytes::from(to_bytes(&item));
+        let buf = Bytes::from(to_frame(&item)?);
         return self.0.encode(buf, dst);
     }
 }
@@ -34,21 +44,73 @@ impl Encoder<Arc<Transaction>> for super::EnCodec {
 
     fn encode(&mut self, item: Arc<Transaction>, dst: &mut BytesMut) -> Result<(), Self::Error> {
         let bor: &Transaction = item.borrow();
-        let buf = Bytes::from(to_bytes(bor));
+        let buf = Bytes::from(to_frame(bor)?);
         return self.0.encode(buf, dst);
     }
 }
 
+impl Encoder<TxBatch> for super::EnCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: TxBatch, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut buf = BytesMut::from(&BATCH_MARKER[..]);
+        buf.put_slice(&to_frame(&item.0)?);
+        return self.0.encode(buf.freeze(), dst);
+    }
+}
+
+// Batches are handed out one transaction at a time, so the server side does
+// not care how the client framed them
 impl Decoder for Codec {
     type Item = Transaction;
     type Error = Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        match self.0.decode(src)? {
-            Some(in_data) => Ok(Some(Transaction::from_bytes(&in_data))),
-            None => Ok(None),
+        if let Some(tx) = self.1.pop_front() {
+            return Ok(Some(tx));
+        }
+        loop {
+            let in_data = match self.0.decode(src)? {
+                Some(in_data) => in_data,
+                None => return Ok(None),
+            };
+            if !in_data.starts_with(&BATCH_MARKER) {
+                return Ok(Some(Transaction::from_bytes(&in_data)));
+            }
+            let batch: Vec<Transaction> = bincode::deserialize(&in_data[BATCH_MARKER.len()..])
+                .map_err(|e| Error::new(std::io::ErrorKind::InvalidData, e))?;
+            self.1.extend(batch);
+            // An empty batch yields nothing, so move on to the next frame
+            if let Some(tx) = self.1.pop_front() {
+                return Ok(Some(tx));
+            }
+        }
+    }
+}
+
+/// Writes `txs` to `w`, at most `batch` transactions per frame.
+pub async fn submit<W, I>(w: &mut W, txs: I, batch: usize) -> Result<(), Error>
+where
+    W: AsyncWrite + Unpin,
+    I: IntoIterator<Item = Transaction>,
+{
+    let batch = batch.max(1);
+    let mut codec = super::EnCodec::new();
+    let mut buf = BytesMut::new();
+    let mut pending = Vec::with_capacity(batch);
+    for tx in txs {
+        pending.push(tx);
+        if pending.len() == batch {
+            codec.encode(TxBatch(std::mem::take(&mut pending)), &mut buf)?;
+            w.write_all(&buf).await?;
+            buf.clear();
         }
     }
+    if !pending.is_empty() {
+        codec.encode(TxBatch(pending), &mut buf)?;
+        w.write_all(&buf).await?;
+    }
+    w.flush().await
 }
 
 impl std::clone::Clone for Codec {
@@ -56,3 +118,30 @@ impl std::clone::Clone for Codec {
         Codec::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn batch_of_ten_thousand_comes_out_one_by_one() {
+        let txs: Vec<_> = (0..10_000).map(|i| crate::new_dummy_tx(i, 16)).collect();
+        let mut wire = Vec::new();
+        submit(&mut wire, txs.clone(), txs.len()).await.unwrap();
+        // Old clients keep sending one transaction per frame
+        let mut src = BytesMut::from(&wire[..]);
+        super::super::EnCodec::new()
+            .encode(crate::new_dummy_tx(10_000, 16), &mut src)
+            .unwrap();
+
+        let mut codec = Codec::new();
+        let mut received = Vec::new();
+        while let Some(tx) = codec.decode(&mut src).unwrap() {
+            received.push(tx.data);
+        }
+        assert_eq!(received.len(), 10_001);
+        for (i, data) in received.iter().enumerate() {
+            assert_eq!(*data, (i as u64).to_be_bytes().to_vec());
+        }
+    }
+}
@@ -0,0 +1,262 @@
+// Sending every block to every connected client without spawning a task per
+// client per block
+
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::Mutex;
+
+/// Number of concurrent client writes when the config does not say
+pub const DEFAULT_FANOUT: usize = 64;
+
+struct Client<W> {
+    // Gone once the client is dropped
+    writer: Mutex<Option<W>>,
+    pending: std::sync::Mutex<VecDeque<Bytes>>,
+    // Whether a worker already has this client on its list
+    scheduled: AtomicBool,
+    dropped: AtomicBool,
+}
+
+impl<W> Client<W> {
+    fn pop(&self) -> Option<Bytes> {
+        self.pending.lock().unwrap().pop_front()
+    }
+
+    fn is_dropped(&self) -> bool {
+        self.dropped.load(Ordering::SeqCst)
+    }
+}
+
+/// Writes encoded blocks to a set of clients using a fixed pool of workers,
+/// so at most `workers` writes are in progress at any time. Each client is
+/// served by one worker at a time, in the order the blocks were sent, and
+/// the writers stay in the pool between blocks. A client that takes longer
+/// than the write timeout to accept a block is dropped, so that it holds up
+/// neither a worker nor the other clients, and so is one whose connection
+/// failed.
+pub struct Fanout<W> {
+    clients: Vec<Arc<Client<W>>>,
+    jobs: UnboundedSender<Arc<Client<W>>>,
+    in_flight: Arc<AtomicUsize>,
+    max_in_flight: Arc<AtomicUsize>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl<W> Fanout<W>
+where
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    /// Starts `workers` worker tasks on the current runtime. 0 means
+    /// `DEFAULT_FANOUT`. Without a `write_timeout` a write may take as long
+    /// as the client makes it.
+    pub fn new(workers: usize, write_timeout: Option<Duration>) -> Self {
+        let workers = if workers == 0 {
+            DEFAULT_FANOUT
+        } else {
+            workers
+        };
+        let (jobs, recv) = unbounded_channel::<Arc<Client<W>>>();
+        let recv = Arc::new(Mutex::new(recv));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let dropped = Arc::new(AtomicUsize::new(0));
+        for _ in 0..workers {
+            let recv = Arc::clone(&recv);
+            let in_flight = Arc::clone(&in_flight);
+            let max_in_flight = Arc::clone(&max_in_flight);
+            let dropped = Arc::clone(&dropped);
+            tokio::spawn(async move {
+                loop {
+                    let client = match recv.lock().await.recv().await {
+                        Some(client) => client,
+                        None => return,
+                    };
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(now, Ordering::SeqCst);
+                    let mut writer = client.writer.lock().await;
+                    loop {
+                        while let Some(data) = client.pop() {
+                            let w = match writer.as_mut() {
+                                Some(w) => w,
+                                None => break,
+                            };
+                            let written = match write_timeout {
+                                Some(t) => tokio::time::timeout(t, w.write_all(&data)).await,
+                                None => Ok(w.write_all(&data).await),
+                            };
+                            match written {
+                                Ok(Ok(())) => continue,
+                                Ok(Err(e)) => log::warn!(target:"util", "Failed to write to a client: {}; dropping it", e),
+                                Err(_) => {
+                                    log::warn!(target:"util", "A client did not take a block within {:?}; dropping it", write_timeout.unwrap());
+                                    dropped.fetch_add(1, Ordering::SeqCst);
+                                }
+                            }
+                            client.dropped.store(true, Ordering::SeqCst);
+                            *writer = None;
+                            client.pending.lock().unwrap().clear();
+                        }
+                        client.scheduled.store(false, Ordering::SeqCst);
+                        // Something may have been queued after the last pop
+                        if client.pending.lock().unwrap().is_empty()
+                            || client.scheduled.swap(true, Ordering::SeqCst)
+                        {
+                            break;
+                        }
+                    }
+                    drop(writer);
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                }
+            });
+        }
+        Fanout {
+            clients: Vec::new(),
+            jobs,
+            in_flight,
+            max_in_flight,
+            dropped,
+        }
+    }
+
+    pub fn add_client(&mut self, writer: W) {
+        self.clients.retain(|c| !c.is_dropped());
+        self.clients.push(Arc::new(Client {
+            writer: Mutex::new(Some(writer)),
+            pending: std::sync::Mutex::new(VecDeque::new()),
+            scheduled: AtomicBool::new(false),
+            dropped: AtomicBool::new(false),
+        }));
+    }
+
+    /// Number of clients that have not been dropped
+    pub fn num_clients(&self) -> usize {
+        self.clients.iter().filter(|c| !c.is_dropped()).count()
+    }
+
+    /// Queues `data` for every client that has not been dropped.
+    pub fn send(&self, data: Bytes) {
+        for client in self.clients.iter().filter(|c| !c.is_dropped()) {
+            client.pending.lock().unwrap().push_back(data.clone());
+            if !client.scheduled.swap(true, Ordering::SeqCst) {
+                let _ = self.jobs.send(Arc::clone(client));
+            }
+        }
+    }
+
+    /// Number of clients being written to right now
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// The most clients that were ever being written to at once
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Clients dropped for taking too long to accept a block
+    pub fn dropped_clients(&self) -> usize {
+        self.dropped.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{duplex, AsyncReadExt};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn thousand_clients_bounded_writes() {
+        const CLIENTS: usize = 1000;
+        const WORKERS: usize = 8;
+        let mut fanout = Fanout::new(WORKERS, None);
+        let mut readers = Vec::with_capacity(CLIENTS);
+        for _ in 0..CLIENTS {
+            let (w, r) = duplex(64);
+            fanout.add_client(w);
+            readers.push(r);
+        }
+        for blk in 0..3u8 {
+            fanout.send(Bytes::from(vec![blk; 16]));
+        }
+        for mut r in readers {
+            let mut buf = [0u8; 48];
+            r.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf[..16], &[0; 16]);
+            assert_eq!(&buf[16..32], &[1; 16]);
+            assert_eq!(&buf[32..], &[2; 16]);
+        }
+        assert!(fanout.max_in_flight() <= WORKERS);
+        assert!(fanout.max_in_flight() > 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn client_that_never_reads_is_dropped() {
+        const BLOCK: usize = 256;
+        let mut fanout = Fanout::new(2, Some(Duration::from_millis(100)));
+        // Room for one block, and it never reads
+        let (w, mut stuck) = duplex(BLOCK);
+        fanout.add_client(w);
+        let mut readers = Vec::new();
+        for _ in 0..2 {
+            let (w, r) = duplex(BLOCK);
+            fanout.add_client(w);
+            readers.push(r);
+        }
+        let read_blocks = |mut readers: Vec<tokio::io::DuplexStream>, blocks: Vec<u8>| async move {
+            for r in readers.iter_mut() {
+                for blk in blocks.iter() {
+                    let mut buf = [0u8; BLOCK];
+                    r.read_exact(&mut buf).await.unwrap();
+                    assert_eq!(buf, [*blk; BLOCK]);
+                }
+            }
+            readers
+        };
+
+        for blk in 0..3u8 {
+            fanout.send(Bytes::from(vec![blk; BLOCK]));
+        }
+        let readers = read_blocks(readers, vec![0, 1, 2]).await;
+        let wait = async {
+            while fanout.num_clients() > 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .expect("the stuck client was not dropped");
+        assert_eq!(fanout.dropped_clients(), 1);
+
+        // The others keep getting blocks
+        fanout.send(Bytes::from(vec![3; BLOCK]));
+        read_blocks(readers, vec![3]).await;
+        // and the stuck one's writer is gone: it sees what fit, then the end
+        let mut rest = Vec::new();
+        stuck.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, vec![0; BLOCK]);
+    }
+
+    #[tokio::test]
+    async fn a_client_whose_connection_failed_is_dropped() {
+        let mut fanout = Fanout::new(1, None);
+        let (w, r) = duplex(64);
+        drop(r);
+        fanout.add_client(w);
+        fanout.send(Bytes::from(vec![0; 16]));
+        let wait = async {
+            while fanout.num_clients() > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .expect("the client was not dropped");
+        // It was not slow, only gone
+        assert_eq!(fanout.dropped_clients(), 0);
+    }
+}
@@ -1,6 +1,7 @@
 use serde::Serialize;
 use serde_json::to_vec_pretty;
 use serde_yaml::to_writer;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, prelude::*};
 use toml::to_string_pretty;
@@ -16,8 +17,36 @@ pub fn file_to_ips(filename: String) -> Vec<String> {
     ips
 }
 
-pub fn to_bytes(obj: &impl Serialize) -> Vec<u8> {
-    return bincode::serialize(&obj).unwrap();
+/// The wire encoding of `obj`. Fails for values bincode cannot encode, such
+/// as sequences of unknown length.
+pub fn to_bytes(obj: &impl Serialize) -> bincode::Result<Vec<u8>> {
+    bincode::serialize(&obj)
+}
+
+/// The bytes that get signed or hashed for `obj`. The encoding is
+/// deterministic only if every collection in `obj` is: signed types keep
+/// their maps as `BTreeMap`s or as vectors built with `sorted`, never as a
+/// `HashMap`, whose iteration order differs from one replica to the next.
+pub fn canonical_bytes(obj: &impl Serialize) -> bincode::Result<Vec<u8>> {
+    to_bytes(obj)
+}
+
+/// The entries of `map` in key order, for putting a `HashMap` into
+/// something that is signed
+pub fn sorted<K: Ord + Clone, V: Clone>(map: &HashMap<K, V>) -> Vec<(K, V)> {
+    let mut entries: Vec<(K, V)> = map.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+/// `to_bytes` with the failure as the `io::Error` codecs return
+pub fn to_frame(obj: &impl Serialize) -> io::Result<Vec<u8>> {
+    to_bytes(obj).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// The length of `obj` as `to_bytes` would encode it, without encoding it
+pub fn encoded_len(obj: &impl Serialize) -> usize {
+    bincode::serialized_size(&obj).unwrap() as usize
 }
 
 pub fn write_json(filename: String, obj: &impl Serialize) {
@@ -43,3 +72,54 @@ pub fn write_yaml(filename: String, obj: &impl Serialize) {
     let f = File::create(filename).unwrap();
     to_writer(f, obj).unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{canonical_bytes, sorted, to_bytes, to_frame};
+    use serde::ser::{Serialize, SerializeSeq, Serializer};
+    use std::collections::{BTreeMap, HashMap};
+
+    /// A sequence that does not say how long it is, which bincode refuses
+    struct Unsized;
+
+    impl Serialize for Unsized {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(None)?;
+            seq.serialize_element(&0u8)?;
+            seq.end()
+        }
+    }
+
+    #[test]
+    fn serialization_failures_are_returned() {
+        assert!(to_bytes(&Unsized).is_err());
+        let err = to_frame(&Unsized).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(to_bytes(&vec![1u8]).unwrap(), vec![1, 0, 0, 0, 0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn insertion_order_does_not_change_the_signed_bytes() {
+        let keys: Vec<u16> = (0..64).collect();
+        let mut forward = HashMap::new();
+        let mut backward = HashMap::new();
+        let mut forward_tree = BTreeMap::new();
+        let mut backward_tree = BTreeMap::new();
+        for k in keys.iter() {
+            forward.insert(*k, vec![*k as u8]);
+            forward_tree.insert(*k, vec![*k as u8]);
+        }
+        for k in keys.iter().rev() {
+            backward.insert(*k, vec![*k as u8]);
+            backward_tree.insert(*k, vec![*k as u8]);
+        }
+        assert_eq!(
+            canonical_bytes(&(7u64, sorted(&forward))).unwrap(),
+            canonical_bytes(&(7u64, sorted(&backward))).unwrap()
+        );
+        assert_eq!(
+            canonical_bytes(&forward_tree).unwrap(),
+            canonical_bytes(&backward_tree).unwrap()
+        );
+    }
+}
@@ -8,8 +8,13 @@ mod tests {
     }
 }
 
+pub mod admission;
 pub mod codec;
+pub mod fanout;
 pub mod io;
+pub mod peers;
+pub mod sequence;
+pub mod socket;
 // pub mod channel;
 
 pub fn new_dummy_tx(i: u64, payload: usize) -> Transaction {
@@ -22,4 +27,4 @@ pub fn new_dummy_tx(i: u64, payload: usize) -> Transaction {
     t
 }
 
-pub const CHANNEL_SIZE: usize = 100_000;
+pub const CHANNEL_SIZE: usize = config::DEFAULT_CHANNEL_SIZE;
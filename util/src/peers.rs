@@ -0,0 +1,385 @@
+// Accepting the connections of the other replicas at startup, and reading
+// from them once they are up
+
+use crate::socket;
+use config::SocketOptions;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio_stream::{Stream, StreamExt, StreamMap};
+use types::Replica;
+
+/// How long to back off after a failed accept, e.g. when out of descriptors
+pub const ACCEPT_RETRY: Duration = Duration::from_millis(100);
+
+/// Identifies one connection in the logs, so that its lines can be told
+/// apart from those of other connections, including earlier ones of the same
+/// peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConnId(u64);
+
+impl ConnId {
+    /// An id no other connection of this process has
+    pub fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        ConnId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl fmt::Display for ConnId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "c{}", self.0)
+    }
+}
+
+/// A step in the life of an incoming connection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    /// The connection was accepted and the handshake starts
+    Accepted,
+    /// The handshake named the peer
+    Identified,
+    /// The connection is kept for the peer
+    Established,
+    /// The peer already has a connection
+    Rejected,
+    /// The handshake failed
+    Dropped,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnEvent {
+    pub conn: ConnId,
+    pub stage: Stage,
+    pub addr: SocketAddr,
+    /// Known once the connection identified itself
+    pub peer: Option<Replica>,
+}
+
+/// Logs `ev` along with the connection, peer and address it is about
+pub fn log_event(ev: &ConnEvent) {
+    let peer = ev.peer.map_or_else(|| "?".to_string(), |p| p.to_string());
+    match ev.stage {
+        Stage::Accepted => log::debug!(target:"util", "conn={} peer={} addr={} accepted", ev.conn, peer, ev.addr),
+        Stage::Identified => log::debug!(target:"util", "conn={} peer={} addr={} identified", ev.conn, peer, ev.addr),
+        Stage::Established => log::debug!(target:"util", "conn={} peer={} addr={} established", ev.conn, peer, ev.addr),
+        Stage::Rejected => log::error!(target:"util", "conn={} peer={} addr={} rejected: the peer is already connected; is the id configured twice?", ev.conn, peer, ev.addr),
+        Stage::Dropped => log::warn!(target:"util", "conn={} peer={} addr={} dropped: did not identify", ev.conn, peer, ev.addr),
+    }
+}
+
+/// Accepts connections until `expected` distinct peers have identified
+/// themselves. `identify` performs the handshake on a fresh connection and
+/// returns the peer it belongs to, or `None` to drop it. Accept errors and
+/// connections that fail to identify are logged and do not count towards
+/// `expected`. A second connection claiming an id we already accepted is
+/// dropped, since two replicas configured with the same id would otherwise
+/// silently replace each other. Every connection is set up with `opts`
+/// before the handshake.
+pub async fn accept_peers<F, Fut>(
+    listener: &TcpListener,
+    expected: usize,
+    opts: &SocketOptions,
+    identify: F,
+) -> HashMap<Replica, TcpStream>
+where
+    F: FnMut(TcpStream) -> Fut,
+    Fut: Future<Output = Option<(Replica, TcpStream)>>,
+{
+    accept_peers_with(listener, expected, opts, identify, |_| ()).await
+}
+
+/// Like `accept_peers`, but also hands every lifecycle event it logs to
+/// `observe`
+pub async fn accept_peers_with<F, Fut, O>(
+    listener: &TcpListener,
+    expected: usize,
+    opts: &SocketOptions,
+    mut identify: F,
+    mut observe: O,
+) -> HashMap<Replica, TcpStream>
+where
+    F: FnMut(TcpStream) -> Fut,
+    Fut: Future<Output = Option<(Replica, TcpStream)>>,
+    O: FnMut(&ConnEvent),
+{
+    let mut peers = HashMap::with_capacity(expected);
+    while peers.len() < expected {
+        let (conn, from) = match listener.accept().await {
+            Ok(x) => x,
+            Err(e) => {
+                log::warn!(target:"util", "Failed to accept a connection: {}", e);
+                tokio::time::sleep(ACCEPT_RETRY).await;
+                continue;
+            }
+        };
+        if let Err(e) = socket::configure(&conn, opts) {
+            log::warn!(target:"util", "Failed to set the socket options of {}: {}", from, e);
+        }
+        let mut ev = ConnEvent {
+            conn: ConnId::next(),
+            stage: Stage::Accepted,
+            addr: from,
+            peer: None,
+        };
+        let mut emit = |ev: &ConnEvent| {
+            log_event(ev);
+            observe(ev);
+        };
+        emit(&ev);
+        match identify(conn).await {
+            Some((id, conn)) => {
+                ev.peer = Some(id);
+                ev.stage = Stage::Identified;
+                emit(&ev);
+                if peers.contains_key(&id) {
+                    ev.stage = Stage::Rejected;
+                } else {
+                    peers.insert(id, conn);
+                    ev.stage = Stage::Established;
+                }
+                emit(&ev);
+            }
+            None => {
+                ev.stage = Stage::Dropped;
+                emit(&ev);
+            }
+        }
+    }
+    peers
+}
+
+/// A peer's messages, then `None` once its stream has ended
+type PeerStream<T, E> = Pin<Box<dyn Stream<Item = Option<Result<T, E>>> + Send>>;
+
+fn peer_stream<S, T, E>(stream: S) -> PeerStream<T, E>
+where
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    Box::pin(stream.map(Some).chain(tokio_stream::once(None)))
+}
+
+/// Forwards the messages of every peer's stream to `out`, tagged with the
+/// peer. A stream that fails or ends is dropped on its own and its peer
+/// handed to `lost`, so that it can be reconnected, while the other peers
+/// are read as before. A stream sent on `rejoin` replaces the peer's
+/// current one. Returns once `out` is closed, or once no stream is left and
+/// none can join.
+pub async fn route_peers<S, T, E, L>(
+    streams: HashMap<Replica, S>,
+    mut rejoin: UnboundedReceiver<(Replica, S)>,
+    out: UnboundedSender<(Replica, T)>,
+    mut lost: L,
+) where
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+    T: Send + 'static,
+    E: fmt::Display + Send + 'static,
+    L: FnMut(Replica),
+{
+    let mut map = StreamMap::new();
+    for (id, stream) in streams {
+        map.insert(id, peer_stream(stream));
+    }
+    let mut rejoin_open = true;
+    loop {
+        tokio::select! {
+            Some((id, item)) = map.next(), if !map.is_empty() => match item {
+                Some(Ok(msg)) => {
+                    if out.send((id, msg)).is_err() {
+                        return;
+                    }
+                }
+                Some(Err(e)) => {
+                    log::warn!(target:"util", "peer={} failed to receive: {}; dropping its stream", id, e);
+                    map.remove(&id);
+                    lost(id);
+                }
+                None => {
+                    log::warn!(target:"util", "peer={} closed its stream", id);
+                    map.remove(&id);
+                    lost(id);
+                }
+            },
+            joined = rejoin.recv(), if rejoin_open => match joined {
+                Some((id, stream)) => {
+                    log::debug!(target:"util", "peer={} rejoined", id);
+                    map.insert(id, peer_stream(stream));
+                }
+                None => rejoin_open = false,
+            },
+            else => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::mpsc::unbounded_channel;
+    use tokio_stream::wrappers::UnboundedReceiverStream;
+
+    #[tokio::test]
+    async fn bogus_connection_is_skipped() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let dialers = tokio::spawn(async move {
+            // Connects and hangs up before saying who it is.
+            drop(TcpStream::connect(addr).await.unwrap());
+            let mut conns = Vec::new();
+            for id in 1..4 as Replica {
+                let mut conn = TcpStream::connect(addr).await.unwrap();
+                conn.write_u16(id).await.unwrap();
+                conns.push(conn);
+            }
+            conns
+        });
+        let peers = accept_peers(&listener, 3, &SocketOptions::default(), |mut conn| async move {
+            match conn.read_u16().await {
+                Ok(id) => Some((id, conn)),
+                Err(_) => None,
+            }
+        })
+        .await;
+        let mut ids: Vec<_> = peers.keys().cloned().collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 2, 3]);
+        dialers.await.unwrap();
+    }
+    #[tokio::test]
+    async fn duplicate_id_is_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let dialers = tokio::spawn(async move {
+            let mut conns = Vec::new();
+            // The first two dialers both claim id 1 and say which one they are
+            for (id, who) in [(1, 10), (1, 11), (2, 20), (3, 30)].iter() {
+                let mut conn = TcpStream::connect(addr).await.unwrap();
+                conn.write_u16(*id).await.unwrap();
+                conn.write_u16(*who).await.unwrap();
+                conns.push(conn);
+            }
+            conns
+        });
+        let mut peers = accept_peers(&listener, 3, &SocketOptions::default(), |mut conn| async move {
+            match conn.read_u16().await {
+                Ok(id) => Some((id, conn)),
+                Err(_) => None,
+            }
+        })
+        .await;
+        let mut ids: Vec<_> = peers.keys().cloned().collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 2, 3]);
+        // Peer 1 is still the dialer that got there first
+        let first = peers.get_mut(&1).unwrap();
+        assert_eq!(first.read_u16().await.unwrap(), 10);
+        assert_eq!(peers.get_mut(&2).unwrap().read_u16().await.unwrap(), 20);
+        dialers.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn events_of_a_connection_share_its_id() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let dialers = tokio::spawn(async move {
+            let mut conns = Vec::new();
+            drop(TcpStream::connect(addr).await.unwrap());
+            for id in [1, 1, 2].iter() {
+                let mut conn = TcpStream::connect(addr).await.unwrap();
+                conn.write_u16(*id).await.unwrap();
+                conns.push(conn);
+            }
+            conns
+        });
+        let mut events = Vec::new();
+        accept_peers_with(
+            &listener,
+            2,
+            &SocketOptions::default(),
+            |mut conn| async move {
+                match conn.read_u16().await {
+                    Ok(id) => Some((id, conn)),
+                    Err(_) => None,
+                }
+            },
+            |ev| events.push(ev.clone()),
+        )
+        .await;
+        dialers.await.unwrap();
+
+        let mut by_conn: Vec<Vec<ConnEvent>> = Vec::new();
+        for ev in events {
+            match by_conn.iter_mut().find(|c| c[0].conn == ev.conn) {
+                Some(c) => c.push(ev),
+                None => by_conn.push(vec![ev]),
+            }
+        }
+        let stages: Vec<Vec<Stage>> = by_conn
+            .iter()
+            .map(|c| c.iter().map(|ev| ev.stage).collect())
+            .collect();
+        use Stage::*;
+        assert_eq!(
+            stages,
+            vec![
+                vec![Accepted, Dropped],
+                vec![Accepted, Identified, Established],
+                vec![Accepted, Identified, Rejected],
+                vec![Accepted, Identified, Established],
+            ]
+        );
+        for c in by_conn.iter() {
+            assert!(c.iter().all(|ev| ev.addr == c[0].addr));
+        }
+        assert_eq!(by_conn[1][2].peer, Some(1));
+        assert_eq!(by_conn[2][2].peer, Some(1));
+        assert_ne!(by_conn[1][0].conn, by_conn[2][0].conn);
+    }
+
+    #[tokio::test]
+    async fn a_lost_peer_does_not_stop_the_others() {
+        let mut senders = HashMap::new();
+        let mut streams = HashMap::new();
+        for id in 1..4 as Replica {
+            let (send, recv) = unbounded_channel::<Result<u8, String>>();
+            senders.insert(id, send);
+            streams.insert(id, UnboundedReceiverStream::new(recv));
+        }
+        let (rejoin_send, rejoin) = unbounded_channel();
+        let (out, mut routed) = unbounded_channel();
+        let (lost_send, mut lost) = unbounded_channel();
+        tokio::spawn(route_peers(streams, rejoin, out, move |id| {
+            let _ = lost_send.send(id);
+        }));
+
+        senders[&1].send(Ok(10)).unwrap();
+        assert_eq!(routed.recv().await, Some((1, 10)));
+        // Peer 2 hangs up and peer 3's connection breaks
+        drop(senders.remove(&2));
+        senders[&3].send(Err("connection reset".to_string())).unwrap();
+        let mut gone = vec![lost.recv().await.unwrap(), lost.recv().await.unwrap()];
+        gone.sort();
+        assert_eq!(gone, vec![2, 3]);
+        // Whatever else comes from the broken stream is not read
+        senders[&3].send(Ok(30)).unwrap();
+        senders[&1].send(Ok(11)).unwrap();
+        assert_eq!(routed.recv().await, Some((1, 11)));
+
+        // Peer 2 reconnects
+        let (send, recv) = unbounded_channel();
+        rejoin_send.send((2, UnboundedReceiverStream::new(recv))).unwrap();
+        send.send(Ok(20)).unwrap();
+        assert_eq!(routed.recv().await, Some((2, 20)));
+        senders[&1].send(Ok(12)).unwrap();
+        assert_eq!(routed.recv().await, Some((1, 12)));
+    }
+}
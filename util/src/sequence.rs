@@ -0,0 +1,204 @@
+// Per-peer sequence numbers on outbound batches, so that resending after a
+// reconnect can neither reorder, duplicate nor silently drop a frame
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+pub type Seq = u64;
+
+/// A frame as it goes on the wire to one peer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sequenced<T> {
+    pub seq: Seq,
+    pub frame: T,
+}
+
+/// Sending half for one peer. Frames are numbered from 0 in the order they
+/// are pushed and kept until the peer acknowledges them, so whatever was
+/// still in flight when a connection broke can be resent on the next one.
+pub struct OutSeq<T> {
+    next: Seq,
+    unacked: VecDeque<Sequenced<T>>,
+}
+
+impl<T: Clone> OutSeq<T> {
+    pub fn new() -> Self {
+        OutSeq {
+            next: 0,
+            unacked: VecDeque::new(),
+        }
+    }
+
+    /// Numbers `frame` and returns it ready to be written
+    pub fn push(&mut self, frame: T) -> Sequenced<T> {
+        let out = Sequenced {
+            seq: self.next,
+            frame,
+        };
+        self.next += 1;
+        self.unacked.push_back(out.clone());
+        out
+    }
+
+    /// Forgets every frame below `next_expected`, the first sequence number
+    /// the peer has not delivered yet
+    pub fn ack(&mut self, next_expected: Seq) {
+        while let Some(f) = self.unacked.front() {
+            if f.seq >= next_expected {
+                break;
+            }
+            self.unacked.pop_front();
+        }
+    }
+
+    /// The frames to write on a fresh connection, in order, given the first
+    /// sequence number the peer has not delivered yet. A peer expecting a
+    /// frame that was acknowledged already, or one never sent, started over
+    /// or talks to a sender that did; the frames not acknowledged are then
+    /// numbered again from what it expects.
+    pub fn resend(&mut self, next_expected: Seq) -> Vec<Sequenced<T>> {
+        let first = self.unacked.front().map_or(self.next, |f| f.seq);
+        if next_expected < first || next_expected > self.next {
+            log::warn!(target:"util", "The peer expects frame {} but {} to {} are kept; numbering them again", next_expected, first, self.next);
+            for (i, f) in self.unacked.iter_mut().enumerate() {
+                f.seq = next_expected + i as Seq;
+            }
+            self.next = next_expected + self.unacked.len() as Seq;
+        }
+        self.ack(next_expected);
+        self.unacked.iter().cloned().collect()
+    }
+
+    /// Number of frames the peer has not acknowledged
+    pub fn unacked(&self) -> usize {
+        self.unacked.len()
+    }
+}
+
+impl<T: Clone> Default for OutSeq<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the receiving half made of an incoming frame
+#[derive(Debug, PartialEq)]
+pub enum Delivery<T> {
+    /// The next frame in order, to be handed on
+    Deliver(T),
+    /// Already delivered, e.g. resent after a reconnect
+    Duplicate(Seq),
+    /// Frames before this one are missing. It is not delivered; the sender
+    /// resends from `expected` once it learns about it.
+    Gap { expected: Seq, got: Seq },
+}
+
+/// Receiving half for one peer. Only ever delivers frames in sequence order.
+pub struct InSeq {
+    next: Seq,
+}
+
+impl InSeq {
+    pub fn new() -> Self {
+        InSeq { next: 0 }
+    }
+
+    pub fn accept<T>(&mut self, msg: Sequenced<T>) -> Delivery<T> {
+        if msg.seq < self.next {
+            return Delivery::Duplicate(msg.seq);
+        }
+        if msg.seq > self.next {
+            log::warn!(target:"util", "Expected frame {} but got {}", self.next, msg.seq);
+            return Delivery::Gap {
+                expected: self.next,
+                got: msg.seq,
+            };
+        }
+        self.next += 1;
+        Delivery::Deliver(msg.frame)
+    }
+
+    /// The first sequence number not delivered yet. This is what the peer
+    /// is told when it reconnects.
+    pub fn next_expected(&self) -> Seq {
+        self.next
+    }
+}
+
+impl Default for InSeq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnect_keeps_order_and_drops_nothing() {
+        let mut out = OutSeq::new();
+        let mut inb = InSeq::new();
+        let mut delivered = Vec::new();
+
+        // Frames 0..4 make it across, 4..6 are in flight when the
+        // connection breaks, 6 is written to the new connection before the
+        // resend is done.
+        let sent: Vec<_> = (0..7u32).map(|i| out.push(i)).collect();
+        for f in sent[..4].iter().cloned() {
+            match inb.accept(f) {
+                Delivery::Deliver(x) => delivered.push(x),
+                d => panic!("unexpected {:?}", d),
+            }
+        }
+        assert_eq!(
+            inb.accept(sent[6].clone()),
+            Delivery::Gap {
+                expected: 4,
+                got: 6
+            }
+        );
+
+        // The new connection starts with where the receiver is at, and the
+        // sender may still repeat frames the receiver already has.
+        let mut resent = vec![sent[2].clone()];
+        resent.extend(out.resend(inb.next_expected()));
+        assert_eq!(out.unacked(), 3);
+        for f in resent {
+            match inb.accept(f) {
+                Delivery::Deliver(x) => delivered.push(x),
+                Delivery::Duplicate(seq) => assert!(seq < 4),
+                d => panic!("unexpected {:?}", d),
+            }
+        }
+        assert_eq!(delivered, (0..7).collect::<Vec<_>>());
+        out.ack(inb.next_expected());
+        assert_eq!(out.unacked(), 0);
+    }
+
+    #[test]
+    fn a_peer_that_started_over_gets_what_it_did_not_acknowledge() {
+        let mut out = OutSeq::new();
+        for i in 0..5u32 {
+            out.push(i);
+        }
+        out.ack(3);
+
+        // The receiver restarted and knows nothing
+        let mut inb = InSeq::new();
+        let resent = out.resend(inb.next_expected());
+        assert_eq!(resent.iter().map(|f| f.seq).collect::<Vec<_>>(), vec![0, 1]);
+        for f in resent {
+            assert!(matches!(inb.accept(f), Delivery::Deliver(_)));
+        }
+        assert_eq!(inb.accept(out.push(5)), Delivery::Deliver(5));
+
+        // The sender restarted, and the receiver is further along
+        let mut out = OutSeq::new();
+        out.push(9u32);
+        let resent = out.resend(inb.next_expected());
+        assert_eq!(resent, vec![Sequenced { seq: 3, frame: 9 }]);
+        assert_eq!(inb.accept(resent[0].clone()), Delivery::Deliver(9));
+        assert_eq!(inb.accept(out.push(10)), Delivery::Deliver(10));
+    }
+}
@@ -0,0 +1,78 @@
+// Setting up the TCP connections between replicas, accepted or dialed alike
+
+use config::SocketOptions;
+use socket2::{SockRef, TcpKeepalive};
+use std::io;
+use std::time::Duration;
+use tokio::net::{TcpStream, ToSocketAddrs};
+
+/// Applies `opts` to a connection to another replica. Small messages are
+/// sent right away rather than batched.
+pub fn configure(stream: &TcpStream, opts: &SocketOptions) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let sock = SockRef::from(stream);
+    if opts.keepalive_idle > 0 {
+        let keepalive = TcpKeepalive::new().with_time(Duration::from_secs(opts.keepalive_idle));
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+        let keepalive = keepalive
+            .with_interval(Duration::from_secs(opts.keepalive_interval))
+            .with_retries(opts.keepalive_count);
+        sock.set_tcp_keepalive(&keepalive)?;
+    }
+    if let Some(size) = opts.send_buffer {
+        sock.set_send_buffer_size(size)?;
+    }
+    if let Some(size) = opts.recv_buffer {
+        sock.set_recv_buffer_size(size)?;
+    }
+    Ok(())
+}
+
+/// Dials `addr` and configures the connection with `opts`
+pub async fn connect<A: ToSocketAddrs>(addr: A, opts: &SocketOptions) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect(addr).await?;
+    configure(&stream, opts)?;
+    Ok(stream)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn options_are_set_on_both_ends() {
+        let opts = SocketOptions {
+            keepalive_idle: 7,
+            keepalive_interval: 3,
+            keepalive_count: 4,
+            send_buffer: Some(64 * 1024),
+            recv_buffer: Some(64 * 1024),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dialed = connect(listener.local_addr().unwrap(), &opts).await.unwrap();
+        let (accepted, _) = listener.accept().await.unwrap();
+        configure(&accepted, &opts).unwrap();
+
+        for stream in [&dialed, &accepted].iter() {
+            assert!(stream.nodelay().unwrap());
+            let sock = SockRef::from(*stream);
+            assert!(sock.keepalive().unwrap());
+            assert_eq!(sock.keepalive_time().unwrap(), Duration::from_secs(7));
+            assert_eq!(sock.keepalive_interval().unwrap(), Duration::from_secs(3));
+            assert_eq!(sock.keepalive_retries().unwrap(), 4);
+            // The kernel may round the sizes up, but not down
+            assert!(sock.send_buffer_size().unwrap() >= 64 * 1024);
+            assert!(sock.recv_buffer_size().unwrap() >= 64 * 1024);
+        }
+    }
+
+    #[tokio::test]
+    async fn keepalive_can_be_turned_off() {
+        let mut opts = SocketOptions::default();
+        opts.keepalive_idle = 0;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dialed = connect(listener.local_addr().unwrap(), &opts).await.unwrap();
+        assert!(!SockRef::from(&dialed).keepalive().unwrap());
+    }
+}